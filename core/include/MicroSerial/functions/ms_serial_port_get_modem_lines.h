@@ -0,0 +1,21 @@
+#ifndef MICROSERIAL_FUNCTIONS_MS_SERIAL_PORT_GET_MODEM_LINES_H
+#define MICROSERIAL_FUNCTIONS_MS_SERIAL_PORT_GET_MODEM_LINES_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+struct ms_serial_port;
+
+/**
+ * @brief Read the current modem line state as a MS_SERIAL_LINE_* bitmask.
+ */
+int ms_serial_port_get_modem_lines(struct ms_serial_port *port, uint32_t *out_lines);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MICROSERIAL_FUNCTIONS_MS_SERIAL_PORT_GET_MODEM_LINES_H */
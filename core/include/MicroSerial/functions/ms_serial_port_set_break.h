@@ -0,0 +1,19 @@
+#ifndef MICROSERIAL_FUNCTIONS_MS_SERIAL_PORT_SET_BREAK_H
+#define MICROSERIAL_FUNCTIONS_MS_SERIAL_PORT_SET_BREAK_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+struct ms_serial_port;
+
+/**
+ * @brief Assert (non-zero @p enabled) or release a break condition on TX.
+ */
+int ms_serial_port_set_break(struct ms_serial_port *port, int enabled);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MICROSERIAL_FUNCTIONS_MS_SERIAL_PORT_SET_BREAK_H */
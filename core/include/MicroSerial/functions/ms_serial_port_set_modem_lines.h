@@ -0,0 +1,24 @@
+#ifndef MICROSERIAL_FUNCTIONS_MS_SERIAL_PORT_SET_MODEM_LINES_H
+#define MICROSERIAL_FUNCTIONS_MS_SERIAL_PORT_SET_MODEM_LINES_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+struct ms_serial_port;
+
+/**
+ * @brief Drive output modem lines.
+ *
+ * Only lines present in @p mask (MS_SERIAL_LINE_DTR / MS_SERIAL_LINE_RTS) are
+ * touched; their new state is taken from the matching bits of @p values.
+ */
+int ms_serial_port_set_modem_lines(struct ms_serial_port *port, uint32_t mask, uint32_t values);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MICROSERIAL_FUNCTIONS_MS_SERIAL_PORT_SET_MODEM_LINES_H */
@@ -0,0 +1,22 @@
+#ifndef MICROSERIAL_FUNCTIONS_MS_SERIAL_PORT_TX_PENDING_H
+#define MICROSERIAL_FUNCTIONS_MS_SERIAL_PORT_TX_PENDING_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+struct ms_serial_port;
+
+/**
+ * @brief Number of bytes queued by ms_serial_port_write() that the I/O thread
+ * has not yet handed to the device.
+ */
+size_t ms_serial_port_tx_pending(struct ms_serial_port *port);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MICROSERIAL_FUNCTIONS_MS_SERIAL_PORT_TX_PENDING_H */
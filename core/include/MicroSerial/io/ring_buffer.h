@@ -5,6 +5,7 @@
 #include "MicroSerial/functions/ms_ring_buffer_free.h"
 #include "MicroSerial/functions/ms_ring_buffer_write.h"
 #include "MicroSerial/functions/ms_ring_buffer_read.h"
+#include "MicroSerial/functions/ms_ring_buffer_peek.h"
 #include "MicroSerial/functions/ms_ring_buffer_size.h"
 
 #endif /* MICROSERIAL_IO_RING_BUFFER_H */
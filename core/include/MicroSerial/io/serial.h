@@ -10,7 +10,11 @@
 #include "MicroSerial/functions/ms_serial_port_configure.h"
 #include "MicroSerial/functions/ms_serial_port_start.h"
 #include "MicroSerial/functions/ms_serial_port_write.h"
+#include "MicroSerial/functions/ms_serial_port_tx_pending.h"
 #include "MicroSerial/functions/ms_serial_port_close.h"
 #include "MicroSerial/functions/ms_serial_port_poll.h"
+#include "MicroSerial/functions/ms_serial_port_set_modem_lines.h"
+#include "MicroSerial/functions/ms_serial_port_get_modem_lines.h"
+#include "MicroSerial/functions/ms_serial_port_set_break.h"
 
 #endif /* MICROSERIAL_IO_SERIAL_H */
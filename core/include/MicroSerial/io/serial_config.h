@@ -26,6 +26,18 @@ typedef enum ms_serial_flow_control {
     MS_SERIAL_FLOW_XON_XOFF
 } ms_serial_flow_control_t;
 
+/**
+ * @brief Modem control and status lines, used as bitmask values.
+ */
+typedef enum ms_serial_modem_line {
+    MS_SERIAL_LINE_DTR = 1 << 0,
+    MS_SERIAL_LINE_RTS = 1 << 1,
+    MS_SERIAL_LINE_CTS = 1 << 2,
+    MS_SERIAL_LINE_DSR = 1 << 3,
+    MS_SERIAL_LINE_RI = 1 << 4,
+    MS_SERIAL_LINE_CD = 1 << 5
+} ms_serial_modem_line_t;
+
 /**
  * @brief Serial port configuration descriptor.
  */
@@ -56,22 +56,20 @@ static void dispatch_tx(ms_serial_port_t *port) {
     }
     uint8_t buffer[MS_SERIAL_IO_CHUNK];
     for (;;) {
-        size_t available = ms_ring_buffer_read(port->tx_buffer, buffer, sizeof(buffer));
+        // Bytes leave the ring only once written, so a blocked write keeps
+        // their order and they still count as pending.
+        size_t available = ms_ring_buffer_peek(port->tx_buffer, buffer, sizeof(buffer));
         if (available == 0) {
             break;
         }
-        size_t offset = 0;
-        while (offset < available) {
-            ssize_t written = write(port->fd, buffer + offset, available - offset);
-            if (written > 0) {
-                offset += (size_t)written;
-            } else if (written < 0 && (errno == EAGAIN || errno == EWOULDBLOCK)) {
-                ms_ring_buffer_write(port->tx_buffer, buffer + offset, available - offset);
-                return;
-            } else {
-                ms_serial_emit_event(port, -errno, "write error");
-                return;
-            }
+        ssize_t written = write(port->fd, buffer, available);
+        if (written > 0) {
+            ms_ring_buffer_read(port->tx_buffer, buffer, (size_t)written);
+        } else if (written < 0 && (errno == EAGAIN || errno == EWOULDBLOCK)) {
+            return;
+        } else {
+            ms_serial_emit_event(port, -errno, "write error");
+            return;
         }
     }
 }
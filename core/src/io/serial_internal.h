@@ -26,5 +26,8 @@ typedef struct ms_serial_port {
 
 int ms_posix_configure_port(int fd, const ms_serial_config_t *config);
 int ms_posix_apply_flow_control(int fd, ms_serial_flow_control_t flow);
+int ms_posix_set_modem_lines(int fd, uint32_t mask, uint32_t values);
+int ms_posix_get_modem_lines(int fd, uint32_t *out_lines);
+int ms_posix_set_break(int fd, int enabled);
 
 #endif /* MICROSERIAL_SERIAL_INTERNAL_H */
@@ -97,6 +97,44 @@ ssize_t ms_serial_port_write(struct ms_serial_port *handle, const uint8_t *data,
     return (ssize_t)written;
 }
 
+size_t ms_serial_port_tx_pending(struct ms_serial_port *handle) {
+    if (!handle) {
+        return 0;
+    }
+    ms_serial_port_t *port = (ms_serial_port_t *)handle;
+    if (!port->tx_buffer) {
+        return 0;
+    }
+    return ms_ring_buffer_size(port->tx_buffer);
+}
+
+int ms_serial_port_set_modem_lines(struct ms_serial_port *handle, uint32_t mask, uint32_t values) {
+    if (!handle) {
+        return -EINVAL;
+    }
+    if (mask & ~(uint32_t)(MS_SERIAL_LINE_DTR | MS_SERIAL_LINE_RTS)) {
+        return -EINVAL;
+    }
+    ms_serial_port_t *port = (ms_serial_port_t *)handle;
+    return ms_posix_set_modem_lines(port->fd, mask, values);
+}
+
+int ms_serial_port_get_modem_lines(struct ms_serial_port *handle, uint32_t *out_lines) {
+    if (!handle || !out_lines) {
+        return -EINVAL;
+    }
+    ms_serial_port_t *port = (ms_serial_port_t *)handle;
+    return ms_posix_get_modem_lines(port->fd, out_lines);
+}
+
+int ms_serial_port_set_break(struct ms_serial_port *handle, int enabled) {
+    if (!handle) {
+        return -EINVAL;
+    }
+    ms_serial_port_t *port = (ms_serial_port_t *)handle;
+    return ms_posix_set_break(port->fd, enabled);
+}
+
 void ms_serial_port_close(struct ms_serial_port *handle) {
     if (!handle) {
         return;
@@ -7,6 +7,7 @@
 #include <errno.h>
 #include <fcntl.h>
 #include <string.h>
+#include <sys/ioctl.h>
 #include <termios.h>
 #include <unistd.h>
 
@@ -76,6 +77,66 @@ int ms_posix_apply_flow_control(int fd, ms_serial_flow_control_t flow) {
     return 0;
 }
 
+int ms_posix_set_modem_lines(int fd, uint32_t mask, uint32_t values) {
+    int status = 0;
+    if (ioctl(fd, TIOCMGET, &status) < 0) {
+        return -errno;
+    }
+    if (mask & MS_SERIAL_LINE_DTR) {
+        if (values & MS_SERIAL_LINE_DTR) {
+            status |= TIOCM_DTR;
+        } else {
+            status &= ~TIOCM_DTR;
+        }
+    }
+    if (mask & MS_SERIAL_LINE_RTS) {
+        if (values & MS_SERIAL_LINE_RTS) {
+            status |= TIOCM_RTS;
+        } else {
+            status &= ~TIOCM_RTS;
+        }
+    }
+    if (ioctl(fd, TIOCMSET, &status) < 0) {
+        return -errno;
+    }
+    return 0;
+}
+
+int ms_posix_get_modem_lines(int fd, uint32_t *out_lines) {
+    int status = 0;
+    if (ioctl(fd, TIOCMGET, &status) < 0) {
+        return -errno;
+    }
+    uint32_t lines = 0;
+    if (status & TIOCM_DTR) {
+        lines |= MS_SERIAL_LINE_DTR;
+    }
+    if (status & TIOCM_RTS) {
+        lines |= MS_SERIAL_LINE_RTS;
+    }
+    if (status & TIOCM_CTS) {
+        lines |= MS_SERIAL_LINE_CTS;
+    }
+    if (status & TIOCM_DSR) {
+        lines |= MS_SERIAL_LINE_DSR;
+    }
+    if (status & TIOCM_RI) {
+        lines |= MS_SERIAL_LINE_RI;
+    }
+    if (status & TIOCM_CD) {
+        lines |= MS_SERIAL_LINE_CD;
+    }
+    *out_lines = lines;
+    return 0;
+}
+
+int ms_posix_set_break(int fd, int enabled) {
+    if (ioctl(fd, enabled ? TIOCSBRK : TIOCCBRK) < 0) {
+        return -errno;
+    }
+    return 0;
+}
+
 int ms_posix_configure_port(int fd, const ms_serial_config_t *config) {
     if (!config) {
         return -EINVAL;
@@ -109,3 +109,18 @@ size_t ms_ring_buffer_read(ms_ring_buffer_t *buffer, uint8_t *data, size_t lengt
     atomic_store_explicit(&buffer->tail, tail, memory_order_release);
     return to_read;
 }
+
+size_t ms_ring_buffer_peek(const ms_ring_buffer_t *buffer, uint8_t *data, size_t length) {
+    if (!buffer || !data || length == 0) {
+        return 0;
+    }
+    size_t head = atomic_load_explicit(&buffer->head, memory_order_acquire);
+    size_t tail = atomic_load_explicit(&buffer->tail, memory_order_relaxed);
+    size_t available = ring_distance(head, tail, buffer->capacity);
+    size_t to_read = length < available ? length : available;
+    for (size_t i = 0; i < to_read; ++i) {
+        data[i] = buffer->data[tail];
+        tail = (tail + 1) & (buffer->capacity - 1);
+    }
+    return to_read;
+}
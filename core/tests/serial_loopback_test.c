@@ -135,6 +135,11 @@ int main(void) {
         return EXIT_FAILURE;
     }
 
+    if (ms_serial_port_tx_pending(port) != 0) {
+        fprintf(stderr, "tx ring not drained\n");
+        return EXIT_FAILURE;
+    }
+
     ms_serial_port_stop(port);
     ms_serial_port_close(port);
 
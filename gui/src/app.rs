@@ -2,22 +2,55 @@ use std::time::{Duration, Instant};
 
 use eframe::egui::{self, Align, Color32, ComboBox, Frame, Layout, Margin, RichText, Rounding};
 use strum::IntoEnumIterator;
+use time::OffsetDateTime;
+use time::format_description::FormatItem;
 use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
 
-use crate::console::{ConsoleBuffer, ConsoleEntry, ConsoleViewMode};
+use crate::bridge::{
+    BridgeDirection, BridgeMessage, BridgeRule, BridgeState, RuleAction, RuleScope, SerialBridge,
+};
+use crate::checksum::{ByteOrder, Checksum, ChecksumAlgorithm};
+use crate::console::{self, ConsoleBuffer, ConsoleEntry, ConsoleViewMode, LineFramingKind};
 use crate::core::{FlowControl, Parity, SerialConfig, SerialDevice, StopBits};
+#[cfg(unix)]
+use crate::daemon_client::DaemonClient;
+use crate::decoder::DecoderKind;
 use crate::device_scan::DeviceScanner;
 use crate::diagnostics::DiagnosticsState;
+#[cfg(unix)]
+use crate::emulator::{DeviceEmulator, EmulatorEvent, EmulatorScript};
+use crate::expect::{CaseStatus, TestRun, TestSuite, TestTarget};
+use crate::file_send::{EndingTranslation, FileSender};
+use crate::macros::{self, Macro};
+use crate::net_client::RemoteTarget;
+use crate::net_server::{SerialServer, ServerMessage, ServerProtocol, ServerRequest};
+use crate::pcapng::{self, FramingKind, InterfaceInfo, LiveCapture, PacketDirection};
+use crate::recorder::{self, RecordingHeader};
 use crate::renderer::RendererDiagnostics;
-use crate::send_panel::{PayloadError, SendMode, SendPanelState};
+use crate::replay::{ReplayMessage, ReplayOutput, ReplayTargetKind, ReplayTimingKind, Replayer};
+use crate::scheduler::{ScheduledItem, ScheduledStep, SendScheduler};
+use crate::scripting::{SavedScript, ScriptOutput, ScriptRequest, ScriptRunner};
+use crate::send_panel::LineEnding;
+use crate::send_panel::{self, PayloadError, SendMode, SendPanelState, TextEncoding, TextOptions};
 use crate::session::{SerialSession, SessionError, SessionMessage};
 use crate::settings::{self, Settings};
+use crate::template::TemplateCounters;
 use crate::theme::ThemeState;
+use crate::transfer::{
+    FileTransfer, TransferDirection, TransferFile, TransferProtocol, TransferState,
+};
+use crate::triggers::{Notifier, PatternKind, TriggerAction, TriggerEngine, TriggerRule};
+#[cfg(unix)]
+use crate::virtual_port::VirtualPort;
+use crate::websocket::{WebSocketMessage, WebSocketServer};
 
 const REFRESH_INTERVAL: Duration = Duration::from_secs(4);
 const BAUD_PRESETS: &[u32] = &[
     9_600, 19_200, 38_400, 57_600, 115_200, 230_400, 460_800, 921_600,
 ];
+const RECORDING_STAMP: &[FormatItem<'static>] =
+    format_description!("[year][month][day]-[hour][minute][second]");
 const HELP_URL: &str = "https://github.com/microserial/docs/blob/main/docs/gui/first_run.md";
 
 pub struct MicroSerialApp {
@@ -27,8 +60,57 @@ pub struct MicroSerialApp {
     selected_port: Option<String>,
     config: SerialConfig,
     session: Option<SerialSession>,
+    bridge: BridgeState,
+    server: Option<SerialServer>,
+    server_token: String,
+    websocket: Option<WebSocketServer>,
+    websocket_token: String,
+    remote_input: String,
+    #[cfg(unix)]
+    virtual_port: Option<VirtualPort>,
+    capture: Option<LiveCapture>,
+    export_source: String,
+    replay: Option<Replayer>,
+    replay_source: String,
+    replay_port: Option<String>,
+    #[cfg(unix)]
+    emulator: Option<DeviceEmulator>,
+    #[cfg(unix)]
+    emulator_answered: u64,
+    script: Option<ScriptRunner>,
+    test_file: String,
+    test_run: Option<TestRun>,
+    /// Whether the finished run was announced in the status bar.
+    test_reported: bool,
+    triggers: TriggerEngine,
+    /// Profile the trigger engine was built for; cleared when the rules are edited.
+    triggers_profile: Option<String>,
+    notifier: Notifier,
+    scheduler: SendScheduler,
+    /// Per-template counters, shared by the send bar and the scheduler.
+    templates: TemplateCounters,
+    macros_open: bool,
+    /// File used by macro import and export.
+    macro_path: String,
+    /// Profile whose scheduled items the running timers belong to.
+    scheduler_profile: String,
+    /// Profile whose history the send panel shows.
+    history_profile: String,
+    /// Index of the saved script open in the editor.
+    script_selected: Option<usize>,
+    script_draft: SavedScript,
+    transfer: Option<FileTransfer>,
+    /// Whether the progress dialog is shown.
+    transfer_open: bool,
+    /// Whether the finished transfer was announced in the console.
+    transfer_reported: bool,
+    file_send: Option<FileSender>,
+    /// Whether the "Send file" dialog is shown.
+    file_send_open: bool,
     console: ConsoleBuffer,
     send_panel: SendPanelState,
+    /// Preview under the send bar, rebuilt only when its inputs change.
+    preview: Option<PayloadPreview>,
     diagnostics: DiagnosticsState,
     settings: Settings,
     theme_state: ThemeState,
@@ -38,6 +120,35 @@ pub struct MicroSerialApp {
     custom_baud: String,
 }
 
+/// What [`MicroSerialApp::payload_preview`] shows, and what it was built from.
+struct PayloadPreview {
+    input: String,
+    mode: SendMode,
+    options: TextOptions,
+    checksum: Option<Checksum>,
+    seq: u64,
+    text: Result<String, String>,
+}
+
+/// Why [`MicroSerialApp::write_outbound`] sent nothing.
+enum OutboundError {
+    NotConnected,
+    /// Refused because stray bytes would corrupt the transfer's protocol stream.
+    TransferRunning,
+    Session(SessionError),
+}
+
+impl OutboundError {
+    /// The error as a session error, for callers that report those.
+    fn into_session(self) -> SessionError {
+        match self {
+            OutboundError::NotConnected => SessionError::Write(-libc::ENOTCONN),
+            OutboundError::TransferRunning => SessionError::Write(-libc::EBUSY),
+            OutboundError::Session(err) => err,
+        }
+    }
+}
+
 struct StatusBanner {
     message: String,
     tone: StatusTone,
@@ -74,6 +185,8 @@ impl MicroSerialApp {
             .map(|profile| profile.config.clone())
             .unwrap_or_else(SerialConfig::default);
         let custom_baud = config.baud_rate.to_string();
+        let server_token = settings.server.token.clone().unwrap_or_default();
+        let websocket_token = settings.websocket.token.clone().unwrap_or_default();
         let mut console = ConsoleBuffer::default();
         console.show_timestamps = settings.show_timestamps;
         console.view_mode = settings.console_view;
@@ -85,15 +198,53 @@ impl MicroSerialApp {
             diagnostics.renderer.software_backend = true;
         }
 
-        Self {
+        let mut app = Self {
             renderer,
             scanner,
             ports: Vec::new(),
             selected_port: None,
             config,
             session: None,
+            bridge: BridgeState::default(),
+            server: None,
+            server_token,
+            websocket: None,
+            websocket_token,
+            remote_input: String::new(),
+            #[cfg(unix)]
+            virtual_port: None,
+            capture: None,
+            export_source: String::new(),
+            replay: None,
+            replay_source: String::new(),
+            replay_port: None,
+            #[cfg(unix)]
+            emulator: None,
+            #[cfg(unix)]
+            emulator_answered: 0,
+            script: None,
+            test_file: String::new(),
+            test_run: None,
+            test_reported: false,
+            triggers: TriggerEngine::default(),
+            triggers_profile: None,
+            notifier: Notifier::default(),
+            scheduler: SendScheduler::default(),
+            templates: TemplateCounters::default(),
+            macros_open: false,
+            macro_path: "macros.json".to_string(),
+            scheduler_profile: String::new(),
+            history_profile: String::new(),
+            script_selected: None,
+            script_draft: SavedScript::default(),
+            transfer: None,
+            transfer_open: false,
+            transfer_reported: false,
+            file_send: None,
+            file_send_open: false,
             console,
             send_panel: SendPanelState::new(),
+            preview: None,
             diagnostics,
             settings,
             theme_state,
@@ -101,7 +252,9 @@ impl MicroSerialApp {
             settings_dirty: false,
             last_save: Instant::now(),
             custom_baud,
-        }
+        };
+        app.apply_console_settings();
+        app
     }
 
     fn mark_dirty(&mut self) {
@@ -117,16 +270,46 @@ impl MicroSerialApp {
             if let Some(error) = result.error {
                 self.set_status(&error, StatusTone::Warn);
             }
+            #[cfg(unix)]
+            if self.settings.daemon.attach {
+                self.refresh_daemon_ports();
+            }
+        }
+    }
+
+    /// Replaces the local scan with the ports the daemon sees.
+    #[cfg(unix)]
+    fn refresh_daemon_ports(&mut self) {
+        let listed = DaemonClient::connect_to(&self.settings.daemon)
+            .map_err(|err| err.to_string())
+            .and_then(|client| client.list().map_err(|err| err.to_string()));
+        match listed {
+            Ok(listed) => {
+                self.ports = listed
+                    .into_iter()
+                    .map(|port| SerialDevice {
+                        description: match (port.open, port.description.is_empty()) {
+                            (true, true) => "open in daemon".to_string(),
+                            (true, false) => format!("{} (open in daemon)", port.description),
+                            (false, _) => port.description,
+                        },
+                        path: port.path,
+                    })
+                    .collect();
+            }
+            Err(err) => self.set_status(&format!("Daemon unavailable: {err}"), StatusTone::Warn),
         }
     }
 
     fn poll_session(&mut self) {
+        self.sync_triggers();
         if let Some(session) = &mut self.session {
             for message in session.poll() {
+                if let (Some(websocket), SessionMessage::Event(_)) = (&self.websocket, &message) {
+                    websocket.publish(&message);
+                }
                 match message {
-                    SessionMessage::Data(bytes) => {
-                        self.console.push_rx(&bytes);
-                    }
+                    SessionMessage::Data(bytes) => self.handle_rx(&bytes),
                     SessionMessage::Event(event) => {
                         self.console
                             .push_event(&format!("{}: {}", event.code, event.message));
@@ -137,216 +320,2707 @@ impl MicroSerialApp {
         }
     }
 
-    fn connect(&mut self) {
-        let Some(path) = self.selected_port.clone() else {
-            self.set_status("Select a port to connect", StatusTone::Warn);
+    /// Per-profile state such as scheduled sends follows this profile name.
+    fn active_profile_name(&self) -> String {
+        self.settings
+            .profiles
+            .active
+            .clone()
+            .unwrap_or_else(|| "Default".to_string())
+    }
+
+    /// Rebuilds the trigger engine after the rules or the active profile changed.
+    fn sync_triggers(&mut self) {
+        let profile = self.active_profile_name();
+        if self.triggers_profile.as_ref() == Some(&profile) {
             return;
-        };
-        match SerialSession::open(&path, &self.config) {
-            Ok(session) => {
-                self.session = Some(session);
-                self.set_status(&format!("Connected to {path}"), StatusTone::Success);
-            }
-            Err(err) => {
-                self.set_status(&format!("Connect failed: {err}"), StatusTone::Error);
+        }
+        let rules = self
+            .settings
+            .profiles
+            .get_active()
+            .map_or(&[][..], |profile| profile.triggers.as_slice());
+        self.triggers = TriggerEngine::new(rules);
+        self.triggers_profile = Some(profile);
+    }
+
+    fn run_triggers(&mut self, bytes: &[u8]) {
+        let now = Instant::now();
+        for fire in self.triggers.feed(bytes, now) {
+            self.console
+                .push_event(&format!("trigger {}: {}", fire.rule, fire.matched.trim()));
+            for action in &fire.actions {
+                match action {
+                    TriggerAction::Highlight => self.console.highlight_last_rx(&fire.matched),
+                    TriggerAction::Notify => {
+                        self.notifier.notify(
+                            &format!("MicroSerial: {}", fire.rule),
+                            &fire.matched,
+                            now,
+                        );
+                        self.set_status(
+                            &format!("Trigger {}: {}", fire.rule, fire.matched.trim()),
+                            StatusTone::Warn,
+                        );
+                    }
+                    TriggerAction::Send { .. } => {
+                        let Some(Ok(payload)) = action.payload() else {
+                            continue;
+                        };
+                        let source = format!("Trigger {}", fire.rule);
+                        if let Err(OutboundError::Session(err)) =
+                            self.write_outbound(&source, &payload)
+                        {
+                            self.set_status(
+                                &format!("{source} send failed: {err}"),
+                                StatusTone::Error,
+                            );
+                        }
+                    }
+                    TriggerAction::StartRecording => {
+                        let recording = self
+                            .session
+                            .as_ref()
+                            .is_some_and(|session| session.recording().is_some());
+                        if !recording {
+                            self.start_recording();
+                        }
+                    }
+                    TriggerAction::StopRecording => self.stop_recording(),
+                    TriggerAction::PauseConsole => self.console.pause(),
+                }
             }
         }
     }
 
-    fn disconnect(&mut self) {
-        if self.session.is_some() {
-            self.session = None;
-            self.set_status("Disconnected", StatusTone::Info);
+    /// Text send options of the active profile.
+    fn send_options(&self) -> TextOptions {
+        self.settings
+            .profiles
+            .get_active()
+            .map(|profile| profile.send.clone())
+            .unwrap_or_default()
+    }
+
+    /// Swaps in the saved history when the active profile changes.
+    fn sync_history(&mut self) {
+        let profile = self.active_profile_name();
+        if profile == self.history_profile {
+            return;
         }
+        let entries = self
+            .settings
+            .profiles
+            .get_active()
+            .map_or(&[][..], |profile| profile.history.as_slice());
+        self.send_panel
+            .load_history(entries, self.settings.history.limit);
+        self.history_profile = profile;
     }
 
-    fn send_current_payload(&mut self) {
-        let payload = match self.send_panel.parse_payload(&self.send_panel.input) {
-            Ok(bytes) => bytes,
-            Err(PayloadError::InvalidHex) => {
-                self.set_status("Invalid hex payload", StatusTone::Error);
-                return;
-            }
+    /// Applies the console memory limit, spill file and row framing from the settings.
+    fn apply_console_settings(&mut self) {
+        let storage = &self.settings.console;
+        self.console.set_limit(storage.memory_limit_mb << 20);
+        self.console
+            .set_framing(storage.framing(), storage.flush_after(), storage.merge_tx);
+        self.console.set_max_row(storage.max_row);
+        let spill = storage
+            .spill
+            .then(|| std::path::PathBuf::from(&storage.spill_path));
+        if let Err(err) = self.console.set_spill(spill.as_deref()) {
+            self.settings.console.spill = false;
+            self.set_status(
+                &format!("Console spill unavailable: {err}"),
+                StatusTone::Error,
+            );
+        }
+    }
+
+    fn poll_console(&mut self) {
+        if let Some(err) = self.console.take_spill_error() {
+            self.settings.console.spill = false;
+            self.mark_dirty();
+            self.set_status(&format!("Console spill stopped: {err}"), StatusTone::Error);
+        }
+        self.console.tick(Instant::now());
+        self.console.refresh_view();
+    }
+
+    fn store_history(&mut self) {
+        let Some(profile) = self
+            .settings
+            .profiles
+            .profiles
+            .iter_mut()
+            .find(|profile| profile.name == self.history_profile)
+        else {
+            return;
         };
+        profile.history = self.send_panel.history.iter().cloned().collect();
+        self.mark_dirty();
+    }
 
-        match self.session.as_mut() {
-            Some(session) => match session.write(&payload) {
-                Ok(()) => {
-                    self.console.push_tx(&payload);
-                    self.set_status("Payload sent", StatusTone::Success);
-                    let value = self.send_panel.input.clone();
-                    self.send_panel.push_history(value);
-                }
-                Err(SessionError::Truncated) => {
-                    self.set_status("Write truncated", StatusTone::Warn);
-                }
-                Err(err) => {
-                    self.set_status(&format!("Write error: {err}"), StatusTone::Error);
+    fn poll_scheduler(&mut self) {
+        // Timer keys index the active profile's list.
+        let profile = self.active_profile_name();
+        if profile != self.scheduler_profile {
+            self.scheduler.stop_all();
+            self.scheduler_profile = profile;
+        }
+        if self.session.is_none() {
+            return;
+        }
+        for (key, payload) in self.scheduler.due(Instant::now(), &mut self.templates) {
+            if let Err(err) = self.write_outbound("Scheduled send", &payload) {
+                self.scheduler.stop(key);
+                if let OutboundError::Session(err) = err {
+                    self.set_status(&format!("Scheduled send stopped: {err}"), StatusTone::Error);
                 }
-            },
-            None => self.set_status("Not connected", StatusTone::Warn),
+                break;
+            }
+        }
+        for (name, err) in self.scheduler.take_failures() {
+            self.set_status(&format!("{name} stopped: {err}"), StatusTone::Error);
         }
     }
 
-    fn set_status(&mut self, message: &str, tone: StatusTone) {
-        self.status = Some(StatusBanner {
-            message: message.to_string(),
-            tone,
-            created: Instant::now(),
-        });
+    fn start_schedule(&mut self, key: usize) {
+        if self.session.is_none() {
+            self.set_status("Connect a port to start scheduled sends", StatusTone::Warn);
+            return;
+        }
+        if self.transfer_running() {
+            self.set_status(
+                "Scheduled sends cannot start while a file transfer runs",
+                StatusTone::Warn,
+            );
+            return;
+        }
+        let Some(profile) = self.settings.profiles.get_active() else {
+            return;
+        };
+        let Some(item) = profile.schedules.get(key).cloned() else {
+            return;
+        };
+        let options = profile.send.clone();
+        if let Err(err) =
+            self.scheduler
+                .start(key, &item, &options, &self.templates, Instant::now())
+        {
+            self.set_status(&format!("{}: {err}", item.name), StatusTone::Error);
+        }
     }
 
-    fn status_pill(&self, ui: &mut egui::Ui) {
-        let (label, tone) = if self.session.is_some() {
-            ("Connected", StatusTone::Success)
-        } else {
-            ("Disconnected", StatusTone::Warn)
+    /// Saves a history or favorite entry as a scheduled item and starts it.
+    fn arm_schedule(&mut self, entry: &send_panel::HistoryEntry) {
+        let Some(profile) = self.settings.profiles.get_active_mut() else {
+            return;
         };
-        Frame::none()
-            .fill(tone.color())
-            .rounding(Rounding::same(10.0))
-            .inner_margin(Margin::symmetric(12.0, 6.0))
-            .show(ui, |ui| {
-                ui.label(RichText::new(label).color(Color32::WHITE).strong());
-            });
+        let items = &mut profile.schedules;
+        let mut item = ScheduledItem::single(&entry.value, entry.mode);
+        item.steps[0].checksum = entry.checksum.clone();
+        items.push(item);
+        let key = items.len() - 1;
+        self.mark_dirty();
+        self.start_schedule(key);
     }
 
-    fn show_status_banner(&mut self, ui: &mut egui::Ui) {
-        if let Some(banner) = &self.status {
-            if banner.created.elapsed() < Duration::from_secs(6) {
-                Frame::none()
-                    .fill(banner.tone.color())
-                    .rounding(Rounding::same(10.0))
-                    .inner_margin(Margin::symmetric(12.0, 6.0))
-                    .show(ui, |ui| {
-                        ui.label(
-                            RichText::new(&banner.message)
-                                .color(Color32::WHITE)
-                                .strong(),
-                        );
-                    });
-                return;
+    /// Fans device output, live or replayed, out to the console, triggers and
+    /// every attached consumer.
+    fn handle_rx(&mut self, bytes: &[u8]) {
+        // Protocol traffic belongs to the transfer alone; only the capture sees it.
+        if let Some(transfer) = self.transfer.as_mut().filter(|t| t.is_running()) {
+            transfer.feed(bytes, Instant::now());
+            self.capture_chunk(0, PacketDirection::Inbound, bytes);
+            return;
+        }
+        self.console.push_rx(bytes);
+        self.capture_chunk(0, PacketDirection::Inbound, bytes);
+        if let Some(server) = &self.server {
+            server.publish(bytes);
+        }
+        #[cfg(unix)]
+        if let Some(virtual_port) = &self.virtual_port {
+            virtual_port.publish(bytes);
+        }
+        if let Some(websocket) = &self.websocket {
+            websocket.publish(&SessionMessage::Data(bytes.to_vec()));
+        }
+        if let Some(script) = &self.script {
+            script.feed(bytes);
+        }
+        if let Some(run) = &mut self.test_run {
+            run.feed(bytes);
+        }
+        if let Some(sender) = &mut self.file_send {
+            sender.feed(bytes, Instant::now());
+        }
+        self.run_triggers(bytes);
+    }
+
+    /// Pushes configuration edits to the live session and any network clients.
+    fn apply_live_config(&mut self) {
+        let Some(session) = self.session.as_mut() else {
+            return;
+        };
+        if *session.config() == self.config {
+            return;
+        }
+        match session.reconfigure(&self.config) {
+            Ok(()) => {
+                self.console.push_event(&format!(
+                    "applied {} bps, {} data bits, parity {}, {} stop bits, flow {}",
+                    self.config.baud_rate,
+                    self.config.data_bits,
+                    self.config.parity,
+                    self.config.stop_bits,
+                    self.config.flow_control
+                ));
+                if let Some(server) = &self.server {
+                    server.update_config(&self.config);
+                }
+            }
+            Err(err) => {
+                self.config = session.config().clone();
+                self.custom_baud = self.config.baud_rate.to_string();
+                self.set_status(&format!("Reconfigure failed: {err}"), StatusTone::Error);
             }
         }
-        self.status = None;
     }
 
-    fn save_settings_if_needed(&mut self) {
-        if self.settings_dirty && self.last_save.elapsed() > Duration::from_secs(1) {
-            if let Err(err) = self.settings.save() {
-                self.set_status(&format!("Settings save failed: {err}"), StatusTone::Error);
+    fn poll_server(&mut self) {
+        let Some(server) = &self.server else {
+            return;
+        };
+        if let Some(lines) = self
+            .session
+            .as_ref()
+            .and_then(|session| session.modem_lines().ok())
+        {
+            server.update_lines(lines);
+        }
+        for message in server.poll() {
+            match message {
+                ServerMessage::Request { peer, request } => {
+                    self.apply_server_request(peer, request)
+                }
+                ServerMessage::Connected { peer, role } => {
+                    self.console
+                        .push_event(&format!("network client {peer} connected as {role}"));
+                }
+                ServerMessage::Disconnected { peer } => {
+                    self.console
+                        .push_event(&format!("network client {peer} disconnected"));
+                }
+                ServerMessage::Promoted { peer } => {
+                    self.console
+                        .push_event(&format!("network client {peer} is now the writer"));
+                }
+                ServerMessage::Rejected { peer, reason } => {
+                    self.console
+                        .push_event(&format!("network client {peer} rejected: {reason}"));
+                }
             }
-            self.last_save = Instant::now();
-            self.settings_dirty = false;
         }
     }
 
-    fn devices_panel(&mut self, ui: &mut egui::Ui) {
-        ui.heading("Devices");
-        ui.horizontal(|ui| {
-            if ui
-                .add(egui::Button::new(
-                    RichText::new("Refresh").text_style(egui::TextStyle::Button),
-                ))
-                .clicked()
+    fn apply_server_request(&mut self, peer: std::net::SocketAddr, request: ServerRequest) {
+        if let ServerRequest::Write(bytes) = &request {
+            if let Err(OutboundError::Session(err)) =
+                self.write_outbound(&format!("Write from {peer}"), bytes)
             {
-                self.scanner.refresh();
+                self.console
+                    .push_event(&format!("network request from {peer} failed: {err}"));
             }
-            ui.hyperlink_to("Help", HELP_URL);
-            if let Some(result) = self.scanner.last_result() {
-                ui.label(format!(
-                    "Last scan {:.1?} ago ({:.1?})",
-                    result.completed_at.elapsed(),
-                    result.duration
-                ));
+            return;
+        }
+        let Some(session) = self.session.as_mut() else {
+            if let (ServerRequest::Configure(_), Some(server)) = (&request, &self.server) {
+                server.update_config(&self.config);
             }
-        });
+            return;
+        };
+        let (result, note) = match request {
+            ServerRequest::Write(_) => return,
+            ServerRequest::Configure(config) => {
+                let result = session.reconfigure(&config);
+                // Tells the client what is in effect, including after a refusal.
+                if let Some(server) = &self.server {
+                    server.update_config(session.config());
+                }
+                let note = format!("{peer} set {} bps", config.baud_rate);
+                if result.is_ok() {
+                    self.custom_baud = config.baud_rate.to_string();
+                    self.config = config;
+                }
+                (result, Some(note))
+            }
+            ServerRequest::SetDtr(enabled) => (
+                session.set_dtr(enabled),
+                Some(format!("{peer} set DTR {enabled}")),
+            ),
+            ServerRequest::SetRts(enabled) => (
+                session.set_rts(enabled),
+                Some(format!("{peer} set RTS {enabled}")),
+            ),
+            ServerRequest::SetBreak(enabled) => (
+                session.set_break(enabled),
+                Some(format!("{peer} set break {enabled}")),
+            ),
+        };
+        if let Some(note) = note {
+            self.console.push_event(&note);
+        }
+        if let Err(err) = result {
+            self.console
+                .push_event(&format!("network request from {peer} failed: {err}"));
+        }
+    }
 
-        if self.ports.is_empty() {
-            ui.spacing_mut().item_spacing.y = 8.0;
-            ui.label("No serial devices detected.");
+    fn start_server(&mut self) {
+        if self.session.is_none() {
+            self.set_status("Connect a port before sharing it", StatusTone::Warn);
+            return;
+        }
+        let token = self.server_token.trim();
+        self.settings.server.token = (!token.is_empty()).then(|| token.to_string());
+        self.mark_dirty();
+        let lines = self
+            .session
+            .as_ref()
+            .and_then(|session| session.modem_lines().ok())
+            .unwrap_or_default();
+        match SerialServer::start(self.settings.server.clone(), &self.config, lines) {
+            Ok(server) => {
+                self.set_status(
+                    &format!("Serving on {}", server.local_addr()),
+                    StatusTone::Success,
+                );
+                self.server = Some(server);
+            }
+            Err(err) => {
+                self.set_status(&format!("Server failed: {err}"), StatusTone::Error);
+            }
         }
+    }
 
-        for port in &self.ports {
-            let selected = self.selected_port.as_deref() == Some(&port.path);
-            let label = if port.description.is_empty() {
-                port.path.clone()
-            } else {
-                format!("{}\n{}", port.description, port.path)
-            };
-            let response = ui.selectable_label(selected, label);
-            if response.clicked() {
-                self.selected_port = Some(port.path.clone());
+    fn stop_server(&mut self) {
+        if self.server.take().is_some() {
+            self.set_status("Network server stopped", StatusTone::Info);
+        }
+    }
+
+    fn poll_websocket(&mut self) {
+        let Some(websocket) = &self.websocket else {
+            return;
+        };
+        for message in websocket.poll() {
+            match message {
+                WebSocketMessage::Write { peer, data } => {
+                    let source = format!("WebSocket write from {peer}");
+                    if let Err(OutboundError::Session(err)) = self.write_outbound(&source, &data) {
+                        self.console.push_event(&format!("{source} failed: {err}"));
+                    }
+                }
+                WebSocketMessage::Connected { peer, writable } => {
+                    let access = if writable { "read-write" } else { "read-only" };
+                    self.console
+                        .push_event(&format!("WebSocket client {peer} connected ({access})"));
+                }
+                WebSocketMessage::Disconnected { peer } => {
+                    self.console
+                        .push_event(&format!("WebSocket client {peer} disconnected"));
+                }
             }
         }
     }
 
-    fn empty_state(&mut self, ui: &mut egui::Ui) {
-        if !self.ports.is_empty() {
+    fn start_websocket(&mut self) {
+        let (Some(_), Some(port)) = (&self.session, self.selected_port.clone()) else {
+            self.set_status("Connect a port before streaming it", StatusTone::Warn);
             return;
+        };
+        let token = self.websocket_token.trim();
+        self.settings.websocket.token = (!token.is_empty()).then(|| token.to_string());
+        self.mark_dirty();
+        match WebSocketServer::start(&self.settings.websocket, &port) {
+            Ok(websocket) => {
+                self.set_status(
+                    &format!("Streaming on ws://{}", websocket.local_addr()),
+                    StatusTone::Success,
+                );
+                self.websocket = Some(websocket);
+            }
+            Err(err) => {
+                self.set_status(&format!("WebSocket failed: {err}"), StatusTone::Error);
+            }
         }
-        ui.vertical_centered(|ui| {
-            ui.add_space(32.0);
-            egui::Frame::group(ui.style())
-                .fill(Color32::from_rgb(25, 90, 140).gamma_multiply(0.15))
-                .show(ui, |ui| {
-                    ui.vertical_centered(|ui| {
-                        ui.heading("No serial devices detected");
-                        ui.label("Connect a device or check permissions.");
-                        if ui.button("Refresh").clicked() {
-                            self.scanner.refresh();
-                        }
-                        ui.hyperlink_to("Help with permissions", HELP_URL);
-                    });
-                });
-            ui.add_space(24.0);
-        });
     }
 
-    fn configuration_panel(&mut self, ui: &mut egui::Ui) {
-        ui.collapsing("Connection", |ui| {
-            ui.horizontal(|ui| {
-                ui.label("Port");
-                ComboBox::from_id_source("port_combo")
-                    .selected_text(
-                        self.selected_port
-                            .as_deref()
-                            .map(|p| p.to_string())
-                            .unwrap_or_else(|| "Select".to_string()),
-                    )
-                    .show_ui(ui, |ui| {
-                        for port in &self.ports {
-                            ui.selectable_value(
-                                &mut self.selected_port,
+    fn stop_websocket(&mut self) {
+        if self.websocket.take().is_some() {
+            self.set_status("WebSocket stream stopped", StatusTone::Info);
+        }
+    }
+
+    fn poll_script(&mut self) {
+        let Some(script) = self.script.as_mut() else {
+            return;
+        };
+        let name = script.name().to_string();
+        for output in script.poll() {
+            match output {
+                ScriptOutput::Request(request) => self.apply_script_request(&name, request),
+                ScriptOutput::Log(text) => self.console.push_script(&format!("{name}: {text}")),
+                ScriptOutput::Finished(Ok(())) => {
+                    self.console.push_script(&format!("{name} finished"));
+                }
+                ScriptOutput::Finished(Err(err)) => {
+                    self.console.push_script(&format!("{name} stopped: {err}"));
+                    self.set_status(&format!("Script {name}: {err}"), StatusTone::Warn);
+                }
+            }
+        }
+        if self.script.as_ref().is_some_and(ScriptRunner::is_finished) {
+            self.script = None;
+        }
+    }
+
+    fn apply_script_request(&mut self, name: &str, request: ScriptRequest) {
+        if self.transfer_running() {
+            self.set_status(
+                &format!("{name}: request dropped while a file transfer runs"),
+                StatusTone::Warn,
+            );
+            return;
+        }
+        let Some(session) = self.session.as_mut() else {
+            self.console
+                .push_script(&format!("{name}: not connected, request dropped"));
+            return;
+        };
+        let result = match request {
+            ScriptRequest::Send(bytes) => self
+                .write_outbound(name, &bytes)
+                .map_err(OutboundError::into_session),
+            ScriptRequest::SetDtr(enabled) => session.set_dtr(enabled),
+            ScriptRequest::SetRts(enabled) => session.set_rts(enabled),
+        };
+        if let Err(err) = result {
+            self.console
+                .push_script(&format!("{name}: request failed: {err}"));
+        }
+    }
+
+    fn run_script(&mut self, script: &SavedScript) {
+        if self.script.is_some() {
+            self.set_status("A script is already running", StatusTone::Warn);
+            return;
+        }
+        let name = if script.name.trim().is_empty() {
+            "script".to_string()
+        } else {
+            script.name.trim().to_string()
+        };
+        self.console.push_script(&format!("{name} started"));
+        self.script = Some(ScriptRunner::start(
+            &name,
+            &script.source,
+            DecoderKind::Lines,
+        ));
+    }
+
+    fn cancel_script(&mut self) {
+        if let Some(script) = &self.script {
+            script.cancel();
+        }
+    }
+
+    fn poll_test_run(&mut self) {
+        if self.session.is_none() {
+            return;
+        }
+        let Some(mut run) = self.test_run.take() else {
+            return;
+        };
+        run.advance(&mut AppTarget { app: self }, Instant::now());
+        self.test_run = Some(run);
+        self.report_test_run();
+    }
+
+    fn report_test_run(&mut self) {
+        let Some(run) = &self.test_run else {
+            return;
+        };
+        if !run.is_finished() || self.test_reported {
+            return;
+        }
+        self.test_reported = true;
+        let total = run.results().len();
+        let message = match run.failures() {
+            0 => (
+                format!("{}: all {total} tests passed", run.suite().name),
+                StatusTone::Success,
+            ),
+            failed => (
+                format!("{}: {failed} of {total} tests failed", run.suite().name),
+                StatusTone::Error,
+            ),
+        };
+        self.console.push_event(&message.0);
+        self.set_status(&message.0, message.1);
+    }
+
+    fn start_test_run(&mut self) {
+        if self.session.is_none() {
+            self.set_status("Connect a port before running tests", StatusTone::Warn);
+            return;
+        }
+        match TestSuite::load(std::path::Path::new(self.test_file.trim())) {
+            Ok(suite) => {
+                self.console
+                    .push_event(&format!("Running test suite {}", suite.name));
+                self.test_run = Some(TestRun::new(suite));
+                self.test_reported = false;
+            }
+            Err(err) => self.set_status(&err.to_string(), StatusTone::Error),
+        }
+    }
+
+    fn cancel_test_run(&mut self, reason: &str) {
+        if let Some(run) = &mut self.test_run {
+            run.cancel(reason);
+        }
+        self.report_test_run();
+    }
+
+    fn export_junit(&mut self) {
+        let Some(run) = &self.test_run else {
+            return;
+        };
+        let path = std::path::Path::new(self.test_file.trim()).with_extension("junit.xml");
+        match std::fs::write(&path, run.junit()) {
+            Ok(()) => self.set_status(
+                &format!("JUnit report written to {}", path.display()),
+                StatusTone::Success,
+            ),
+            Err(err) => self.set_status(
+                &format!("Writing JUnit report failed: {err}"),
+                StatusTone::Error,
+            ),
+        }
+    }
+
+    fn transfer_running(&self) -> bool {
+        self.transfer.as_ref().is_some_and(FileTransfer::is_running)
+    }
+
+    fn start_transfer(&mut self) {
+        if self.session.is_none() {
+            self.set_status("Connect a port before transferring files", StatusTone::Warn);
+            return;
+        }
+        let settings = &self.settings.transfer;
+        let protocol = settings.protocol;
+        let now = Instant::now();
+        let transfer = match settings.direction {
+            TransferDirection::Send => {
+                let files: Result<Vec<TransferFile>, String> = settings
+                    .files
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(|path| {
+                        TransferFile::load(std::path::Path::new(path))
+                            .map_err(|err| format!("{path}: {err}"))
+                    })
+                    .collect();
+                match files {
+                    Ok(files) if files.is_empty() => {
+                        self.set_status("Enter the files to send", StatusTone::Warn);
+                        return;
+                    }
+                    Ok(files) if files.len() > 1 && !protocol.is_batch() => {
+                        self.set_status(
+                            &format!("{protocol} sends one file; use YMODEM or ZMODEM for several"),
+                            StatusTone::Warn,
+                        );
+                        return;
+                    }
+                    Ok(files) => FileTransfer::send(protocol, files, now),
+                    Err(err) => {
+                        self.set_status(&err, StatusTone::Error);
+                        return;
+                    }
+                }
+            }
+            TransferDirection::Receive => {
+                let destination = std::path::PathBuf::from(settings.destination.trim());
+                if protocol.is_batch() && !destination.is_dir() {
+                    self.set_status(
+                        &format!("{protocol} needs a directory to save into"),
+                        StatusTone::Warn,
+                    );
+                    return;
+                }
+                FileTransfer::receive(protocol, destination, now)
+            }
+        };
+        // Scheduled sends would corrupt the protocol stream.
+        self.scheduler.stop_all();
+        self.console.push_event(&format!(
+            "{protocol} {} started; console paused until it ends",
+            match settings.direction {
+                TransferDirection::Send => "send",
+                TransferDirection::Receive => "receive",
+            }
+        ));
+        self.transfer = Some(transfer);
+        self.transfer_open = true;
+        self.transfer_reported = false;
+    }
+
+    fn poll_transfer(&mut self) {
+        let (Some(transfer), Some(session)) = (self.transfer.as_mut(), self.session.as_mut())
+        else {
+            return;
+        };
+        let outgoing = transfer.poll(Instant::now());
+        if !outgoing.is_empty() {
+            match session.write(&outgoing) {
+                Ok(()) => self.capture_chunk(0, PacketDirection::Outbound, &outgoing),
+                Err(err) => transfer.abort(format!("write failed: {err}")),
+            }
+        }
+        self.report_transfer();
+    }
+
+    fn cancel_transfer(&mut self) {
+        if let Some(transfer) = &mut self.transfer {
+            transfer.cancel();
+        }
+        // Sends the cancel sequence while the port is still open.
+        self.poll_transfer();
+    }
+
+    fn report_transfer(&mut self) {
+        let Some(transfer) = &self.transfer else {
+            return;
+        };
+        if transfer.is_running() || self.transfer_reported {
+            return;
+        }
+        self.transfer_reported = true;
+        let protocol = transfer.protocol();
+        let (message, tone) = match transfer.state() {
+            TransferState::Completed => (
+                format!(
+                    "{protocol} transfer complete ({} files)",
+                    transfer.progress().files_done
+                ),
+                StatusTone::Success,
+            ),
+            TransferState::Failed(reason) => (
+                format!("{protocol} transfer failed: {reason}"),
+                StatusTone::Error,
+            ),
+            TransferState::Cancelled => {
+                (format!("{protocol} transfer cancelled"), StatusTone::Warn)
+            }
+            TransferState::Running => return,
+        };
+        let saved: Vec<String> = transfer
+            .saved()
+            .iter()
+            .map(|path| format!("saved {}", path.display()))
+            .collect();
+        for note in saved {
+            self.console.push_event(&note);
+        }
+        self.console.push_event(&message);
+        self.set_status(&message, tone);
+    }
+
+    fn start_file_send(&mut self) {
+        if self.session.is_none() {
+            self.set_status("Connect a port before sending a file", StatusTone::Warn);
+            return;
+        }
+        if self.transfer_running() {
+            self.set_status(
+                "Wait for the file transfer to finish before sending a file",
+                StatusTone::Warn,
+            );
+            return;
+        }
+        let settings = &self.settings.file_send;
+        let path = settings.path.trim();
+        match std::fs::read(path) {
+            Ok(data) => {
+                self.console
+                    .push_event(&format!("sending {path} ({} bytes)", data.len()));
+                self.file_send = Some(FileSender::new(&data, settings, Instant::now()));
+            }
+            Err(err) => self.set_status(&format!("{path}: {err}"), StatusTone::Error),
+        }
+    }
+
+    fn poll_file_send(&mut self) {
+        let (Some(sender), Some(session)) = (self.file_send.as_mut(), self.session.as_mut()) else {
+            return;
+        };
+        if !sender.is_running() {
+            return;
+        }
+        let bytes = sender.poll(Instant::now(), session.tx_pending());
+        if !bytes.is_empty()
+            && let Err(err) = self.write_outbound("File send", &bytes)
+            && let Some(sender) = self.file_send.as_mut()
+        {
+            sender.abort(format!("write failed: {}", err.into_session()));
+        }
+        self.report_file_send();
+    }
+
+    fn cancel_file_send(&mut self) {
+        let Some(sender) = &mut self.file_send else {
+            return;
+        };
+        if sender.is_running() {
+            sender.cancel();
+            self.report_file_send();
+        }
+    }
+
+    /// Announces a send that just stopped running.
+    fn report_file_send(&mut self) {
+        let Some(sender) = &self.file_send else {
+            return;
+        };
+        let (message, tone) = match sender.state() {
+            TransferState::Running => return,
+            TransferState::Completed => (
+                format!("file sent ({} bytes)", sender.total()),
+                StatusTone::Success,
+            ),
+            TransferState::Failed(reason) => {
+                (format!("file send failed: {reason}"), StatusTone::Error)
+            }
+            TransferState::Cancelled => (
+                format!("file send cancelled after {} bytes", sender.sent()),
+                StatusTone::Warn,
+            ),
+        };
+        self.console.push_event(&message);
+        self.set_status(&message, tone);
+    }
+
+    #[cfg(unix)]
+    fn poll_virtual_port(&mut self) {
+        let chunks = match &self.virtual_port {
+            Some(virtual_port) if self.session.is_some() => virtual_port.poll(),
+            _ => return,
+        };
+        for chunk in chunks {
+            match self.write_outbound("Virtual port write", &chunk) {
+                Ok(()) | Err(OutboundError::TransferRunning) => {}
+                Err(OutboundError::NotConnected) => return,
+                Err(OutboundError::Session(err)) => {
+                    self.console
+                        .push_event(&format!("virtual port write failed: {err}"));
+                }
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn start_virtual_port(&mut self) {
+        if self.session.is_none() {
+            self.set_status("Connect a port before sharing it", StatusTone::Warn);
+            return;
+        }
+        self.mark_dirty();
+        match VirtualPort::create(&self.settings.virtual_port.link) {
+            Ok(virtual_port) => {
+                let note = format!(
+                    "sharing as {} ({})",
+                    virtual_port.link().display(),
+                    virtual_port.slave_path().display()
+                );
+                self.console.push_event(&note);
+                self.set_status(&note, StatusTone::Success);
+                self.virtual_port = Some(virtual_port);
+            }
+            Err(err) => {
+                self.set_status(&format!("Virtual port failed: {err}"), StatusTone::Error);
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn stop_virtual_port(&mut self) {
+        if let Some(virtual_port) = self.virtual_port.take() {
+            self.console.push_event(&format!(
+                "stopped sharing {}",
+                virtual_port.link().display()
+            ));
+        }
+    }
+
+    fn start_recording(&mut self) {
+        let (Some(session), Some(port)) = (self.session.as_mut(), self.selected_port.as_deref())
+        else {
+            self.set_status("Connect a port before recording", StatusTone::Warn);
+            return;
+        };
+        let stamp = OffsetDateTime::now_utc()
+            .format(RECORDING_STAMP)
+            .unwrap_or_default();
+        let path = std::path::Path::new(&self.settings.recordings.directory).join(format!(
+            "{}-{stamp}.{}",
+            file_stem_for(port),
+            recorder::FILE_EXTENSION
+        ));
+        let header =
+            RecordingHeader::new(port, &self.config, self.settings.profiles.active.clone());
+        match session.start_recording(&path, &header) {
+            Ok(()) => {
+                let note = format!("recording to {}", path.display());
+                self.console.push_event(&note);
+                self.set_status(&note, StatusTone::Success);
+            }
+            Err(err) => {
+                self.set_status(&format!("Recording failed: {err}"), StatusTone::Error);
+            }
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        let Some(status) = self
+            .session
+            .as_mut()
+            .and_then(|session| session.stop_recording())
+        else {
+            return;
+        };
+        let note = format!(
+            "recording saved to {} ({} records)",
+            status.path.display(),
+            status.records
+        );
+        self.console.push_event(&note);
+        self.set_status(&note, StatusTone::Info);
+    }
+
+    /// Writes bytes from the user, a client or an automation and logs them as TX.
+    ///
+    /// Every such write goes through here so none can slip into a running file
+    /// transfer; a refused write sets a status message and sends nothing.
+    fn write_outbound(&mut self, source: &str, bytes: &[u8]) -> Result<(), OutboundError> {
+        if self.transfer_running() {
+            self.set_status(
+                &format!("{source} not sent while a file transfer runs"),
+                StatusTone::Warn,
+            );
+            return Err(OutboundError::TransferRunning);
+        }
+        let session = self.session.as_mut().ok_or(OutboundError::NotConnected)?;
+        session.write(bytes).map_err(OutboundError::Session)?;
+        self.log_tx(bytes);
+        Ok(())
+    }
+
+    fn log_tx(&mut self, bytes: &[u8]) {
+        self.console.push_tx(bytes);
+        self.capture_chunk(0, PacketDirection::Outbound, bytes);
+        if let Some(websocket) = &self.websocket {
+            websocket.publish_tx(bytes);
+        }
+    }
+
+    fn capture_chunk(&mut self, interface: u32, direction: PacketDirection, bytes: &[u8]) {
+        let Some(capture) = self.capture.as_mut() else {
+            return;
+        };
+        if bytes.is_empty() {
+            return;
+        }
+        if let Err(err) = capture.chunk(interface, direction, bytes) {
+            self.capture = None;
+            self.set_status(&format!("pcapng capture stopped: {err}"), StatusTone::Error);
+        }
+    }
+
+    fn tick_capture(&mut self) {
+        if let Some(Err(err)) = self.capture.as_mut().map(LiveCapture::tick) {
+            self.capture = None;
+            self.set_status(&format!("pcapng capture stopped: {err}"), StatusTone::Error);
+        }
+    }
+
+    fn start_capture(&mut self) {
+        let Some(framing) = self.settings.pcap.framing() else {
+            self.set_status("Invalid pcapng delimiter", StatusTone::Error);
+            return;
+        };
+        let Some(port) = self.selected_port.clone() else {
+            self.set_status("Connect a port before capturing", StatusTone::Warn);
+            return;
+        };
+        let mut interfaces = vec![InterfaceInfo::for_port(&port, &self.config, None)];
+        if self.bridge.is_active() {
+            let port_b = self.bridge.port_b.clone().unwrap_or_default();
+            interfaces.push(InterfaceInfo::for_port(&port_b, &self.config, None));
+        } else if self.session.is_none() {
+            self.set_status("Connect a port before capturing", StatusTone::Warn);
+            return;
+        }
+        let stamp = OffsetDateTime::now_utc()
+            .format(RECORDING_STAMP)
+            .unwrap_or_default();
+        let path = std::path::Path::new(&self.settings.recordings.directory)
+            .join(format!("{}-{stamp}.pcapng", file_stem_for(&port)));
+        match LiveCapture::create(&path, framing, &interfaces) {
+            Ok(capture) => {
+                let note = format!("capturing pcapng to {}", path.display());
+                self.console.push_event(&note);
+                self.set_status(&note, StatusTone::Success);
+                self.capture = Some(capture);
+            }
+            Err(err) => {
+                self.set_status(&format!("pcapng capture failed: {err}"), StatusTone::Error);
+            }
+        }
+    }
+
+    fn stop_capture(&mut self) {
+        let Some(capture) = self.capture.take() else {
+            return;
+        };
+        match capture.finish() {
+            Ok(path) => {
+                let note = format!("pcapng saved to {}", path.display());
+                self.console.push_event(&note);
+                self.set_status(&note, StatusTone::Info);
+            }
+            Err(err) => {
+                self.set_status(&format!("pcapng capture failed: {err}"), StatusTone::Error);
+            }
+        }
+    }
+
+    fn export_recording(&mut self) {
+        let Some(framing) = self.settings.pcap.framing() else {
+            self.set_status("Invalid pcapng delimiter", StatusTone::Error);
+            return;
+        };
+        let source = std::path::PathBuf::from(self.export_source.trim());
+        let output = source.with_extension("pcapng");
+        match pcapng::export_recording(&source, &output, framing) {
+            Ok(chunks) => self.set_status(
+                &format!("Exported {chunks} chunks to {}", output.display()),
+                StatusTone::Success,
+            ),
+            Err(err) => self.set_status(&format!("Export failed: {err}"), StatusTone::Error),
+        }
+    }
+
+    fn poll_replay(&mut self) {
+        let Some(replay) = &self.replay else {
+            return;
+        };
+        for message in replay.poll() {
+            match message {
+                ReplayMessage::Data(bytes) => self.handle_rx(&bytes),
+                ReplayMessage::Error(err) => {
+                    self.replay = None;
+                    self.console.push_event(&format!("replay stopped: {err}"));
+                    self.set_status(&format!("Replay stopped: {err}"), StatusTone::Error);
+                    return;
+                }
+                ReplayMessage::Finished => {
+                    self.console.push_event("replay finished");
+                    self.set_status("Replay finished", StatusTone::Info);
+                }
+            }
+        }
+    }
+
+    fn start_replay(&mut self) {
+        let source = std::path::PathBuf::from(self.replay_source.trim());
+        let records = match recorder::load(&source) {
+            Ok((_, records)) => records,
+            Err(err) => {
+                self.set_status(&format!("Cannot open recording: {err}"), StatusTone::Error);
+                return;
+            }
+        };
+        let (output, target) = match self.settings.replay.target {
+            ReplayTargetKind::Console => (ReplayOutput::Channel, "console".to_string()),
+            ReplayTargetKind::Port => {
+                let Some(port) = self.replay_port.clone() else {
+                    self.set_status("Select a port to replay into", StatusTone::Warn);
+                    return;
+                };
+                if self.session.is_some() && self.selected_port.as_ref() == Some(&port) {
+                    self.set_status("Replay port is the connected port", StatusTone::Warn);
+                    return;
+                }
+                let mut session = match SerialSession::open(&port, &self.config) {
+                    Ok(session) => session,
+                    Err(err) => {
+                        self.set_status(&format!("Replay port failed: {err}"), StatusTone::Error);
+                        return;
+                    }
+                };
+                let sink = move |data: &[u8]| {
+                    // Replies from the host software are not shown; drop them.
+                    session.poll();
+                    session.write(data).map_err(std::io::Error::other)
+                };
+                (ReplayOutput::Sink(Box::new(sink)), port)
+            }
+            #[cfg(not(unix))]
+            ReplayTargetKind::VirtualPort => {
+                self.set_status(
+                    "Virtual ports are not supported on this platform",
+                    StatusTone::Warn,
+                );
+                return;
+            }
+            #[cfg(unix)]
+            ReplayTargetKind::VirtualPort => {
+                let link = self.settings.replay.link.clone();
+                let virtual_port = match VirtualPort::create(&link) {
+                    Ok(virtual_port) => virtual_port,
+                    Err(err) => {
+                        self.set_status(&format!("Replay port failed: {err}"), StatusTone::Error);
+                        return;
+                    }
+                };
+                let sink = move |data: &[u8]| {
+                    virtual_port.publish(data);
+                    Ok(())
+                };
+                (ReplayOutput::Sink(Box::new(sink)), link)
+            }
+        };
+        let replayer = Replayer::start(&records, self.settings.replay.timing(), output);
+        let note = format!(
+            "replaying {} ({} chunks) into {target}",
+            source.display(),
+            replayer.chunks()
+        );
+        self.console.push_event(&note);
+        self.set_status(&note, StatusTone::Success);
+        self.replay = Some(replayer);
+    }
+
+    fn stop_replay(&mut self) {
+        if self.replay.take().is_some() {
+            self.set_status("Replay stopped", StatusTone::Info);
+        }
+    }
+
+    #[cfg(unix)]
+    fn poll_emulator(&mut self) {
+        let Some(emulator) = &self.emulator else {
+            return;
+        };
+        for event in emulator.poll() {
+            match event {
+                EmulatorEvent::Answered { exchange } => {
+                    self.emulator_answered += 1;
+                    self.console
+                        .push_event(&format!("emulator answered request #{}", exchange + 1));
+                }
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn start_emulator(&mut self) {
+        let source = std::path::PathBuf::from(self.replay_source.trim());
+        let script = match recorder::load(&source) {
+            Ok((_, records)) => EmulatorScript::from_records(&records),
+            Err(err) => {
+                self.set_status(&format!("Cannot open recording: {err}"), StatusTone::Error);
+                return;
+            }
+        };
+        if script.exchanges.is_empty() {
+            self.set_status("Recording contains no TX requests", StatusTone::Warn);
+            return;
+        }
+        let requests = script.exchanges.len();
+        match DeviceEmulator::start(script, &self.settings.emulator.link) {
+            Ok(emulator) => {
+                let note = format!(
+                    "emulating {} ({requests} requests) at {}",
+                    source.display(),
+                    emulator.link().display()
+                );
+                self.console.push_event(&note);
+                self.set_status(&note, StatusTone::Success);
+                self.emulator_answered = 0;
+                self.emulator = Some(emulator);
+            }
+            Err(err) => {
+                self.set_status(&format!("Emulator failed: {err}"), StatusTone::Error);
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn stop_emulator(&mut self) {
+        if let Some(mut emulator) = self.emulator.take() {
+            let unmatched = emulator.stop();
+            self.console.push_event(&format!(
+                "emulator stopped: {} requests answered, {unmatched} bytes unmatched",
+                self.emulator_answered
+            ));
+            self.set_status("Emulator stopped", StatusTone::Info);
+        }
+    }
+
+    fn poll_bridge(&mut self) {
+        let Some(bridge) = &self.bridge.bridge else {
+            return;
+        };
+        for message in bridge.poll() {
+            match message {
+                BridgeMessage::Data {
+                    direction,
+                    original,
+                    forwarded,
+                } => {
+                    self.console.push_data(direction.into(), &forwarded);
+                    // Interface 0 is port A, 1 is port B.
+                    let (source, target) = match direction {
+                        BridgeDirection::AToB => (0, 1),
+                        BridgeDirection::BToA => (1, 0),
+                    };
+                    self.capture_chunk(source, PacketDirection::Inbound, &original);
+                    self.capture_chunk(target, PacketDirection::Outbound, &forwarded);
+                    if forwarded != original {
+                        self.console.push_event(&format!(
+                            "{direction} rewritten by rules (original: {})",
+                            console::to_hex(&original)
+                        ));
+                    }
+                    let decoder = self.bridge.decoder_kinds[direction.index()];
+                    for frame in self.bridge.decode(direction, &original) {
+                        self.console.push_event(&format!(
+                            "{direction} {decoder}: {}",
+                            console::to_hex(&frame)
+                        ));
+                    }
+                }
+                BridgeMessage::Event { direction, event } => {
+                    self.console
+                        .push_event(&format!("{direction} {}: {}", event.code, event.message));
+                }
+            }
+        }
+    }
+
+    fn start_bridge(&mut self) {
+        let Some(path_a) = self.selected_port.clone() else {
+            self.set_status("Select port A to bridge", StatusTone::Warn);
+            return;
+        };
+        let Some(path_b) = self.bridge.port_b.clone() else {
+            self.set_status("Select port B to bridge", StatusTone::Warn);
+            return;
+        };
+        if path_a == path_b {
+            self.set_status("Bridge needs two different ports", StatusTone::Warn);
+            return;
+        }
+        if self.session.is_some() {
+            self.disconnect();
+        }
+        match SerialBridge::open(&path_a, &self.config, &path_b, &self.config) {
+            Ok(bridge) => {
+                self.bridge.bridge = Some(bridge);
+                self.bridge.reset_decoders();
+                self.bridge.sync_rules();
+                self.set_status(
+                    &format!("Bridging {path_a} ↔ {path_b}"),
+                    StatusTone::Success,
+                );
+            }
+            Err(err) => {
+                self.set_status(&format!("Bridge failed: {err}"), StatusTone::Error);
+            }
+        }
+    }
+
+    fn stop_bridge(&mut self) {
+        if self.bridge.bridge.take().is_some() {
+            self.set_status("Bridge stopped", StatusTone::Info);
+        }
+    }
+
+    fn connect(&mut self) {
+        let Some(path) = self.selected_port.clone() else {
+            self.set_status("Select a port to connect", StatusTone::Warn);
+            return;
+        };
+        if self.bridge.is_active() {
+            self.set_status("Stop the bridge before connecting", StatusTone::Warn);
+            return;
+        }
+        #[cfg(unix)]
+        if self.settings.daemon.attach {
+            match SerialSession::attach(&self.settings.daemon, &path, &self.config) {
+                Ok(session) => {
+                    // Adopt the daemon's settings so the shared port is not
+                    // reconfigured behind other clients.
+                    self.config = session.config().clone();
+                    self.session = Some(session);
+                    self.set_status(
+                        &format!("Attached to {path} via daemon"),
+                        StatusTone::Success,
+                    );
+                }
+                Err(err) => {
+                    self.set_status(&format!("Attach failed: {err}"), StatusTone::Error);
+                }
+            }
+            return;
+        }
+        match SerialSession::open(&path, &self.config) {
+            Ok(session) => {
+                self.session = Some(session);
+                self.set_status(&format!("Connected to {path}"), StatusTone::Success);
+            }
+            Err(err) => {
+                self.set_status(&format!("Connect failed: {err}"), StatusTone::Error);
+            }
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.cancel_transfer();
+        self.cancel_file_send();
+        self.cancel_test_run("port closed");
+        self.scheduler.stop_all();
+        self.stop_server();
+        self.stop_websocket();
+        #[cfg(unix)]
+        self.stop_virtual_port();
+        self.stop_recording();
+        self.stop_capture();
+        if self.session.is_some() {
+            self.session = None;
+            self.set_status("Disconnected", StatusTone::Info);
+        }
+    }
+
+    /// Sends a macro with its own line ending and the profile's other text options.
+    fn send_macro(&mut self, item: &Macro) {
+        let mut options = self.send_options();
+        options.ending = item.ending;
+        let context = self.templates.context(&item.value);
+        let payload = match item.mode.build(&item.value, &options, None, &context) {
+            Ok(payload) => payload.bytes,
+            Err(err) => {
+                self.set_status(&format!("{}: {err}", item.name), StatusTone::Error);
+                return;
+            }
+        };
+        match self.write_outbound(&item.name, &payload) {
+            Ok(()) => {
+                if item.mode == SendMode::Template {
+                    self.templates.advance(&item.value);
+                }
+                self.set_status(&format!("Sent {}", item.name), StatusTone::Success);
+            }
+            Err(OutboundError::NotConnected) => self.set_status("Not connected", StatusTone::Warn),
+            Err(OutboundError::TransferRunning) => {}
+            Err(OutboundError::Session(SessionError::Truncated)) => {
+                self.set_status("Write truncated", StatusTone::Warn);
+            }
+            Err(OutboundError::Session(err)) => {
+                self.set_status(&format!("Write error: {err}"), StatusTone::Error);
+            }
+        }
+    }
+
+    /// Sends the macro whose hotkey was pressed.
+    ///
+    /// While a text field has focus, only hotkeys with Ctrl or Alt fire.
+    fn macro_hotkeys(&mut self, ctx: &egui::Context) {
+        let Some(profile) = self.settings.profiles.get_active() else {
+            return;
+        };
+        let typing = ctx.wants_keyboard_input();
+        let pressed = profile
+            .macros
+            .iter()
+            .find(|item| {
+                let Some((modifiers, key)) = item.shortcut() else {
+                    return false;
+                };
+                if typing && !(modifiers.command || modifiers.alt) {
+                    return false;
+                }
+                ctx.input_mut(|input| {
+                    input.modifiers.matches_exact(modifiers) && input.consume_key(modifiers, key)
+                })
+            })
+            .cloned();
+        if let Some(item) = pressed {
+            self.send_macro(&item);
+        }
+    }
+
+    /// Turns a history or favorite entry into a macro of the active profile.
+    fn add_macro(&mut self, entry: &send_panel::HistoryEntry) {
+        let Some(profile) = self.settings.profiles.get_active_mut() else {
+            return;
+        };
+        let name: String = entry.value.chars().take(16).collect();
+        let mut item = Macro::new(&name, &entry.value, entry.mode);
+        if entry.mode != SendMode::Hex {
+            item.ending = profile.send.ending;
+        }
+        profile.macros.push(item);
+        self.set_status(&format!("Added macro {name}"), StatusTone::Success);
+        self.mark_dirty();
+    }
+
+    fn send_current_payload(&mut self) {
+        self.preview = None;
+        let options = self.send_options();
+        let context = self.templates.context(&self.send_panel.input);
+        let payload =
+            match self
+                .send_panel
+                .parse_payload(&self.send_panel.input, &options, &context)
+            {
+                Ok(payload) => payload.bytes,
+                Err(PayloadError::InvalidHex) => {
+                    self.set_status("Invalid hex payload", StatusTone::Error);
+                    return;
+                }
+                Err(err) => {
+                    self.set_status(&format!("Invalid payload: {err}"), StatusTone::Error);
+                    return;
+                }
+            };
+
+        match self.write_outbound("Payload", &payload) {
+            Ok(()) => {
+                self.set_status("Payload sent", StatusTone::Success);
+                let value = self.send_panel.input.clone();
+                if self.send_panel.mode == SendMode::Template {
+                    self.templates.advance(&value);
+                }
+                self.send_panel
+                    .push_history(value, self.settings.history.limit);
+                self.store_history();
+            }
+            Err(OutboundError::NotConnected) => self.set_status("Not connected", StatusTone::Warn),
+            Err(OutboundError::TransferRunning) => {}
+            Err(OutboundError::Session(SessionError::Truncated)) => {
+                self.set_status("Write truncated", StatusTone::Warn);
+            }
+            Err(OutboundError::Session(err)) => {
+                self.set_status(&format!("Write error: {err}"), StatusTone::Error);
+            }
+        }
+    }
+
+    fn set_status(&mut self, message: &str, tone: StatusTone) {
+        self.status = Some(StatusBanner {
+            message: message.to_string(),
+            tone,
+            created: Instant::now(),
+        });
+    }
+
+    fn status_pill(&self, ui: &mut egui::Ui) {
+        let (label, tone) = if self.session.is_some() {
+            ("Connected", StatusTone::Success)
+        } else {
+            ("Disconnected", StatusTone::Warn)
+        };
+        Frame::none()
+            .fill(tone.color())
+            .rounding(Rounding::same(10.0))
+            .inner_margin(Margin::symmetric(12.0, 6.0))
+            .show(ui, |ui| {
+                ui.label(RichText::new(label).color(Color32::WHITE).strong());
+            });
+    }
+
+    fn show_status_banner(&mut self, ui: &mut egui::Ui) {
+        if let Some(banner) = &self.status {
+            if banner.created.elapsed() < Duration::from_secs(6) {
+                Frame::none()
+                    .fill(banner.tone.color())
+                    .rounding(Rounding::same(10.0))
+                    .inner_margin(Margin::symmetric(12.0, 6.0))
+                    .show(ui, |ui| {
+                        ui.label(
+                            RichText::new(&banner.message)
+                                .color(Color32::WHITE)
+                                .strong(),
+                        );
+                    });
+                return;
+            }
+        }
+        self.status = None;
+    }
+
+    fn save_settings_if_needed(&mut self) {
+        if self.settings_dirty && self.last_save.elapsed() > Duration::from_secs(1) {
+            if let Err(err) = self.settings.save() {
+                self.set_status(&format!("Settings save failed: {err}"), StatusTone::Error);
+            }
+            self.last_save = Instant::now();
+            self.settings_dirty = false;
+        }
+    }
+
+    fn devices_panel(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Devices");
+        ui.horizontal(|ui| {
+            if ui
+                .add(egui::Button::new(
+                    RichText::new("Refresh").text_style(egui::TextStyle::Button),
+                ))
+                .clicked()
+            {
+                self.scanner.refresh();
+            }
+            ui.hyperlink_to("Help", HELP_URL);
+            if let Some(result) = self.scanner.last_result() {
+                ui.label(format!(
+                    "Last scan {:.1?} ago ({:.1?})",
+                    result.completed_at.elapsed(),
+                    result.duration
+                ));
+            }
+        });
+
+        if self.ports.is_empty() {
+            ui.spacing_mut().item_spacing.y = 8.0;
+            ui.label("No serial devices detected.");
+        }
+
+        for port in &self.ports {
+            let selected = self.selected_port.as_deref() == Some(&port.path);
+            let label = if port.description.is_empty() {
+                port.path.clone()
+            } else {
+                format!("{}\n{}", port.description, port.path)
+            };
+            let response = ui.selectable_label(selected, label);
+            if response.clicked() {
+                self.selected_port = Some(port.path.clone());
+            }
+        }
+
+        ui.separator();
+        ui.label(RichText::new("Remote").strong());
+        let mut removed = None;
+        for (index, target) in self.settings.remote_targets.iter().enumerate() {
+            ui.horizontal(|ui| {
+                let selected = self.selected_port.as_deref() == Some(target.as_str());
+                if ui.selectable_label(selected, target).clicked() {
+                    self.selected_port = Some(target.clone());
+                }
+                if ui.small_button("✕").on_hover_text("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            let target = self.settings.remote_targets.remove(index);
+            if self.selected_port.as_deref() == Some(target.as_str()) {
+                self.selected_port = None;
+            }
+            self.mark_dirty();
+        }
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.remote_input).hint_text("rfc2217://host:port"),
+            );
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("Add").clicked() || submitted {
+                self.add_remote_target();
+            }
+        });
+    }
+
+    fn add_remote_target(&mut self) {
+        let Some(target) = RemoteTarget::parse(&self.remote_input) else {
+            self.set_status(
+                "Remote targets look like tcp://host:port or rfc2217://host:port",
+                StatusTone::Warn,
+            );
+            return;
+        };
+        let target = target.to_string();
+        if !self.settings.remote_targets.contains(&target) {
+            self.settings.remote_targets.push(target.clone());
+            self.mark_dirty();
+        }
+        self.selected_port = Some(target);
+        self.remote_input.clear();
+    }
+
+    fn empty_state(&mut self, ui: &mut egui::Ui) {
+        if !self.ports.is_empty() {
+            return;
+        }
+        ui.vertical_centered(|ui| {
+            ui.add_space(32.0);
+            egui::Frame::group(ui.style())
+                .fill(Color32::from_rgb(25, 90, 140).gamma_multiply(0.15))
+                .show(ui, |ui| {
+                    ui.vertical_centered(|ui| {
+                        ui.heading("No serial devices detected");
+                        ui.label("Connect a device or check permissions.");
+                        if ui.button("Refresh").clicked() {
+                            self.scanner.refresh();
+                        }
+                        ui.hyperlink_to("Help with permissions", HELP_URL);
+                    });
+                });
+            ui.add_space(24.0);
+        });
+    }
+
+    fn configuration_panel(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Connection", |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Port");
+                ComboBox::from_id_source("port_combo")
+                    .selected_text(
+                        self.selected_port
+                            .as_deref()
+                            .map(|p| p.to_string())
+                            .unwrap_or_else(|| "Select".to_string()),
+                    )
+                    .show_ui(ui, |ui| {
+                        for port in &self.ports {
+                            ui.selectable_value(
+                                &mut self.selected_port,
                                 Some(port.path.clone()),
                                 format!("{} ({})", port.description, port.path),
                             );
                         }
+                        for target in &self.settings.remote_targets {
+                            ui.selectable_value(
+                                &mut self.selected_port,
+                                Some(target.clone()),
+                                target.as_str(),
+                            );
+                        }
+                    });
+            });
+
+            #[cfg(unix)]
+            self.daemon_row(ui);
+
+            ui.separator();
+            self.baud_row(ui);
+            self.data_bits_row(ui);
+            self.parity_row(ui);
+            self.stop_bits_row(ui);
+            self.flow_control_row(ui);
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Connect").clicked() {
+                    self.connect();
+                }
+                if ui.button("Disconnect").clicked() {
+                    self.disconnect();
+                }
+                self.status_pill(ui);
+            });
+        });
+
+        ui.separator();
+        ui.collapsing("Recording", |ui| {
+            self.recording_panel(ui);
+        });
+
+        ui.separator();
+        ui.collapsing("Scripts", |ui| {
+            self.scripts_panel(ui);
+        });
+
+        ui.separator();
+        ui.collapsing("Triggers", |ui| {
+            self.triggers_panel(ui);
+        });
+
+        ui.separator();
+        ui.collapsing("Tests", |ui| {
+            self.tests_panel(ui);
+        });
+
+        ui.separator();
+        ui.collapsing("File transfer", |ui| {
+            self.transfer_panel(ui);
+        });
+
+        ui.separator();
+        ui.collapsing("Replay", |ui| {
+            self.replay_panel(ui);
+            #[cfg(unix)]
+            {
+                ui.separator();
+                self.emulator_panel(ui);
+            }
+        });
+
+        ui.separator();
+        ui.collapsing("Wireshark (pcapng)", |ui| {
+            self.pcap_panel(ui);
+        });
+
+        ui.separator();
+        ui.collapsing("Network server", |ui| {
+            self.server_panel(ui);
+        });
+
+        ui.separator();
+        ui.collapsing("WebSocket stream", |ui| {
+            self.websocket_panel(ui);
+        });
+
+        #[cfg(unix)]
+        {
+            ui.separator();
+            ui.collapsing("Virtual port", |ui| {
+                self.virtual_port_panel(ui);
+            });
+        }
+
+        ui.separator();
+        ui.collapsing("Bridge", |ui| {
+            self.bridge_panel(ui);
+        });
+
+        ui.separator();
+        ui.collapsing("Profiles", |ui| {
+            self.profiles_ui(ui);
+        });
+    }
+
+    fn recording_panel(&mut self, ui: &mut egui::Ui) {
+        let status = self
+            .session
+            .as_ref()
+            .and_then(|session| session.recording());
+        ui.add_enabled_ui(status.is_none(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Directory");
+                if ui
+                    .text_edit_singleline(&mut self.settings.recordings.directory)
+                    .changed()
+                {
+                    self.settings_dirty = true;
+                }
+            });
+        });
+        match status {
+            Some(status) => {
+                ui.label(format!("● {}", status.path.display()));
+                ui.label(format!(
+                    "{} records, {} bytes, {:.0?}",
+                    status.records, status.bytes, status.elapsed
+                ));
+                if ui.button("Stop recording").clicked() {
+                    self.stop_recording();
+                }
+            }
+            None => {
+                let connected = self.session.is_some();
+                if ui
+                    .add_enabled(connected, egui::Button::new("Start recording"))
+                    .clicked()
+                {
+                    self.start_recording();
+                }
+            }
+        }
+    }
+
+    fn replay_panel(&mut self, ui: &mut egui::Ui) {
+        let idle = self.replay.is_none();
+        ui.add_enabled_ui(idle, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Recording");
+                ui.text_edit_singleline(&mut self.replay_source);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Into");
+                let mut target = self.settings.replay.target;
+                ComboBox::from_id_source("replay_target")
+                    .selected_text(target.to_string())
+                    .show_ui(ui, |ui| {
+                        for option in ReplayTargetKind::iter().filter(|kind| kind.is_supported()) {
+                            ui.selectable_value(&mut target, option, option.to_string());
+                        }
+                    });
+                if target != self.settings.replay.target {
+                    self.settings.replay.target = target;
+                    self.settings_dirty = true;
+                }
+                match target {
+                    ReplayTargetKind::Console => {}
+                    ReplayTargetKind::Port => {
+                        ComboBox::from_id_source("replay_port")
+                            .selected_text(self.replay_port.as_deref().unwrap_or("Select"))
+                            .show_ui(ui, |ui| {
+                                for port in &self.ports {
+                                    ui.selectable_value(
+                                        &mut self.replay_port,
+                                        Some(port.path.clone()),
+                                        format!("{} ({})", port.description, port.path),
+                                    );
+                                }
+                            });
+                    }
+                    ReplayTargetKind::VirtualPort => {
+                        if ui
+                            .text_edit_singleline(&mut self.settings.replay.link)
+                            .changed()
+                        {
+                            self.settings_dirty = true;
+                        }
+                    }
+                }
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.label("Timing");
+            let mut timing = self.settings.replay.timing;
+            ComboBox::from_id_source("replay_timing")
+                .selected_text(timing.to_string())
+                .show_ui(ui, |ui| {
+                    for option in ReplayTimingKind::iter() {
+                        ui.selectable_value(&mut timing, option, option.to_string());
+                    }
+                });
+            let mut changed = timing != self.settings.replay.timing;
+            self.settings.replay.timing = timing;
+            if timing == ReplayTimingKind::Scaled {
+                let drag = egui::DragValue::new(&mut self.settings.replay.speed)
+                    .clamp_range(0.1..=100.0)
+                    .speed(0.1)
+                    .suffix("×");
+                changed |= ui.add(drag).changed();
+            }
+            if changed {
+                self.settings_dirty = true;
+                if let Some(replay) = &self.replay {
+                    replay.set_timing(self.settings.replay.timing());
+                }
+            }
+        });
+        let Some(replay) = &self.replay else {
+            if ui.button("Start replay").clicked() {
+                self.start_replay();
+            }
+            return;
+        };
+        let duration = replay.duration().as_secs_f64();
+        let mut position = replay.position().as_secs_f64();
+        ui.add(
+            egui::ProgressBar::new(replay.progress())
+                .text(format!("{position:.1} s / {duration:.1} s")),
+        );
+        if ui
+            .add(egui::Slider::new(&mut position, 0.0..=duration).show_value(false))
+            .changed()
+        {
+            replay.seek(Duration::from_secs_f64(position));
+        }
+        let mut stop = false;
+        ui.horizontal(|ui| {
+            let paused = replay.is_paused();
+            if ui.button(if paused { "Resume" } else { "Pause" }).clicked() {
+                replay.set_paused(!paused);
+            }
+            stop = ui.button("Stop replay").clicked();
+        });
+        ui.ctx().request_repaint();
+        if stop {
+            self.stop_replay();
+        }
+    }
+
+    #[cfg(unix)]
+    fn emulator_panel(&mut self, ui: &mut egui::Ui) {
+        let running = self.emulator.is_some();
+        ui.add_enabled_ui(!running, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Emulator link");
+                if ui
+                    .text_edit_singleline(&mut self.settings.emulator.link)
+                    .changed()
+                {
+                    self.settings_dirty = true;
+                }
+            });
+        });
+        match &self.emulator {
+            Some(emulator) => {
+                ui.label(format!(
+                    "{} → {}",
+                    emulator.link().display(),
+                    emulator.slave_path().display()
+                ));
+                ui.label(format!("{} requests answered", self.emulator_answered));
+                if ui.button("Stop emulator").clicked() {
+                    self.stop_emulator();
+                }
+            }
+            None => {
+                if ui.button("Emulate this capture").clicked() {
+                    self.start_emulator();
+                }
+            }
+        }
+    }
+
+    fn pcap_panel(&mut self, ui: &mut egui::Ui) {
+        ui.add_enabled_ui(self.capture.is_none(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Packets");
+                let mut framing = self.settings.pcap.framing;
+                ComboBox::from_id_source("pcap_framing")
+                    .selected_text(framing.to_string())
+                    .show_ui(ui, |ui| {
+                        for option in FramingKind::iter() {
+                            ui.selectable_value(&mut framing, option, option.to_string());
+                        }
+                    });
+                if framing != self.settings.pcap.framing {
+                    self.settings.pcap.framing = framing;
+                    self.settings_dirty = true;
+                }
+                match framing {
+                    FramingKind::Chunk => {}
+                    FramingKind::Delimiter => {
+                        let edit =
+                            egui::TextEdit::singleline(&mut self.settings.pcap.delimiter_hex)
+                                .desired_width(60.0)
+                                .hint_text("0D 0A");
+                        if ui.add(edit).changed() {
+                            self.settings_dirty = true;
+                        }
+                    }
+                    FramingKind::IdleGap => {
+                        let drag = egui::DragValue::new(&mut self.settings.pcap.idle_gap_ms)
+                            .clamp_range(1..=10_000)
+                            .suffix(" ms");
+                        if ui.add(drag).changed() {
+                            self.settings_dirty = true;
+                        }
+                    }
+                }
+            });
+        });
+        match &self.capture {
+            Some(capture) => {
+                ui.label(format!(
+                    "● {} ({} chunks)",
+                    capture.path().display(),
+                    capture.chunks()
+                ));
+                if ui.button("Stop pcapng capture").clicked() {
+                    self.stop_capture();
+                }
+            }
+            None => {
+                let live = self.session.is_some() || self.bridge.is_active();
+                if ui
+                    .add_enabled(live, egui::Button::new("Start pcapng capture"))
+                    .clicked()
+                {
+                    self.start_capture();
+                }
+            }
+        }
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.export_source).hint_text("recording.msrec"),
+            );
+            if ui.button("Export to pcapng").clicked() {
+                self.export_recording();
+            }
+        });
+    }
+
+    fn server_panel(&mut self, ui: &mut egui::Ui) {
+        let running = self.server.is_some();
+        ui.add_enabled_ui(!running, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Bind");
+                if ui
+                    .text_edit_singleline(&mut self.settings.server.bind)
+                    .changed()
+                {
+                    self.settings_dirty = true;
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Protocol");
+                let mut protocol = self.settings.server.protocol;
+                ComboBox::from_id_source("server_protocol")
+                    .selected_text(protocol.to_string())
+                    .show_ui(ui, |ui| {
+                        for option in ServerProtocol::iter() {
+                            ui.selectable_value(&mut protocol, option, option.to_string());
+                        }
+                    });
+                if protocol != self.settings.server.protocol {
+                    self.settings.server.protocol = protocol;
+                    self.settings_dirty = true;
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Token");
+                ui.add(egui::TextEdit::singleline(&mut self.server_token).password(true));
+            });
+        });
+        ui.horizontal(|ui| {
+            if running {
+                if ui.button("Stop server").clicked() {
+                    self.stop_server();
+                }
+            } else if ui.button("Start server").clicked() {
+                self.start_server();
+            }
+        });
+        if let Some(server) = &self.server {
+            ui.label(format!("Listening on {}", server.local_addr()));
+            let clients = server.clients();
+            if clients.is_empty() {
+                ui.label("No clients connected");
+            }
+            for (peer, role) in clients {
+                ui.label(format!("{peer} ({role})"));
+            }
+        }
+    }
+
+    fn triggers_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(profile) = self.settings.profiles.get_active_mut() else {
+            return;
+        };
+        ui.weak(format!("Rules for profile {}", profile.name));
+        let rules = &mut profile.triggers;
+        let mut changed = false;
+        let mut removed = None;
+        for (index, rule) in rules.iter_mut().enumerate() {
+            ui.push_id(("trigger", index), |ui| {
+                Frame::group(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        changed |= ui.checkbox(&mut rule.enabled, "").changed();
+                        changed |= ui.text_edit_singleline(&mut rule.name).changed();
+                        if ui.small_button("Remove").clicked() {
+                            removed = Some(index);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let mut kind = rule.kind;
+                        ComboBox::from_id_source("trigger_kind")
+                            .selected_text(kind.to_string())
+                            .width(70.0)
+                            .show_ui(ui, |ui| {
+                                for option in PatternKind::iter() {
+                                    ui.selectable_value(&mut kind, option, option.to_string());
+                                }
+                            });
+                        changed |= kind != rule.kind;
+                        rule.kind = kind;
+                        changed |= ui.text_edit_singleline(&mut rule.pattern).changed();
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Debounce");
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut rule.debounce_ms)
+                                    .clamp_range(0..=600_000)
+                                    .suffix(" ms"),
+                            )
+                            .changed();
+                        if rule.debounce() > Duration::from_millis(rule.debounce_ms) {
+                            ui.weak(format!(
+                                "{} ms while the rule sends",
+                                rule.debounce().as_millis()
+                            ));
+                        }
                     });
+                    changed |= trigger_actions_editor(ui, &mut rule.actions);
+                    if let Some(problem) = rule.problem() {
+                        ui.colored_label(StatusTone::Error.color(), problem);
+                    }
+                });
             });
+        }
+        if let Some(index) = removed {
+            rules.remove(index);
+            changed = true;
+        }
+        if ui.button("Add rule").clicked() {
+            let name = format!("Rule {}", rules.len() + 1);
+            rules.push(TriggerRule::new(name));
+            changed = true;
+        }
+        if changed {
+            self.triggers_profile = None;
+            self.mark_dirty();
+        }
+    }
 
-            ui.separator();
-            self.baud_row(ui);
-            self.data_bits_row(ui);
-            self.parity_row(ui);
-            self.stop_bits_row(ui);
-            self.flow_control_row(ui);
+    fn tests_panel(&mut self, ui: &mut egui::Ui) {
+        let running = self.test_run.as_ref().is_some_and(|run| !run.is_finished());
+        ui.add_enabled_ui(!running, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Test file");
+                ui.text_edit_singleline(&mut self.test_file);
+            });
+        });
+        ui.horizontal(|ui| {
+            if running {
+                if ui.button("Cancel").clicked() {
+                    self.cancel_test_run("cancelled");
+                }
+            } else if ui.button("Run tests").clicked() {
+                self.start_test_run();
+            }
+            if !running && self.test_run.is_some() && ui.button("Export JUnit").clicked() {
+                self.export_junit();
+            }
+        });
+        let Some(run) = &self.test_run else {
+            return;
+        };
+        for (case, result) in run.suite().cases.iter().zip(run.results()) {
+            let (mark, tone) = match &result.status {
+                CaseStatus::Pending => ("·", StatusTone::Info),
+                CaseStatus::Running => ("▶", StatusTone::Info),
+                CaseStatus::Passed => ("✔", StatusTone::Success),
+                CaseStatus::Failed(_) | CaseStatus::Error(_) => ("✘", StatusTone::Error),
+                CaseStatus::Skipped => ("–", StatusTone::Warn),
+            };
+            ui.horizontal(|ui| {
+                ui.colored_label(tone.color(), mark);
+                ui.label(&result.name);
+                match &result.status {
+                    CaseStatus::Running => {
+                        ui.weak(format!("step {}/{}", result.step + 1, case.steps.len()));
+                    }
+                    CaseStatus::Passed | CaseStatus::Failed(_) | CaseStatus::Error(_) => {
+                        ui.weak(format!("{} ms", result.duration.as_millis()));
+                    }
+                    _ => {}
+                }
+            });
+            if let CaseStatus::Failed(message) | CaseStatus::Error(message) = &result.status {
+                ui.colored_label(tone.color(), format!("   {message}"));
+            }
+        }
+    }
 
-            ui.separator();
+    fn transfer_panel(&mut self, ui: &mut egui::Ui) {
+        let running = self.transfer_running();
+        let mut changed = false;
+        ui.add_enabled_ui(!running, |ui| {
+            let settings = &mut self.settings.transfer;
             ui.horizontal(|ui| {
-                if ui.button("Connect").clicked() {
-                    self.connect();
+                ui.label("Protocol");
+                ComboBox::from_id_source("transfer_protocol")
+                    .selected_text(settings.protocol.to_string())
+                    .show_ui(ui, |ui| {
+                        for protocol in TransferProtocol::iter() {
+                            changed |= ui
+                                .selectable_value(
+                                    &mut settings.protocol,
+                                    protocol,
+                                    protocol.to_string(),
+                                )
+                                .changed();
+                        }
+                    });
+            });
+            ui.horizontal(|ui| {
+                for direction in TransferDirection::iter() {
+                    changed |= ui
+                        .radio_value(&mut settings.direction, direction, direction.to_string())
+                        .changed();
                 }
-                if ui.button("Disconnect").clicked() {
-                    self.disconnect();
+            });
+            match settings.direction {
+                TransferDirection::Send => {
+                    ui.label("Files, one path per line");
+                    changed |= ui.text_edit_multiline(&mut settings.files).changed();
+                }
+                TransferDirection::Receive => {
+                    ui.horizontal(|ui| {
+                        ui.label("Save to");
+                        changed |= ui.text_edit_singleline(&mut settings.destination).changed();
+                    });
+                }
+            }
+        });
+        if changed {
+            self.mark_dirty();
+        }
+        ui.horizontal(|ui| {
+            if running {
+                if ui.button("Cancel").clicked() {
+                    self.cancel_transfer();
+                }
+            } else if ui.button("Start").clicked() {
+                self.start_transfer();
+            }
+            if self.transfer.is_some() && !self.transfer_open && ui.button("Progress").clicked() {
+                self.transfer_open = true;
+            }
+        });
+    }
+
+    fn transfer_dialog(&mut self, ctx: &egui::Context) {
+        let Some(transfer) = &self.transfer else {
+            return;
+        };
+        let mut cancel = false;
+        let progress = transfer.progress();
+        let running = transfer.is_running();
+        egui::Window::new(format!("{} transfer", transfer.protocol()))
+            .open(&mut self.transfer_open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if progress.file.is_empty() {
+                    ui.label("Waiting for the other side…");
+                } else {
+                    ui.label(format!("File: {}", progress.file));
+                }
+                let (fraction, text) = match progress.total {
+                    Some(total) if total > 0 => (
+                        progress.bytes as f32 / total as f32,
+                        format!("{} / {total} bytes", progress.bytes),
+                    ),
+                    _ => (0.0, format!("{} bytes", progress.bytes)),
+                };
+                ui.add(egui::ProgressBar::new(fraction).text(text));
+                ui.label(format!(
+                    "Files done: {}   Retries: {}",
+                    progress.files_done, progress.retries
+                ));
+                let (state, tone) = match transfer.state() {
+                    TransferState::Running => ("Running".to_string(), StatusTone::Info),
+                    TransferState::Completed => ("Completed".to_string(), StatusTone::Success),
+                    TransferState::Failed(reason) => {
+                        (format!("Failed: {reason}"), StatusTone::Error)
+                    }
+                    TransferState::Cancelled => ("Cancelled".to_string(), StatusTone::Warn),
+                };
+                ui.colored_label(tone.color(), state);
+                if !progress.errors.is_empty() {
+                    ui.separator();
+                    egui::ScrollArea::vertical()
+                        .max_height(120.0)
+                        .stick_to_bottom(true)
+                        .show(ui, |ui| {
+                            for error in &progress.errors {
+                                ui.weak(error);
+                            }
+                        });
+                }
+                if running {
+                    ui.separator();
+                    cancel = ui.button("Cancel").clicked();
+                }
+            });
+        if cancel {
+            self.cancel_transfer();
+        }
+    }
+
+    fn file_send_dialog(&mut self, ctx: &egui::Context) {
+        let mut open = self.file_send_open;
+        let running = self.file_send.as_ref().is_some_and(FileSender::is_running);
+        let transferring = self.transfer_running();
+        let mut changed = false;
+        let mut start = false;
+        let mut cancel = false;
+        egui::Window::new("Send file")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let settings = &mut self.settings.file_send;
+                ui.add_enabled_ui(!running, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("File");
+                        changed |= ui.text_edit_singleline(&mut settings.path).changed();
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Chunk");
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut settings.chunk_size)
+                                    .clamp_range(1..=65_536)
+                                    .suffix(" bytes"),
+                            )
+                            .changed();
+                        ui.label("every");
+                        changed |= ui
+                            .add(egui::DragValue::new(&mut settings.chunk_delay_ms).suffix(" ms"))
+                            .changed();
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Between characters");
+                        changed |= ui
+                            .add(egui::DragValue::new(&mut settings.char_delay_ms).suffix(" ms"))
+                            .changed();
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Line endings");
+                        ComboBox::from_id_source("file_send_translation")
+                            .selected_text(settings.translation.to_string())
+                            .show_ui(ui, |ui| {
+                                for translation in EndingTranslation::iter() {
+                                    changed |= ui
+                                        .selectable_value(
+                                            &mut settings.translation,
+                                            translation,
+                                            translation.to_string(),
+                                        )
+                                        .changed();
+                                }
+                            });
+                    });
+                    changed |= ui
+                        .checkbox(&mut settings.line_mode, "Line by line")
+                        .changed();
+                    ui.add_enabled_ui(settings.line_mode, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Wait for");
+                            changed |= ui.text_edit_singleline(&mut settings.response).changed();
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Timeout");
+                            changed |= ui
+                                .add(
+                                    egui::DragValue::new(&mut settings.response_timeout_ms)
+                                        .suffix(" ms"),
+                                )
+                                .changed();
+                        });
+                    });
+                });
+                ui.separator();
+                if let Some(sender) = &self.file_send {
+                    let total = sender.total().max(1);
+                    ui.add(
+                        egui::ProgressBar::new(sender.sent() as f32 / total as f32).text(format!(
+                            "{} / {} bytes",
+                            sender.sent(),
+                            sender.total()
+                        )),
+                    );
+                    if let Some((line, lines)) = sender.line() {
+                        let waiting = if sender.is_waiting() {
+                            " (waiting for response)"
+                        } else {
+                            ""
+                        };
+                        ui.label(format!("Line {line} of {lines}{waiting}"));
+                    }
+                    match sender.state() {
+                        TransferState::Failed(reason) => {
+                            ui.colored_label(StatusTone::Error.color(), reason);
+                        }
+                        TransferState::Completed => {
+                            ui.colored_label(StatusTone::Success.color(), "Sent");
+                        }
+                        TransferState::Cancelled => {
+                            ui.colored_label(StatusTone::Warn.color(), "Cancelled");
+                        }
+                        TransferState::Running => {}
+                    }
+                }
+                ui.horizontal(|ui| {
+                    if running {
+                        cancel = ui.button("Cancel").clicked();
+                    } else {
+                        start = ui
+                            .add_enabled(!transferring, egui::Button::new("Send"))
+                            .clicked();
+                    }
+                });
+            });
+        self.file_send_open = open;
+        if changed {
+            self.mark_dirty();
+        }
+        if start {
+            self.start_file_send();
+        }
+        if cancel {
+            self.cancel_file_send();
+        }
+    }
+
+    fn scripts_panel(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal_wrapped(|ui| {
+            let mut opened = None;
+            for (index, script) in self.settings.scripts.scripts.iter().enumerate() {
+                let selected = self.script_selected == Some(index);
+                if ui.selectable_label(selected, &script.name).clicked() {
+                    opened = Some(index);
+                }
+            }
+            if let Some(index) = opened {
+                self.script_selected = Some(index);
+                self.script_draft = self.settings.scripts.scripts[index].clone();
+            }
+            if ui.small_button("New").clicked() {
+                self.script_selected = None;
+                self.script_draft = SavedScript::default();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut self.script_draft.name);
+            ui.checkbox(&mut self.script_draft.pinned, "Button")
+                .on_hover_text("Show a button for this script under the send bar");
+        });
+        ui.add(
+            egui::TextEdit::multiline(&mut self.script_draft.source)
+                .code_editor()
+                .desired_rows(8)
+                .hint_text(
+                    "send(\"AT\\r\");\nlet reply = wait_for(\"OK|ERROR\", 1000);\nlog(reply);",
+                ),
+        );
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                let draft = self.script_draft.clone();
+                match self.script_selected {
+                    Some(index) => self.settings.scripts.scripts[index] = draft,
+                    None => {
+                        self.settings.scripts.scripts.push(draft);
+                        self.script_selected = Some(self.settings.scripts.scripts.len() - 1);
+                    }
+                }
+                self.mark_dirty();
+            }
+            if let Some(index) = self.script_selected
+                && ui.button("Delete").clicked()
+            {
+                self.settings.scripts.scripts.remove(index);
+                self.script_selected = None;
+                self.mark_dirty();
+            }
+            match &self.script {
+                Some(script) => {
+                    ui.label(format!("Running {}", script.name()));
+                    if ui.button("Cancel").clicked() {
+                        self.cancel_script();
+                    }
+                }
+                None => {
+                    if ui.button("Run").clicked() {
+                        let draft = self.script_draft.clone();
+                        self.run_script(&draft);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Buttons for pinned scripts, shown under the send bar.
+    fn script_buttons(&mut self, ui: &mut egui::Ui) {
+        let pinned: Vec<SavedScript> = self
+            .settings
+            .scripts
+            .scripts
+            .iter()
+            .filter(|script| script.pinned)
+            .cloned()
+            .collect();
+        if pinned.is_empty() {
+            return;
+        }
+        ui.horizontal_wrapped(|ui| {
+            for script in &pinned {
+                let running = self
+                    .script
+                    .as_ref()
+                    .is_some_and(|runner| runner.name() == script.name.trim());
+                let label = if running {
+                    format!("■ {}", script.name)
+                } else {
+                    format!("▶ {}", script.name)
+                };
+                if ui.button(label).clicked() {
+                    if running {
+                        self.cancel_script();
+                    } else {
+                        self.run_script(script);
+                    }
+                }
+            }
+        });
+    }
+
+    fn websocket_panel(&mut self, ui: &mut egui::Ui) {
+        let running = self.websocket.is_some();
+        ui.add_enabled_ui(!running, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Bind");
+                if ui
+                    .text_edit_singleline(&mut self.settings.websocket.bind)
+                    .changed()
+                {
+                    self.settings_dirty = true;
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Write token");
+                ui.add(egui::TextEdit::singleline(&mut self.websocket_token).password(true))
+                    .on_hover_text("Clients without it can only watch");
+            });
+        });
+        ui.horizontal(|ui| {
+            if running {
+                if ui.button("Stop stream").clicked() {
+                    self.stop_websocket();
+                }
+            } else if ui.button("Start stream").clicked() {
+                self.start_websocket();
+            }
+        });
+        if let Some(websocket) = &self.websocket {
+            ui.label(format!("ws://{}", websocket.local_addr()));
+            let clients = websocket.clients();
+            if clients.is_empty() {
+                ui.label("No clients connected");
+            }
+            for (peer, writable) in clients {
+                let access = if writable { "read-write" } else { "read-only" };
+                ui.label(format!("{peer} ({access})"));
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn virtual_port_panel(&mut self, ui: &mut egui::Ui) {
+        let running = self.virtual_port.is_some();
+        ui.add_enabled_ui(!running, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Link");
+                if ui
+                    .text_edit_singleline(&mut self.settings.virtual_port.link)
+                    .changed()
+                {
+                    self.settings_dirty = true;
+                }
+            });
+        });
+        ui.horizontal(|ui| {
+            if running {
+                if ui.button("Stop sharing").clicked() {
+                    self.stop_virtual_port();
+                }
+            } else if ui.button("Share as virtual port").clicked() {
+                self.start_virtual_port();
+            }
+        });
+        if let Some(virtual_port) = &self.virtual_port {
+            ui.label(format!(
+                "{} → {}",
+                virtual_port.link().display(),
+                virtual_port.slave_path().display()
+            ));
+            let dropped = virtual_port.dropped();
+            if dropped > 0 {
+                ui.label(format!("{dropped} bytes dropped (nobody reading)"));
+            }
+        }
+    }
+
+    fn bridge_panel(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!(
+            "Port A: {}",
+            self.selected_port.as_deref().unwrap_or("select above")
+        ));
+        ui.horizontal(|ui| {
+            ui.label("Port B");
+            ComboBox::from_id_source("bridge_port_b")
+                .selected_text(self.bridge.port_b.as_deref().unwrap_or("Select"))
+                .show_ui(ui, |ui| {
+                    for port in &self.ports {
+                        ui.selectable_value(
+                            &mut self.bridge.port_b,
+                            Some(port.path.clone()),
+                            format!("{} ({})", port.description, port.path),
+                        );
+                    }
+                });
+        });
+        for direction in BridgeDirection::iter() {
+            let index = direction.index();
+            ui.horizontal(|ui| {
+                ui.label(format!("{direction} decoder"));
+                ComboBox::from_id_source(("bridge_decoder", index))
+                    .selected_text(self.bridge.decoder_kinds[index].to_string())
+                    .show_ui(ui, |ui| {
+                        for kind in DecoderKind::iter() {
+                            ui.selectable_value(
+                                &mut self.bridge.decoder_kinds[index],
+                                kind,
+                                kind.to_string(),
+                            );
+                        }
+                    });
+            });
+        }
+
+        ui.separator();
+        ui.label("Rules");
+        let mut remove = None;
+        let mut changed = false;
+        for (index, rule) in self.bridge.rules.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                changed |= ui.checkbox(&mut rule.enabled, "").changed();
+                let action = match &rule.action {
+                    RuleAction::Drop => "drop".to_string(),
+                    RuleAction::Replace(bytes) => format!("→ {}", console::to_hex(bytes)),
+                };
+                ui.label(format!(
+                    "[{}] {} {action}",
+                    rule.scope,
+                    console::to_hex(&rule.pattern)
+                ));
+                if ui.small_button("✕").clicked() {
+                    remove = Some(index);
+                }
+            });
+        }
+        if let Some(index) = remove {
+            self.bridge.rules.remove(index);
+            changed = true;
+        }
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source("bridge_rule_scope")
+                .selected_text(self.bridge.rule_scope.to_string())
+                .show_ui(ui, |ui| {
+                    for scope in RuleScope::iter() {
+                        ui.selectable_value(&mut self.bridge.rule_scope, scope, scope.to_string());
+                    }
+                });
+            ui.checkbox(&mut self.bridge.rule_hex, "Hex");
+            ui.checkbox(&mut self.bridge.rule_drop, "Drop");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Match");
+            ui.text_edit_singleline(&mut self.bridge.rule_pattern);
+        });
+        if !self.bridge.rule_drop {
+            ui.horizontal(|ui| {
+                ui.label("Replace");
+                ui.text_edit_singleline(&mut self.bridge.rule_replacement);
+            });
+        }
+        if ui.button("Add rule").clicked() {
+            match self.parse_bridge_rule() {
+                Ok(rule) => {
+                    self.bridge.rules.push(rule);
+                    self.bridge.rule_pattern.clear();
+                    self.bridge.rule_replacement.clear();
+                    changed = true;
+                }
+                Err(message) => self.set_status(message, StatusTone::Error),
+            }
+        }
+        if changed {
+            self.bridge.sync_rules();
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            if self.bridge.is_active() {
+                if ui.button("Stop bridge").clicked() {
+                    self.stop_bridge();
+                }
+            } else if ui.button("Start bridge").clicked() {
+                self.start_bridge();
+            }
+        });
+    }
+
+    fn parse_bridge_rule(&self) -> Result<BridgeRule, &'static str> {
+        let parse = |value: &str| -> Result<Vec<u8>, &'static str> {
+            if self.bridge.rule_hex {
+                send_panel::parse_hex(value).map_err(|_| "Invalid hex in rule")
+            } else {
+                Ok(value.as_bytes().to_vec())
+            }
+        };
+        let pattern = parse(&self.bridge.rule_pattern)?;
+        if pattern.is_empty() {
+            return Err("Rule pattern is empty");
+        }
+        let action = if self.bridge.rule_drop {
+            RuleAction::Drop
+        } else {
+            RuleAction::Replace(parse(&self.bridge.rule_replacement)?)
+        };
+        Ok(BridgeRule {
+            enabled: true,
+            scope: self.bridge.rule_scope,
+            pattern,
+            action,
+        })
+    }
+
+    #[cfg(unix)]
+    fn daemon_row(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let response = ui
+                .checkbox(&mut self.settings.daemon.attach, "Attach to daemon")
+                .on_hover_text("Open ports through a running `microserial_gui daemon`");
+            if response.changed() {
+                self.mark_dirty();
+                self.scanner.refresh();
+            }
+            if self.settings.daemon.attach
+                && ui
+                    .text_edit_singleline(&mut self.settings.daemon.socket)
+                    .changed()
+            {
+                self.mark_dirty();
+            }
+            if self.settings.daemon.attach && !self.settings.daemon.socket.contains('/') {
+                let mut token = self.settings.daemon.token.clone().unwrap_or_default();
+                ui.label("Token");
+                if ui
+                    .add(egui::TextEdit::singleline(&mut token).password(true))
+                    .changed()
+                {
+                    self.settings.daemon.token = Some(token).filter(|token| !token.is_empty());
+                    self.mark_dirty();
                 }
-                self.status_pill(ui);
-            });
-        });
-
-        ui.separator();
-        ui.collapsing("Profiles", |ui| {
-            self.profiles_ui(ui);
+            }
         });
     }
 
@@ -491,10 +3165,7 @@ impl MicroSerialApp {
                     .unwrap_or_else(|| "Default".into());
                 self.settings
                     .profiles
-                    .upsert(crate::profiles::SerialProfile::new(
-                        profile_name,
-                        self.config.clone(),
-                    ));
+                    .save_config(&profile_name, self.config.clone());
                 self.set_status("Profile saved", StatusTone::Success);
                 self.mark_dirty();
             }
@@ -526,6 +3197,15 @@ impl MicroSerialApp {
             if ui.button("Clear").clicked() {
                 self.console.clear();
             }
+            if self.console.paused_at.is_some() {
+                if ui.button("Resume").clicked() {
+                    self.console.resume();
+                }
+            } else if ui.button("Pause").clicked() {
+                self.console.pause();
+            }
+            ui.menu_button("Lines", |ui| self.console_lines_menu(ui));
+            ui.menu_button("Storage", |ui| self.console_storage_menu(ui));
             ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                 ui.checkbox(&mut self.console.show_timestamps, "Timestamps");
                 if self.console.show_timestamps != self.settings.show_timestamps {
@@ -560,91 +3240,724 @@ impl MicroSerialApp {
         });
         ui.separator();
 
-        egui::ScrollArea::vertical()
+        // Only the visible rows are laid out; every row has the same height.
+        let line = ui
+            .text_style_height(&egui::TextStyle::Body)
+            .max(ui.text_style_height(&egui::TextStyle::Monospace));
+        let row_height = match self.console.view_mode {
+            ConsoleViewMode::Mixed => line * 2.0 + ui.spacing().item_spacing.y,
+            ConsoleViewMode::Text | ConsoleViewMode::Hex => line,
+        };
+        egui::ScrollArea::both()
+            .auto_shrink(false)
             .stick_to_bottom(true)
-            .show(ui, |ui| {
-                for entry in self.console.iter() {
-                    self.console_row(ui, entry);
+            .show_rows(ui, row_height, self.console.rows(), |ui, rows| {
+                for row in rows {
+                    if let Some(entry) = self.console.row(row) {
+                        self.console_row(ui, entry);
+                    }
+                }
+            });
+    }
+
+    /// Memory limit, spill file and usage of the console history.
+    fn console_storage_menu(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        let storage = &mut self.settings.console;
+        ui.horizontal(|ui| {
+            ui.label("Memory limit");
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut storage.memory_limit_mb)
+                        .clamp_range(1..=4096)
+                        .suffix(" MB"),
+                )
+                .changed();
+        });
+        changed |= ui
+            .checkbox(&mut storage.spill, "Spill older entries to disk")
+            .changed();
+        ui.add_enabled_ui(storage.spill, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("File");
+                changed |= ui
+                    .text_edit_singleline(&mut storage.spill_path)
+                    .lost_focus();
+            });
+        });
+        ui.separator();
+        let used = self.console.memory_used() as f64 / f64::from(1 << 20);
+        ui.label(format!(
+            "{used:.1} MB of {} MB, {} rows",
+            storage.memory_limit_mb,
+            self.console.len()
+        ));
+        let evicted = self.console.evicted();
+        match self.console.spill_path() {
+            Some(path) => ui.label(format!("{evicted} rows spilled to {}", path.display())),
+            None => ui.label(format!("{evicted} rows dropped")),
+        };
+        if changed {
+            self.apply_console_settings();
+            self.mark_dirty();
+        }
+    }
+
+    /// How received and sent bytes are split into console rows.
+    fn console_lines_menu(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        let lines = &mut self.settings.console;
+        ui.horizontal(|ui| {
+            ui.label("Split");
+            ComboBox::from_id_source("console_framing")
+                .selected_text(lines.framing.to_string())
+                .show_ui(ui, |ui| {
+                    for option in LineFramingKind::iter() {
+                        changed |= ui
+                            .selectable_value(&mut lines.framing, option, option.to_string())
+                            .changed();
+                    }
+                });
+            match lines.framing {
+                LineFramingKind::Chunk | LineFramingKind::Lf | LineFramingKind::CrLf => {}
+                LineFramingKind::Byte => {
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut lines.delimiter).hexadecimal(2, false, true))
+                        .changed();
+                }
+                LineFramingKind::IdleGap => {
+                    changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut lines.idle_gap_ms)
+                                .clamp_range(1..=10_000)
+                                .suffix(" ms"),
+                        )
+                        .changed();
                 }
+                LineFramingKind::Length => {
+                    changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut lines.frame_length)
+                                .clamp_range(1..=4096)
+                                .suffix(" bytes"),
+                        )
+                        .changed();
+                }
+            }
+        });
+        ui.add_enabled_ui(lines.framing != LineFramingKind::Chunk, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Show partial rows after");
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut lines.flush_ms)
+                            .clamp_range(0..=60_000)
+                            .suffix(" ms"),
+                    )
+                    .on_hover_text("0 waits until the row is complete")
+                    .changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label("Split rows longer than");
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut lines.max_row)
+                            .clamp_range(16..=1 << 20)
+                            .suffix(" bytes"),
+                    )
+                    .changed();
             });
+            changed |= ui
+                .checkbox(&mut lines.merge_tx, "Merge sent bytes into received rows")
+                .changed();
+        });
+        if changed {
+            self.apply_console_settings();
+            self.mark_dirty();
+        }
     }
 
     fn console_row(&self, ui: &mut egui::Ui, entry: &ConsoleEntry) {
-        Frame::group(ui.style())
-            .fill(Color32::from_rgba_premultiplied(32, 64, 96, 20))
-            .show(ui, |ui| {
-                ui.horizontal(|ui| {
-                    ui.colored_label(Color32::from_rgb(90, 140, 210), entry.direction.label());
-                    if self.console.show_timestamps {
-                        if let Ok(ts) = entry.timestamp.format(&Rfc3339) {
-                            ui.label(ts);
-                        }
+        let background = ui.painter().add(egui::Shape::Noop);
+        let response = ui.horizontal(|ui| {
+            ui.colored_label(Color32::from_rgb(90, 140, 210), entry.direction.label());
+            if self.console.show_timestamps {
+                if let Ok(ts) = entry.timestamp.format(&Rfc3339) {
+                    ui.label(ts);
+                }
+            }
+            match self.console.view_mode {
+                ConsoleViewMode::Text => {
+                    ui.add(egui::Label::new(entry.display_text()).wrap(false));
+                }
+                ConsoleViewMode::Hex => {
+                    ui.add(egui::Label::new(RichText::new(entry.hex()).monospace()).wrap(false));
+                }
+                ConsoleViewMode::Mixed => {
+                    ui.vertical(|ui| {
+                        ui.add(egui::Label::new(entry.display_text()).wrap(false));
+                        ui.add(
+                            egui::Label::new(RichText::new(entry.hex()).monospace().weak())
+                                .wrap(false),
+                        );
+                    });
+                }
+            }
+        });
+        if entry.highlighted {
+            let fill = StatusTone::Warn.color().gamma_multiply(0.35);
+            ui.painter().set(
+                background,
+                egui::Shape::rect_filled(response.response.rect, Rounding::same(2.0), fill),
+            );
+        }
+    }
+
+    /// Line ending, escapes and encoding of text sends for the active profile.
+    fn text_options_row(&mut self, ui: &mut egui::Ui) {
+        let Some(profile) = self.settings.profiles.get_active_mut() else {
+            return;
+        };
+        let options = &mut profile.send;
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Ending");
+            let ending_label = |ending: Option<LineEnding>| match ending {
+                Some(ending) => ending.to_string().to_uppercase(),
+                None => "None".to_string(),
+            };
+            ComboBox::from_id_source("send_ending")
+                .selected_text(ending_label(options.ending))
+                .show_ui(ui, |ui| {
+                    for ending in std::iter::once(None).chain(LineEnding::iter().map(Some)) {
+                        changed |= ui
+                            .selectable_value(&mut options.ending, ending, ending_label(ending))
+                            .changed();
+                    }
+                });
+            changed |= ui
+                .checkbox(&mut options.escapes, "Escapes")
+                .on_hover_text("Interpret \\r, \\n, \\t, \\0, \\xHH and other C escapes")
+                .changed();
+            ui.label("Encoding");
+            ComboBox::from_id_source("send_encoding")
+                .selected_text(options.encoding.to_string())
+                .show_ui(ui, |ui| {
+                    for encoding in TextEncoding::iter() {
+                        changed |= ui
+                            .selectable_value(&mut options.encoding, encoding, encoding.to_string())
+                            .changed();
+                    }
+                });
+        });
+        if changed {
+            self.mark_dirty();
+        }
+    }
+
+    /// Checksum appended to every payload; kept with each history entry.
+    fn checksum_row(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Checksum");
+            let selected = match &self.send_panel.checksum {
+                Some(checksum) => checksum.algorithm.to_string(),
+                None => "Off".to_string(),
+            };
+            ComboBox::from_id_source("send_checksum")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    if ui
+                        .selectable_label(self.send_panel.checksum.is_none(), "Off")
+                        .clicked()
+                    {
+                        self.send_panel.checksum = None;
                     }
-                    match self.console.view_mode {
-                        ConsoleViewMode::Text => {
-                            ui.label(&entry.text);
+                    for algorithm in ChecksumAlgorithm::iter() {
+                        let current = self
+                            .send_panel
+                            .checksum
+                            .as_ref()
+                            .is_some_and(|checksum| checksum.algorithm == algorithm);
+                        if ui
+                            .selectable_label(current, algorithm.to_string())
+                            .clicked()
+                        {
+                            let checksum = self
+                                .send_panel
+                                .checksum
+                                .get_or_insert_with(Checksum::default);
+                            checksum.algorithm = algorithm;
                         }
-                        ConsoleViewMode::Hex => {
-                            ui.label(&entry.hex);
+                    }
+                });
+            let Some(checksum) = &mut self.send_panel.checksum else {
+                return;
+            };
+            if checksum.size() > 1 {
+                ComboBox::from_id_source("send_checksum_order")
+                    .selected_text(checksum.order.to_string())
+                    .show_ui(ui, |ui| {
+                        for order in ByteOrder::iter() {
+                            ui.selectable_value(&mut checksum.order, order, order.to_string());
                         }
-                        ConsoleViewMode::Mixed => {
-                            ui.vertical(|ui| {
-                                ui.label(&entry.text);
-                                ui.add_space(2.0);
-                                ui.label(RichText::new(&entry.hex).monospace().weak());
+                    });
+            }
+            ui.label("Skip");
+            ui.add(egui::DragValue::new(&mut checksum.skip_start).suffix(" first"));
+            ui.add(egui::DragValue::new(&mut checksum.skip_end).suffix(" last"));
+        });
+        let Some(checksum) = &mut self.send_panel.checksum else {
+            return;
+        };
+        if checksum.algorithm != ChecksumAlgorithm::Crc {
+            return;
+        }
+        let params = &mut checksum.params;
+        ui.horizontal(|ui| {
+            ui.label("Width");
+            ui.add(
+                egui::DragValue::new(&mut params.width)
+                    .clamp_range(1..=32)
+                    .suffix(" bit"),
+            );
+            let digits = usize::from(params.width.div_ceil(4));
+            ui.label("Poly");
+            ui.add(egui::DragValue::new(&mut params.poly).hexadecimal(digits, false, true));
+            ui.label("Init");
+            ui.add(egui::DragValue::new(&mut params.init).hexadecimal(digits, false, true));
+            ui.label("XorOut");
+            ui.add(egui::DragValue::new(&mut params.xorout).hexadecimal(digits, false, true));
+            ui.checkbox(&mut params.reflect, "Reflect");
+        });
+    }
+
+    /// Shows the exact bytes the input would send, or why it cannot be sent.
+    fn payload_preview(&mut self, ui: &mut egui::Ui) {
+        if self.send_panel.input.is_empty() {
+            return;
+        }
+        if self.send_panel.mode == SendMode::Template {
+            ui.horizontal(|ui| {
+                let count = self.templates.count(&self.send_panel.input);
+                ui.weak(format!("Next seq: {count}"));
+                if count > 0 && ui.small_button("Reset").clicked() {
+                    self.templates.reset(&self.send_panel.input);
+                }
+            });
+        }
+        // Templates may read files, so they are not expanded every frame.
+        let options = self.send_options();
+        let seq = self.templates.count(&self.send_panel.input);
+        let panel = &self.send_panel;
+        let stale = self.preview.as_ref().is_none_or(|preview| {
+            preview.input != panel.input
+                || preview.mode != panel.mode
+                || preview.options != options
+                || preview.checksum != panel.checksum
+                || preview.seq != seq
+        });
+        if stale {
+            let context = self.templates.context(&panel.input);
+            let text = panel
+                .parse_payload(&panel.input, &options, &context)
+                .map(|payload| {
+                    let mut text = format!(
+                        "{} bytes: {}",
+                        payload.bytes.len(),
+                        console::to_hex(&payload.bytes)
+                    );
+                    if let (Some(checksum), Some(value)) = (&panel.checksum, payload.checksum) {
+                        text.push_str(&format!(
+                            "  ({} {})",
+                            checksum.algorithm,
+                            checksum.format(value)
+                        ));
+                    }
+                    text
+                })
+                .map_err(|err| err.to_string());
+            self.preview = Some(PayloadPreview {
+                input: panel.input.clone(),
+                mode: panel.mode,
+                options,
+                checksum: panel.checksum.clone(),
+                seq,
+                text,
+            });
+        }
+        match self.preview.as_ref().map(|preview| &preview.text) {
+            Some(Ok(text)) => {
+                ui.label(RichText::new(text).monospace().weak());
+            }
+            Some(Err(err)) => {
+                ui.label(RichText::new(err).color(StatusTone::Error.color()));
+            }
+            None => {}
+        }
+    }
+
+    fn macro_bar(&mut self, ui: &mut egui::Ui) {
+        let macros = self
+            .settings
+            .profiles
+            .get_active()
+            .map(|profile| profile.macros.clone())
+            .unwrap_or_default();
+        let mut send = None;
+        ui.horizontal(|ui| {
+            ui.label("Macros");
+            if ui.small_button("Edit…").clicked() {
+                self.macros_open = true;
+            }
+        });
+        for (group, members) in macros::groups(&macros) {
+            ui.horizontal_wrapped(|ui| {
+                if !group.is_empty() {
+                    ui.label(RichText::new(group).strong());
+                }
+                for index in members {
+                    let item = &macros[index];
+                    let mut button = egui::Button::new(&item.name);
+                    if let Some([r, g, b]) = item.color {
+                        button = button.fill(Color32::from_rgb(r, g, b));
+                    }
+                    let hover = match &item.hotkey {
+                        Some(hotkey) => format!("{}  ({hotkey})", item.value),
+                        None => item.value.clone(),
+                    };
+                    if ui.add(button).on_hover_text(hover).clicked() {
+                        send = Some(index);
+                    }
+                }
+            });
+        }
+        if let Some(index) = send {
+            self.send_macro(&macros[index]);
+        }
+    }
+
+    /// Edits the macros of the active profile, and imports or exports them.
+    fn macro_dialog(&mut self, ctx: &egui::Context) {
+        let mut open = self.macros_open;
+        let mut changed = false;
+        let mut import = false;
+        let mut export = false;
+        let title = format!("Macros: {}", self.active_profile_name());
+        egui::Window::new(title)
+            .id(egui::Id::new("macros"))
+            .open(&mut open)
+            .default_width(560.0)
+            .show(ctx, |ui| {
+                let Some(profile) = self.settings.profiles.get_active_mut() else {
+                    ui.label("No active profile");
+                    return;
+                };
+                let ending_label = |ending: Option<LineEnding>| match ending {
+                    Some(ending) => ending.to_string().to_uppercase(),
+                    None => "None".to_string(),
+                };
+                let count = profile.macros.len();
+                let mut moved = None;
+                let mut removed = None;
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .show(ui, |ui| {
+                        for (index, item) in profile.macros.iter_mut().enumerate() {
+                            ui.push_id(("macro", index), |ui| {
+                                ui.horizontal(|ui| {
+                                    changed |= ui
+                                        .add(
+                                            egui::TextEdit::singleline(&mut item.name)
+                                                .hint_text("Name")
+                                                .desired_width(110.0),
+                                        )
+                                        .changed();
+                                    changed |= ui
+                                        .add(
+                                            egui::TextEdit::singleline(&mut item.group)
+                                                .hint_text("Group")
+                                                .desired_width(90.0),
+                                        )
+                                        .changed();
+                                    let mut hotkey = item.hotkey.clone().unwrap_or_default();
+                                    if ui
+                                        .add(
+                                            egui::TextEdit::singleline(&mut hotkey)
+                                                .hint_text("Hotkey")
+                                                .desired_width(90.0),
+                                        )
+                                        .on_hover_text("For example F5 or Ctrl+Shift+R")
+                                        .changed()
+                                    {
+                                        let hotkey = hotkey.trim();
+                                        item.hotkey =
+                                            (!hotkey.is_empty()).then(|| hotkey.to_string());
+                                        changed = true;
+                                    }
+                                    if item.hotkey.is_some() && item.shortcut().is_none() {
+                                        ui.colored_label(StatusTone::Error.color(), "?")
+                                            .on_hover_text("Unknown hotkey");
+                                    }
+                                    let mut colored = item.color.is_some();
+                                    if ui.checkbox(&mut colored, "Colour").changed() {
+                                        item.color = colored.then_some([70, 110, 170]);
+                                        changed = true;
+                                    }
+                                    if let Some(color) = &mut item.color {
+                                        changed |= ui.color_edit_button_srgb(color).changed();
+                                    }
+                                    if ui
+                                        .add_enabled(index > 0, egui::Button::new("⏶").small())
+                                        .clicked()
+                                    {
+                                        moved = Some((index, index - 1));
+                                    }
+                                    if ui
+                                        .add_enabled(
+                                            index + 1 < count,
+                                            egui::Button::new("⏷").small(),
+                                        )
+                                        .clicked()
+                                    {
+                                        moved = Some((index, index + 1));
+                                    }
+                                    if ui.small_button("✕").clicked() {
+                                        removed = Some(index);
+                                    }
+                                });
+                                ui.horizontal(|ui| {
+                                    ComboBox::from_id_source("mode")
+                                        .selected_text(item.mode.label())
+                                        .width(80.0)
+                                        .show_ui(ui, |ui| {
+                                            for mode in SendMode::iter() {
+                                                changed |= ui
+                                                    .selectable_value(
+                                                        &mut item.mode,
+                                                        mode,
+                                                        mode.label(),
+                                                    )
+                                                    .changed();
+                                            }
+                                        });
+                                    changed |= ui
+                                        .add(
+                                            egui::TextEdit::singleline(&mut item.value)
+                                                .hint_text("Payload")
+                                                .desired_width(300.0),
+                                        )
+                                        .changed();
+                                    ui.add_enabled_ui(item.mode != SendMode::Hex, |ui| {
+                                        ComboBox::from_id_source("ending")
+                                            .selected_text(ending_label(item.ending))
+                                            .width(60.0)
+                                            .show_ui(ui, |ui| {
+                                                for ending in std::iter::once(None)
+                                                    .chain(LineEnding::iter().map(Some))
+                                                {
+                                                    changed |= ui
+                                                        .selectable_value(
+                                                            &mut item.ending,
+                                                            ending,
+                                                            ending_label(ending),
+                                                        )
+                                                        .changed();
+                                                }
+                                            });
+                                    });
+                                });
                             });
+                            ui.separator();
                         }
-                    }
+                    });
+                if let Some((from, to)) = moved {
+                    profile.macros.swap(from, to);
+                    changed = true;
+                }
+                if let Some(index) = removed {
+                    profile.macros.remove(index);
+                    changed = true;
+                }
+                if ui.button("Add macro").clicked() {
+                    let name = format!("Macro {}", profile.macros.len() + 1);
+                    profile.macros.push(Macro::new(&name, "", SendMode::Text));
+                    changed = true;
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("File");
+                    ui.text_edit_singleline(&mut self.macro_path);
+                    import = ui.button("Import").clicked();
+                    export = ui.button("Export").clicked();
                 });
             });
+        self.macros_open = open;
+        if import {
+            self.import_macros();
+        }
+        if export {
+            self.export_macros();
+        }
+        if changed {
+            self.mark_dirty();
+        }
+    }
+
+    fn import_macros(&mut self) {
+        let path = std::path::PathBuf::from(self.macro_path.trim());
+        let imported = match macros::import(&path) {
+            Ok(imported) => imported,
+            Err(err) => {
+                self.set_status(&format!("Import failed: {err}"), StatusTone::Error);
+                return;
+            }
+        };
+        let Some(profile) = self.settings.profiles.get_active_mut() else {
+            return;
+        };
+        let count = imported.len();
+        let replaced = macros::merge(&mut profile.macros, imported);
+        self.set_status(
+            &format!("Imported {count} macros, {replaced} replaced"),
+            StatusTone::Success,
+        );
+        self.mark_dirty();
+    }
+
+    fn export_macros(&mut self) {
+        let path = std::path::PathBuf::from(self.macro_path.trim());
+        let macros = self
+            .settings
+            .profiles
+            .get_active()
+            .map(|profile| profile.macros.clone())
+            .unwrap_or_default();
+        match macros::export(&path, &macros) {
+            Ok(()) => self.set_status(
+                &format!("Exported {} macros to {}", macros.len(), path.display()),
+                StatusTone::Success,
+            ),
+            Err(err) => self.set_status(&format!("Export failed: {err}"), StatusTone::Error),
+        }
     }
 
     fn send_panel(&mut self, ui: &mut egui::Ui) {
+        let transferring = self.transfer_running();
         ui.vertical(|ui| {
             ui.heading("Send");
             ui.horizontal(|ui| {
                 ComboBox::from_id_source("send_mode")
                     .selected_text(self.send_panel.mode.label())
                     .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut self.send_panel.mode,
-                            SendMode::Text,
-                            SendMode::Text.label(),
-                        );
-                        ui.selectable_value(
-                            &mut self.send_panel.mode,
-                            SendMode::Hex,
-                            SendMode::Hex.label(),
-                        );
+                        for mode in SendMode::iter() {
+                            ui.selectable_value(&mut self.send_panel.mode, mode, mode.label());
+                        }
                     });
-                let response = ui.text_edit_singleline(&mut self.send_panel.input);
-                let send_clicked = ui.button("Send").clicked();
+                let input_id = egui::Id::new("send_input");
+                if ui.memory(|memory| memory.has_focus(input_id)) {
+                    let recalled = if ui
+                        .input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp))
+                    {
+                        self.send_panel.recall_older();
+                        true
+                    } else if ui
+                        .input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown))
+                    {
+                        self.send_panel.recall_newer();
+                        true
+                    } else {
+                        false
+                    };
+                    if recalled {
+                        move_cursor_to_end(ui.ctx(), input_id, &self.send_panel.input);
+                    }
+                }
+                let response =
+                    ui.add(egui::TextEdit::singleline(&mut self.send_panel.input).id(input_id));
+                // Sends would corrupt a running file transfer.
+                let idle = !self.transfer_running();
+                let send_clicked = ui
+                    .add_enabled(idle, egui::Button::new("Send"))
+                    .on_disabled_hover_text("A file transfer is running")
+                    .clicked();
                 let enter_pressed =
-                    response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    idle && response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
                 if send_clicked || enter_pressed {
                     self.send_current_payload();
                 }
+                if ui.button("Send file…").clicked() {
+                    self.file_send_open = true;
+                }
             });
+            self.text_options_row(ui);
+            self.checksum_row(ui);
+            self.payload_preview(ui);
+            self.script_buttons(ui);
+            self.macro_bar(ui);
             ui.separator();
-            ui.label("History");
+            ui.horizontal(|ui| {
+                ui.label("History");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.send_panel.search)
+                        .hint_text("Search")
+                        .desired_width(160.0),
+                );
+                ui.label("Keep");
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut self.settings.history.limit)
+                            .clamp_range(1..=1000),
+                    )
+                    .changed()
+                {
+                    self.send_panel.trim_history(self.settings.history.limit);
+                    self.store_history();
+                }
+                if ui
+                    .small_button("Clear")
+                    .on_hover_text("Remove everything but favorites")
+                    .clicked()
+                {
+                    self.send_panel.clear_history();
+                    self.store_history();
+                }
+            });
             egui::ScrollArea::vertical()
                 .max_height(120.0)
                 .show(ui, |ui| {
-                    let entries: Vec<_> = self.send_panel.history.iter().cloned().collect();
-                    for (index, entry) in entries.into_iter().enumerate() {
+                    let entries: Vec<_> = self
+                        .send_panel
+                        .matching_history()
+                        .map(|(index, entry)| (index, entry.clone()))
+                        .collect();
+                    for (index, entry) in entries {
                         ui.horizontal(|ui| {
                             if ui
                                 .selectable_label(false, format!("{}", entry.value))
                                 .clicked()
                             {
-                                self.send_panel.input = entry.value.clone();
-                                self.send_panel.mode = entry.mode;
+                                self.send_panel.select(index);
                             }
                             if ui
                                 .small_button(if entry.favorited { "★" } else { "☆" })
                                 .clicked()
                             {
                                 self.send_panel.toggle_favorite(index);
+                                self.store_history();
+                            }
+                            if ui
+                                .add_enabled(!transferring, egui::Button::new("⏱").small())
+                                .on_hover_text("Repeat this payload")
+                                .clicked()
+                            {
+                                self.arm_schedule(&entry);
+                            }
+                            if ui
+                                .small_button("➕")
+                                .on_hover_text("Add to the macro bar")
+                                .clicked()
+                            {
+                                self.add_macro(&entry);
                             }
                         });
                     }
@@ -658,13 +3971,174 @@ impl MicroSerialApp {
                         if ui.button(format!("★ {}", fav.value)).clicked() {
                             self.send_panel.input = fav.value.clone();
                             self.send_panel.mode = fav.mode;
+                            self.send_panel.checksum = fav.checksum.clone();
+                        }
+                        if ui
+                            .add_enabled(!transferring, egui::Button::new("⏱").small())
+                            .on_hover_text("Repeat this payload")
+                            .clicked()
+                        {
+                            self.arm_schedule(&fav);
                         }
                     }
                 });
             }
+            ui.separator();
+            let running = self.scheduler.timers().len();
+            egui::CollapsingHeader::new(format!("Scheduled ({running} running)"))
+                .id_source("scheduled_sends")
+                .show(ui, |ui| self.scheduled_list(ui));
         });
     }
 
+    fn scheduled_list(&mut self, ui: &mut egui::Ui) {
+        let transferring = self.transfer_running();
+        let mut changed = false;
+        let mut start = None;
+        let mut stop = None;
+        let mut removed = None;
+        let Some(profile) = self.settings.profiles.get_active_mut() else {
+            return;
+        };
+        let items = &mut profile.schedules;
+        for (key, item) in items.iter_mut().enumerate() {
+            let timer = self.scheduler.timer(key);
+            ui.push_id(("scheduled", key), |ui| {
+                ui.horizontal(|ui| match timer {
+                    Some(timer) => {
+                        if ui.button("■ Stop").clicked() {
+                            stop = Some(key);
+                        }
+                        let cycles = match timer.repeat() {
+                            Some(repeat) => format!("{}/{repeat}", timer.cycles()),
+                            None => timer.cycles().to_string(),
+                        };
+                        ui.label(RichText::new(&item.name).strong());
+                        ui.weak(format!("cycle {cycles}, {} sent", timer.sent()));
+                    }
+                    None => {
+                        if ui
+                            .add_enabled(!transferring, egui::Button::new("▶ Start"))
+                            .clicked()
+                        {
+                            start = Some(key);
+                        }
+                        changed |= ui.text_edit_singleline(&mut item.name).changed();
+                        if ui.small_button("Remove").clicked() {
+                            removed = Some(key);
+                        }
+                    }
+                });
+                if timer.is_some() {
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Every");
+                    changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut item.interval_ms)
+                                .clamp_range(1..=86_400_000)
+                                .suffix(" ms"),
+                        )
+                        .changed();
+                    let mut forever = item.repeat.is_none();
+                    if ui.checkbox(&mut forever, "Until stopped").changed() {
+                        item.repeat = if forever { None } else { Some(10) };
+                        changed = true;
+                    }
+                    if let Some(repeat) = &mut item.repeat {
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(repeat)
+                                    .clamp_range(1..=u32::MAX)
+                                    .suffix(" times"),
+                            )
+                            .changed();
+                    }
+                });
+                let mut removed_step = None;
+                for (index, step) in item.steps.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label("after");
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut step.delay_ms)
+                                    .clamp_range(0..=86_400_000)
+                                    .suffix(" ms"),
+                            )
+                            .changed();
+                        ComboBox::from_id_source(("step_mode", index))
+                            .selected_text(step.mode.label())
+                            .width(80.0)
+                            .show_ui(ui, |ui| {
+                                for mode in SendMode::iter() {
+                                    changed |= ui
+                                        .selectable_value(&mut step.mode, mode, mode.label())
+                                        .changed();
+                                }
+                            });
+                        changed |= ui.text_edit_singleline(&mut step.value).changed();
+                        if let Some(checksum) = &step.checksum {
+                            ui.weak(format!("+ {}", checksum.algorithm));
+                        }
+                        if ui.small_button("✕").clicked() {
+                            removed_step = Some(index);
+                        }
+                    });
+                }
+                if let Some(index) = removed_step {
+                    item.steps.remove(index);
+                    changed = true;
+                }
+                if ui.small_button("Add step").clicked() {
+                    item.steps.push(ScheduledStep {
+                        value: String::new(),
+                        mode: SendMode::Text,
+                        delay_ms: 100,
+                        checksum: None,
+                    });
+                    changed = true;
+                }
+            });
+            ui.separator();
+        }
+        if ui.button("New sequence").clicked() {
+            let name = format!("Sequence {}", items.len() + 1);
+            items.push(ScheduledItem {
+                name,
+                steps: Vec::new(),
+                interval_ms: ScheduledItem::DEFAULT_INTERVAL_MS,
+                repeat: None,
+            });
+            changed = true;
+        }
+        if let Some(key) = removed {
+            // Keys of running timers are indices, so only stopped items can be removed
+            // and later running items must be re-keyed.
+            items.remove(key);
+            let restart: Vec<usize> = self
+                .scheduler
+                .timers()
+                .iter()
+                .map(|timer| timer.key)
+                .filter(|running| *running > key)
+                .collect();
+            for running in restart {
+                self.scheduler.rekey(running, running - 1);
+            }
+            changed = true;
+        }
+        if changed {
+            self.mark_dirty();
+        }
+        if let Some(key) = stop {
+            self.scheduler.stop(key);
+        }
+        if let Some(key) = start {
+            self.start_schedule(key);
+        }
+    }
+
     fn top_bar(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
         ui.horizontal(|ui| {
             ui.heading(RichText::new("MicroSerial").size(self.theme_state.font_size + 4.0));
@@ -719,11 +4193,83 @@ impl MicroSerialApp {
     }
 }
 
+/// Turns a port path or remote URL into something usable in a file name.
+/// Edits a rule's action list; returns whether anything changed.
+fn trigger_actions_editor(ui: &mut egui::Ui, actions: &mut Vec<TriggerAction>) -> bool {
+    let mut changed = false;
+    let mut removed = None;
+    for (index, action) in actions.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source(("trigger_action", index))
+                .selected_text(action.label())
+                .show_ui(ui, |ui| {
+                    for option in TriggerAction::ALL {
+                        let selected =
+                            std::mem::discriminant(&option) == std::mem::discriminant(action);
+                        if ui.selectable_label(selected, option.label()).clicked() && !selected {
+                            *action = option;
+                            changed = true;
+                        }
+                    }
+                });
+            if let TriggerAction::Send { payload, hex } = action {
+                changed |= ui.text_edit_singleline(payload).changed();
+                changed |= ui.checkbox(hex, "Hex").changed();
+            }
+            if ui.small_button("✕").clicked() {
+                removed = Some(index);
+            }
+        });
+    }
+    if let Some(index) = removed {
+        actions.remove(index);
+        changed = true;
+    }
+    if ui.small_button("Add action").clicked() {
+        actions.push(TriggerAction::Highlight);
+        changed = true;
+    }
+    changed
+}
+
+fn file_stem_for(port: &str) -> String {
+    let name: String = port
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .collect();
+    if name.is_empty() {
+        "session".to_string()
+    } else {
+        name
+    }
+}
+
 impl eframe::App for MicroSerialApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.theme_state.apply(ctx);
         self.poll_scanner();
         self.poll_session();
+        self.poll_server();
+        self.poll_websocket();
+        #[cfg(unix)]
+        self.poll_virtual_port();
+        self.poll_bridge();
+        self.tick_capture();
+        self.poll_replay();
+        #[cfg(unix)]
+        self.poll_emulator();
+        self.poll_script();
+        self.poll_test_run();
+        self.poll_console();
+        self.sync_history();
+        self.poll_scheduler();
+        self.poll_transfer();
+        self.poll_file_send();
+        self.apply_live_config();
+        self.macro_hotkeys(ctx);
 
         egui::TopBottomPanel::top("top_bar").show(ctx, |ui| {
             self.top_bar(ctx, ui);
@@ -749,6 +4295,9 @@ impl eframe::App for MicroSerialApp {
             });
 
         self.diagnostics.show(ctx);
+        self.transfer_dialog(ctx);
+        self.file_send_dialog(ctx);
+        self.macro_dialog(ctx);
         self.save_settings_if_needed();
         ctx.request_repaint_after(Duration::from_millis(16));
     }
@@ -757,3 +4306,44 @@ impl eframe::App for MicroSerialApp {
         let _ = self.settings.save();
     }
 }
+
+/// Puts the text cursor of a single-line edit after its last character.
+fn move_cursor_to_end(ctx: &egui::Context, id: egui::Id, text: &str) {
+    if let Some(mut state) = egui::text_edit::TextEditState::load(ctx, id) {
+        let end = egui::text::CCursor::new(text.chars().count());
+        state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::one(end)));
+        state.store(ctx, id);
+    }
+}
+
+/// Passes test writes through [`MicroSerialApp::write_outbound`].
+struct AppTarget<'a> {
+    app: &'a mut MicroSerialApp,
+}
+
+impl AppTarget<'_> {
+    fn session(&mut self) -> Result<&mut SerialSession, SessionError> {
+        self.app
+            .session
+            .as_mut()
+            .ok_or(SessionError::Control(-libc::ENOTCONN))
+    }
+}
+
+impl TestTarget for AppTarget<'_> {
+    fn write(&mut self, data: &[u8]) -> Result<(), SessionError> {
+        self.app
+            .write_outbound("Test run", data)
+            .map_err(OutboundError::into_session)
+    }
+
+    fn set_dtr(&mut self, enabled: bool) -> Result<(), SessionError> {
+        self.session()?.set_dtr(enabled)
+    }
+
+    fn set_rts(&mut self, enabled: bool) -> Result<(), SessionError> {
+        self.session()?.set_rts(enabled)
+    }
+}
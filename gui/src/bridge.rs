@@ -0,0 +1,411 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+use thiserror::Error;
+
+use crate::console::Direction;
+use crate::core::{SerialConfig, SerialPort};
+use crate::decoder::{DecoderKind, FrameDecoder};
+use crate::session::SessionEvent;
+
+const WORKER_TICK: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum BridgeDirection {
+    #[strum(to_string = "A→B")]
+    AToB,
+    #[strum(to_string = "B→A")]
+    BToA,
+}
+
+impl BridgeDirection {
+    pub fn index(self) -> usize {
+        match self {
+            BridgeDirection::AToB => 0,
+            BridgeDirection::BToA => 1,
+        }
+    }
+}
+
+impl From<BridgeDirection> for Direction {
+    fn from(direction: BridgeDirection) -> Self {
+        match direction {
+            BridgeDirection::AToB => Direction::AToB,
+            BridgeDirection::BToA => Direction::BToA,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum RuleScope {
+    #[strum(to_string = "Both")]
+    Both,
+    #[strum(to_string = "A→B")]
+    AToB,
+    #[strum(to_string = "B→A")]
+    BToA,
+}
+
+impl RuleScope {
+    fn applies_to(self, direction: BridgeDirection) -> bool {
+        match self {
+            RuleScope::Both => true,
+            RuleScope::AToB => direction == BridgeDirection::AToB,
+            RuleScope::BToA => direction == BridgeDirection::BToA,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RuleAction {
+    Drop,
+    Replace(Vec<u8>),
+}
+
+/// Live modification applied to forwarded traffic.
+///
+/// Patterns are matched within a single read chunk; a pattern split across
+/// two reads from the device is forwarded untouched.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BridgeRule {
+    pub enabled: bool,
+    pub scope: RuleScope,
+    pub pattern: Vec<u8>,
+    pub action: RuleAction,
+}
+
+impl BridgeRule {
+    pub fn apply(&self, data: &[u8]) -> Option<Vec<u8>> {
+        if !self.enabled || self.pattern.is_empty() || data.len() < self.pattern.len() {
+            return None;
+        }
+        let replacement: &[u8] = match &self.action {
+            RuleAction::Drop => &[],
+            RuleAction::Replace(bytes) => bytes,
+        };
+        let mut output = Vec::with_capacity(data.len());
+        let mut index = 0;
+        let mut matched = false;
+        while index < data.len() {
+            if data[index..].starts_with(&self.pattern) {
+                output.extend_from_slice(replacement);
+                index += self.pattern.len();
+                matched = true;
+            } else {
+                output.push(data[index]);
+                index += 1;
+            }
+        }
+        matched.then_some(output)
+    }
+}
+
+pub fn apply_rules(rules: &[BridgeRule], direction: BridgeDirection, data: &[u8]) -> Vec<u8> {
+    let mut current = data.to_vec();
+    for rule in rules.iter().filter(|rule| rule.scope.applies_to(direction)) {
+        if let Some(rewritten) = rule.apply(&current) {
+            current = rewritten;
+        }
+    }
+    current
+}
+
+#[derive(Debug, Clone)]
+pub enum BridgeMessage {
+    Data {
+        direction: BridgeDirection,
+        original: Vec<u8>,
+        forwarded: Vec<u8>,
+    },
+    Event {
+        direction: BridgeDirection,
+        event: SessionEvent,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum BridgeError {
+    #[error("port {side} open failed with code {code}")]
+    Open { side: &'static str, code: i32 },
+    #[error("port {side} configuration failed with code {code}")]
+    Configure { side: &'static str, code: i32 },
+    #[error("port {side} start failed with code {code}")]
+    Start { side: &'static str, code: i32 },
+}
+
+/// Forwards bytes between two serial ports while reporting both directions.
+///
+/// Each port's I/O thread hands received chunks to a forwarding worker that
+/// rewrites them according to the active rules and writes them to the
+/// opposite port, so forwarding never waits on the GUI frame rate.
+pub struct SerialBridge {
+    rules: Arc<Mutex<Vec<BridgeRule>>>,
+    rx: Receiver<BridgeMessage>,
+    stop: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl SerialBridge {
+    pub fn open(
+        path_a: &str,
+        config_a: &SerialConfig,
+        path_b: &str,
+        config_b: &SerialConfig,
+    ) -> Result<Self, BridgeError> {
+        let (chunk_tx, chunk_rx) = mpsc::channel();
+        let (message_tx, message_rx) = mpsc::channel();
+        let port_a = open_side(
+            "A",
+            path_a,
+            config_a,
+            BridgeDirection::AToB,
+            &chunk_tx,
+            &message_tx,
+        )?;
+        let port_b = open_side(
+            "B",
+            path_b,
+            config_b,
+            BridgeDirection::BToA,
+            &chunk_tx,
+            &message_tx,
+        )?;
+
+        let rules = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker_rules = Arc::clone(&rules);
+        let worker_stop = Arc::clone(&stop);
+        let worker = thread::spawn(move || {
+            forward_loop(
+                port_a,
+                port_b,
+                chunk_rx,
+                message_tx,
+                worker_rules,
+                worker_stop,
+            );
+        });
+
+        Ok(Self {
+            rules,
+            rx: message_rx,
+            stop,
+            worker: Some(worker),
+        })
+    }
+
+    pub fn set_rules(&self, rules: Vec<BridgeRule>) {
+        if let Ok(mut guard) = self.rules.lock() {
+            *guard = rules;
+        }
+    }
+
+    pub fn poll(&self) -> Vec<BridgeMessage> {
+        let mut messages = Vec::new();
+        while let Ok(msg) = self.rx.try_recv() {
+            messages.push(msg);
+        }
+        messages
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for SerialBridge {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn open_side(
+    side: &'static str,
+    path: &str,
+    config: &SerialConfig,
+    direction: BridgeDirection,
+    chunk_tx: &Sender<(BridgeDirection, Vec<u8>)>,
+    message_tx: &Sender<BridgeMessage>,
+) -> Result<SerialPort, BridgeError> {
+    let mut port = SerialPort::open(path).map_err(|code| BridgeError::Open { side, code })?;
+    port.configure(config)
+        .map_err(|code| BridgeError::Configure { side, code })?;
+    let data_tx = chunk_tx.clone();
+    let event_tx = message_tx.clone();
+    port.start(
+        move |bytes| {
+            let _ = data_tx.send((direction, bytes.to_vec()));
+        },
+        move |code, message| {
+            let _ = event_tx.send(BridgeMessage::Event {
+                direction,
+                event: SessionEvent {
+                    code,
+                    message: message.to_string(),
+                },
+            });
+        },
+    )
+    .map_err(|code| BridgeError::Start { side, code })?;
+    Ok(port)
+}
+
+fn forward_loop(
+    mut port_a: SerialPort,
+    mut port_b: SerialPort,
+    chunks: Receiver<(BridgeDirection, Vec<u8>)>,
+    messages: Sender<BridgeMessage>,
+    rules: Arc<Mutex<Vec<BridgeRule>>>,
+    stop: Arc<AtomicBool>,
+) {
+    while !stop.load(Ordering::SeqCst) {
+        let (direction, original) = match chunks.recv_timeout(WORKER_TICK) {
+            Ok(chunk) => chunk,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let forwarded = match rules.lock() {
+            Ok(guard) => apply_rules(&guard, direction, &original),
+            Err(_) => original.clone(),
+        };
+        if !forwarded.is_empty() {
+            let target = match direction {
+                BridgeDirection::AToB => &mut port_b,
+                BridgeDirection::BToA => &mut port_a,
+            };
+            match target.write(&forwarded) {
+                Ok(written) if written == forwarded.len() => {}
+                Ok(written) => {
+                    let _ = messages.send(BridgeMessage::Event {
+                        direction,
+                        event: SessionEvent {
+                            code: 0,
+                            message: format!(
+                                "forward truncated ({written}/{} bytes)",
+                                forwarded.len()
+                            ),
+                        },
+                    });
+                }
+                Err(code) => {
+                    let _ = messages.send(BridgeMessage::Event {
+                        direction,
+                        event: SessionEvent {
+                            code,
+                            message: "forward write failed".to_string(),
+                        },
+                    });
+                }
+            }
+        }
+        let _ = messages.send(BridgeMessage::Data {
+            direction,
+            original,
+            forwarded,
+        });
+    }
+    port_a.stop();
+    port_b.stop();
+}
+
+/// GUI-side state for the bridge panel.
+pub struct BridgeState {
+    pub bridge: Option<SerialBridge>,
+    pub port_b: Option<String>,
+    pub rules: Vec<BridgeRule>,
+    pub decoder_kinds: [DecoderKind; 2],
+    decoders: [FrameDecoder; 2],
+    pub rule_pattern: String,
+    pub rule_replacement: String,
+    pub rule_hex: bool,
+    pub rule_scope: RuleScope,
+    pub rule_drop: bool,
+}
+
+impl BridgeState {
+    pub fn is_active(&self) -> bool {
+        self.bridge.is_some()
+    }
+
+    pub fn sync_rules(&self) {
+        if let Some(bridge) = &self.bridge {
+            bridge.set_rules(self.rules.clone());
+        }
+    }
+
+    /// Runs the decoder configured for `direction`, returning completed frames.
+    pub fn decode(&mut self, direction: BridgeDirection, data: &[u8]) -> Vec<Vec<u8>> {
+        let index = direction.index();
+        self.decoders[index].set_kind(self.decoder_kinds[index]);
+        self.decoders[index].feed(data)
+    }
+
+    pub fn reset_decoders(&mut self) {
+        for decoder in &mut self.decoders {
+            decoder.reset();
+        }
+    }
+}
+
+impl Default for BridgeState {
+    fn default() -> Self {
+        Self {
+            bridge: None,
+            port_b: None,
+            rules: Vec::new(),
+            decoder_kinds: [DecoderKind::None; 2],
+            decoders: [FrameDecoder::default(), FrameDecoder::default()],
+            rule_pattern: String::new(),
+            rule_replacement: String::new(),
+            rule_hex: false,
+            rule_scope: RuleScope::Both,
+            rule_drop: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BridgeDirection, BridgeRule, RuleAction, RuleScope, apply_rules};
+
+    #[test]
+    fn replace_rule_rewrites_every_match() {
+        let rule = BridgeRule {
+            enabled: true,
+            scope: RuleScope::Both,
+            pattern: b"AT".to_vec(),
+            action: RuleAction::Replace(b"at".to_vec()),
+        };
+        assert_eq!(
+            apply_rules(&[rule], BridgeDirection::AToB, b"AT+GMR;AT"),
+            b"at+GMR;at"
+        );
+    }
+
+    #[test]
+    fn scoped_drop_rule_only_hits_its_direction() {
+        let rule = BridgeRule {
+            enabled: true,
+            scope: RuleScope::BToA,
+            pattern: vec![0x00],
+            action: RuleAction::Drop,
+        };
+        let rules = [rule];
+        assert_eq!(
+            apply_rules(&rules, BridgeDirection::AToB, &[1, 0, 2]),
+            vec![1, 0, 2]
+        );
+        assert_eq!(
+            apply_rules(&rules, BridgeDirection::BToA, &[1, 0, 2]),
+            vec![1, 2]
+        );
+    }
+}
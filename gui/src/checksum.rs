@@ -0,0 +1,311 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+/// A CRC in the usual Rocksoft notation. `reflect` covers both input and output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrcParams {
+    /// 1 to 32 bits; the value is sent in as many whole bytes as it needs.
+    pub width: u8,
+    pub poly: u32,
+    pub init: u32,
+    pub reflect: bool,
+    pub xorout: u32,
+}
+
+impl CrcParams {
+    pub const CRC16_MODBUS: Self = Self {
+        width: 16,
+        poly: 0x8005,
+        init: 0xFFFF,
+        reflect: true,
+        xorout: 0,
+    };
+    /// CRC-16/CCITT-FALSE, as used by most "CCITT" implementations.
+    pub const CRC16_CCITT: Self = Self {
+        width: 16,
+        poly: 0x1021,
+        init: 0xFFFF,
+        reflect: false,
+        xorout: 0,
+    };
+    /// CRC-16/XMODEM, the CCITT polynomial starting from zero.
+    pub const CRC16_XMODEM: Self = Self {
+        width: 16,
+        poly: 0x1021,
+        init: 0,
+        reflect: false,
+        xorout: 0,
+    };
+    pub const CRC32: Self = Self {
+        width: 32,
+        poly: 0x04C1_1DB7,
+        init: 0xFFFF_FFFF,
+        reflect: true,
+        xorout: 0xFFFF_FFFF,
+    };
+
+    fn width(&self) -> u32 {
+        u32::from(self.width.clamp(1, 32))
+    }
+
+    fn mask(&self) -> u64 {
+        (1u64 << self.width()) - 1
+    }
+
+    /// Bytes needed to send a value of this width.
+    pub fn size(&self) -> usize {
+        self.width().div_ceil(8) as usize
+    }
+
+    pub fn compute(&self, data: &[u8]) -> u32 {
+        let width = self.width();
+        let mask = self.mask();
+        let poly = u64::from(self.poly) & mask;
+        let init = u64::from(self.init) & mask;
+        let crc = if self.reflect {
+            let poly = reflect(poly, width);
+            data.iter().fold(reflect(init, width), |crc, &byte| {
+                (0..8).fold(crc ^ u64::from(byte), |crc, _| {
+                    if crc & 1 == 1 {
+                        (crc >> 1) ^ poly
+                    } else {
+                        crc >> 1
+                    }
+                })
+            })
+        } else {
+            // CRCs narrower than a byte run left-aligned in an 8-bit register.
+            let pad = 8u32.saturating_sub(width);
+            let wide = width + pad;
+            let wide_mask = (1u64 << wide) - 1;
+            let top = 1u64 << (wide - 1);
+            let crc = data.iter().fold(init << pad, |crc, &byte| {
+                (0..8).fold(crc ^ (u64::from(byte) << (wide - 8)), |crc, _| {
+                    if crc & top != 0 {
+                        ((crc << 1) ^ (poly << pad)) & wide_mask
+                    } else {
+                        (crc << 1) & wide_mask
+                    }
+                })
+            });
+            crc >> pad
+        };
+        ((crc ^ u64::from(self.xorout)) & mask) as u32
+    }
+}
+
+fn reflect(value: u64, width: u32) -> u64 {
+    value.reverse_bits() >> (64 - width)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum ChecksumAlgorithm {
+    #[default]
+    #[strum(to_string = "CRC-16/MODBUS")]
+    Crc16Modbus,
+    #[strum(to_string = "CRC-16/CCITT")]
+    Crc16Ccitt,
+    #[strum(to_string = "CRC-32")]
+    Crc32,
+    /// A CRC with the parameters in [`Checksum::params`].
+    #[strum(to_string = "Custom CRC")]
+    Crc,
+    /// XOR of all bytes.
+    #[strum(to_string = "XOR")]
+    Xor8,
+    /// Sum of all bytes, modulo 256.
+    #[strum(to_string = "Sum")]
+    Sum8,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum ByteOrder {
+    #[default]
+    #[strum(to_string = "Big endian")]
+    Big,
+    #[strum(to_string = "Little endian")]
+    Little,
+}
+
+/// A checksum appended to a payload.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checksum {
+    pub algorithm: ChecksumAlgorithm,
+    /// Used by [`ChecksumAlgorithm::Crc`]; the presets bring their own.
+    pub params: CrcParams,
+    pub order: ByteOrder,
+    /// Bytes at the start of the payload that are not covered, such as a sync byte.
+    pub skip_start: usize,
+    /// Bytes at the end of the payload that are not covered.
+    pub skip_end: usize,
+}
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Self {
+            algorithm: ChecksumAlgorithm::Crc16Modbus,
+            params: CrcParams::CRC16_MODBUS,
+            // MODBUS RTU sends the low byte first.
+            order: ByteOrder::Little,
+            skip_start: 0,
+            skip_end: 0,
+        }
+    }
+}
+
+impl Checksum {
+    /// The CRC parameters in effect, or `None` for XOR and sum.
+    pub fn crc(&self) -> Option<CrcParams> {
+        match self.algorithm {
+            ChecksumAlgorithm::Crc16Modbus => Some(CrcParams::CRC16_MODBUS),
+            ChecksumAlgorithm::Crc16Ccitt => Some(CrcParams::CRC16_CCITT),
+            ChecksumAlgorithm::Crc32 => Some(CrcParams::CRC32),
+            ChecksumAlgorithm::Crc => Some(self.params),
+            ChecksumAlgorithm::Xor8 | ChecksumAlgorithm::Sum8 => None,
+        }
+    }
+
+    /// Size of the appended value in bytes.
+    pub fn size(&self) -> usize {
+        self.crc().map_or(1, |params| params.size())
+    }
+
+    /// The checksum of the covered range, or `None` if the payload is shorter than the skipped bytes.
+    pub fn compute(&self, payload: &[u8]) -> Option<u32> {
+        let end = payload.len().checked_sub(self.skip_end)?;
+        let covered = payload.get(self.skip_start..end)?;
+        Some(match self.crc() {
+            Some(params) => params.compute(covered),
+            None if self.algorithm == ChecksumAlgorithm::Xor8 => {
+                u32::from(covered.iter().fold(0u8, |acc, byte| acc ^ byte))
+            }
+            None => u32::from(
+                covered
+                    .iter()
+                    .fold(0u8, |acc, byte| acc.wrapping_add(*byte)),
+            ),
+        })
+    }
+
+    /// The value as it goes on the wire.
+    pub fn to_bytes(&self, value: u32) -> Vec<u8> {
+        let len = self.size();
+        match self.order {
+            ByteOrder::Big => value.to_be_bytes()[4 - len..].to_vec(),
+            ByteOrder::Little => value.to_le_bytes()[..len].to_vec(),
+        }
+    }
+
+    /// The value as hex, padded to its width.
+    pub fn format(&self, value: u32) -> String {
+        format!("0x{value:0width$X}", width = self.size() * 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECK: &[u8] = b"123456789";
+
+    #[test]
+    fn presets_match_catalogue_check_values() {
+        assert_eq!(CrcParams::CRC16_MODBUS.compute(CHECK), 0x4B37);
+        assert_eq!(CrcParams::CRC16_CCITT.compute(CHECK), 0x29B1);
+        assert_eq!(CrcParams::CRC16_XMODEM.compute(CHECK), 0x31C3);
+        assert_eq!(CrcParams::CRC32.compute(CHECK), 0xCBF4_3926);
+        let crc8 = CrcParams {
+            width: 8,
+            poly: 0x07,
+            init: 0,
+            reflect: false,
+            xorout: 0,
+        };
+        assert_eq!(crc8.compute(CHECK), 0xF4);
+        // CRC-16/X-25: reflected with a final XOR.
+        let x25 = CrcParams {
+            width: 16,
+            poly: 0x1021,
+            init: 0xFFFF,
+            reflect: true,
+            xorout: 0xFFFF,
+        };
+        assert_eq!(x25.compute(CHECK), 0x906E);
+    }
+
+    #[test]
+    fn odd_widths_round_up_to_whole_bytes() {
+        let crc5_usb = CrcParams {
+            width: 5,
+            poly: 0x05,
+            init: 0x1F,
+            reflect: true,
+            xorout: 0x1F,
+        };
+        assert_eq!(crc5_usb.compute(CHECK), 0x19);
+        let crc5_epc = CrcParams {
+            width: 5,
+            poly: 0x09,
+            init: 0x09,
+            reflect: false,
+            xorout: 0,
+        };
+        assert_eq!(crc5_epc.compute(CHECK), 0x00);
+        let crc15_can = CrcParams {
+            width: 15,
+            poly: 0x4599,
+            init: 0,
+            reflect: false,
+            xorout: 0,
+        };
+        assert_eq!(crc15_can.compute(CHECK), 0x059E);
+
+        let checksum = Checksum {
+            algorithm: ChecksumAlgorithm::Crc,
+            params: crc15_can,
+            order: ByteOrder::Big,
+            ..Checksum::default()
+        };
+        assert_eq!(checksum.size(), 2);
+        assert_eq!(checksum.to_bytes(0x059E), [0x05, 0x9E]);
+        assert_eq!(checksum.format(0x059E), "0x059E");
+        let checksum = Checksum {
+            params: crc5_usb,
+            ..checksum
+        };
+        assert_eq!(checksum.size(), 1);
+        assert_eq!(checksum.to_bytes(0x19), [0x19]);
+    }
+
+    #[test]
+    fn range_and_byte_order_apply() {
+        // MODBUS "read holding registers": CRC 0xCDC5 is sent as C5 CD.
+        let frame = [0x01, 0x03, 0x00, 0x00, 0x00, 0x0A];
+        let checksum = Checksum::default();
+        let value = checksum.compute(&frame).expect("covered");
+        assert_eq!(checksum.to_bytes(value), [0xC5, 0xCD]);
+        assert_eq!(checksum.format(value), "0xCDC5");
+
+        let xor = Checksum {
+            algorithm: ChecksumAlgorithm::Xor8,
+            skip_start: 1,
+            skip_end: 1,
+            ..Checksum::default()
+        };
+        assert_eq!(xor.compute(b"$ABC*"), Some(u32::from(b'A' ^ b'B' ^ b'C')));
+        assert_eq!(xor.to_bytes(0x40), [0x40]);
+        assert_eq!(xor.compute(b"$"), None);
+
+        let sum = Checksum {
+            algorithm: ChecksumAlgorithm::Sum8,
+            ..Checksum::default()
+        };
+        assert_eq!(sum.compute(&[0xF0, 0x20]), Some(0x10));
+        let crc32 = Checksum {
+            algorithm: ChecksumAlgorithm::Crc32,
+            order: ByteOrder::Big,
+            ..Checksum::default()
+        };
+        assert_eq!(crc32.to_bytes(0xCBF4_3926), [0xCB, 0xF4, 0x39, 0x26]);
+    }
+}
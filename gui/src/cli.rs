@@ -0,0 +1,609 @@
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::commands::{self, CaptureOptions, Payload, ProbeOptions, SendOptions, TestOptions};
+use crate::core::SerialConfig;
+#[cfg(unix)]
+use crate::daemon::{Daemon, DaemonError};
+use crate::expect::TestFileError;
+use crate::recorder::RecordingError;
+use crate::renderer::LAUNCH_FLAGS;
+use crate::send_panel;
+use crate::send_panel::LineEnding;
+use crate::session::SessionError;
+use crate::settings::Settings;
+use crate::terminal::{self, TerminalOptions};
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+/// `send --wait` or `capture --until` did not see the pattern in time.
+pub const EXIT_TIMEOUT: i32 = 3;
+pub const EXIT_OPEN: i32 = 4;
+pub const EXIT_CONFIGURE: i32 = 5;
+/// The port opened but failed later (start, write, modem control).
+pub const EXIT_IO: i32 = 6;
+/// `test` ran, but at least one case failed.
+pub const EXIT_TESTS_FAILED: i32 = 7;
+
+const DEFAULT_SEND_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub const USAGE: &str = "\
+usage: microserial_gui [--force-software] [--headless-detect]
+       microserial_gui list [--json]
+       microserial_gui send <port> (--text <s> | --hex <bytes> | --file <path>) [options]
+       microserial_gui capture <port> [options]
+       microserial_gui probe <port> [--json] [port options]
+       microserial_gui test <port> <file.toml> [--junit <path>] [port options]
+       microserial_gui term <port> [options]
+       microserial_gui daemon [--socket <path>] [--tcp <host:port> --token <s>]
+
+Without a subcommand the GUI is started.
+
+port options (all subcommands that open a port):
+  --profile <name>   use a saved profile instead of the active one
+  --baud <rate>      override the profile's baud rate
+
+send options:
+  --eol <cr|lf|crlf> append a line ending to --text
+  --wait <text>      succeed once the response contains <text>
+  --wait-hex <bytes> succeed once the response contains these bytes
+  --timeout <ms>     how long to wait for sending and the response (default 2000)
+  --dump             print the response as a hex dump
+
+capture options:
+  --duration <s>     stop after this many seconds
+  --until <text>     stop once <text> was received
+  --output <file>    also write a .msrec recording
+  --dump             print as a hex dump
+
+test options:
+  --junit <path>     write a JUnit XML report
+
+term options:
+  --hex              show received bytes as a hex dump
+  --echo             echo typed characters locally
+  --eol <cr|lf|crlf> line ending sent for Enter and expected from the device (default cr)
+
+daemon options:
+  --socket <path>    Unix socket to listen on (default from settings)
+  --tcp <host:port>  also accept TCP clients on a loopback address
+  --token <s>        token TCP clients must send first (required with --tcp)
+
+In the terminal, Ctrl-] quits and Ctrl-T opens the menu (Ctrl-T h for help).
+
+exit codes: 0 ok, 1 failure, 2 usage, 3 timeout, 4 open failed,
+            5 configure failed, 6 I/O failed, 7 tests failed";
+
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("{0}")]
+    Usage(String),
+    #[error("unknown profile '{0}'")]
+    UnknownProfile(String),
+    #[error("{port}: {source}")]
+    Session { port: String, source: SessionError },
+    #[error("port enumeration failed with code {0}")]
+    Enumerate(i32),
+    #[error("timed out waiting for {0:?}")]
+    Timeout(String),
+    #[error("recording failed: {0}")]
+    Recording(#[from] RecordingError),
+    #[error("I/O failed: {0}")]
+    Io(#[from] io::Error),
+    #[cfg(unix)]
+    #[error(transparent)]
+    Daemon(#[from] DaemonError),
+    #[error(transparent)]
+    TestFile(#[from] TestFileError),
+    #[error("{0} of {1} test cases failed")]
+    TestsFailed(usize, usize),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) | CliError::UnknownProfile(_) | CliError::TestFile(_) => EXIT_USAGE,
+            CliError::Timeout(_) => EXIT_TIMEOUT,
+            CliError::TestsFailed(..) => EXIT_TESTS_FAILED,
+            CliError::Session { source, .. } => match source {
+                SessionError::Open(_) => EXIT_OPEN,
+                SessionError::Configure(_) => EXIT_CONFIGURE,
+                _ => EXIT_IO,
+            },
+            CliError::Enumerate(_) | CliError::Recording(_) | CliError::Io(_) => EXIT_FAILURE,
+            #[cfg(unix)]
+            CliError::Daemon(_) => EXIT_FAILURE,
+        }
+    }
+}
+
+/// Port selection shared by the subcommands that open a port.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PortOptions {
+    pub port: String,
+    pub profile: Option<String>,
+    pub baud: Option<u32>,
+}
+
+impl PortOptions {
+    /// Picks the named (or active) profile from `settings` and applies overrides.
+    pub fn resolve_config(&self, settings: &Settings) -> Result<SerialConfig, CliError> {
+        let mut config = match &self.profile {
+            Some(name) => settings
+                .profiles
+                .profiles
+                .iter()
+                .find(|profile| profile.name == *name)
+                .map(|profile| profile.config.clone())
+                .ok_or_else(|| CliError::UnknownProfile(name.clone()))?,
+            None => settings
+                .profiles
+                .get_active()
+                .map(|profile| profile.config.clone())
+                .unwrap_or_default(),
+        };
+        if let Some(baud) = self.baud {
+            config.baud_rate = baud;
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    List {
+        json: bool,
+    },
+    Send(SendOptions),
+    Capture(CaptureOptions),
+    Probe(ProbeOptions),
+    Test(TestOptions),
+    Terminal(TerminalOptions),
+    #[cfg(unix)]
+    Daemon(DaemonOptions),
+}
+
+/// Overrides for the daemon settings saved by the GUI.
+#[cfg(unix)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DaemonOptions {
+    pub socket: Option<String>,
+    pub tcp: Option<String>,
+    pub token: Option<String>,
+}
+
+/// Walks the arguments after the subcommand name.
+struct Args<'a> {
+    args: std::slice::Iter<'a, String>,
+}
+
+impl<'a> Args<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        self.args.next().map(String::as_str)
+    }
+
+    fn value(&mut self, flag: &str) -> Result<&'a str, CliError> {
+        self.next()
+            .ok_or_else(|| CliError::Usage(format!("{flag} needs a value")))
+    }
+
+    fn parsed<T: std::str::FromStr>(&mut self, flag: &str) -> Result<T, CliError> {
+        let value = self.value(flag)?;
+        value
+            .parse()
+            .map_err(|_| CliError::Usage(format!("invalid value '{value}' for {flag}")))
+    }
+
+    fn hex(&mut self, flag: &str) -> Result<Vec<u8>, CliError> {
+        send_panel::parse_hex(self.value(flag)?)
+            .map_err(|err| CliError::Usage(format!("{flag}: {err}")))
+    }
+
+    /// Handles `--profile`, `--baud` and the port argument itself.
+    fn port_arg(&mut self, arg: &str, port: &mut PortOptions) -> Result<(), CliError> {
+        match arg {
+            "--profile" => port.profile = Some(self.value(arg)?.to_string()),
+            "--baud" => port.baud = Some(self.parsed(arg)?),
+            flag if flag.starts_with("--") => {
+                return Err(CliError::Usage(format!("unknown option {flag}")));
+            }
+            path if port.port.is_empty() => port.port = path.to_string(),
+            extra => return Err(CliError::Usage(format!("unexpected argument {extra}"))),
+        }
+        Ok(())
+    }
+}
+
+fn require_port(command: &str, port: &PortOptions) -> Result<(), CliError> {
+    if port.port.is_empty() {
+        return Err(CliError::Usage(format!("{command} needs a port")));
+    }
+    Ok(())
+}
+
+/// Parses the command line; `Ok(None)` means no subcommand, so the GUI starts.
+///
+/// Only an empty command line or one made of GUI launch flags starts the GUI;
+/// anything else that is not a subcommand is a usage error.
+pub fn parse(args: &[String]) -> Result<Option<Command>, CliError> {
+    if args.iter().all(|arg| LAUNCH_FLAGS.contains(&arg.as_str())) {
+        return Ok(None);
+    }
+    let Some((name, rest)) = args.split_first() else {
+        return Ok(None);
+    };
+    let mut args = Args { args: rest.iter() };
+    let command = match name.as_str() {
+        "list" => parse_list(&mut args)?,
+        "send" => Command::Send(parse_send(&mut args)?),
+        "capture" => Command::Capture(parse_capture(&mut args)?),
+        "probe" => Command::Probe(parse_probe(&mut args)?),
+        "test" => Command::Test(parse_test(&mut args)?),
+        "term" => Command::Terminal(parse_terminal(&mut args)?),
+        #[cfg(unix)]
+        "daemon" => Command::Daemon(parse_daemon(&mut args)?),
+        #[cfg(not(unix))]
+        "daemon" => {
+            return Err(CliError::Usage(
+                "the daemon needs Unix domain sockets and is not available on this platform"
+                    .to_string(),
+            ));
+        }
+        "help" | "--help" | "-h" => Command::Help,
+        flag if LAUNCH_FLAGS.contains(&flag) => {
+            return Err(CliError::Usage(format!(
+                "{flag} only applies when starting the GUI"
+            )));
+        }
+        other if other.starts_with('-') => {
+            return Err(CliError::Usage(format!("unknown option {other}")));
+        }
+        other => return Err(CliError::Usage(format!("unknown command {other}"))),
+    };
+    Ok(Some(command))
+}
+
+fn parse_list(args: &mut Args<'_>) -> Result<Command, CliError> {
+    let mut json = false;
+    while let Some(arg) = args.next() {
+        match arg {
+            "--json" => json = true,
+            other => return Err(CliError::Usage(format!("unexpected argument {other}"))),
+        }
+    }
+    Ok(Command::List { json })
+}
+
+fn parse_send(args: &mut Args<'_>) -> Result<SendOptions, CliError> {
+    let mut port = PortOptions::default();
+    let mut payload = None;
+    let mut eol = None;
+    let mut wait = None;
+    let mut timeout = DEFAULT_SEND_TIMEOUT;
+    let mut hex = false;
+    while let Some(arg) = args.next() {
+        let next_payload = match arg {
+            "--text" => Some(Payload::Text(args.value(arg)?.to_string())),
+            "--hex" => Some(Payload::Bytes(args.hex(arg)?)),
+            "--file" => Some(Payload::File(PathBuf::from(args.value(arg)?))),
+            _ => None,
+        };
+        if let Some(next) = next_payload {
+            if payload.replace(next).is_some() {
+                return Err(CliError::Usage("send takes one payload".to_string()));
+            }
+            continue;
+        }
+        match arg {
+            "--eol" => eol = Some(args.parsed::<LineEnding>(arg)?),
+            "--wait" => wait = Some(args.value(arg)?.as_bytes().to_vec()),
+            "--wait-hex" => wait = Some(args.hex(arg)?),
+            "--timeout" => timeout = Duration::from_millis(args.parsed(arg)?),
+            "--dump" => hex = true,
+            other => args.port_arg(other, &mut port)?,
+        }
+    }
+    require_port("send", &port)?;
+    let payload =
+        payload.ok_or_else(|| CliError::Usage("send needs --text, --hex or --file".to_string()))?;
+    Ok(SendOptions {
+        port,
+        payload,
+        eol,
+        wait,
+        timeout,
+        hex,
+    })
+}
+
+fn parse_capture(args: &mut Args<'_>) -> Result<CaptureOptions, CliError> {
+    let mut options = CaptureOptions {
+        port: PortOptions::default(),
+        duration: None,
+        until: None,
+        output: None,
+        hex: false,
+    };
+    while let Some(arg) = args.next() {
+        match arg {
+            "--duration" => {
+                let seconds: f64 = args.parsed(arg)?;
+                options.duration = Some(
+                    Duration::try_from_secs_f64(seconds)
+                        .map_err(|_| CliError::Usage(format!("invalid duration {seconds}")))?,
+                );
+            }
+            "--until" => options.until = Some(args.value(arg)?.as_bytes().to_vec()),
+            "--output" => options.output = Some(PathBuf::from(args.value(arg)?)),
+            "--dump" => options.hex = true,
+            other => args.port_arg(other, &mut options.port)?,
+        }
+    }
+    require_port("capture", &options.port)?;
+    Ok(options)
+}
+
+fn parse_probe(args: &mut Args<'_>) -> Result<ProbeOptions, CliError> {
+    let mut options = ProbeOptions {
+        port: PortOptions::default(),
+        json: false,
+    };
+    while let Some(arg) = args.next() {
+        match arg {
+            "--json" => options.json = true,
+            other => args.port_arg(other, &mut options.port)?,
+        }
+    }
+    require_port("probe", &options.port)?;
+    Ok(options)
+}
+
+fn parse_test(args: &mut Args<'_>) -> Result<TestOptions, CliError> {
+    let mut options = TestOptions {
+        port: PortOptions::default(),
+        file: PathBuf::new(),
+        junit: None,
+    };
+    while let Some(arg) = args.next() {
+        match arg {
+            "--junit" => options.junit = Some(PathBuf::from(args.value(arg)?)),
+            path if !arg.starts_with("--")
+                && !options.port.port.is_empty()
+                && options.file.as_os_str().is_empty() =>
+            {
+                options.file = PathBuf::from(path);
+            }
+            other => args.port_arg(other, &mut options.port)?,
+        }
+    }
+    require_port("test", &options.port)?;
+    if options.file.as_os_str().is_empty() {
+        return Err(CliError::Usage("test needs a test file".to_string()));
+    }
+    Ok(options)
+}
+
+fn parse_terminal(args: &mut Args<'_>) -> Result<TerminalOptions, CliError> {
+    let mut options = TerminalOptions::default();
+    while let Some(arg) = args.next() {
+        match arg {
+            "--hex" => options.hex = true,
+            "--echo" => options.echo = true,
+            "--eol" => options.eol = args.parsed::<LineEnding>(arg)?,
+            other => args.port_arg(other, &mut options.port)?,
+        }
+    }
+    require_port("term", &options.port)?;
+    Ok(options)
+}
+
+#[cfg(unix)]
+fn parse_daemon(args: &mut Args<'_>) -> Result<DaemonOptions, CliError> {
+    let mut options = DaemonOptions::default();
+    while let Some(arg) = args.next() {
+        match arg {
+            "--socket" => options.socket = Some(args.value(arg)?.to_string()),
+            "--tcp" => options.tcp = Some(args.value(arg)?.to_string()),
+            "--token" => options.token = Some(args.value(arg)?.to_string()),
+            other => return Err(CliError::Usage(format!("unexpected argument {other}"))),
+        }
+    }
+    Ok(options)
+}
+
+#[cfg(unix)]
+fn run_daemon(options: &DaemonOptions, settings: &Settings) -> Result<(), CliError> {
+    let mut daemon_settings = settings.daemon.clone();
+    if let Some(socket) = &options.socket {
+        daemon_settings.socket = socket.clone();
+    }
+    if options.tcp.is_some() {
+        daemon_settings.tcp = options.tcp.clone();
+    }
+    if options.token.is_some() {
+        daemon_settings.token = options.token.clone();
+    }
+    let daemon = Daemon::start(&daemon_settings, &settings.recordings.directory)?;
+    eprintln!(
+        "microserial: daemon listening on {}",
+        daemon.socket().display()
+    );
+    if let Some(addr) = daemon.tcp_addr() {
+        eprintln!("microserial: daemon listening on tcp {addr}");
+    }
+    daemon.wait();
+    Ok(())
+}
+
+/// Runs a subcommand and returns the process exit code.
+pub fn run(command: Command) -> i32 {
+    let settings = Settings::load().unwrap_or_default();
+    let result = match command {
+        Command::Help => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Command::List { json } => commands::list(json),
+        Command::Send(options) => options
+            .port
+            .resolve_config(&settings)
+            .and_then(|config| commands::send(&options, &config)),
+        Command::Capture(options) => options
+            .port
+            .resolve_config(&settings)
+            .and_then(|config| commands::capture(&options, &config)),
+        Command::Probe(options) => options
+            .port
+            .resolve_config(&settings)
+            .and_then(|config| commands::probe(&options, &config)),
+        Command::Test(options) => options
+            .port
+            .resolve_config(&settings)
+            .and_then(|config| commands::test(&options, &config)),
+        Command::Terminal(options) => options
+            .port
+            .resolve_config(&settings)
+            .and_then(|config| terminal::run(&options, &config)),
+        #[cfg(unix)]
+        Command::Daemon(options) => run_daemon(&options, &settings),
+    };
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(err) => {
+            eprintln!("microserial: {err}");
+            err.exit_code()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn gui_flags_are_not_subcommands() {
+        assert_eq!(parse(&args("")).unwrap(), None);
+        assert_eq!(parse(&args("--force-software")).unwrap(), None);
+        assert_eq!(
+            parse(&args("--headless-detect --force-software")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn unknown_commands_are_usage_errors() {
+        assert!(matches!(parse(&args("lsit")), Err(CliError::Usage(_))));
+        assert!(matches!(parse(&args("--verbose")), Err(CliError::Usage(_))));
+        assert!(matches!(
+            parse(&args("--force-software list")),
+            Err(CliError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn term_parses_port_and_options() {
+        let command = parse(&args("term /dev/ttyUSB0 --baud 9600 --hex --eol crlf")).unwrap();
+        let Some(Command::Terminal(options)) = command else {
+            panic!("expected terminal command");
+        };
+        assert_eq!(options.port.port, "/dev/ttyUSB0");
+        assert_eq!(options.port.baud, Some(9600));
+        assert!(options.hex);
+        assert!(!options.echo);
+        assert_eq!(options.eol, LineEnding::CrLf);
+    }
+
+    #[test]
+    fn term_rejects_bad_arguments() {
+        assert!(matches!(parse(&args("term")), Err(CliError::Usage(_))));
+        assert!(matches!(
+            parse(&args("term /dev/ttyS0 --baud fast")),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse(&args("term /dev/ttyS0 --bogus")),
+            Err(CliError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn send_parses_payload_and_wait() {
+        let command = parse(&args(
+            "send /dev/ttyACM0 --hex 0102 --wait-hex 06 --timeout 500 --dump",
+        ))
+        .unwrap();
+        let Some(Command::Send(options)) = command else {
+            panic!("expected send command");
+        };
+        assert_eq!(options.payload, Payload::Bytes(vec![1, 2]));
+        assert_eq!(options.wait, Some(vec![6]));
+        assert_eq!(options.timeout, Duration::from_millis(500));
+        assert!(options.hex);
+        assert!(matches!(
+            parse(&args("send /dev/ttyACM0 --text a --file b")),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse(&args("send /dev/ttyACM0")),
+            Err(CliError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn capture_and_list_options() {
+        let command = parse(&args("capture /dev/ttyS1 --duration 1.5 --until OK")).unwrap();
+        let Some(Command::Capture(options)) = command else {
+            panic!("expected capture command");
+        };
+        assert_eq!(options.duration, Some(Duration::from_millis(1500)));
+        assert_eq!(options.until.as_deref(), Some(&b"OK"[..]));
+        assert_eq!(
+            parse(&args("list --json")).unwrap(),
+            Some(Command::List { json: true })
+        );
+    }
+
+    #[test]
+    fn session_errors_map_to_exit_codes() {
+        let error = |source| CliError::Session {
+            port: "/dev/ttyS0".to_string(),
+            source,
+        };
+        assert_eq!(error(SessionError::Open(-2)).exit_code(), EXIT_OPEN);
+        assert_eq!(
+            error(SessionError::Configure(-22)).exit_code(),
+            EXIT_CONFIGURE
+        );
+        assert_eq!(error(SessionError::Truncated).exit_code(), EXIT_IO);
+        assert_eq!(CliError::Timeout("OK".into()).exit_code(), EXIT_TIMEOUT);
+        assert_eq!(CliError::TestsFailed(1, 3).exit_code(), EXIT_TESTS_FAILED);
+    }
+
+    #[test]
+    fn test_takes_port_then_file() {
+        let command = parse(&args(
+            "test /dev/ttyUSB0 smoke.toml --junit out.xml --baud 9600",
+        ))
+        .unwrap();
+        let Some(Command::Test(options)) = command else {
+            panic!("expected test command");
+        };
+        assert_eq!(options.port.port, "/dev/ttyUSB0");
+        assert_eq!(options.port.baud, Some(9600));
+        assert_eq!(options.file, PathBuf::from("smoke.toml"));
+        assert_eq!(options.junit, Some(PathBuf::from("out.xml")));
+        assert!(matches!(
+            parse(&args("test /dev/ttyUSB0")),
+            Err(CliError::Usage(_))
+        ));
+    }
+}
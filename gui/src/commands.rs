@@ -0,0 +1,433 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::cli::{CliError, PortOptions};
+use crate::core::{self, ModemLines, SerialConfig};
+use crate::expect::{CaseStatus, TestRun, TestSuite};
+use crate::recorder::RecordingHeader;
+use crate::send_panel::LineEnding;
+use crate::session::{SerialSession, SessionError, SessionMessage};
+use crate::terminal::HexDump;
+use crate::usb::UsbIdentity;
+
+const POLL_TICK: Duration = Duration::from_millis(5);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Payload {
+    Text(String),
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+impl Payload {
+    fn bytes(&self, eol: Option<LineEnding>) -> Result<Vec<u8>, CliError> {
+        Ok(match self {
+            Payload::Text(text) => {
+                let mut bytes = text.as_bytes().to_vec();
+                if let Some(eol) = eol {
+                    bytes.extend_from_slice(eol.bytes());
+                }
+                bytes
+            }
+            Payload::Bytes(bytes) => bytes.clone(),
+            Payload::File(path) => std::fs::read(path)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SendOptions {
+    pub port: PortOptions,
+    pub payload: Payload,
+    /// Appended to text payloads.
+    pub eol: Option<LineEnding>,
+    /// Bytes the response must contain before `send` succeeds.
+    pub wait: Option<Vec<u8>>,
+    pub timeout: Duration,
+    pub hex: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaptureOptions {
+    pub port: PortOptions,
+    pub duration: Option<Duration>,
+    pub until: Option<Vec<u8>>,
+    /// Also writes a `.msrec` recording.
+    pub output: Option<PathBuf>,
+    pub hex: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProbeOptions {
+    pub port: PortOptions,
+    pub json: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestOptions {
+    pub port: PortOptions,
+    pub file: PathBuf,
+    /// Where to write the JUnit XML report.
+    pub junit: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PortListing {
+    pub path: String,
+    pub description: String,
+    pub usb: Option<UsbIdentity>,
+}
+
+/// Result of `probe`, printed as JSON for scripts.
+#[derive(Debug, Serialize)]
+pub struct ProbeReport {
+    pub port: String,
+    pub ok: bool,
+    pub config: SerialConfig,
+    /// Step that failed (`open`, `configure`, `start`, ...) when `ok` is false.
+    pub stage: Option<&'static str>,
+    pub code: Option<i32>,
+    pub error: Option<String>,
+    pub modem_lines: Option<ModemLines>,
+    pub usb: Option<UsbIdentity>,
+}
+
+impl ProbeReport {
+    pub fn new(
+        port: &str,
+        config: &SerialConfig,
+        result: Result<ModemLines, SessionError>,
+    ) -> Self {
+        let mut report = Self {
+            port: port.to_string(),
+            ok: result.is_ok(),
+            config: config.clone(),
+            stage: None,
+            code: None,
+            error: None,
+            modem_lines: None,
+            usb: UsbIdentity::lookup(port),
+        };
+        match result {
+            Ok(lines) => report.modem_lines = Some(lines),
+            Err(err) => {
+                let (stage, code) = match err {
+                    SessionError::Open(code) => ("open", Some(code)),
+                    SessionError::Configure(code) => ("configure", Some(code)),
+                    SessionError::Start(code) => ("start", Some(code)),
+                    SessionError::Control(code) => ("control", Some(code)),
+                    SessionError::Write(code) => ("write", Some(code)),
+                    SessionError::Truncated => ("write", None),
+                };
+                report.stage = Some(stage);
+                report.code = code;
+                report.error = Some(match code {
+                    Some(code) if code != 0 => {
+                        io::Error::from_raw_os_error(code.saturating_abs()).to_string()
+                    }
+                    _ => err.to_string(),
+                });
+            }
+        }
+        report
+    }
+}
+
+/// Writes received bytes to stdout, raw or as a hex dump.
+struct Output {
+    stdout: io::Stdout,
+    hex: Option<HexDump>,
+}
+
+impl Output {
+    fn new(hex: bool) -> Self {
+        Self {
+            stdout: io::stdout(),
+            hex: hex.then(HexDump::default),
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        match &mut self.hex {
+            Some(dump) => self.stdout.write_all(dump.format(data).as_bytes())?,
+            None => self.stdout.write_all(data)?,
+        }
+        self.stdout.flush()
+    }
+}
+
+/// Finds a byte pattern across chunk boundaries.
+#[derive(Debug)]
+pub struct PatternMatcher {
+    pattern: Vec<u8>,
+    tail: Vec<u8>,
+}
+
+impl PatternMatcher {
+    pub fn new(pattern: Vec<u8>) -> Self {
+        Self {
+            pattern,
+            tail: Vec::new(),
+        }
+    }
+
+    pub fn feed(&mut self, data: &[u8]) -> bool {
+        if self.pattern.is_empty() {
+            return true;
+        }
+        self.tail.extend_from_slice(data);
+        let found = self
+            .tail
+            .windows(self.pattern.len())
+            .any(|window| window == self.pattern);
+        let keep = self.pattern.len() - 1;
+        if self.tail.len() > keep {
+            self.tail.drain(..self.tail.len() - keep);
+        }
+        found
+    }
+}
+
+fn open(port: &PortOptions, config: &SerialConfig) -> Result<SerialSession, CliError> {
+    SerialSession::open(&port.port, config).map_err(|source| CliError::Session {
+        port: port.port.clone(),
+        source,
+    })
+}
+
+/// Streams received data to `output` until `deadline` or until `matcher` fires.
+///
+/// Returns whether the pattern was seen.
+fn stream(
+    session: &SerialSession,
+    output: &mut Output,
+    mut matcher: Option<PatternMatcher>,
+    deadline: Option<Instant>,
+) -> Result<bool, CliError> {
+    loop {
+        for message in session.poll() {
+            match message {
+                SessionMessage::Data(bytes) => {
+                    output.write(&bytes)?;
+                    if matcher.as_mut().is_some_and(|matcher| matcher.feed(&bytes)) {
+                        return Ok(true);
+                    }
+                }
+                SessionMessage::Event(event) => eprintln!("--- {} ---", event.message),
+            }
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Ok(false);
+        }
+        std::thread::sleep(POLL_TICK);
+    }
+}
+
+pub fn list(json: bool) -> Result<(), CliError> {
+    let ports: Vec<PortListing> = core::list_serial_ports()
+        .map_err(CliError::Enumerate)?
+        .into_iter()
+        .map(|device| PortListing {
+            usb: UsbIdentity::lookup(&device.path),
+            path: device.path,
+            description: device.description,
+        })
+        .collect();
+    let mut stdout = io::stdout();
+    if json {
+        serde_json::to_writer_pretty(&mut stdout, &ports).map_err(io::Error::from)?;
+        writeln!(stdout)?;
+        return Ok(());
+    }
+    for port in &ports {
+        write!(stdout, "{}\t{}", port.path, port.description)?;
+        if let Some(usb) = &port.usb {
+            write!(stdout, "\t{:04x}:{:04x}", usb.vendor_id, usb.product_id)?;
+            if let Some(serial) = &usb.serial {
+                write!(stdout, " {serial}")?;
+            }
+        }
+        writeln!(stdout)?;
+    }
+    Ok(())
+}
+
+/// Writes `payload` in pieces that fit the free TX ring space, then waits until
+/// the ring is empty, so closing the session does not discard queued bytes.
+fn transmit(
+    session: &mut SerialSession,
+    port: &str,
+    payload: &[u8],
+    timeout: Duration,
+) -> Result<(), CliError> {
+    let deadline = Instant::now() + timeout;
+    // The ring is at least this large and keeps one slot free.
+    let capacity = (session.config().tx_buffer_size as usize).saturating_sub(1);
+    let mut remaining = payload;
+    loop {
+        let pending = session.tx_pending();
+        let free = capacity.saturating_sub(pending).min(remaining.len());
+        if free > 0 {
+            let (chunk, rest) = remaining.split_at(free);
+            session.write(chunk).map_err(|source| CliError::Session {
+                port: port.to_string(),
+                source,
+            })?;
+            remaining = rest;
+            continue;
+        }
+        if remaining.is_empty() && pending == 0 {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(CliError::Timeout(format!(
+                "{} queued bytes to be sent",
+                remaining.len() + pending
+            )));
+        }
+        std::thread::sleep(POLL_TICK);
+    }
+}
+
+pub fn send(options: &SendOptions, config: &SerialConfig) -> Result<(), CliError> {
+    let payload = options.payload.bytes(options.eol)?;
+    let mut session = open(&options.port, config)?;
+    transmit(&mut session, &options.port.port, &payload, options.timeout)?;
+    let Some(pattern) = &options.wait else {
+        return Ok(());
+    };
+    let mut output = Output::new(options.hex);
+    let deadline = Instant::now() + options.timeout;
+    if stream(
+        &session,
+        &mut output,
+        Some(PatternMatcher::new(pattern.clone())),
+        Some(deadline),
+    )? {
+        Ok(())
+    } else {
+        Err(CliError::Timeout(
+            String::from_utf8_lossy(pattern).into_owned(),
+        ))
+    }
+}
+
+pub fn capture(options: &CaptureOptions, config: &SerialConfig) -> Result<(), CliError> {
+    let mut session = open(&options.port, config)?;
+    if let Some(path) = &options.output {
+        let header = RecordingHeader::new(&options.port.port, config, options.port.profile.clone());
+        session.start_recording(path, &header)?;
+    }
+    let mut output = Output::new(options.hex);
+    let deadline = options.duration.map(|duration| Instant::now() + duration);
+    let matcher = options.until.clone().map(PatternMatcher::new);
+    let found = stream(&session, &mut output, matcher, deadline)?;
+    if let Some(status) = session.stop_recording() {
+        eprintln!(
+            "--- recorded {} records to {} ---",
+            status.records,
+            status.path.display()
+        );
+    }
+    match &options.until {
+        Some(pattern) if !found => Err(CliError::Timeout(
+            String::from_utf8_lossy(pattern).into_owned(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub fn probe(options: &ProbeOptions, config: &SerialConfig) -> Result<(), CliError> {
+    let result = SerialSession::open(&options.port.port, config).map(|session| {
+        // A port without modem lines (e.g. a pty) still probes fine.
+        session.modem_lines().unwrap_or_default()
+    });
+    let report = ProbeReport::new(&options.port.port, config, result);
+    let mut stdout = io::stdout();
+    if options.json {
+        serde_json::to_writer_pretty(&mut stdout, &report).map_err(io::Error::from)?;
+        writeln!(stdout)?;
+    } else if report.ok {
+        writeln!(stdout, "{}: ok", report.port)?;
+    }
+    result.map(|_| ()).map_err(|source| CliError::Session {
+        port: options.port.port.clone(),
+        source,
+    })
+}
+
+pub fn test(options: &TestOptions, config: &SerialConfig) -> Result<(), CliError> {
+    let suite = TestSuite::load(&options.file)?;
+    let mut session = open(&options.port, config)?;
+    let mut run = TestRun::new(suite);
+    let mut reported = 0;
+    let mut stdout = io::stdout();
+    loop {
+        for message in session.poll() {
+            match message {
+                SessionMessage::Data(bytes) => run.feed(&bytes),
+                SessionMessage::Event(event) => eprintln!("--- {} ---", event.message),
+            }
+        }
+        run.advance(&mut session, Instant::now());
+        for result in &run.results()[reported..] {
+            if !result.status.is_done() {
+                break;
+            }
+            let millis = result.duration.as_millis();
+            match &result.status {
+                CaseStatus::Passed => writeln!(stdout, "PASS {} ({millis} ms)", result.name)?,
+                CaseStatus::Failed(message) | CaseStatus::Error(message) => {
+                    writeln!(stdout, "FAIL {} ({millis} ms): {message}", result.name)?
+                }
+                _ => writeln!(stdout, "SKIP {}", result.name)?,
+            }
+            reported += 1;
+        }
+        if run.is_finished() {
+            break;
+        }
+        std::thread::sleep(POLL_TICK);
+    }
+    if let Some(path) = &options.junit {
+        std::fs::write(path, run.junit())?;
+    }
+    match run.failures() {
+        0 => Ok(()),
+        failed => Err(CliError::TestsFailed(failed, run.results().len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_matches_across_chunks() {
+        let mut matcher = PatternMatcher::new(b"OK\r\n".to_vec());
+        assert!(!matcher.feed(b"AT\r\r\nO"));
+        assert!(!matcher.feed(b"K"));
+        assert!(matcher.feed(b"\r\nextra"));
+    }
+
+    #[test]
+    fn probe_report_names_failed_stage() {
+        let report = ProbeReport::new(
+            "/dev/ttyMissing",
+            &SerialConfig::default(),
+            Err(SessionError::Open(-libc::ENOENT)),
+        );
+        assert!(!report.ok);
+        assert_eq!(report.stage, Some("open"));
+        assert_eq!(report.code, Some(-libc::ENOENT));
+        assert!(
+            report
+                .error
+                .as_deref()
+                .is_some_and(|err| err.contains("No such file"))
+        );
+    }
+}
@@ -1,13 +1,27 @@
+use std::collections::VecDeque;
 use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
 use time::{OffsetDateTime, format_description::well_known::Rfc3339};
 
+/// Bookkeeping counted against the memory limit for every entry, on top of its bytes.
+const ENTRY_OVERHEAD: usize = std::mem::size_of::<ConsoleEntry>();
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
     Rx,
     Tx,
     Event,
+    AToB,
+    BToA,
+    /// Output of a running script.
+    Script,
 }
 
 impl Direction {
@@ -16,6 +30,9 @@ impl Direction {
             Direction::Rx => "RX",
             Direction::Tx => "TX",
             Direction::Event => "EVT",
+            Direction::AToB => "A→B",
+            Direction::BToA => "B→A",
+            Direction::Script => "SCR",
         }
     }
 }
@@ -27,93 +44,861 @@ pub enum ConsoleViewMode {
     Mixed,
 }
 
+/// How received and sent bytes are grouped into console rows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LineFraming {
+    /// Every read or write becomes its own row.
+    Chunk,
+    /// A row ends after each occurrence of the delimiter (kept in the row).
+    Delimiter(Vec<u8>),
+    /// A row ends when the stream stays quiet for longer than the gap.
+    IdleGap(Duration),
+    /// A row ends after this many bytes.
+    Length(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum LineFramingKind {
+    #[strum(to_string = "Raw chunk")]
+    Chunk,
+    #[strum(to_string = "LF")]
+    Lf,
+    #[strum(to_string = "CRLF")]
+    CrLf,
+    #[strum(to_string = "Custom byte")]
+    Byte,
+    #[strum(to_string = "Idle gap")]
+    IdleGap,
+    #[strum(to_string = "Fixed length")]
+    Length,
+}
+
+/// How much console history is kept in memory, where older entries go, and how rows are framed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsoleSettings {
+    pub memory_limit_mb: usize,
+    /// Append entries that no longer fit in memory to `spill_path`.
+    pub spill: bool,
+    pub spill_path: String,
+    #[serde(default = "default_framing")]
+    pub framing: LineFramingKind,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: u8,
+    #[serde(default = "default_idle_gap")]
+    pub idle_gap_ms: u64,
+    #[serde(default = "default_frame_length")]
+    pub frame_length: usize,
+    /// Quiet time after which a partial row is shown anyway; 0 waits for the rest.
+    #[serde(default = "default_flush")]
+    pub flush_ms: u64,
+    /// Put sent bytes into the same rows as received bytes, as a terminal shows echo.
+    #[serde(default)]
+    pub merge_tx: bool,
+    /// Longest row; a row without its delimiter is completed at this length.
+    #[serde(default = "default_max_row")]
+    pub max_row: usize,
+}
+
+fn default_framing() -> LineFramingKind {
+    LineFramingKind::Lf
+}
+
+fn default_delimiter() -> u8 {
+    b'\n'
+}
+
+fn default_idle_gap() -> u64 {
+    20
+}
+
+fn default_frame_length() -> usize {
+    16
+}
+
+fn default_flush() -> u64 {
+    500
+}
+
+fn default_max_row() -> usize {
+    4096
+}
+
+impl Default for ConsoleSettings {
+    fn default() -> Self {
+        let spill_path = ProjectDirs::from("dev", "MicroSerial", "MicroSerial")
+            .map(|dirs| dirs.data_dir().join("console-spill.log"))
+            .unwrap_or_else(|| PathBuf::from("console-spill.log"));
+        Self {
+            memory_limit_mb: 64,
+            spill: false,
+            spill_path: spill_path.display().to_string(),
+            framing: default_framing(),
+            delimiter: default_delimiter(),
+            idle_gap_ms: default_idle_gap(),
+            frame_length: default_frame_length(),
+            flush_ms: default_flush(),
+            merge_tx: false,
+            max_row: default_max_row(),
+        }
+    }
+}
+
+impl ConsoleSettings {
+    pub fn framing(&self) -> LineFraming {
+        match self.framing {
+            LineFramingKind::Chunk => LineFraming::Chunk,
+            LineFramingKind::Lf => LineFraming::Delimiter(b"\n".to_vec()),
+            LineFramingKind::CrLf => LineFraming::Delimiter(b"\r\n".to_vec()),
+            LineFramingKind::Byte => LineFraming::Delimiter(vec![self.delimiter]),
+            LineFramingKind::IdleGap => {
+                LineFraming::IdleGap(Duration::from_millis(self.idle_gap_ms))
+            }
+            LineFramingKind::Length => LineFraming::Length(self.frame_length.max(1)),
+        }
+    }
+
+    pub fn flush_after(&self) -> Option<Duration> {
+        (self.flush_ms > 0).then(|| Duration::from_millis(self.flush_ms))
+    }
+}
+
+/// One console row. Text and hex are rendered from `data` when the row is shown.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConsoleEntry {
     pub timestamp: OffsetDateTime,
     pub direction: Direction,
-    pub text: String,
-    pub hex: String,
+    pub data: Vec<u8>,
+    /// Set by a trigger rule.
+    #[serde(default)]
+    pub highlighted: bool,
 }
 
 impl ConsoleEntry {
+    fn new(direction: Direction, data: Vec<u8>) -> Self {
+        Self {
+            timestamp: OffsetDateTime::now_utc(),
+            direction,
+            data,
+            highlighted: false,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.data).into_owned()
+    }
+
+    /// The text on one line, with control characters written as escapes.
+    pub fn display_text(&self) -> String {
+        let mut out = String::with_capacity(self.data.len());
+        for character in String::from_utf8_lossy(&self.data).chars() {
+            match character {
+                '\r' => out.push_str("\\r"),
+                '\n' => out.push_str("\\n"),
+                '\t' => out.push_str("\\t"),
+                character if character.is_control() => {
+                    out.push_str(&format!("\\x{:02x}", u32::from(character)))
+                }
+                character => out.push(character),
+            }
+        }
+        out
+    }
+
+    pub fn hex(&self) -> String {
+        to_hex(&self.data)
+    }
+
     pub fn matches(&self, filter: &str) -> bool {
         if filter.trim().is_empty() {
             return true;
         }
         let filter_lower = filter.to_ascii_lowercase();
-        self.text.to_ascii_lowercase().contains(&filter_lower)
-            || self.hex.to_ascii_lowercase().contains(&filter_lower)
+        self.text().to_ascii_lowercase().contains(&filter_lower)
+            || self.hex().to_ascii_lowercase().contains(&filter_lower)
+    }
+
+    fn size(&self) -> usize {
+        ENTRY_OVERHEAD + self.data.len()
     }
 }
 
 impl fmt::Display for ConsoleEntry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let ts = self.timestamp.format(&Rfc3339).unwrap_or_default();
-        write!(f, "[{ts}] {} {}", self.direction.label(), self.text)
+        write!(f, "[{ts}] {} {}", self.direction.label(), self.text())
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A row still being assembled.
+#[derive(Debug)]
+struct PendingRow {
+    /// The stream the row belongs to; TX shares the RX stream when merged.
+    stream: Direction,
+    entry: ConsoleEntry,
+    last_chunk: Instant,
+}
+
+/// Entries evicted from memory, one per line: timestamp, direction and hex bytes.
+struct Spill {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl fmt::Debug for Spill {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Spill").field("path", &self.path).finish()
+    }
+}
+
+/// Console history as a ring bounded by memory use.
+///
+/// Entries are addressed by an absolute index that keeps counting when old entries
+/// are evicted, so a pause point or a filter result stays valid.
+#[derive(Debug)]
 pub struct ConsoleBuffer {
-    pub entries: Vec<ConsoleEntry>,
+    entries: VecDeque<ConsoleEntry>,
+    /// Absolute index of `entries[0]`; also the number of evicted entries.
+    first: u64,
+    bytes: usize,
+    limit: usize,
+    spill: Option<Spill>,
+    spill_error: Option<String>,
     pub show_timestamps: bool,
     pub view_mode: ConsoleViewMode,
     pub filter: String,
+    /// While paused only entries before this absolute index are shown; new entries are still kept.
+    pub paused_at: Option<u64>,
+    framing: LineFraming,
+    flush_after: Option<Duration>,
+    merge_tx: bool,
+    max_row: usize,
+    /// Rows still being assembled; their bytes count against `limit`.
+    pending: Vec<PendingRow>,
+    /// Absolute index of the first row completed by the latest RX or TX data.
+    pushed_from: u64,
+    /// Absolute indices of the entries matching `matched_filter`, up to `scanned`.
+    matches: Vec<u64>,
+    matched_filter: String,
+    scanned: u64,
 }
 
 impl Default for ConsoleBuffer {
     fn default() -> Self {
         Self {
-            entries: Vec::new(),
+            entries: VecDeque::new(),
+            first: 0,
+            bytes: 0,
+            limit: ConsoleSettings::default().memory_limit_mb << 20,
+            spill: None,
+            spill_error: None,
             show_timestamps: true,
             view_mode: ConsoleViewMode::Mixed,
             filter: String::new(),
+            paused_at: None,
+            framing: LineFraming::Chunk,
+            flush_after: None,
+            merge_tx: false,
+            max_row: default_max_row(),
+            pending: Vec::new(),
+            pushed_from: 0,
+            matches: Vec::new(),
+            matched_filter: String::new(),
+            scanned: 0,
         }
     }
 }
 
 impl ConsoleBuffer {
     pub fn push_rx(&mut self, data: &[u8]) {
-        self.entries.push(ConsoleEntry {
-            timestamp: OffsetDateTime::now_utc(),
-            direction: Direction::Rx,
-            text: String::from_utf8_lossy(data).to_string(),
-            hex: to_hex(data),
-        });
+        self.push_data(Direction::Rx, data);
     }
 
     pub fn push_tx(&mut self, data: &[u8]) {
-        self.entries.push(ConsoleEntry {
-            timestamp: OffsetDateTime::now_utc(),
-            direction: Direction::Tx,
-            text: String::from_utf8_lossy(data).to_string(),
-            hex: to_hex(data),
-        });
+        self.push_data(Direction::Tx, data);
+    }
+
+    pub fn push_data(&mut self, direction: Direction, data: &[u8]) {
+        self.push_framed(direction, data, Instant::now());
     }
 
     pub fn push_event(&mut self, message: &str) {
-        self.entries.push(ConsoleEntry {
-            timestamp: OffsetDateTime::now_utc(),
-            direction: Direction::Event,
-            text: message.to_string(),
-            hex: to_hex(message.as_bytes()),
-        });
+        self.push(ConsoleEntry::new(
+            Direction::Event,
+            message.as_bytes().to_vec(),
+        ));
+    }
+
+    pub fn push_script(&mut self, message: &str) {
+        self.push(ConsoleEntry::new(
+            Direction::Script,
+            message.as_bytes().to_vec(),
+        ));
+    }
+
+    /// Adds `data` to the row assembled for its stream, completing rows as the framing says.
+    fn push_framed(&mut self, direction: Direction, data: &[u8], now: Instant) {
+        self.pushed_from = self.end();
+        if self.framing == LineFraming::Chunk {
+            self.push(ConsoleEntry::new(direction, data.to_vec()));
+            return;
+        }
+        let stream = match direction {
+            Direction::Tx if self.merge_tx => Direction::Rx,
+            direction => direction,
+        };
+        self.expire(now, Some(stream));
+        let framing = self.framing.clone();
+        let max_row = self.max_row;
+        for &byte in data {
+            let row = self.pending_row(stream, direction, now);
+            row.push(byte);
+            let complete = row.len() >= max_row
+                || match &framing {
+                    LineFraming::Delimiter(delimiter) => row.ends_with(delimiter),
+                    LineFraming::Length(length) => row.len() >= *length,
+                    LineFraming::Chunk | LineFraming::IdleGap(_) => false,
+                };
+            self.bytes += 1;
+            if complete {
+                self.complete(stream);
+            }
+        }
+        self.evict();
+    }
+
+    fn pending_row(
+        &mut self,
+        stream: Direction,
+        direction: Direction,
+        now: Instant,
+    ) -> &mut Vec<u8> {
+        let index = match self.pending.iter().position(|row| row.stream == stream) {
+            Some(index) => index,
+            None => {
+                self.bytes += ENTRY_OVERHEAD;
+                self.pending.push(PendingRow {
+                    stream,
+                    entry: ConsoleEntry::new(direction, Vec::new()),
+                    last_chunk: now,
+                });
+                self.pending.len() - 1
+            }
+        };
+        let row = &mut self.pending[index];
+        row.last_chunk = now;
+        &mut row.entry.data
+    }
+
+    fn complete(&mut self, stream: Direction) {
+        if let Some(index) = self.pending.iter().position(|row| row.stream == stream) {
+            let row = self.pending.remove(index);
+            self.finish(row);
+        }
+    }
+
+    /// Moves a pending row into the history; its bytes were already counted.
+    fn finish(&mut self, row: PendingRow) {
+        self.bytes -= row.entry.size();
+        self.push(row.entry);
+    }
+
+    /// Completes rows whose idle gap or flush timeout has passed, for one stream or all.
+    fn expire(&mut self, now: Instant, stream: Option<Direction>) {
+        let gap = match self.framing {
+            LineFraming::IdleGap(gap) => Some(gap),
+            _ => None,
+        };
+        let timeout = match (gap, self.flush_after) {
+            (Some(gap), Some(flush)) => gap.min(flush),
+            (gap, flush) => match gap.or(flush) {
+                Some(timeout) => timeout,
+                None => return,
+            },
+        };
+        let (expired, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|row| {
+                stream.is_none_or(|stream| row.stream == stream)
+                    && now.saturating_duration_since(row.last_chunk) > timeout
+            });
+        self.pending = pending;
+        for row in expired {
+            self.finish(row);
+        }
+    }
+
+    /// Shows partial rows that have been quiet for longer than the gap or flush timeout.
+    pub fn tick(&mut self, now: Instant) {
+        self.expire(now, None);
+    }
+
+    /// Completes every partial row.
+    pub fn flush(&mut self) {
+        for row in std::mem::take(&mut self.pending) {
+            self.finish(row);
+        }
+    }
+
+    /// Changes how rows are framed; rows in progress are completed first.
+    pub fn set_framing(
+        &mut self,
+        framing: LineFraming,
+        flush_after: Option<Duration>,
+        merge_tx: bool,
+    ) {
+        if framing != self.framing || merge_tx != self.merge_tx {
+            self.flush();
+        }
+        self.framing = framing;
+        self.flush_after = flush_after;
+        self.merge_tx = merge_tx;
+    }
+
+    fn push(&mut self, entry: ConsoleEntry) {
+        self.bytes += entry.size();
+        self.entries.push_back(entry);
+        self.evict();
+    }
+
+    /// Drops the oldest entries until the ring fits its limit, keeping the newest entry.
+    fn evict(&mut self) {
+        let mut spilled = false;
+        while self.bytes > self.limit && self.entries.len() > 1 {
+            let Some(entry) = self.entries.pop_front() else {
+                break;
+            };
+            self.bytes -= entry.size();
+            self.first += 1;
+            if let Some(spill) = &mut self.spill {
+                let ts = entry.timestamp.format(&Rfc3339).unwrap_or_default();
+                let line = format!("{ts}\t{}\t{}", entry.direction.label(), entry.hex());
+                if let Err(err) = writeln!(spill.writer, "{line}") {
+                    self.spill_error = Some(format!("{}: {err}", spill.path.display()));
+                    self.spill = None;
+                }
+                spilled = true;
+            }
+        }
+        if spilled {
+            self.flush_spill();
+        }
+        let first = self.first;
+        let stale = self.matches.partition_point(|&index| index < first);
+        self.matches.drain(..stale);
+        self.scanned = self.scanned.max(first);
+    }
+
+    fn flush_spill(&mut self) {
+        if let Some(spill) = &mut self.spill
+            && let Err(err) = spill.writer.flush()
+        {
+            self.spill_error = Some(format!("{}: {err}", spill.path.display()));
+            self.spill = None;
+        }
+    }
+
+    /// Sets the longest row; longer rows are split even without their delimiter.
+    pub fn set_max_row(&mut self, bytes: usize) {
+        self.max_row = bytes.max(1);
+    }
+
+    /// Sets the memory limit in bytes, evicting entries that no longer fit.
+    pub fn set_limit(&mut self, bytes: usize) {
+        self.limit = bytes;
+        self.evict();
+    }
+
+    /// Appends evicted entries to `path` from now on, or stops spilling with `None`.
+    pub fn set_spill(&mut self, path: Option<&Path>) -> io::Result<()> {
+        self.flush_spill();
+        self.spill = None;
+        if let Some(path) = path {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            self.spill = Some(Spill {
+                path: path.to_path_buf(),
+                writer: BufWriter::new(file),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn spill_path(&self) -> Option<&Path> {
+        self.spill.as_ref().map(|spill| spill.path.as_path())
+    }
+
+    /// A write to the spill file that failed since the last call; spilling stops after one.
+    pub fn take_spill_error(&mut self) -> Option<String> {
+        self.spill_error.take()
+    }
+
+    /// Memory counted against the limit, partial rows included.
+    pub fn memory_used(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries evicted so far, spilled or not.
+    pub fn evicted(&self) -> u64 {
+        self.first
+    }
+
+    fn end(&self) -> u64 {
+        self.first + self.entries.len() as u64
+    }
+
+    fn get(&self, index: u64) -> Option<&ConsoleEntry> {
+        self.entries
+            .get(usize::try_from(index.checked_sub(self.first)?).ok()?)
     }
 
     pub fn clear(&mut self) {
+        self.pending.clear();
+        self.first = self.end();
         self.entries.clear();
+        self.bytes = 0;
+        self.matches.clear();
+        self.scanned = self.first;
+        if self.paused_at.is_some() {
+            self.paused_at = Some(self.first);
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(self.end());
+        }
+    }
+
+    pub fn resume(&mut self) {
+        self.paused_at = None;
+    }
+
+    /// Marks the RX row that holds `matched`, newest first: the row still being
+    /// assembled, then the rows completed by the latest data. Falls back to the most
+    /// recent RX row when the match spans rows.
+    pub fn highlight_last_rx(&mut self, matched: &str) {
+        let holds = |entry: &ConsoleEntry| String::from_utf8_lossy(&entry.data).contains(matched);
+        let pending = self
+            .pending
+            .iter()
+            .position(|row| row.stream == Direction::Rx);
+        if let Some(index) = pending.filter(|&index| holds(&self.pending[index].entry)) {
+            self.pending[index].entry.highlighted = true;
+            return;
+        }
+        let recent = self.pushed_from.max(self.first)..self.end();
+        let completed = recent.rev().find(|&index| {
+            self.get(index)
+                .is_some_and(|entry| entry.direction == Direction::Rx && holds(entry))
+        });
+        let target = match (completed, pending) {
+            (Some(index), _) => self.entries.get_mut((index - self.first) as usize),
+            (None, Some(index)) => Some(&mut self.pending[index].entry),
+            (None, None) => self
+                .entries
+                .iter_mut()
+                .rev()
+                .find(|entry| entry.direction == Direction::Rx),
+        };
+        if let Some(entry) = target {
+            entry.highlighted = true;
+        }
+    }
+
+    /// Brings the filter results up to date; only entries added since the last call are
+    /// checked unless the filter changed.
+    pub fn refresh_view(&mut self) {
+        if self.filter.trim().is_empty() {
+            return;
+        }
+        if self.filter != self.matched_filter {
+            self.matched_filter = self.filter.clone();
+            self.matches.clear();
+            self.scanned = self.first;
+        }
+        let end = self.end();
+        for index in self.scanned..end {
+            if self
+                .get(index)
+                .is_some_and(|entry| entry.matches(&self.matched_filter))
+            {
+                self.matches.push(index);
+            }
+        }
+        self.scanned = end;
+    }
+
+    fn shown_end(&self) -> u64 {
+        self.paused_at.unwrap_or(u64::MAX).min(self.end())
+    }
+
+    /// Number of rows to show; call [`Self::refresh_view`] first.
+    pub fn rows(&self) -> usize {
+        let end = self.shown_end();
+        if self.filter.trim().is_empty() {
+            end.saturating_sub(self.first) as usize
+        } else {
+            self.matches.partition_point(|&index| index < end)
+        }
+    }
+
+    /// The entry shown at `row`, counted from the oldest.
+    pub fn row(&self, row: usize) -> Option<&ConsoleEntry> {
+        if self.filter.trim().is_empty() {
+            self.entries.get(row)
+        } else {
+            self.get(*self.matches.get(row)?)
+        }
     }
 
+    /// The rows, oldest first.
     pub fn iter(&self) -> impl Iterator<Item = &ConsoleEntry> {
+        let shown = self.shown_end().saturating_sub(self.first) as usize;
         self.entries
             .iter()
+            .take(shown)
             .filter(|entry| entry.matches(&self.filter))
     }
 }
 
-fn to_hex(data: &[u8]) -> String {
+pub fn to_hex(data: &[u8]) -> String {
     data.iter()
         .map(|byte| format!("{byte:02X}"))
         .collect::<Vec<_>>()
         .join(" ")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(limit_entries: usize, payload: usize) -> ConsoleBuffer {
+        let mut console = ConsoleBuffer::default();
+        console.set_limit(limit_entries * (ENTRY_OVERHEAD + payload));
+        console
+    }
+
+    #[test]
+    fn ring_evicts_oldest_and_keeps_bytes_exact() {
+        let mut console = buffer(3, 4);
+        for index in 0..5u8 {
+            console.push_rx(&[index, 0xFF, b'\r', 0x00]);
+        }
+        assert_eq!(console.len(), 3);
+        assert_eq!(console.evicted(), 2);
+        assert!(console.memory_used() <= 3 * (ENTRY_OVERHEAD + 4));
+        let entry = console.row(0).expect("row");
+        assert_eq!(entry.data, [2, 0xFF, b'\r', 0x00]);
+        assert_eq!(entry.hex(), "02 FF 0D 00");
+        assert_eq!(entry.display_text(), "\\x02\u{FFFD}\\r\\x00");
+
+        // A single entry larger than the limit is still kept.
+        console.push_rx(&[0; 64]);
+        assert_eq!(console.len(), 1);
+    }
+
+    #[test]
+    fn pause_and_filter_survive_eviction() {
+        let mut console = buffer(4, 3);
+        for value in ["ok1", "err", "ok2"] {
+            console.push_rx(value.as_bytes());
+        }
+        console.pause();
+        console.push_rx(b"ok3");
+        console.refresh_view();
+        assert_eq!(console.rows(), 3);
+
+        console.filter = "ok".to_string();
+        console.refresh_view();
+        assert_eq!(console.rows(), 2);
+        console.push_rx(b"ok4");
+        console.push_rx(b"ok5");
+        console.refresh_view();
+        // "ok1" and "err" were evicted; the pause still hides "ok3" onwards.
+        assert_eq!(console.evicted(), 2);
+        assert_eq!(console.rows(), 1);
+        assert_eq!(
+            console.row(0).map(ConsoleEntry::text).as_deref(),
+            Some("ok2")
+        );
+
+        console.resume();
+        assert_eq!(console.rows(), 4);
+        assert_eq!(
+            console.row(3).map(ConsoleEntry::text).as_deref(),
+            Some("ok5")
+        );
+        console.filter.clear();
+        assert_eq!(console.rows(), 4);
+        assert_eq!(console.iter().count(), 4);
+    }
+
+    #[test]
+    fn evicted_entries_spill_to_disk() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        // The data directory may not exist yet on first use.
+        let path = dir.path().join("data").join("spill.log");
+        let mut console = buffer(1, 2);
+        console.set_spill(Some(&path)).expect("spill");
+        console.push_rx(b"AB");
+        console.push_event("x");
+        console.push_tx(&[0x00, 0x01]);
+        let spilled = std::fs::read_to_string(&path).expect("read");
+        let lines: Vec<Vec<&str>> = spilled
+            .lines()
+            .map(|line| line.split('\t').skip(1).collect())
+            .collect();
+        assert_eq!(lines, [vec!["RX", "41 42"], vec!["EVT", "78"]]);
+        assert!(console.take_spill_error().is_none());
+    }
+
+    fn framed(
+        framing: LineFraming,
+        flush_after: Option<Duration>,
+        merge_tx: bool,
+    ) -> ConsoleBuffer {
+        let mut console = ConsoleBuffer::default();
+        console.set_framing(framing, flush_after, merge_tx);
+        console
+    }
+
+    fn rows(console: &ConsoleBuffer) -> Vec<(Direction, String)> {
+        (0..console.rows())
+            .filter_map(|row| console.row(row))
+            .map(|entry| (entry.direction, entry.display_text()))
+            .collect()
+    }
+
+    #[test]
+    fn delimiter_and_length_framing_assemble_rows() {
+        let start = Instant::now();
+        let mut console = framed(LineFraming::Delimiter(b"\r\n".to_vec()), None, false);
+        console.push_framed(Direction::Rx, b"temp=2", start);
+        console.push_framed(Direction::Rx, b"1\r", start);
+        console.push_framed(Direction::Rx, b"\nOK\r\nhum", start);
+        console.push_event("note");
+        assert_eq!(
+            rows(&console),
+            [
+                (Direction::Rx, "temp=21\\r\\n".to_string()),
+                (Direction::Rx, "OK\\r\\n".to_string()),
+                (Direction::Event, "note".to_string()),
+            ]
+        );
+        // Without a flush timeout the partial row waits for its delimiter.
+        console.tick(start + Duration::from_secs(60));
+        assert_eq!(console.rows(), 3);
+        console.flush();
+        assert_eq!(
+            console.row(3).map(ConsoleEntry::text).as_deref(),
+            Some("hum")
+        );
+
+        let mut console = framed(LineFraming::Length(4), None, false);
+        console.push_framed(Direction::Rx, &[1, 2, 3], start);
+        console.push_framed(Direction::Rx, &[4, 5, 6, 7, 8, 9], start);
+        let data: Vec<_> = console.iter().map(|entry| entry.data.clone()).collect();
+        assert_eq!(data, [vec![1, 2, 3, 4], vec![5, 6, 7, 8]]);
+    }
+
+    #[test]
+    fn idle_gap_and_flush_timeout_complete_partial_rows() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut console = framed(LineFraming::IdleGap(ms(20)), None, false);
+        console.push_framed(Direction::Rx, b"AB", start);
+        console.push_framed(Direction::Rx, b"C", start + ms(10));
+        console.push_framed(Direction::Rx, b"D", start + ms(50));
+        console.tick(start + ms(60));
+        assert_eq!(
+            console.iter().map(ConsoleEntry::text).collect::<Vec<_>>(),
+            ["ABC"]
+        );
+        console.tick(start + ms(71));
+        assert_eq!(console.rows(), 2);
+
+        let mut console = framed(LineFraming::Delimiter(b"\n".to_vec()), Some(ms(500)), false);
+        console.push_framed(Direction::Rx, b"> ", start);
+        console.highlight_last_rx(">");
+        console.tick(start + ms(400));
+        assert!(console.is_empty());
+        console.tick(start + ms(501));
+        let entry = console.row(0).expect("flushed row");
+        assert_eq!(entry.text(), "> ");
+        assert!(entry.highlighted);
+    }
+
+    #[test]
+    fn rows_without_delimiter_are_capped_and_counted() {
+        let start = Instant::now();
+        let mut console = framed(LineFraming::Delimiter(b"\n".to_vec()), None, false);
+        console.set_max_row(64);
+        console.set_limit(4 * (ENTRY_OVERHEAD + 64));
+        console.push_framed(Direction::Rx, &[b'x'; 40], start);
+        assert!(console.is_empty());
+        assert_eq!(console.memory_used(), ENTRY_OVERHEAD + 40);
+
+        for _ in 0..100 {
+            console.push_framed(Direction::Rx, &[b'x'; 100], start);
+        }
+        assert!(console.iter().all(|entry| entry.data.len() == 64));
+        assert!(console.evicted() > 0);
+        assert!(console.memory_used() <= 4 * (ENTRY_OVERHEAD + 64));
+    }
+
+    #[test]
+    fn highlight_marks_the_row_holding_the_match() {
+        let start = Instant::now();
+        let mut console = framed(LineFraming::Delimiter(b"\n".to_vec()), None, false);
+        console.push_framed(Direction::Rx, b"panic\r\nboot", start);
+        console.highlight_last_rx("panic");
+        console.flush();
+        let marked: Vec<_> = console
+            .iter()
+            .map(|entry| (entry.text(), entry.highlighted))
+            .collect();
+        assert_eq!(
+            marked,
+            [("panic\r\n".to_string(), true), ("boot".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn tx_merges_into_rx_rows_when_enabled() {
+        let start = Instant::now();
+        let lf = LineFraming::Delimiter(b"\n".to_vec());
+        let mut console = framed(lf.clone(), None, false);
+        console.push_framed(Direction::Rx, b"> ", start);
+        console.push_framed(Direction::Tx, b"help\n", start);
+        console.push_framed(Direction::Rx, b"ok\n", start);
+        assert_eq!(
+            rows(&console),
+            [
+                (Direction::Tx, "help\\n".to_string()),
+                (Direction::Rx, "> ok\\n".to_string()),
+            ]
+        );
+
+        let mut console = framed(lf, None, true);
+        console.push_framed(Direction::Rx, b"> ", start);
+        console.push_framed(Direction::Tx, b"help\n", start);
+        console.push_framed(Direction::Rx, b"ok\n", start);
+        assert_eq!(
+            rows(&console),
+            [
+                (Direction::Rx, "> help\\n".to_string()),
+                (Direction::Rx, "ok\\n".to_string()),
+            ]
+        );
+    }
+}
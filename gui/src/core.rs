@@ -22,6 +22,11 @@ pub struct SerialPort {
     callbacks: Option<Arc<CallbackState>>,
 }
 
+// The core guards its TX ring with a mutex and keeps no thread affinity for a
+// handle, so a port may be owned by a worker thread other than the one that
+// opened it.
+unsafe impl Send for SerialPort {}
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct SerialDevice {
     pub path: String,
@@ -56,7 +61,7 @@ pub enum FlowControl {
     XonXoff,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SerialConfig {
     pub baud_rate: u32,
     pub data_bits: u8,
@@ -129,6 +134,31 @@ impl Default for SerialConfig {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModemLines {
+    pub dtr: bool,
+    pub rts: bool,
+    pub cts: bool,
+    pub dsr: bool,
+    pub ri: bool,
+    pub cd: bool,
+}
+
+impl ModemLines {
+    #[allow(non_upper_case_globals)]
+    pub fn from_raw(bits: u32) -> Self {
+        let has = |line: ms_serial_modem_line| bits & line != 0;
+        Self {
+            dtr: has(ms_serial_modem_line_MS_SERIAL_LINE_DTR),
+            rts: has(ms_serial_modem_line_MS_SERIAL_LINE_RTS),
+            cts: has(ms_serial_modem_line_MS_SERIAL_LINE_CTS),
+            dsr: has(ms_serial_modem_line_MS_SERIAL_LINE_DSR),
+            ri: has(ms_serial_modem_line_MS_SERIAL_LINE_RI),
+            cd: has(ms_serial_modem_line_MS_SERIAL_LINE_CD),
+        }
+    }
+}
+
 struct CallbackState {
     on_data: Mutex<Box<dyn FnMut(&[u8]) + Send + 'static>>,
     on_event: Mutex<Box<dyn FnMut(i32, &str) + Send + 'static>>,
@@ -213,6 +243,50 @@ impl SerialPort {
         self.callbacks = None;
     }
 
+    /// Drives DTR and/or RTS; `None` leaves the corresponding line untouched.
+    pub fn set_modem_lines(&mut self, dtr: Option<bool>, rts: Option<bool>) -> Result<(), i32> {
+        let mut mask = 0;
+        let mut values = 0;
+        for (state, line) in [
+            (dtr, ms_serial_modem_line_MS_SERIAL_LINE_DTR),
+            (rts, ms_serial_modem_line_MS_SERIAL_LINE_RTS),
+        ] {
+            if let Some(enabled) = state {
+                mask |= line;
+                if enabled {
+                    values |= line;
+                }
+            }
+        }
+        let rc = unsafe { ms_serial_port_set_modem_lines(self.handle, mask, values) };
+        if rc != 0 {
+            return Err(rc);
+        }
+        Ok(())
+    }
+
+    pub fn modem_lines(&self) -> Result<ModemLines, i32> {
+        let mut bits: u32 = 0;
+        let rc = unsafe { ms_serial_port_get_modem_lines(self.handle, &mut bits) };
+        if rc != 0 {
+            return Err(rc);
+        }
+        Ok(ModemLines::from_raw(bits))
+    }
+
+    pub fn set_break(&mut self, enabled: bool) -> Result<(), i32> {
+        let rc = unsafe { ms_serial_port_set_break(self.handle, c_int::from(enabled)) };
+        if rc != 0 {
+            return Err(rc);
+        }
+        Ok(())
+    }
+
+    /// Bytes accepted by [`SerialPort::write`] that are not yet on the wire.
+    pub fn tx_pending(&self) -> usize {
+        unsafe { ms_serial_port_tx_pending(self.handle) }
+    }
+
     pub fn write(&mut self, data: &[u8]) -> Result<usize, i32> {
         let rc = unsafe { ms_serial_port_write(self.handle, data.as_ptr(), data.len()) };
         if rc < 0 {
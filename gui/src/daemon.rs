@@ -0,0 +1,891 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::core::{self, ModemLines, SerialConfig};
+use crate::recorder::{self, RecordingHeader};
+use crate::session::{RecordingStatus, SerialSession, SessionError, SessionMessage};
+use crate::usb::UsbIdentity;
+
+const ACCEPT_TICK: Duration = Duration::from_millis(50);
+const READ_TICK: Duration = Duration::from_millis(200);
+const PUMP_TICK: Duration = Duration::from_millis(5);
+const TOKEN_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_TOKEN_LINE: usize = 1024;
+/// Lines queued for a client before it counts as too slow and is disconnected.
+const CLIENT_BACKLOG: usize = 4096;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DaemonSettings {
+    pub socket: String,
+    /// Optional loopback `host:port` for clients that cannot use the socket.
+    pub tcp: Option<String>,
+    /// Sent by TCP clients as their first line; required when `tcp` is set.
+    pub token: Option<String>,
+    /// Open ports through the daemon instead of directly.
+    pub attach: bool,
+}
+
+impl Default for DaemonSettings {
+    fn default() -> Self {
+        Self {
+            socket: default_socket_path().display().to_string(),
+            tcp: None,
+            token: None,
+            attach: false,
+        }
+    }
+}
+
+/// `$XDG_RUNTIME_DIR/microserial.sock`, or a per-user path in `/tmp`.
+pub fn default_socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("microserial.sock"),
+        None => {
+            let uid = unsafe { libc::getuid() };
+            PathBuf::from(format!("/tmp/microserial-{uid}.sock"))
+        }
+    }
+}
+
+/// Serializes bytes as a lowercase hex string in JSON.
+pub mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer, de};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = data.iter().map(|byte| format!("{byte:02x}")).collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        crate::send_panel::parse_hex(&hex).map_err(de::Error::custom)
+    }
+}
+
+/// One API call; serialized as `{"id": 1, "method": "open", "params": {...}}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum Request {
+    List,
+    Sessions,
+    /// Opens the port, or joins it when another client already has it open.
+    Open {
+        port: String,
+        /// Defaults to the core default configuration when omitted.
+        #[serde(default)]
+        config: Option<SerialConfig>,
+    },
+    Close {
+        port: String,
+    },
+    Configure {
+        port: String,
+        config: SerialConfig,
+    },
+    Write {
+        port: String,
+        #[serde(with = "hex_bytes")]
+        data: Vec<u8>,
+    },
+    SetModemLines {
+        port: String,
+        dtr: Option<bool>,
+        rts: Option<bool>,
+    },
+    ModemLines {
+        port: String,
+    },
+    SetBreak {
+        port: String,
+        enabled: bool,
+    },
+    /// Streams `rx`, `tx` and `event` notifications for the port to this client.
+    Subscribe {
+        port: String,
+    },
+    Unsubscribe {
+        port: String,
+    },
+    StartRecording {
+        port: String,
+        path: Option<PathBuf>,
+    },
+    StopRecording {
+        port: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RequestEnvelope {
+    pub id: u64,
+    #[serde(flatten)]
+    pub request: Request,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiErrorKind {
+    BadRequest,
+    NotOpen,
+    Open,
+    Configure,
+    Start,
+    Io,
+    Recording,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Error)]
+#[error("{message}")]
+pub struct ApiError {
+    pub kind: ApiErrorKind,
+    /// Negative errno from the port layer, or 0.
+    pub code: i32,
+    pub message: String,
+}
+
+impl ApiError {
+    fn new(kind: ApiErrorKind, code: i32, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn not_open(port: &str) -> Self {
+        Self::new(
+            ApiErrorKind::NotOpen,
+            -libc::ENODEV,
+            format!("{port} is not open"),
+        )
+    }
+}
+
+impl From<SessionError> for ApiError {
+    fn from(err: SessionError) -> Self {
+        let (kind, code) = match err {
+            SessionError::Open(code) => (ApiErrorKind::Open, code),
+            SessionError::Configure(code) => (ApiErrorKind::Configure, code),
+            SessionError::Start(code) => (ApiErrorKind::Start, code),
+            SessionError::Write(code) | SessionError::Control(code) => (ApiErrorKind::Io, code),
+            SessionError::Truncated => (ApiErrorKind::Io, -libc::EIO),
+        };
+        Self::new(kind, code, err.to_string())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub id: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ApiError>,
+}
+
+/// Notifications pushed to subscribed clients.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DaemonEvent {
+    Rx {
+        port: String,
+        #[serde(with = "hex_bytes")]
+        data: Vec<u8>,
+    },
+    /// Bytes another client wrote.
+    Tx {
+        port: String,
+        #[serde(with = "hex_bytes")]
+        data: Vec<u8>,
+    },
+    Event {
+        port: String,
+        code: i32,
+        message: String,
+    },
+    Configured {
+        port: String,
+        config: SerialConfig,
+    },
+    Closed {
+        port: String,
+    },
+}
+
+/// A line sent by the daemon: a reply to a request or a notification.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ServerLine {
+    Response(Response),
+    Event(DaemonEvent),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListedPort {
+    pub path: String,
+    pub description: String,
+    pub usb: Option<UsbIdentity>,
+    pub open: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingInfo {
+    pub path: PathBuf,
+    pub records: u64,
+    pub bytes: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub port: String,
+    pub config: SerialConfig,
+    pub subscribers: usize,
+    pub recording: Option<RecordingInfo>,
+}
+
+#[derive(Debug, Error)]
+pub enum DaemonError {
+    #[error("daemon already running at {0}")]
+    Running(String),
+    #[error("cannot listen on {address}: {source}")]
+    Bind { address: String, source: io::Error },
+    #[error("TCP address {0} is not a loopback address")]
+    NotLoopback(String),
+    #[error("a token is required to listen on TCP")]
+    TokenRequired,
+}
+
+/// The sending side of one client connection.
+#[derive(Clone)]
+struct Client {
+    id: u64,
+    outbound: SyncSender<String>,
+    /// Set when the client fell too far behind; its connection is then closed.
+    lagged: Arc<AtomicBool>,
+}
+
+struct PortEntry {
+    session: Mutex<SerialSession>,
+    subscribers: Mutex<Vec<Client>>,
+}
+
+impl PortEntry {
+    fn session(&self) -> MutexGuard<'_, SerialSession> {
+        lock(&self.session)
+    }
+
+    fn subscribers(&self) -> MutexGuard<'_, Vec<Client>> {
+        lock(&self.subscribers)
+    }
+
+    fn broadcast(&self, event: &DaemonEvent, except: Option<u64>) {
+        let Ok(line) = serde_json::to_string(&ServerLine::Event(event.clone())) else {
+            return;
+        };
+        self.subscribers().retain(|subscriber| {
+            if Some(subscriber.id) == except {
+                return true;
+            }
+            match subscriber.outbound.try_send(line.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    subscriber.lagged.store(true, Ordering::SeqCst);
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+    }
+}
+
+struct Shared {
+    stop: AtomicBool,
+    next_client: AtomicU64,
+    recordings: PathBuf,
+    /// Entries are shared so requests can work on a port without holding
+    /// the map locked; each session has its own lock.
+    ports: Mutex<HashMap<String, Arc<PortEntry>>>,
+}
+
+impl Shared {
+    fn ports(&self) -> MutexGuard<'_, HashMap<String, Arc<PortEntry>>> {
+        lock(&self.ports)
+    }
+
+    fn entries(&self) -> Vec<(String, Arc<PortEntry>)> {
+        self.ports()
+            .iter()
+            .map(|(port, entry)| (port.clone(), Arc::clone(entry)))
+            .collect()
+    }
+
+    fn lookup(&self, port: &str) -> Result<Arc<PortEntry>, ApiError> {
+        self.ports()
+            .get(port)
+            .cloned()
+            .ok_or_else(|| ApiError::not_open(port))
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Owns sessions on behalf of local clients speaking line-delimited JSON.
+///
+/// Ports stay open until a client closes them, so test rigs can keep a
+/// device attached while observers come and go. The optional TCP listener
+/// only binds loopback addresses and requires the configured token.
+pub struct Daemon {
+    socket: PathBuf,
+    tcp: Option<std::net::SocketAddr>,
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Daemon {
+    /// Starts listening; recordings requested without a path go to `recordings`.
+    pub fn start(
+        settings: &DaemonSettings,
+        recordings: impl Into<PathBuf>,
+    ) -> Result<Self, DaemonError> {
+        let socket = PathBuf::from(&settings.socket);
+        let unix = bind_unix(&socket)?;
+        let tcp = match &settings.tcp {
+            Some(address) => {
+                let token = settings
+                    .token
+                    .clone()
+                    .filter(|token| !token.is_empty())
+                    .ok_or(DaemonError::TokenRequired)?;
+                Some((bind_loopback(address)?, token))
+            }
+            None => None,
+        };
+        let tcp_addr = tcp
+            .as_ref()
+            .and_then(|(listener, _)| listener.local_addr().ok());
+
+        let shared = Arc::new(Shared {
+            stop: AtomicBool::new(false),
+            next_client: AtomicU64::new(1),
+            recordings: recordings.into(),
+            ports: Mutex::new(HashMap::new()),
+        });
+        let mut threads = Vec::new();
+        let accept_shared = Arc::clone(&shared);
+        threads.push(thread::spawn(move || {
+            accept_loop(&accept_shared, None, || {
+                unix.accept()
+                    .map(|(stream, _)| -> Box<dyn Connection> { Box::new(stream) })
+            })
+        }));
+        if let Some((listener, token)) = tcp {
+            let accept_shared = Arc::clone(&shared);
+            let token: Arc<str> = token.into();
+            threads.push(thread::spawn(move || {
+                accept_loop(&accept_shared, Some(token), || {
+                    listener
+                        .accept()
+                        .map(|(stream, _)| -> Box<dyn Connection> { Box::new(stream) })
+                })
+            }));
+        }
+        let pump_shared = Arc::clone(&shared);
+        threads.push(thread::spawn(move || pump(&pump_shared)));
+
+        Ok(Self {
+            socket,
+            tcp: tcp_addr,
+            shared,
+            threads,
+        })
+    }
+
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    pub fn tcp_addr(&self) -> Option<std::net::SocketAddr> {
+        self.tcp
+    }
+
+    /// Serves until the process exits.
+    ///
+    /// The daemon is consumed, so nothing can stop it any more; keep it and
+    /// call [`Daemon::stop`] instead when it must shut down in-process.
+    pub fn wait(mut self) {
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.shared.stop.store(true, Ordering::SeqCst);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+        self.shared.ports().clear();
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Binds the socket, replacing a stale file but never a live daemon.
+fn bind_unix(socket: &Path) -> Result<UnixListener, DaemonError> {
+    let bind_error = |source| DaemonError::Bind {
+        address: socket.display().to_string(),
+        source,
+    };
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(DaemonError::Running(socket.display().to_string()));
+        }
+        std::fs::remove_file(socket).map_err(bind_error)?;
+    }
+    if let Some(parent) = socket.parent() {
+        std::fs::create_dir_all(parent).map_err(bind_error)?;
+    }
+    let listener = UnixListener::bind(socket).map_err(bind_error)?;
+    listener.set_nonblocking(true).map_err(bind_error)?;
+    Ok(listener)
+}
+
+/// Binds a nonblocking TCP listener, refusing addresses other hosts can reach.
+fn bind_loopback(address: &str) -> Result<TcpListener, DaemonError> {
+    let bind_error = |source| DaemonError::Bind {
+        address: address.to_string(),
+        source,
+    };
+    let mut resolved = address.to_socket_addrs().map_err(bind_error)?.peekable();
+    if resolved.peek().is_none() || !resolved.all(|addr| addr.ip().is_loopback()) {
+        return Err(DaemonError::NotLoopback(address.to_string()));
+    }
+    let listener = TcpListener::bind(address).map_err(bind_error)?;
+    listener.set_nonblocking(true).map_err(bind_error)?;
+    Ok(listener)
+}
+
+/// A client stream, Unix or TCP.
+trait Connection: Read + Write + Send {
+    fn split(&self) -> io::Result<Box<dyn Connection>>;
+    fn prepare(&self) -> io::Result<()>;
+}
+
+impl Connection for UnixStream {
+    fn split(&self) -> io::Result<Box<dyn Connection>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+
+    fn prepare(&self) -> io::Result<()> {
+        self.set_nonblocking(false)?;
+        self.set_read_timeout(Some(READ_TICK))
+    }
+}
+
+impl Connection for TcpStream {
+    fn split(&self) -> io::Result<Box<dyn Connection>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+
+    fn prepare(&self) -> io::Result<()> {
+        self.set_nonblocking(false)?;
+        let _ = self.set_nodelay(true);
+        self.set_read_timeout(Some(READ_TICK))
+    }
+}
+
+fn accept_loop(
+    shared: &Arc<Shared>,
+    token: Option<Arc<str>>,
+    accept: impl Fn() -> io::Result<Box<dyn Connection>>,
+) {
+    while !shared.stop.load(Ordering::SeqCst) {
+        match accept() {
+            Ok(stream) => {
+                let client_shared = Arc::clone(shared);
+                let token = token.clone();
+                thread::spawn(move || {
+                    let _ = serve_client(&client_shared, stream, token.as_deref());
+                });
+            }
+            // WouldBlock from the nonblocking listener, or a transient accept failure.
+            Err(_) => thread::sleep(ACCEPT_TICK),
+        }
+    }
+}
+
+fn serve_client(
+    shared: &Arc<Shared>,
+    stream: Box<dyn Connection>,
+    token: Option<&str>,
+) -> io::Result<()> {
+    stream.prepare()?;
+    let mut writer = stream.split()?;
+    let mut reader = BufReader::new(stream);
+    if let Some(token) = token
+        && !authenticate(&mut reader, token, &shared.stop)
+    {
+        return Ok(());
+    }
+    let (outbound, lines) = mpsc::sync_channel::<String>(CLIENT_BACKLOG);
+    let client = Client {
+        id: shared.next_client.fetch_add(1, Ordering::Relaxed),
+        outbound,
+        lagged: Arc::new(AtomicBool::new(false)),
+    };
+    let writer_thread = thread::spawn(move || {
+        for line in lines {
+            if writer.write_all(line.as_bytes()).is_err() || writer.write_all(b"\n").is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut line = Vec::new();
+    while !shared.stop.load(Ordering::SeqCst) && !client.lagged.load(Ordering::SeqCst) {
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) if line.ends_with(b"\n") => {
+                let reply = handle_line(shared, &client, &line);
+                line.clear();
+                if client.outbound.send(reply).is_err() {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(_) => break,
+        }
+    }
+
+    for (_, entry) in shared.entries() {
+        entry
+            .subscribers()
+            .retain(|subscriber| subscriber.id != client.id);
+    }
+    drop(client);
+    let _ = writer_thread.join();
+    Ok(())
+}
+
+/// Reads the first line from a TCP client and checks it against `token`.
+fn authenticate(reader: &mut impl BufRead, token: &str, stop: &AtomicBool) -> bool {
+    let started = Instant::now();
+    let mut line = Vec::new();
+    while started.elapsed() < TOKEN_TIMEOUT && !stop.load(Ordering::SeqCst) {
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => return false,
+            Ok(_) if line.ends_with(b"\n") => {
+                return String::from_utf8_lossy(&line).trim() == token;
+            }
+            Ok(_) => {}
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(_) => return false,
+        }
+        if line.len() > MAX_TOKEN_LINE {
+            return false;
+        }
+    }
+    false
+}
+
+fn handle_line(shared: &Shared, client: &Client, line: &[u8]) -> String {
+    let response = match serde_json::from_slice::<Value>(line) {
+        Ok(value) => {
+            let id = value.get("id").and_then(Value::as_u64).unwrap_or(0);
+            match serde_json::from_value::<RequestEnvelope>(value) {
+                Ok(envelope) => match handle(shared, client, envelope.request) {
+                    Ok(result) => Response {
+                        id,
+                        result: Some(result),
+                        error: None,
+                    },
+                    Err(error) => Response {
+                        id,
+                        result: None,
+                        error: Some(error),
+                    },
+                },
+                Err(err) => bad_request(id, err),
+            }
+        }
+        Err(err) => bad_request(0, err),
+    };
+    serde_json::to_string(&ServerLine::Response(response)).unwrap_or_default()
+}
+
+fn bad_request(id: u64, err: serde_json::Error) -> Response {
+    Response {
+        id,
+        result: None,
+        error: Some(ApiError::new(
+            ApiErrorKind::BadRequest,
+            -libc::EINVAL,
+            err.to_string(),
+        )),
+    }
+}
+
+/// Runs one request. The ports map is only locked to look entries up, so a
+/// slow device or enumeration never stalls other clients or the pump.
+fn handle(shared: &Shared, client: &Client, request: Request) -> Result<Value, ApiError> {
+    match request {
+        Request::List => {
+            let devices = core::list_serial_ports()
+                .map_err(|code| ApiError::new(ApiErrorKind::Io, code, "port enumeration failed"))?;
+            let open: Vec<String> = shared.ports().keys().cloned().collect();
+            let mut listed: Vec<ListedPort> = devices
+                .into_iter()
+                .map(|device| ListedPort {
+                    open: open.contains(&device.path),
+                    usb: UsbIdentity::lookup(&device.path),
+                    path: device.path,
+                    description: device.description,
+                })
+                .collect();
+            for path in open {
+                if !listed.iter().any(|port| port.path == path) {
+                    listed.push(ListedPort {
+                        path,
+                        description: String::new(),
+                        usb: None,
+                        open: true,
+                    });
+                }
+            }
+            to_value(listed)
+        }
+        Request::Sessions => {
+            let mut sessions: Vec<SessionInfo> = shared
+                .entries()
+                .into_iter()
+                .map(|(port, entry)| {
+                    let session = entry.session();
+                    SessionInfo {
+                        port,
+                        config: session.config().clone(),
+                        subscribers: entry.subscribers().len(),
+                        recording: session.recording().map(recording_info),
+                    }
+                })
+                .collect();
+            sessions.sort_by(|a, b| a.port.cmp(&b.port));
+            to_value(sessions)
+        }
+        Request::Open { port, config } => open(shared, port, config),
+        Request::Close { port } => {
+            let entry = shared
+                .ports()
+                .remove(&port)
+                .ok_or_else(|| ApiError::not_open(&port))?;
+            entry.broadcast(&DaemonEvent::Closed { port }, None);
+            Ok(Value::Null)
+        }
+        Request::Configure { port, config } => {
+            let entry = shared.lookup(&port)?;
+            entry.session().reconfigure(&config)?;
+            entry.broadcast(&DaemonEvent::Configured { port, config }, None);
+            Ok(Value::Null)
+        }
+        Request::Write { port, data } => {
+            let entry = shared.lookup(&port)?;
+            entry.session().write(&data)?;
+            entry.broadcast(&DaemonEvent::Tx { port, data }, Some(client.id));
+            Ok(Value::Null)
+        }
+        Request::SetModemLines { port, dtr, rts } => {
+            let entry = shared.lookup(&port)?;
+            let mut session = entry.session();
+            if let Some(enabled) = dtr {
+                session.set_dtr(enabled)?;
+            }
+            if let Some(enabled) = rts {
+                session.set_rts(enabled)?;
+            }
+            Ok(Value::Null)
+        }
+        Request::ModemLines { port } => {
+            let lines: ModemLines = shared.lookup(&port)?.session().modem_lines()?;
+            to_value(lines)
+        }
+        Request::SetBreak { port, enabled } => {
+            shared.lookup(&port)?.session().set_break(enabled)?;
+            Ok(Value::Null)
+        }
+        Request::Subscribe { port } => {
+            let entry = shared.lookup(&port)?;
+            let mut subscribers = entry.subscribers();
+            if !subscribers
+                .iter()
+                .any(|subscriber| subscriber.id == client.id)
+            {
+                subscribers.push(client.clone());
+            }
+            Ok(Value::Null)
+        }
+        Request::Unsubscribe { port } => {
+            shared
+                .lookup(&port)?
+                .subscribers()
+                .retain(|subscriber| subscriber.id != client.id);
+            Ok(Value::Null)
+        }
+        Request::StartRecording { port, path } => {
+            let entry = shared.lookup(&port)?;
+            let mut session = entry.session();
+            let path = path.unwrap_or_else(|| recording_path(&shared.recordings, &port));
+            let header = RecordingHeader::new(&port, session.config(), None);
+            session
+                .start_recording(&path, &header)
+                .map_err(|err| ApiError::new(ApiErrorKind::Recording, 0, err.to_string()))?;
+            to_value(path)
+        }
+        Request::StopRecording { port } => {
+            let status = shared.lookup(&port)?.session().stop_recording();
+            to_value(status.map(recording_info))
+        }
+    }
+}
+
+/// Opens `port` without holding the ports lock, so a slow device does not
+/// stall other clients; a port opened concurrently by someone else wins.
+fn open(shared: &Shared, port: String, config: Option<SerialConfig>) -> Result<Value, ApiError> {
+    if let Ok(entry) = shared.lookup(&port) {
+        return to_value(entry.session().config());
+    }
+    let config = config.unwrap_or_default();
+    let opened = SerialSession::open(&port, &config);
+    let mut ports = shared.ports();
+    if let Some(entry) = ports.get(&port).cloned() {
+        drop(ports);
+        return to_value(entry.session().config());
+    }
+    ports.insert(
+        port,
+        Arc::new(PortEntry {
+            session: Mutex::new(opened?),
+            subscribers: Mutex::new(Vec::new()),
+        }),
+    );
+    to_value(config)
+}
+
+fn recording_info(status: RecordingStatus) -> RecordingInfo {
+    RecordingInfo {
+        path: status.path,
+        records: status.records,
+        bytes: status.bytes,
+    }
+}
+
+/// `<directory>/<port name>-<unix time>.msrec`
+fn recording_path(directory: &Path, port: &str) -> PathBuf {
+    let name: String = port
+        .rsplit('/')
+        .next()
+        .unwrap_or("session")
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .collect();
+    let stamp = time::OffsetDateTime::now_utc().unix_timestamp();
+    directory.join(format!("{name}-{stamp}.{}", recorder::FILE_EXTENSION))
+}
+
+fn to_value(value: impl Serialize) -> Result<Value, ApiError> {
+    serde_json::to_value(value)
+        .map_err(|err| ApiError::new(ApiErrorKind::BadRequest, 0, err.to_string()))
+}
+
+/// Moves session output to subscribers.
+///
+/// A session busy with a slow request is skipped until the next tick, so
+/// one port never holds up the others.
+fn pump(shared: &Shared) {
+    while !shared.stop.load(Ordering::SeqCst) {
+        for (port, entry) in shared.entries() {
+            let messages = match entry.session.try_lock() {
+                Ok(session) => session.poll(),
+                Err(TryLockError::Poisoned(err)) => err.into_inner().poll(),
+                Err(TryLockError::WouldBlock) => continue,
+            };
+            for message in messages {
+                let event = match message {
+                    SessionMessage::Data(data) => DaemonEvent::Rx {
+                        port: port.clone(),
+                        data,
+                    },
+                    SessionMessage::Event(event) => DaemonEvent::Event {
+                        port: port.clone(),
+                        code: event.code,
+                        message: event.message,
+                    },
+                };
+                entry.broadcast(&event, None);
+            }
+        }
+        thread::sleep(PUMP_TICK);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_use_method_and_params() {
+        let envelope = RequestEnvelope {
+            id: 7,
+            request: Request::Write {
+                port: "/dev/ttyUSB0".to_string(),
+                data: vec![0x41, 0x0d],
+            },
+        };
+        let json = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "id": 7,
+                "method": "write",
+                "params": { "port": "/dev/ttyUSB0", "data": "410d" }
+            })
+        );
+        let parsed: RequestEnvelope = serde_json::from_str(r#"{"id":1,"method":"list"}"#).unwrap();
+        assert_eq!(parsed.request, Request::List);
+    }
+
+    #[test]
+    fn server_lines_distinguish_replies_and_events() {
+        let reply: ServerLine = serde_json::from_str(r#"{"id":3,"result":null}"#).unwrap();
+        assert!(matches!(
+            reply,
+            ServerLine::Response(Response { id: 3, .. })
+        ));
+        let event: ServerLine =
+            serde_json::from_str(r#"{"event":"rx","port":"p","data":"4f4b"}"#).unwrap();
+        assert_eq!(
+            event,
+            ServerLine::Event(DaemonEvent::Rx {
+                port: "p".to_string(),
+                data: b"OK".to_vec(),
+            })
+        );
+    }
+}
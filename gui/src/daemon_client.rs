@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::core::{ModemLines, SerialConfig};
+use crate::daemon::{
+    ApiError, ApiErrorKind, DaemonEvent, DaemonSettings, ListedPort, RecordingInfo, Request,
+    RequestEnvelope, Response, ServerLine, SessionInfo,
+};
+
+const CALL_TIMEOUT: Duration = Duration::from_secs(5);
+const EVENT_TICK: Duration = Duration::from_millis(100);
+
+type Pending = Arc<Mutex<HashMap<u64, Sender<Response>>>>;
+
+/// Either end of a daemon connection.
+enum Stream {
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+impl Stream {
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(match self {
+            Stream::Unix(stream) => Stream::Unix(stream.try_clone()?),
+            Stream::Tcp(stream) => Stream::Tcp(stream.try_clone()?),
+        })
+    }
+
+    fn shutdown(&self) {
+        let _ = match self {
+            Stream::Unix(stream) => stream.shutdown(Shutdown::Both),
+            Stream::Tcp(stream) => stream.shutdown(Shutdown::Both),
+        };
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Unix(stream) => stream.read(buf),
+            Stream::Tcp(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Unix(stream) => stream.write(buf),
+            Stream::Tcp(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Unix(stream) => stream.flush(),
+            Stream::Tcp(stream) => stream.flush(),
+        }
+    }
+}
+
+/// A connection to a running [`Daemon`](crate::daemon::Daemon).
+///
+/// Calls block until the matching reply arrives; notifications for
+/// subscribed ports are queued and read with [`DaemonClient::events`].
+pub struct DaemonClient {
+    writer: Mutex<Stream>,
+    next_id: AtomicU64,
+    pending: Pending,
+    events: Mutex<Option<Receiver<DaemonEvent>>>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl DaemonClient {
+    /// Connects to a Unix socket path, or to `host:port` when `address`
+    /// contains no `/`.
+    pub fn connect(address: &str) -> io::Result<Self> {
+        Self::connect_with_token(address, None)
+    }
+
+    /// Connects to the address in `settings`, sending its token over TCP.
+    pub fn connect_to(settings: &DaemonSettings) -> io::Result<Self> {
+        Self::connect_with_token(&settings.socket, settings.token.as_deref())
+    }
+
+    /// Like [`DaemonClient::connect`], but sends `token` first on TCP
+    /// connections as the daemon requires there.
+    pub fn connect_with_token(address: &str, token: Option<&str>) -> io::Result<Self> {
+        let stream = if address.contains('/') {
+            Stream::Unix(UnixStream::connect(PathBuf::from(address))?)
+        } else {
+            let mut stream = TcpStream::connect(address)?;
+            let _ = stream.set_nodelay(true);
+            if let Some(token) = token {
+                stream.write_all(format!("{token}\n").as_bytes())?;
+            }
+            Stream::Tcp(stream)
+        };
+        let reader_stream = stream.try_clone()?;
+        let pending = Pending::default();
+        let (events_tx, events) = mpsc::channel();
+        let reader_pending = Arc::clone(&pending);
+        let reader =
+            thread::spawn(move || read_replies(reader_stream, &reader_pending, &events_tx));
+        Ok(Self {
+            writer: Mutex::new(stream),
+            next_id: AtomicU64::new(1),
+            pending,
+            events: Mutex::new(Some(events)),
+            reader: Some(reader),
+        })
+    }
+
+    /// Sends a request and waits for its result.
+    pub fn call(&self, request: Request) -> Result<Value, ApiError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (reply_tx, reply) = mpsc::channel();
+        lock(&self.pending).insert(id, reply_tx);
+        let mut line = serde_json::to_vec(&RequestEnvelope { id, request })
+            .map_err(|err| io_error(-libc::EINVAL, err.to_string()))?;
+        line.push(b'\n');
+        let sent = lock(&self.writer).write_all(&line);
+        if let Err(err) = sent {
+            lock(&self.pending).remove(&id);
+            return Err(io_error(errno(&err), err.to_string()));
+        }
+        let response = reply.recv_timeout(CALL_TIMEOUT).map_err(|err| {
+            lock(&self.pending).remove(&id);
+            match err {
+                mpsc::RecvTimeoutError::Timeout => {
+                    io_error(-libc::ETIMEDOUT, "daemon did not answer")
+                }
+                mpsc::RecvTimeoutError::Disconnected => {
+                    io_error(-libc::ECONNRESET, "daemon connection closed")
+                }
+            }
+        })?;
+        match response.error {
+            Some(error) => Err(error),
+            None => Ok(response.result.unwrap_or(Value::Null)),
+        }
+    }
+
+    fn call_as<T: DeserializeOwned>(&self, request: Request) -> Result<T, ApiError> {
+        let value = self.call(request)?;
+        serde_json::from_value(value).map_err(|err| io_error(-libc::EPROTO, err.to_string()))
+    }
+
+    /// Takes the notification queue; later calls return `None`.
+    pub fn events(&self) -> Option<Receiver<DaemonEvent>> {
+        lock(&self.events).take()
+    }
+
+    pub fn list(&self) -> Result<Vec<ListedPort>, ApiError> {
+        self.call_as(Request::List)
+    }
+
+    pub fn sessions(&self) -> Result<Vec<SessionInfo>, ApiError> {
+        self.call_as(Request::Sessions)
+    }
+
+    /// Opens `port`, returning the configuration it runs with; a port other
+    /// clients already opened keeps its current settings.
+    pub fn open(&self, port: &str, config: &SerialConfig) -> Result<SerialConfig, ApiError> {
+        self.call_as(Request::Open {
+            port: port.to_string(),
+            config: Some(config.clone()),
+        })
+    }
+
+    pub fn close(&self, port: &str) -> Result<(), ApiError> {
+        self.call(Request::Close {
+            port: port.to_string(),
+        })
+        .map(drop)
+    }
+
+    pub fn configure(&self, port: &str, config: &SerialConfig) -> Result<(), ApiError> {
+        self.call(Request::Configure {
+            port: port.to_string(),
+            config: config.clone(),
+        })
+        .map(drop)
+    }
+
+    pub fn write(&self, port: &str, data: &[u8]) -> Result<(), ApiError> {
+        self.call(Request::Write {
+            port: port.to_string(),
+            data: data.to_vec(),
+        })
+        .map(drop)
+    }
+
+    pub fn set_modem_lines(
+        &self,
+        port: &str,
+        dtr: Option<bool>,
+        rts: Option<bool>,
+    ) -> Result<(), ApiError> {
+        self.call(Request::SetModemLines {
+            port: port.to_string(),
+            dtr,
+            rts,
+        })
+        .map(drop)
+    }
+
+    pub fn modem_lines(&self, port: &str) -> Result<ModemLines, ApiError> {
+        self.call_as(Request::ModemLines {
+            port: port.to_string(),
+        })
+    }
+
+    pub fn set_break(&self, port: &str, enabled: bool) -> Result<(), ApiError> {
+        self.call(Request::SetBreak {
+            port: port.to_string(),
+            enabled,
+        })
+        .map(drop)
+    }
+
+    pub fn subscribe(&self, port: &str) -> Result<(), ApiError> {
+        self.call(Request::Subscribe {
+            port: port.to_string(),
+        })
+        .map(drop)
+    }
+
+    pub fn unsubscribe(&self, port: &str) -> Result<(), ApiError> {
+        self.call(Request::Unsubscribe {
+            port: port.to_string(),
+        })
+        .map(drop)
+    }
+
+    /// Starts a daemon-side recording; `None` lets the daemon pick a file name.
+    pub fn start_recording(&self, port: &str, path: Option<PathBuf>) -> Result<PathBuf, ApiError> {
+        self.call_as(Request::StartRecording {
+            port: port.to_string(),
+            path,
+        })
+    }
+
+    pub fn stop_recording(&self, port: &str) -> Result<Option<RecordingInfo>, ApiError> {
+        self.call_as(Request::StopRecording {
+            port: port.to_string(),
+        })
+    }
+}
+
+impl Drop for DaemonClient {
+    fn drop(&mut self) {
+        lock(&self.writer).shutdown();
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+fn read_replies(stream: Stream, pending: &Pending, events: &Sender<DaemonEvent>) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        match serde_json::from_str::<ServerLine>(&line) {
+            Ok(ServerLine::Response(response)) => {
+                if let Some(reply) = lock(pending).remove(&response.id) {
+                    let _ = reply.send(response);
+                }
+            }
+            Ok(ServerLine::Event(event)) => {
+                let _ = events.send(event);
+            }
+            Err(_) => {}
+        }
+    }
+    // Dropping the senders fails every call still waiting for a reply.
+    lock(pending).clear();
+}
+
+/// A port opened through the daemon, shaped like [`SerialPort`](crate::core::SerialPort)
+/// so sessions can drive it.
+///
+/// Errors are negative errno values; daemon-side failures keep the code the
+/// daemon reported.
+pub struct DaemonPort {
+    client: Arc<DaemonClient>,
+    port: String,
+    config: SerialConfig,
+    /// The client's notification queue while no dispatcher holds it.
+    events: Option<Receiver<DaemonEvent>>,
+    stop: Arc<AtomicBool>,
+    /// Hands the queue back when it finishes, so the port can start again.
+    dispatcher: Option<JoinHandle<Receiver<DaemonEvent>>>,
+}
+
+impl DaemonPort {
+    /// Connects to the daemon described by `daemon` and opens or joins `port`.
+    pub fn open(daemon: &DaemonSettings, port: &str, config: &SerialConfig) -> Result<Self, i32> {
+        let client = DaemonClient::connect_to(daemon).map_err(|err| errno(&err))?;
+        let config = client.open(port, config).map_err(code)?;
+        let events = client.events();
+        Ok(Self {
+            client: Arc::new(client),
+            port: port.to_string(),
+            config,
+            events,
+            stop: Arc::new(AtomicBool::new(false)),
+            dispatcher: None,
+        })
+    }
+
+    pub fn port(&self) -> &str {
+        &self.port
+    }
+
+    /// The configuration the daemon reported when the port was opened.
+    pub fn config(&self) -> &SerialConfig {
+        &self.config
+    }
+
+    pub fn configure(&mut self, config: &SerialConfig) -> Result<(), i32> {
+        self.client.configure(&self.port, config).map_err(code)?;
+        self.config = config.clone();
+        Ok(())
+    }
+
+    pub fn start<F, E>(&mut self, mut on_data: F, mut on_event: E) -> Result<(), i32>
+    where
+        F: FnMut(&[u8]) + Send + 'static,
+        E: FnMut(i32, &str) + Send + 'static,
+    {
+        if self.dispatcher.is_some() {
+            return Err(-libc::EALREADY);
+        }
+        let events = self.events.take().ok_or(-libc::EALREADY)?;
+        // Anything still queued predates the last stop.
+        while events.try_recv().is_ok() {}
+        if let Err(err) = self.client.subscribe(&self.port) {
+            self.events = Some(events);
+            return Err(code(err));
+        }
+        self.stop.store(false, Ordering::SeqCst);
+        let stop = Arc::clone(&self.stop);
+        let port = self.port.clone();
+        self.dispatcher = Some(thread::spawn(move || {
+            while !stop.load(Ordering::SeqCst) {
+                let event = match events.recv_timeout(EVENT_TICK) {
+                    Ok(event) => event,
+                    Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => {
+                        if !stop.load(Ordering::SeqCst) {
+                            on_event(-libc::ECONNRESET, "daemon connection lost");
+                        }
+                        break;
+                    }
+                };
+                match event {
+                    DaemonEvent::Rx { port: from, data } if from == port => on_data(&data),
+                    DaemonEvent::Event {
+                        port: from,
+                        code,
+                        message,
+                    } if from == port => on_event(code, &message),
+                    DaemonEvent::Configured { port: from, config } if from == port => on_event(
+                        0,
+                        &format!("reconfigured by another client: {} baud", config.baud_rate),
+                    ),
+                    DaemonEvent::Closed { port: from } if from == port => {
+                        on_event(-libc::ENODEV, "port closed by another client");
+                    }
+                    _ => {}
+                }
+            }
+            events
+        }));
+        Ok(())
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<usize, i32> {
+        self.client.write(&self.port, data).map_err(code)?;
+        Ok(data.len())
+    }
+
+    /// Drives DTR and/or RTS; `None` leaves the corresponding line untouched.
+    pub fn set_modem_lines(&mut self, dtr: Option<bool>, rts: Option<bool>) -> Result<(), i32> {
+        self.client
+            .set_modem_lines(&self.port, dtr, rts)
+            .map_err(code)
+    }
+
+    pub fn modem_lines(&self) -> Result<ModemLines, i32> {
+        self.client.modem_lines(&self.port).map_err(code)
+    }
+
+    pub fn set_break(&mut self, enabled: bool) -> Result<(), i32> {
+        self.client.set_break(&self.port, enabled).map_err(code)
+    }
+
+    /// Detaches from the port; the daemon keeps it open for other clients.
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(dispatcher) = self.dispatcher.take() {
+            let _ = self.client.unsubscribe(&self.port);
+            self.events = dispatcher.join().ok();
+        }
+    }
+}
+
+impl Drop for DaemonPort {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn io_error(code: i32, message: impl Into<String>) -> ApiError {
+    ApiError {
+        kind: ApiErrorKind::Io,
+        code,
+        message: message.into(),
+    }
+}
+
+fn code(error: ApiError) -> i32 {
+    if error.code != 0 {
+        error.code
+    } else {
+        -libc::EIO
+    }
+}
+
+fn errno(err: &io::Error) -> i32 {
+    match err.kind() {
+        ErrorKind::NotFound => -libc::ENOENT,
+        _ => -err.raw_os_error().unwrap_or(libc::EIO),
+    }
+}
@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+const SLIP_END: u8 = 0xC0;
+const SLIP_ESC: u8 = 0xDB;
+const SLIP_ESC_END: u8 = 0xDC;
+const SLIP_ESC_ESC: u8 = 0xDD;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum DecoderKind {
+    #[strum(to_string = "None")]
+    None,
+    #[strum(to_string = "Lines")]
+    Lines,
+    #[strum(to_string = "SLIP")]
+    Slip,
+    #[strum(to_string = "COBS")]
+    Cobs,
+}
+
+/// Incremental frame decoder fed with raw chunks as they arrive.
+///
+/// Partial frames are kept between calls so a frame split across several
+/// reads is still reported once it completes.
+#[derive(Debug, Clone)]
+pub struct FrameDecoder {
+    kind: DecoderKind,
+    buffer: Vec<u8>,
+    escaped: bool,
+}
+
+impl FrameDecoder {
+    pub fn new(kind: DecoderKind) -> Self {
+        Self {
+            kind,
+            buffer: Vec::new(),
+            escaped: false,
+        }
+    }
+
+    pub fn kind(&self) -> DecoderKind {
+        self.kind
+    }
+
+    pub fn set_kind(&mut self, kind: DecoderKind) {
+        if self.kind != kind {
+            self.kind = kind;
+            self.reset();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.escaped = false;
+    }
+
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        match self.kind {
+            DecoderKind::None => {}
+            DecoderKind::Lines => {
+                for &byte in data {
+                    if byte == b'\n' {
+                        let mut line = std::mem::take(&mut self.buffer);
+                        if line.last() == Some(&b'\r') {
+                            line.pop();
+                        }
+                        frames.push(line);
+                    } else {
+                        self.buffer.push(byte);
+                    }
+                }
+            }
+            DecoderKind::Slip => {
+                for &byte in data {
+                    match (self.escaped, byte) {
+                        (false, SLIP_END) => {
+                            if !self.buffer.is_empty() {
+                                frames.push(std::mem::take(&mut self.buffer));
+                            }
+                        }
+                        (false, SLIP_ESC) => self.escaped = true,
+                        (false, other) => self.buffer.push(other),
+                        (true, SLIP_ESC_END) => {
+                            self.buffer.push(SLIP_END);
+                            self.escaped = false;
+                        }
+                        (true, SLIP_ESC_ESC) => {
+                            self.buffer.push(SLIP_ESC);
+                            self.escaped = false;
+                        }
+                        (true, other) => {
+                            // Protocol violation: keep the byte so nothing is lost silently.
+                            self.buffer.push(other);
+                            self.escaped = false;
+                        }
+                    }
+                }
+            }
+            DecoderKind::Cobs => {
+                for &byte in data {
+                    if byte == 0 {
+                        let encoded = std::mem::take(&mut self.buffer);
+                        if let Some(frame) = cobs_decode(&encoded) {
+                            frames.push(frame);
+                        }
+                    } else {
+                        self.buffer.push(byte);
+                    }
+                }
+            }
+        }
+        frames
+    }
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new(DecoderKind::None)
+    }
+}
+
+pub fn cobs_decode(encoded: &[u8]) -> Option<Vec<u8>> {
+    if encoded.is_empty() {
+        return None;
+    }
+    let mut output = Vec::with_capacity(encoded.len());
+    let mut index = 0;
+    while index < encoded.len() {
+        let code = encoded[index] as usize;
+        if code == 0 || index + code > encoded.len() {
+            return None;
+        }
+        output.extend_from_slice(&encoded[index + 1..index + code]);
+        index += code;
+        if code < 0xFF && index < encoded.len() {
+            output.push(0);
+        }
+    }
+    Some(output)
+}
+
+pub fn cobs_encode(data: &[u8]) -> Vec<u8> {
+    let mut output = vec![0];
+    let mut code_index = 0;
+    let mut code: u8 = 1;
+    for &byte in data {
+        if byte == 0 {
+            output[code_index] = code;
+            code_index = output.len();
+            output.push(0);
+            code = 1;
+        } else {
+            output.push(byte);
+            code += 1;
+            if code == 0xFF {
+                output[code_index] = code;
+                code_index = output.len();
+                output.push(0);
+                code = 1;
+            }
+        }
+    }
+    output[code_index] = code;
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecoderKind, FrameDecoder, cobs_decode, cobs_encode};
+
+    #[test]
+    fn lines_survive_chunk_splits() {
+        let mut decoder = FrameDecoder::new(DecoderKind::Lines);
+        assert!(decoder.feed(b"hel").is_empty());
+        let frames = decoder.feed(b"lo\r\nwor");
+        assert_eq!(frames, vec![b"hello".to_vec()]);
+        assert_eq!(decoder.feed(b"ld\n"), vec![b"world".to_vec()]);
+    }
+
+    #[test]
+    fn slip_unescapes_payload() {
+        let mut decoder = FrameDecoder::new(DecoderKind::Slip);
+        let frames = decoder.feed(&[0xC0, 0x01, 0xDB, 0xDC, 0xDB, 0xDD, 0x02, 0xC0]);
+        assert_eq!(frames, vec![vec![0x01, 0xC0, 0xDB, 0x02]]);
+    }
+
+    #[test]
+    fn cobs_roundtrip() {
+        let payload = [0x11, 0x00, 0x22, 0x00, 0x00, 0x33];
+        let encoded = cobs_encode(&payload);
+        assert!(!encoded.contains(&0));
+        assert_eq!(cobs_decode(&encoded).expect("decode"), payload);
+
+        let mut decoder = FrameDecoder::new(DecoderKind::Cobs);
+        let mut stream = encoded.clone();
+        stream.push(0);
+        assert_eq!(decoder.feed(&stream), vec![payload.to_vec()]);
+    }
+}
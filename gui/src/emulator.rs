@@ -0,0 +1,446 @@
+#[cfg(unix)]
+use std::collections::VecDeque;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::sync::Arc;
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::mpsc::{self, Receiver, Sender};
+#[cfg(unix)]
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+#[cfg(unix)]
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::recorder::{Record, RecordPayload};
+#[cfg(unix)]
+use crate::virtual_port::{VirtualPort, VirtualPortError};
+
+#[cfg(unix)]
+const WORKER_TICK: Duration = Duration::from_millis(2);
+/// How long a request that may still grow into a longer one waits for more bytes.
+#[cfg(unix)]
+const MATCH_GRACE: Duration = Duration::from_millis(20);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmulatorSettings {
+    /// Symlink the emulated device is published at.
+    pub link: String,
+}
+
+impl Default for EmulatorSettings {
+    fn default() -> Self {
+        Self {
+            link: "/tmp/ttyMicroSerialEmu".to_string(),
+        }
+    }
+}
+
+/// One recorded request and the device output that followed it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Exchange {
+    pub request: Vec<u8>,
+    /// Chunks with their delay after the previous chunk (or the request).
+    pub responses: Vec<(Duration, Vec<u8>)>,
+}
+
+/// Request/response pairs extracted from a recording.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EmulatorScript {
+    /// Output the device produced before the first request, e.g. a boot banner.
+    pub greeting: Vec<(Duration, Vec<u8>)>,
+    pub exchanges: Vec<Exchange>,
+}
+
+impl EmulatorScript {
+    /// Pairs every TX run with the RX chunks recorded until the next TX.
+    ///
+    /// Consecutive TX records form one request, since hosts often write a
+    /// command in several pieces.
+    pub fn from_records(records: &[Record]) -> Self {
+        let mut script = Self::default();
+        let mut last = Duration::ZERO;
+        for record in records {
+            match &record.payload {
+                RecordPayload::Tx(data) if !data.is_empty() => {
+                    match script.exchanges.last_mut() {
+                        Some(exchange) if exchange.responses.is_empty() => {
+                            exchange.request.extend_from_slice(data);
+                        }
+                        _ => script.exchanges.push(Exchange {
+                            request: data.clone(),
+                            responses: Vec::new(),
+                        }),
+                    }
+                    last = record.timestamp;
+                }
+                RecordPayload::Rx(data) if !data.is_empty() => {
+                    let delay = record.timestamp.saturating_sub(last);
+                    let responses = match script.exchanges.last_mut() {
+                        Some(exchange) => &mut exchange.responses,
+                        None => &mut script.greeting,
+                    };
+                    responses.push((delay, data.clone()));
+                    last = record.timestamp;
+                }
+                _ => {}
+            }
+        }
+        script
+    }
+}
+
+/// Decides which recorded response answers the bytes a host wrote.
+///
+/// Identical requests are answered with their recorded responses in turn,
+/// wrapping around, so a device polled for changing values replays them in
+/// order. The longest request wins: a complete request that is also the start
+/// of a longer one is held back until the next bytes rule the longer one out
+/// or [`Matcher::flush`] is called.
+#[derive(Debug)]
+pub struct Matcher {
+    script: EmulatorScript,
+    cursors: Vec<usize>,
+    pending: Vec<u8>,
+    /// Length of the complete request at the start of `pending` that is held back.
+    held: Option<usize>,
+    max_request: usize,
+    unmatched: u64,
+}
+
+impl Matcher {
+    pub fn new(script: EmulatorScript) -> Self {
+        let max_request = script
+            .exchanges
+            .iter()
+            .map(|exchange| exchange.request.len())
+            .max()
+            .unwrap_or(0);
+        Self {
+            cursors: vec![0; script.exchanges.len()],
+            script,
+            pending: Vec::new(),
+            held: None,
+            max_request,
+            unmatched: 0,
+        }
+    }
+
+    pub fn script(&self) -> &EmulatorScript {
+        &self.script
+    }
+
+    /// Bytes the host wrote that matched no recorded request.
+    pub fn unmatched(&self) -> u64 {
+        self.unmatched
+    }
+
+    /// Feeds host bytes and returns the indices of exchanges they completed.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<usize> {
+        let mut matched = Vec::new();
+        for &byte in data {
+            self.push(byte, &mut matched);
+        }
+        matched
+    }
+
+    /// Whether a complete request is held back in case a longer one follows.
+    pub fn is_holding(&self) -> bool {
+        self.held.is_some()
+    }
+
+    /// Answers the held-back request, for when the host stopped writing.
+    pub fn flush(&mut self) -> Vec<usize> {
+        let mut matched = Vec::new();
+        if let Some(held) = self.held.take() {
+            let rest = self.pending.split_off(held);
+            let request = std::mem::take(&mut self.pending);
+            matched.push(self.answer(&request));
+            for byte in rest {
+                self.push(byte, &mut matched);
+            }
+        }
+        matched
+    }
+
+    fn push(&mut self, byte: u8, matched: &mut Vec<usize>) {
+        self.pending.push(byte);
+        if self.held.is_some() {
+            if self.extends_to_longer(self.pending.len()) || self.is_request(&self.pending) {
+                if self.is_request(&self.pending) {
+                    self.complete(self.pending.len(), matched);
+                }
+                return;
+            }
+            // The longer request is ruled out: answer the held one and
+            // reconsider the bytes after it on their own.
+            matched.extend(self.flush());
+            return;
+        }
+        let Some(len) = self.longest_suffix_request() else {
+            if self.pending.len() > self.max_request {
+                let excess = self.pending.len() - self.max_request;
+                self.pending.drain(..excess);
+                self.unmatched += excess as u64;
+            }
+            return;
+        };
+        let noise = self.pending.len() - len;
+        self.pending.drain(..noise);
+        self.unmatched += noise as u64;
+        self.complete(len, matched);
+    }
+
+    /// Answers the request filling `pending`, or holds it back if a longer
+    /// request starts with it.
+    fn complete(&mut self, len: usize, matched: &mut Vec<usize>) {
+        if self.extends_to_longer(len) {
+            self.held = Some(len);
+            return;
+        }
+        self.held = None;
+        let request = std::mem::take(&mut self.pending);
+        matched.push(self.answer(&request));
+    }
+
+    fn requests(&self) -> impl Iterator<Item = &[u8]> {
+        self.script
+            .exchanges
+            .iter()
+            .map(|exchange| exchange.request.as_slice())
+    }
+
+    fn is_request(&self, data: &[u8]) -> bool {
+        self.requests().any(|request| request == data)
+    }
+
+    /// Whether a request longer than `len` bytes starts with `pending`.
+    fn extends_to_longer(&self, len: usize) -> bool {
+        self.requests().any(|request| {
+            request.len() > len.max(self.pending.len()) && request.starts_with(&self.pending)
+        })
+    }
+
+    fn longest_suffix_request(&self) -> Option<usize> {
+        self.requests()
+            .filter(|request| !request.is_empty() && self.pending.ends_with(request))
+            .map(<[u8]>::len)
+            .max()
+    }
+
+    fn answer(&mut self, request: &[u8]) -> usize {
+        let candidates: Vec<usize> = self
+            .script
+            .exchanges
+            .iter()
+            .enumerate()
+            .filter(|(_, exchange)| exchange.request == request)
+            .map(|(index, _)| index)
+            .collect();
+        // The cursor for a request lives at its first occurrence.
+        let cursor = &mut self.cursors[candidates[0]];
+        let index = candidates[*cursor % candidates.len()];
+        *cursor += 1;
+        index
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EmulatorEvent {
+    /// The host sent the request of this exchange; its responses are queued.
+    Answered { exchange: usize },
+}
+
+/// A recorded device played back over a pty, for host-side tests without hardware.
+///
+/// Host software opens [`DeviceEmulator::link`]; whenever it writes one of the
+/// recorded requests, the responses recorded after it are sent back with
+/// their original delays. Only available where ptys are.
+#[cfg(unix)]
+pub struct DeviceEmulator {
+    link: PathBuf,
+    slave_path: PathBuf,
+    stop: Arc<AtomicBool>,
+    events: Receiver<EmulatorEvent>,
+    worker: Option<JoinHandle<Matcher>>,
+}
+
+#[cfg(unix)]
+impl DeviceEmulator {
+    pub fn start(script: EmulatorScript, link: impl AsRef<Path>) -> Result<Self, VirtualPortError> {
+        let port = VirtualPort::create(link)?;
+        let link = port.link().to_path_buf();
+        let slave_path = port.slave_path().to_path_buf();
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, events) = mpsc::channel();
+        let worker_stop = Arc::clone(&stop);
+        let worker = thread::spawn(move || run(port, Matcher::new(script), &worker_stop, &tx));
+        Ok(Self {
+            link,
+            slave_path,
+            stop,
+            events,
+            worker: Some(worker),
+        })
+    }
+
+    pub fn link(&self) -> &Path {
+        &self.link
+    }
+
+    pub fn slave_path(&self) -> &Path {
+        &self.slave_path
+    }
+
+    pub fn poll(&self) -> Vec<EmulatorEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = self.events.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+
+    /// Stops the emulator and returns the unmatched byte count.
+    pub fn stop(&mut self) -> u64 {
+        self.stop.store(true, Ordering::SeqCst);
+        self.worker
+            .take()
+            .and_then(|worker| worker.join().ok())
+            .map(|matcher| matcher.unmatched())
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for DeviceEmulator {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(unix)]
+fn run(
+    port: VirtualPort,
+    mut matcher: Matcher,
+    stop: &AtomicBool,
+    events: &Sender<EmulatorEvent>,
+) -> Matcher {
+    let mut queue: VecDeque<(Instant, Vec<u8>)> = VecDeque::new();
+    let schedule = |queue: &mut VecDeque<(Instant, Vec<u8>)>, chunks: &[(Duration, Vec<u8>)]| {
+        let mut at = queue
+            .back()
+            .map_or_else(Instant::now, |(at, _)| *at)
+            .max(Instant::now());
+        for (delay, data) in chunks {
+            at += *delay;
+            queue.push_back((at, data.clone()));
+        }
+    };
+    schedule(&mut queue, &matcher.script().greeting);
+    let mut last_input = Instant::now();
+    while !stop.load(Ordering::SeqCst) {
+        let mut answered = Vec::new();
+        for chunk in port.poll() {
+            last_input = Instant::now();
+            answered.extend(matcher.feed(&chunk));
+        }
+        if matcher.is_holding() && last_input.elapsed() >= MATCH_GRACE {
+            answered.extend(matcher.flush());
+        }
+        for exchange in answered {
+            let responses = matcher.script().exchanges[exchange].responses.clone();
+            schedule(&mut queue, &responses);
+            let _ = events.send(EmulatorEvent::Answered { exchange });
+        }
+        while queue.front().is_some_and(|(at, _)| *at <= Instant::now()) {
+            if let Some((_, data)) = queue.pop_front() {
+                port.publish(&data);
+            }
+        }
+        thread::sleep(WORKER_TICK);
+    }
+    matcher
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(millis: u64, payload: RecordPayload) -> Record {
+        Record {
+            timestamp: Duration::from_millis(millis),
+            payload,
+        }
+    }
+
+    fn script() -> EmulatorScript {
+        EmulatorScript::from_records(&[
+            record(0, RecordPayload::Rx(b"READY\r\n".to_vec())),
+            record(10, RecordPayload::Tx(b"TEMP".to_vec())),
+            record(12, RecordPayload::Tx(b"?\r".to_vec())),
+            record(40, RecordPayload::Rx(b"21.5\r\n".to_vec())),
+            record(100, RecordPayload::Tx(b"TEMP?\r".to_vec())),
+            record(120, RecordPayload::Rx(b"21.7".to_vec())),
+            record(125, RecordPayload::Rx(b"\r\n".to_vec())),
+            record(200, RecordPayload::Tx(b"ID?\r".to_vec())),
+            record(210, RecordPayload::Rx(b"EMU-1\r\n".to_vec())),
+        ])
+    }
+
+    #[test]
+    fn script_pairs_requests_with_following_output() {
+        let script = script();
+        assert_eq!(
+            script.greeting,
+            vec![(Duration::ZERO, b"READY\r\n".to_vec())]
+        );
+        assert_eq!(script.exchanges.len(), 3);
+        assert_eq!(script.exchanges[0].request, b"TEMP?\r");
+        assert_eq!(
+            script.exchanges[0].responses,
+            vec![(Duration::from_millis(28), b"21.5\r\n".to_vec())]
+        );
+        assert_eq!(
+            script.exchanges[1].responses,
+            vec![
+                (Duration::from_millis(20), b"21.7".to_vec()),
+                (Duration::from_millis(5), b"\r\n".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_requests_cycle_through_recorded_answers() {
+        let mut matcher = Matcher::new(script());
+        assert_eq!(matcher.feed(b"TEMP?\r"), vec![0]);
+        assert_eq!(matcher.feed(b"TE"), Vec::<usize>::new());
+        assert_eq!(matcher.feed(b"MP?\r"), vec![1]);
+        assert_eq!(matcher.feed(b"TEMP?\r"), vec![0]);
+        assert_eq!(matcher.feed(b"noise ID?\r"), vec![2]);
+        assert_eq!(matcher.unmatched(), 6);
+    }
+
+    #[test]
+    fn longest_request_wins_over_its_prefix() {
+        let exchange = |request: &[u8]| Exchange {
+            request: request.to_vec(),
+            responses: Vec::new(),
+        };
+        let mut matcher = Matcher::new(EmulatorScript {
+            greeting: Vec::new(),
+            exchanges: vec![exchange(b"AT"), exchange(b"AT+GMR")],
+        });
+        assert_eq!(matcher.feed(b"AT+GMR"), vec![1]);
+        assert_eq!(matcher.feed(b"AT"), Vec::<usize>::new());
+        assert!(matcher.is_holding());
+        // The next byte rules out `AT+GMR`, so the held `AT` is answered.
+        assert_eq!(matcher.feed(b"AT"), vec![0]);
+        assert_eq!(matcher.flush(), vec![0]);
+        assert!(!matcher.is_holding());
+        assert_eq!(matcher.unmatched(), 0);
+    }
+}
@@ -0,0 +1,657 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use regex::bytes::Regex;
+use serde::Deserialize;
+use thiserror::Error;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::console::to_hex;
+use crate::send_panel;
+use crate::session::{SerialSession, SessionError};
+
+const DEFAULT_TIMEOUT_MS: u64 = 1000;
+/// Received bytes quoted in a failure message.
+const EXCERPT_LEN: usize = 120;
+
+#[derive(Debug, Error)]
+pub enum TestFileError {
+    #[error("cannot read test file: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid test file: {0}")]
+    Parse(#[from] toml::de::Error),
+}
+
+/// A test file: named cases, each a list of steps run in order.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestSuite {
+    pub name: String,
+    /// Default `expect` timeout in milliseconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    #[serde(rename = "case")]
+    pub cases: Vec<TestCase>,
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT_MS
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestCase {
+    pub name: String,
+    #[serde(rename = "step", default)]
+    pub steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "StepSpec")]
+pub enum Step {
+    /// Text with `${name}` placeholders for captured variables.
+    Send(String),
+    SendHex(Vec<u8>),
+    /// Waits for received data matching `pattern`, a regex with `${name}` placeholders.
+    Expect {
+        pattern: String,
+        timeout: Option<u64>,
+        /// Stores the first capture group (or the whole match) under this name.
+        capture: Option<String>,
+    },
+    ExpectBytes {
+        bytes: Vec<u8>,
+        timeout: Option<u64>,
+    },
+    Lines {
+        dtr: Option<bool>,
+        rts: Option<bool>,
+    },
+    Delay(u64),
+}
+
+/// The flat TOML table a step is written as.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StepSpec {
+    send: Option<String>,
+    send_hex: Option<String>,
+    expect: Option<String>,
+    expect_hex: Option<String>,
+    timeout: Option<u64>,
+    capture: Option<String>,
+    dtr: Option<bool>,
+    rts: Option<bool>,
+    delay: Option<u64>,
+}
+
+impl TryFrom<StepSpec> for Step {
+    type Error = String;
+
+    fn try_from(spec: StepSpec) -> Result<Self, String> {
+        let actions = [
+            spec.send.is_some(),
+            spec.send_hex.is_some(),
+            spec.expect.is_some(),
+            spec.expect_hex.is_some(),
+            spec.dtr.is_some() || spec.rts.is_some(),
+            spec.delay.is_some(),
+        ];
+        if actions.iter().filter(|set| **set).count() != 1 {
+            return Err(
+                "a step needs exactly one of send, send_hex, expect, expect_hex, dtr/rts or delay"
+                    .to_string(),
+            );
+        }
+        if spec.timeout.is_some() && spec.expect.is_none() && spec.expect_hex.is_none() {
+            return Err("timeout only applies to expect steps".to_string());
+        }
+        if spec.capture.is_some() && spec.expect.is_none() {
+            return Err("capture only applies to expect steps".to_string());
+        }
+        let hex = |text: &str| send_panel::parse_hex(text).map_err(|err| err.to_string());
+        Ok(if let Some(text) = spec.send {
+            Step::Send(text)
+        } else if let Some(text) = spec.send_hex {
+            Step::SendHex(hex(&text)?)
+        } else if let Some(pattern) = spec.expect {
+            // Placeholders are substituted later; check the rest of the pattern now.
+            Regex::new(&substitute(&pattern, &HashMap::new(), true))
+                .map_err(|err| err.to_string())?;
+            Step::Expect {
+                pattern,
+                timeout: spec.timeout,
+                capture: spec.capture,
+            }
+        } else if let Some(text) = spec.expect_hex {
+            Step::ExpectBytes {
+                bytes: hex(&text)?,
+                timeout: spec.timeout,
+            }
+        } else if let Some(delay) = spec.delay {
+            Step::Delay(delay)
+        } else {
+            Step::Lines {
+                dtr: spec.dtr,
+                rts: spec.rts,
+            }
+        })
+    }
+}
+
+impl TestSuite {
+    pub fn parse(text: &str) -> Result<Self, TestFileError> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, TestFileError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+}
+
+/// Replaces `${name}` with captured values; unknown names become empty.
+fn substitute(text: &str, variables: &HashMap<String, String>, escape: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let name = &rest[start + 2..start + len];
+        let value = variables.get(name).map(String::as_str).unwrap_or("");
+        if escape {
+            out.push_str(&regex::escape(value));
+        } else {
+            out.push_str(value);
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The port operations a test run needs.
+pub trait TestTarget {
+    fn write(&mut self, data: &[u8]) -> Result<(), SessionError>;
+    fn set_dtr(&mut self, enabled: bool) -> Result<(), SessionError>;
+    fn set_rts(&mut self, enabled: bool) -> Result<(), SessionError>;
+}
+
+impl TestTarget for SerialSession {
+    fn write(&mut self, data: &[u8]) -> Result<(), SessionError> {
+        SerialSession::write(self, data)
+    }
+
+    fn set_dtr(&mut self, enabled: bool) -> Result<(), SessionError> {
+        SerialSession::set_dtr(self, enabled)
+    }
+
+    fn set_rts(&mut self, enabled: bool) -> Result<(), SessionError> {
+        SerialSession::set_rts(self, enabled)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CaseStatus {
+    Pending,
+    Running,
+    Passed,
+    /// An expectation was not met.
+    Failed(String),
+    /// The port failed or the run was cancelled.
+    Error(String),
+    Skipped,
+}
+
+impl CaseStatus {
+    pub fn is_done(&self) -> bool {
+        !matches!(self, CaseStatus::Pending | CaseStatus::Running)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CaseResult {
+    pub name: String,
+    pub status: CaseStatus,
+    /// Index of the step running or the step that failed.
+    pub step: usize,
+    pub duration: Duration,
+    /// One line per executed step.
+    pub transcript: Vec<String>,
+}
+
+/// Executes a [`TestSuite`] without blocking.
+///
+/// The owner of the session feeds received data with [`TestRun::feed`] and
+/// calls [`TestRun::advance`] regularly; the CLI does so in a loop, the GUI
+/// once per frame.
+pub struct TestRun {
+    suite: TestSuite,
+    results: Vec<CaseResult>,
+    started: OffsetDateTime,
+    case: usize,
+    step: usize,
+    case_started: Option<Instant>,
+    step_started: Option<Instant>,
+    /// Received data not yet consumed by an expectation.
+    pending: Vec<u8>,
+    variables: HashMap<String, String>,
+}
+
+impl TestRun {
+    pub fn new(suite: TestSuite) -> Self {
+        let results = suite
+            .cases
+            .iter()
+            .map(|case| CaseResult {
+                name: case.name.clone(),
+                status: CaseStatus::Pending,
+                step: 0,
+                duration: Duration::ZERO,
+                transcript: Vec::new(),
+            })
+            .collect();
+        Self {
+            suite,
+            results,
+            started: OffsetDateTime::now_utc(),
+            case: 0,
+            step: 0,
+            case_started: None,
+            step_started: None,
+            pending: Vec::new(),
+            variables: HashMap::new(),
+        }
+    }
+
+    pub fn suite(&self) -> &TestSuite {
+        &self.suite
+    }
+
+    pub fn results(&self) -> &[CaseResult] {
+        &self.results
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.case >= self.suite.cases.len()
+    }
+
+    /// Number of cases that failed or errored.
+    pub fn failures(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|result| matches!(result.status, CaseStatus::Failed(_) | CaseStatus::Error(_)))
+            .count()
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        if !self.is_finished() {
+            self.pending.extend_from_slice(data);
+        }
+    }
+
+    /// Errors the running case and skips the rest.
+    pub fn cancel(&mut self, reason: &str) {
+        if self.is_finished() {
+            return;
+        }
+        if self.case_started.is_some() {
+            self.finish_case(Instant::now(), CaseStatus::Error(reason.to_string()));
+        }
+        for result in &mut self.results[self.case..] {
+            result.status = CaseStatus::Skipped;
+        }
+        self.case = self.suite.cases.len();
+    }
+
+    /// Runs steps until one has to wait for data or time to pass.
+    pub fn advance(&mut self, target: &mut impl TestTarget, now: Instant) {
+        while !self.is_finished() {
+            if self.case_started.is_none() {
+                self.case_started = Some(now);
+                self.step_started = Some(now);
+                self.pending.clear();
+                self.results[self.case].status = CaseStatus::Running;
+            }
+            let Some(step) = self.suite.cases[self.case].steps.get(self.step).cloned() else {
+                self.finish_case(now, CaseStatus::Passed);
+                continue;
+            };
+            let started = self.step_started.unwrap_or(now);
+            match self.run_step(&step, target, now - started) {
+                StepState::Done => {
+                    self.step += 1;
+                    self.results[self.case].step = self.step;
+                    self.step_started = Some(now);
+                }
+                StepState::Waiting => return,
+                StepState::Finished(status) => self.finish_case(now, status),
+            }
+        }
+    }
+
+    fn run_step(
+        &mut self,
+        step: &Step,
+        target: &mut impl TestTarget,
+        elapsed: Duration,
+    ) -> StepState {
+        match step {
+            Step::Send(text) => {
+                let data = substitute(text, &self.variables, false).into_bytes();
+                self.log(format!("send {}", data.escape_ascii()));
+                io_state(target.write(&data))
+            }
+            Step::SendHex(data) => {
+                self.log(format!("send {}", data.escape_ascii()));
+                io_state(target.write(data))
+            }
+            Step::Lines { dtr, rts } => {
+                let mut result = Ok(());
+                if let Some(dtr) = dtr {
+                    self.log(format!("dtr {dtr}"));
+                    result = target.set_dtr(*dtr);
+                }
+                if let (Ok(()), Some(rts)) = (&result, rts) {
+                    self.log(format!("rts {rts}"));
+                    result = target.set_rts(*rts);
+                }
+                io_state(result)
+            }
+            Step::Delay(millis) => {
+                if elapsed < Duration::from_millis(*millis) {
+                    return StepState::Waiting;
+                }
+                self.log(format!("delay {millis} ms"));
+                StepState::Done
+            }
+            Step::Expect {
+                pattern,
+                timeout,
+                capture,
+            } => {
+                let pattern = substitute(pattern, &self.variables, true);
+                let regex = match Regex::new(&pattern) {
+                    Ok(regex) => regex,
+                    Err(err) => return StepState::Finished(CaseStatus::Error(err.to_string())),
+                };
+                let Some(captures) = regex.captures(&self.pending) else {
+                    return self.expect_timeout(&format!("/{pattern}/"), *timeout, elapsed);
+                };
+                let whole = captures.get(0).expect("group 0 always matches");
+                let value = captures.get(1).unwrap_or(whole);
+                let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                let end = whole.end();
+                self.log(format!(
+                    "expect /{pattern}/ matched {:?} after {} ms",
+                    String::from_utf8_lossy(whole.as_bytes()),
+                    elapsed.as_millis()
+                ));
+                if let Some(name) = capture {
+                    self.log(format!("capture {name} = {value:?}"));
+                    self.variables.insert(name.clone(), value);
+                }
+                self.pending.drain(..end);
+                StepState::Done
+            }
+            Step::ExpectBytes { bytes, timeout } => {
+                let found = self
+                    .pending
+                    .windows(bytes.len().max(1))
+                    .position(|window| window == bytes.as_slice());
+                let Some(start) = found else {
+                    let what = to_hex(bytes);
+                    return self.expect_timeout(&what, *timeout, elapsed);
+                };
+                self.log(format!(
+                    "expect {} matched after {} ms",
+                    to_hex(bytes),
+                    elapsed.as_millis()
+                ));
+                self.pending.drain(..start + bytes.len());
+                StepState::Done
+            }
+        }
+    }
+
+    fn expect_timeout(&mut self, what: &str, timeout: Option<u64>, elapsed: Duration) -> StepState {
+        let timeout = timeout.unwrap_or(self.suite.timeout);
+        if elapsed < Duration::from_millis(timeout) {
+            return StepState::Waiting;
+        }
+        let excerpt = &self.pending[self.pending.len().saturating_sub(EXCERPT_LEN)..];
+        let message = format!(
+            "expected {what} within {timeout} ms, received \"{}\"",
+            excerpt.escape_ascii()
+        );
+        self.log(message.clone());
+        StepState::Finished(CaseStatus::Failed(message))
+    }
+
+    fn log(&mut self, line: String) {
+        self.results[self.case].transcript.push(line);
+    }
+
+    fn finish_case(&mut self, now: Instant, status: CaseStatus) {
+        let result = &mut self.results[self.case];
+        result.status = status;
+        result.duration = now - self.case_started.unwrap_or(now);
+        self.case += 1;
+        self.step = 0;
+        self.case_started = None;
+        self.step_started = None;
+    }
+
+    /// The results as a JUnit XML report, as published by CI systems.
+    pub fn junit(&self) -> String {
+        let count = |wanted: fn(&CaseStatus) -> bool| {
+            self.results
+                .iter()
+                .filter(|result| wanted(&result.status))
+                .count()
+        };
+        let failures = count(|status| matches!(status, CaseStatus::Failed(_)));
+        let errors = count(|status| matches!(status, CaseStatus::Error(_)));
+        let skipped = count(|status| !status.is_done() || *status == CaseStatus::Skipped);
+        let total: Duration = self.results.iter().map(|result| result.duration).sum();
+        let timestamp = self.started.format(&Rfc3339).unwrap_or_default();
+        let suite = xml_escape(&self.suite.name);
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuites name=\"{suite}\" tests=\"{}\" failures=\"{failures}\" errors=\"{errors}\" skipped=\"{skipped}\" time=\"{:.3}\">",
+            self.results.len(),
+            total.as_secs_f64()
+        );
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{suite}\" tests=\"{}\" failures=\"{failures}\" errors=\"{errors}\" skipped=\"{skipped}\" time=\"{:.3}\" timestamp=\"{timestamp}\">",
+            self.results.len(),
+            total.as_secs_f64()
+        );
+        for result in &self.results {
+            let _ = write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{suite}\" time=\"{:.3}\">",
+                xml_escape(&result.name),
+                result.duration.as_secs_f64()
+            );
+            match &result.status {
+                CaseStatus::Passed => {}
+                CaseStatus::Failed(message) => {
+                    let _ = write!(
+                        xml,
+                        "\n      <failure message=\"{}\" type=\"expect\"/>",
+                        xml_escape(message)
+                    );
+                }
+                CaseStatus::Error(message) => {
+                    let _ = write!(
+                        xml,
+                        "\n      <error message=\"{}\" type=\"port\"/>",
+                        xml_escape(message)
+                    );
+                }
+                CaseStatus::Pending | CaseStatus::Running | CaseStatus::Skipped => {
+                    xml.push_str("\n      <skipped/>");
+                }
+            }
+            if !result.transcript.is_empty() {
+                let _ = write!(
+                    xml,
+                    "\n      <system-out>{}</system-out>",
+                    xml_escape(&result.transcript.join("\n"))
+                );
+            }
+            if result.status != CaseStatus::Passed || !result.transcript.is_empty() {
+                xml.push_str("\n    ");
+            }
+            xml.push_str("</testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+}
+
+enum StepState {
+    Done,
+    Waiting,
+    Finished(CaseStatus),
+}
+
+fn io_state(result: Result<(), SessionError>) -> StepState {
+    match result {
+        Ok(()) => StepState::Done,
+        Err(err) => StepState::Finished(CaseStatus::Error(err.to_string())),
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\n' | '\t' => out.push(c),
+            c if c.is_control() => {
+                let _ = write!(out, "\\x{:02x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        written: Vec<u8>,
+        dtr: Option<bool>,
+    }
+
+    impl TestTarget for Recorder {
+        fn write(&mut self, data: &[u8]) -> Result<(), SessionError> {
+            self.written.extend_from_slice(data);
+            Ok(())
+        }
+
+        fn set_dtr(&mut self, enabled: bool) -> Result<(), SessionError> {
+            self.dtr = Some(enabled);
+            Ok(())
+        }
+
+        fn set_rts(&mut self, _enabled: bool) -> Result<(), SessionError> {
+            Err(SessionError::Control(-libc::ENOTTY))
+        }
+    }
+
+    const SUITE: &str = r#"
+        name = "modem"
+        timeout = 500
+
+        [[case]]
+        name = "identity"
+        step = [
+            { dtr = true },
+            { send = "AT+CGSN\r" },
+            { expect = '(\d{15})\r\n', capture = "imei" },
+            { send = "AT+ECHO=${imei}\r" },
+            { expect = 'ECHO ${imei}' },
+            { expect_hex = "4F 4B" },
+        ]
+
+        [[case]]
+        name = "silence"
+        step = [{ send = "AT\r" }, { expect = "OK", timeout = 100 }]
+
+        [[case]]
+        name = "rts"
+        step = [{ rts = false }]
+    "#;
+
+    #[test]
+    fn runs_steps_and_reports_failures() {
+        let suite = TestSuite::parse(SUITE).expect("parse");
+        let mut run = TestRun::new(suite);
+        let mut port = Recorder::default();
+        let start = Instant::now();
+
+        run.advance(&mut port, start);
+        assert_eq!(port.dtr, Some(true));
+        assert_eq!(port.written, b"AT+CGSN\r");
+        run.feed(b"490154203237518\r\n");
+        run.advance(&mut port, start + Duration::from_millis(10));
+        assert_eq!(port.written, b"AT+CGSN\rAT+ECHO=490154203237518\r");
+        run.feed(b"ECHO 490154203237518\r\nOK\r\nOK\r\n");
+        run.advance(&mut port, start + Duration::from_millis(20));
+        assert_eq!(run.results()[0].status, CaseStatus::Passed);
+        assert_eq!(run.results()[1].status, CaseStatus::Running);
+
+        // Data left over from the previous case does not satisfy this one.
+        run.advance(&mut port, start + Duration::from_millis(119));
+        assert!(!run.is_finished());
+        run.advance(&mut port, start + Duration::from_millis(121));
+        assert!(run.is_finished());
+        assert!(
+            matches!(&run.results()[1].status, CaseStatus::Failed(message) if message.contains("/OK/"))
+        );
+        assert!(matches!(run.results()[2].status, CaseStatus::Error(_)));
+        assert_eq!(run.failures(), 2);
+
+        let xml = run.junit();
+        assert!(xml.contains(r#"<testsuite name="modem" tests="3" failures="1" errors="1""#));
+        assert!(xml.contains(r#"<testcase name="identity" classname="modem" time="0.020">"#));
+        assert!(xml.contains("capture imei = &quot;490154203237518&quot;"));
+        assert!(xml.contains(r#"<failure message="expected /OK/ within 100 ms"#));
+    }
+
+    #[test]
+    fn rejects_ambiguous_steps() {
+        let err = TestSuite::parse(
+            r#"
+            name = "bad"
+            [[case]]
+            name = "two actions"
+            step = [{ send = "AT", expect = "OK" }]
+            "#,
+        )
+        .expect_err("two actions in one step");
+        assert!(err.to_string().contains("exactly one"));
+        assert!(
+            TestSuite::parse("name = \"bad\"\n[[case]]\nname = \"x\"\nstep = [{ expect = \"(\" }]")
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,399 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+use crate::send_panel::LineEnding;
+use crate::transfer::TransferState;
+
+/// Queued bytes allowed in the TX ring before the next chunk is held back.
+const HIGH_WATER: usize = 4096;
+/// How far pacing may fall behind before missed slots are dropped instead of sent at once.
+const CATCH_UP: Duration = Duration::from_millis(50);
+
+/// Line endings of the file are rewritten to this before sending.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum EndingTranslation {
+    #[default]
+    #[strum(to_string = "Keep")]
+    Keep,
+    #[strum(to_string = "CR")]
+    Cr,
+    #[strum(to_string = "LF")]
+    Lf,
+    #[strum(to_string = "CRLF")]
+    CrLf,
+}
+
+impl EndingTranslation {
+    fn ending(self) -> Option<LineEnding> {
+        match self {
+            EndingTranslation::Keep => None,
+            EndingTranslation::Cr => Some(LineEnding::Cr),
+            EndingTranslation::Lf => Some(LineEnding::Lf),
+            EndingTranslation::CrLf => Some(LineEnding::CrLf),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FileSendSettings {
+    pub path: String,
+    pub chunk_size: usize,
+    /// Pause after each chunk.
+    pub chunk_delay_ms: u64,
+    /// Pause after each byte within a chunk.
+    pub char_delay_ms: u64,
+    pub translation: EndingTranslation,
+    /// Send one line at a time, waiting for `response` after each.
+    pub line_mode: bool,
+    pub response: String,
+    pub response_timeout_ms: u64,
+}
+
+impl Default for FileSendSettings {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            chunk_size: 256,
+            chunk_delay_ms: 0,
+            char_delay_ms: 0,
+            translation: EndingTranslation::Keep,
+            line_mode: false,
+            response: "OK".to_string(),
+            response_timeout_ms: 2000,
+        }
+    }
+}
+
+/// Rewrites every CR, LF and CRLF in `data` to `ending`.
+pub fn translate_endings(data: &[u8], ending: LineEnding) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut bytes = data.iter().peekable();
+    while let Some(&byte) = bytes.next() {
+        match byte {
+            b'\r' => {
+                bytes.next_if_eq(&&b'\n');
+                out.extend_from_slice(ending.bytes());
+            }
+            b'\n' => out.extend_from_slice(ending.bytes()),
+            _ => out.push(byte),
+        }
+    }
+    out
+}
+
+/// Splits `data` after each CR, LF or CRLF, keeping the endings.
+fn split_lines(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (index, &byte) in data.iter().enumerate() {
+        let ends = match byte {
+            b'\n' => true,
+            b'\r' => data.get(index + 1) != Some(&b'\n'),
+            _ => false,
+        };
+        if ends {
+            lines.push(data[start..=index].to_vec());
+            start = index + 1;
+        }
+    }
+    if start < data.len() {
+        lines.push(data[start..].to_vec());
+    }
+    lines
+}
+
+/// Streams a file to the port with pacing, driven by the owner like
+/// [`FileTransfer`](crate::transfer::FileTransfer).
+///
+/// Progress counts bytes that left the TX ring, not bytes handed to it.
+pub struct FileSender {
+    /// Lines in line mode, otherwise the whole file.
+    segments: Vec<Vec<u8>>,
+    segment: usize,
+    offset: usize,
+    chunk_size: usize,
+    chunk_left: usize,
+    chunk_delay: Duration,
+    char_delay: Duration,
+    response: Option<Vec<u8>>,
+    response_timeout: Duration,
+    /// Deadline for the response to the last line.
+    waiting: Option<Instant>,
+    received: Vec<u8>,
+    next_at: Instant,
+    queued: u64,
+    drained: u64,
+    total: u64,
+    state: TransferState,
+}
+
+impl FileSender {
+    pub fn new(data: &[u8], settings: &FileSendSettings, now: Instant) -> Self {
+        let data = match settings.translation.ending() {
+            Some(ending) => translate_endings(data, ending),
+            None => data.to_vec(),
+        };
+        let total = data.len() as u64;
+        let segments = if settings.line_mode {
+            split_lines(&data)
+        } else {
+            vec![data]
+        };
+        let response = (settings.line_mode && !settings.response.is_empty())
+            .then(|| settings.response.as_bytes().to_vec());
+        let chunk_size = settings.chunk_size.max(1);
+        Self {
+            segments,
+            segment: 0,
+            offset: 0,
+            chunk_size,
+            chunk_left: chunk_size,
+            chunk_delay: Duration::from_millis(settings.chunk_delay_ms),
+            char_delay: Duration::from_millis(settings.char_delay_ms),
+            response,
+            response_timeout: Duration::from_millis(settings.response_timeout_ms),
+            waiting: None,
+            received: Vec::new(),
+            next_at: now,
+            queued: 0,
+            drained: 0,
+            total,
+            state: TransferState::Running,
+        }
+    }
+
+    /// Looks for the line response in received data.
+    pub fn feed(&mut self, data: &[u8], now: Instant) {
+        let (Some(_), Some(response)) = (self.waiting, &self.response) else {
+            return;
+        };
+        self.received.extend_from_slice(data);
+        if self
+            .received
+            .windows(response.len())
+            .any(|window| window == response.as_slice())
+        {
+            self.waiting = None;
+            self.received.clear();
+            self.next_at = self.next_at.max(now);
+        } else if self.received.len() > HIGH_WATER {
+            // Only a tail shorter than the response can still become a match.
+            let keep = response.len() - 1;
+            let stale = self.received.len().saturating_sub(keep);
+            self.received.drain(..stale);
+        }
+    }
+
+    /// Returns the bytes to write now, given how much is still queued for the device.
+    pub fn poll(&mut self, now: Instant, tx_pending: usize) -> Vec<u8> {
+        self.drained = self.queued.saturating_sub(tx_pending as u64);
+        let mut out = Vec::new();
+        while self.state == TransferState::Running {
+            if let Some(deadline) = self.waiting {
+                if now >= deadline {
+                    self.state =
+                        TransferState::Failed(format!("no response to line {}", self.segment));
+                }
+                break;
+            }
+            if self.segment == self.segments.len() {
+                if tx_pending == 0 && out.is_empty() {
+                    self.state = TransferState::Completed;
+                }
+                break;
+            }
+            if now < self.next_at || tx_pending + out.len() >= HIGH_WATER {
+                break;
+            }
+            self.emit(now, HIGH_WATER - tx_pending - out.len(), &mut out);
+        }
+        self.queued += out.len() as u64;
+        out
+    }
+
+    /// Queues one byte (with a character delay) or the rest of the current
+    /// chunk, at most `room` bytes.
+    fn emit(&mut self, now: Instant, room: usize, out: &mut Vec<u8>) {
+        let segment = &self.segments[self.segment];
+        let unit = if self.char_delay.is_zero() {
+            self.chunk_left.min(room)
+        } else {
+            1
+        };
+        let end = (self.offset + unit).min(segment.len());
+        out.extend_from_slice(&segment[self.offset..end]);
+        self.chunk_left -= end - self.offset;
+        self.offset = end;
+        let line_done = self.offset == segment.len();
+        let delay = if self.chunk_left == 0 || line_done {
+            self.chunk_left = self.chunk_size;
+            self.chunk_delay
+        } else {
+            self.char_delay
+        };
+        if line_done {
+            self.segment += 1;
+            self.offset = 0;
+            if self.response.is_some() {
+                self.waiting = Some(now + self.response_timeout);
+                self.received.clear();
+            }
+        }
+        self.next_at = self.next_at.max(now.checked_sub(CATCH_UP).unwrap_or(now)) + delay;
+    }
+
+    pub fn cancel(&mut self) {
+        if self.is_running() {
+            self.state = TransferState::Cancelled;
+        }
+    }
+
+    /// Stops the send because of a local problem, such as a failed port write.
+    pub fn abort(&mut self, reason: String) {
+        if self.is_running() {
+            self.state = TransferState::Failed(reason);
+        }
+    }
+
+    /// Bytes that reached the device so far.
+    pub fn sent(&self) -> u64 {
+        self.drained
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The line being sent or answered, and the number of lines, in line mode.
+    pub fn line(&self) -> Option<(usize, usize)> {
+        let answered = self.segment - usize::from(self.waiting.is_some());
+        (self.response.is_some() || self.segments.len() > 1)
+            .then(|| ((answered + 1).min(self.segments.len()), self.segments.len()))
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.waiting.is_some()
+    }
+
+    pub fn state(&self) -> &TransferState {
+        &self.state
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state == TransferState::Running
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn endings_are_translated_and_split() {
+        let data = b"a\r\nb\nc\rd";
+        assert_eq!(
+            translate_endings(data, LineEnding::CrLf),
+            b"a\r\nb\r\nc\r\nd"
+        );
+        assert_eq!(translate_endings(data, LineEnding::Lf), b"a\nb\nc\nd");
+        assert_eq!(
+            split_lines(data),
+            vec![
+                b"a\r\n".to_vec(),
+                b"b\n".to_vec(),
+                b"c\r".to_vec(),
+                b"d".to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn chunks_are_paced_and_held_back_until_the_ring_drains() {
+        let settings = FileSendSettings {
+            chunk_size: 4,
+            chunk_delay_ms: 10,
+            ..FileSendSettings::default()
+        };
+        let start = Instant::now();
+        let mut sender = FileSender::new(b"0123456789", &settings, start);
+        assert_eq!(sender.poll(start, 0), b"0123");
+        assert!(sender.poll(start + ms(9), 0).is_empty());
+        // Nothing new while the ring is full.
+        assert!(sender.poll(start + ms(10), HIGH_WATER).is_empty());
+        assert_eq!(sender.poll(start + ms(10), 4), b"4567");
+        // The first chunk was still queued.
+        assert_eq!(sender.sent(), 0);
+        assert_eq!(sender.poll(start + ms(20), 0), b"89");
+        assert!(sender.is_running());
+        assert!(sender.poll(start + ms(30), 2).is_empty());
+        assert_eq!(sender.sent(), 8);
+        sender.poll(start + ms(31), 0);
+        assert_eq!(sender.state(), &TransferState::Completed);
+        assert_eq!(sender.sent(), sender.total());
+
+        let spaced = FileSendSettings {
+            char_delay_ms: 1,
+            ..FileSendSettings::default()
+        };
+        let mut sender = FileSender::new(b"abcdef", &spaced, start);
+        assert_eq!(sender.poll(start + ms(2), 0), b"abc");
+
+        // A chunk larger than the free room is split at the high-water mark.
+        let large = FileSendSettings {
+            chunk_size: 1024,
+            ..FileSendSettings::default()
+        };
+        let mut sender = FileSender::new(&[0x55; 1024], &large, start);
+        assert_eq!(sender.poll(start, HIGH_WATER - 100).len(), 100);
+        assert_eq!(sender.poll(start, HIGH_WATER - 24).len(), 24);
+        assert_eq!(sender.poll(start, 0).len(), 900);
+    }
+
+    #[test]
+    fn line_mode_waits_for_each_response() {
+        let settings = FileSendSettings {
+            line_mode: true,
+            response: "OK".to_string(),
+            response_timeout_ms: 100,
+            translation: EndingTranslation::Cr,
+            ..FileSendSettings::default()
+        };
+        let start = Instant::now();
+        let mut sender = FileSender::new(b":10\n:20\n:30\n", &settings, start);
+        assert_eq!(sender.poll(start, 0), b":10\r");
+        assert_eq!(sender.line(), Some((1, 3)));
+        assert!(sender.poll(start + ms(50), 0).is_empty());
+        sender.feed(b"O", start + ms(60));
+        sender.feed(b"K\r\n", start + ms(60));
+        assert_eq!(sender.poll(start + ms(60), 0), b":20\r");
+        assert_eq!(sender.line(), Some((2, 3)));
+        assert!(sender.poll(start + ms(160), 0).is_empty());
+        assert_eq!(
+            sender.state(),
+            &TransferState::Failed("no response to line 2".to_string())
+        );
+    }
+
+    #[test]
+    fn response_split_after_long_output_is_found() {
+        let settings = FileSendSettings {
+            line_mode: true,
+            response: "READY".to_string(),
+            ..FileSendSettings::default()
+        };
+        let start = Instant::now();
+        let mut sender = FileSender::new(b"a\nb\n", &settings, start);
+        assert_eq!(sender.poll(start, 0), b"a\n");
+        sender.feed(&[b'.'; HIGH_WATER], start);
+        sender.feed(b"REA", start);
+        sender.feed(b"DY", start);
+        assert!(!sender.is_waiting());
+        assert_eq!(sender.poll(start, 0), b"b\n");
+    }
+}
@@ -1,11 +1,44 @@
 pub mod app;
+pub mod bridge;
+pub mod checksum;
+pub mod cli;
+pub mod commands;
 pub mod console;
 pub mod core;
+#[cfg(unix)]
+pub mod daemon;
+#[cfg(unix)]
+pub mod daemon_client;
+pub mod decoder;
 pub mod device_scan;
 pub mod diagnostics;
+pub mod emulator;
+pub mod expect;
+pub mod file_send;
+pub mod macros;
+pub mod net_client;
+pub mod net_server;
+pub mod pcapng;
 pub mod profiles;
+#[cfg(unix)]
+pub mod pty;
+pub mod recorder;
 pub mod renderer;
+pub mod replay;
+pub mod rfc2217;
+pub mod scheduler;
+pub mod scripting;
 pub mod send_panel;
 pub mod session;
 pub mod settings;
+pub mod template;
+pub mod terminal;
 pub mod theme;
+pub mod transfer;
+pub mod triggers;
+pub mod usb;
+#[cfg(unix)]
+pub mod virtual_port;
+pub mod websocket;
+pub mod xmodem;
+pub mod zmodem;
@@ -0,0 +1,183 @@
+use std::fs;
+use std::path::Path;
+
+use eframe::egui::{Key, Modifiers};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::send_panel::LineEnding;
+use crate::send_panel::SendMode;
+
+/// Version written to exported macro sets.
+const SET_VERSION: u32 = 1;
+
+/// A named button on the macro bar.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Macro {
+    pub name: String,
+    pub value: String,
+    pub mode: SendMode,
+    /// Replaces the profile's line ending for text and templates.
+    #[serde(default)]
+    pub ending: Option<LineEnding>,
+    /// For example `F5` or `Ctrl+Shift+R`.
+    #[serde(default)]
+    pub hotkey: Option<String>,
+    /// Button fill as sRGB.
+    #[serde(default)]
+    pub color: Option<[u8; 3]>,
+    /// Buttons with the same group share a row; empty for the first, unnamed row.
+    #[serde(default)]
+    pub group: String,
+}
+
+impl Macro {
+    pub fn new(name: &str, value: &str, mode: SendMode) -> Self {
+        Self {
+            name: name.to_string(),
+            value: value.to_string(),
+            mode,
+            ending: None,
+            hotkey: None,
+            color: None,
+            group: String::new(),
+        }
+    }
+
+    pub fn shortcut(&self) -> Option<(Modifiers, Key)> {
+        parse_hotkey(self.hotkey.as_deref()?)
+    }
+}
+
+/// Parses `Ctrl+Alt+Shift+<key>`; the key uses egui's names such as `F5`, `A` or `Enter`.
+pub fn parse_hotkey(text: &str) -> Option<(Modifiers, Key)> {
+    let mut modifiers = Modifiers::NONE;
+    let mut parts = text.split('+').map(str::trim).peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return Key::from_name(part).map(|key| (modifiers, key));
+        }
+        match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "cmd" => modifiers.command = true,
+            "alt" => modifiers.alt = true,
+            "shift" => modifiers.shift = true,
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// Groups in order of first appearance, each with the indices of its macros.
+pub fn groups(macros: &[Macro]) -> Vec<(&str, Vec<usize>)> {
+    let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+    for (index, item) in macros.iter().enumerate() {
+        match groups.iter_mut().find(|(name, _)| *name == item.group) {
+            Some((_, members)) => members.push(index),
+            None => groups.push((&item.group, vec![index])),
+        }
+    }
+    groups
+}
+
+#[derive(Debug, Error)]
+pub enum MacroError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid macro set: {0}")]
+    Format(#[from] serde_json::Error),
+    #[error("macro set version {0} is newer than this application")]
+    Version(u32),
+}
+
+/// The JSON file shared between installations.
+#[derive(Debug, Serialize, Deserialize)]
+struct MacroSet {
+    version: u32,
+    macros: Vec<Macro>,
+}
+
+pub fn export(path: &Path, macros: &[Macro]) -> Result<(), MacroError> {
+    let set = MacroSet {
+        version: SET_VERSION,
+        macros: macros.to_vec(),
+    };
+    fs::write(path, serde_json::to_string_pretty(&set)?)?;
+    Ok(())
+}
+
+pub fn import(path: &Path) -> Result<Vec<Macro>, MacroError> {
+    let set: MacroSet = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if set.version > SET_VERSION {
+        return Err(MacroError::Version(set.version));
+    }
+    Ok(set.macros)
+}
+
+/// Adds `imported` to `macros`, replacing macros with the same group and name.
+///
+/// Returns the number of macros that were replaced.
+pub fn merge(macros: &mut Vec<Macro>, imported: Vec<Macro>) -> usize {
+    let mut replaced = 0;
+    for item in imported {
+        match macros
+            .iter_mut()
+            .find(|existing| existing.group == item.group && existing.name == item.name)
+        {
+            Some(existing) => {
+                *existing = item;
+                replaced += 1;
+            }
+            None => macros.push(item),
+        }
+    }
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hotkeys_and_groups_parse() {
+        assert_eq!(parse_hotkey("F5"), Some((Modifiers::NONE, Key::F5)));
+        assert_eq!(
+            parse_hotkey("Ctrl + Shift + R"),
+            Some((Modifiers::COMMAND | Modifiers::SHIFT, Key::R))
+        );
+        assert_eq!(parse_hotkey("Hyper+R"), None);
+        assert_eq!(parse_hotkey("Ctrl+"), None);
+
+        let mut reset = Macro::new("Reset", "ATZ", SendMode::Text);
+        reset.group = "Modem".to_string();
+        let macros = vec![
+            Macro::new("Ping", "AT", SendMode::Text),
+            reset.clone(),
+            Macro::new("Stop", "03", SendMode::Hex),
+        ];
+        assert_eq!(groups(&macros), vec![("", vec![0, 2]), ("Modem", vec![1])]);
+    }
+
+    #[test]
+    fn sets_round_trip_and_merge_by_name() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("lab.json");
+        let mut ping = Macro::new("Ping", "AT", SendMode::Text);
+        ping.ending = Some(LineEnding::CrLf);
+        ping.hotkey = Some("F2".to_string());
+        ping.color = Some([200, 40, 40]);
+        export(&path, std::slice::from_ref(&ping)).expect("export");
+        let imported = import(&path).expect("import");
+        assert_eq!(imported, vec![ping.clone()]);
+
+        let mut macros = vec![
+            Macro::new("Ping", "AT+OLD", SendMode::Text),
+            Macro::new("Stop", "03", SendMode::Hex),
+        ];
+        assert_eq!(merge(&mut macros, imported), 1);
+        assert_eq!(macros[0], ping);
+        assert_eq!(macros.len(), 2);
+
+        fs::write(&path, r#"{"version": 9, "macros": []}"#).expect("write");
+        assert!(matches!(import(&path), Err(MacroError::Version(9))));
+    }
+}
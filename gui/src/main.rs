@@ -1,22 +1,20 @@
-mod app;
-mod console;
-mod core;
-mod device_scan;
-mod diagnostics;
-mod profiles;
-mod renderer;
-mod send_panel;
-mod session;
-mod settings;
-mod theme;
-
-use app::MicroSerialApp;
 use egui_wgpu::WgpuError;
-use renderer::{LaunchConfig, RendererSelection};
-use settings::Settings;
+use microserial_gui::app::MicroSerialApp;
+use microserial_gui::cli;
+use microserial_gui::renderer::{self, LaunchConfig, RendererSelection};
+use microserial_gui::settings::Settings;
 
 fn main() -> eframe::Result<()> {
     env_logger::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match cli::parse(&args) {
+        Ok(Some(command)) => std::process::exit(cli::run(command)),
+        Ok(None) => {}
+        Err(err) => {
+            eprintln!("microserial: {err}\n\n{}", cli::USAGE);
+            std::process::exit(cli::EXIT_USAGE);
+        }
+    }
     let mut launch = LaunchConfig::from_args();
     let settings = Settings::load().unwrap_or_default();
     if settings.force_software {
@@ -0,0 +1,479 @@
+use std::fmt;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use strum::Display;
+
+use crate::core::{ModemLines, SerialConfig};
+use crate::rfc2217::{self, ComPortCommand, Negotiator, TelnetDecoder, TelnetEvent};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const READ_TICK: Duration = Duration::from_millis(100);
+/// How long an RFC 2217 server may take to answer a COM-PORT command.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display)]
+pub enum RemoteScheme {
+    #[strum(to_string = "tcp")]
+    Tcp,
+    #[strum(to_string = "rfc2217")]
+    Rfc2217,
+}
+
+/// A `tcp://host:port` or `rfc2217://host:port` session target.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteTarget {
+    pub scheme: RemoteScheme,
+    pub address: String,
+}
+
+impl RemoteTarget {
+    /// Parses a target URL, returning `None` for anything that is not a remote target.
+    pub fn parse(path: &str) -> Option<Self> {
+        let path = path.trim();
+        let (scheme, address) = if let Some(rest) = path.strip_prefix("tcp://") {
+            (RemoteScheme::Tcp, rest)
+        } else if let Some(rest) = path.strip_prefix("rfc2217://") {
+            (RemoteScheme::Rfc2217, rest)
+        } else {
+            return None;
+        };
+        let address = address.trim_end_matches('/');
+        let (host, port) = address.rsplit_once(':')?;
+        if host.is_empty() || port.parse::<u16>().is_err() {
+            return None;
+        }
+        Some(Self {
+            scheme,
+            address: address.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for RemoteTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.address)
+    }
+}
+
+/// COM-PORT replies from the server, waited on by [`NetworkPort::request`].
+type Replies = Arc<(Mutex<Vec<ComPortCommand>>, Condvar)>;
+
+/// Telnet state of an RFC 2217 connection, kept by whoever reads the socket.
+#[derive(Default)]
+struct Link {
+    decoder: TelnetDecoder,
+    negotiator: Negotiator,
+}
+
+impl Link {
+    fn feed(
+        &mut self,
+        input: &[u8],
+        writer: &Mutex<TcpStream>,
+        lines: &Mutex<ModemLines>,
+        replies: &Replies,
+        on_data: &mut dyn FnMut(&[u8]),
+    ) {
+        for event in self.decoder.feed(input) {
+            match event {
+                TelnetEvent::Data(data) => on_data(&data),
+                TelnetEvent::Negotiate { command, option } => {
+                    if let (Some(reply), Ok(mut writer)) =
+                        (self.negotiator.answer(command, option), writer.lock())
+                    {
+                        let _ = writer.write_all(&reply);
+                    }
+                }
+                TelnetEvent::Subnegotiation { option, payload }
+                    if option == rfc2217::OPT_COM_PORT =>
+                {
+                    let Some((command, true)) = ComPortCommand::parse(&payload) else {
+                        continue;
+                    };
+                    if let Ok(mut lines) = lines.lock() {
+                        track_lines(&mut lines, command);
+                    }
+                    if !matches!(
+                        command,
+                        ComPortCommand::NotifyModemState(_) | ComPortCommand::NotifyLineState(_)
+                    ) {
+                        let (queue, ready) = &**replies;
+                        if let Ok(mut queue) = queue.lock() {
+                            queue.push(command);
+                            ready.notify_all();
+                        }
+                    }
+                }
+                TelnetEvent::Subnegotiation { .. } => {}
+            }
+        }
+    }
+}
+
+/// Client side of a ser2net-style remote port.
+///
+/// Mirrors the [`SerialPort`](crate::core::SerialPort) surface so sessions can
+/// drive either. Errors are negative errno values like the C core returns.
+/// Raw TCP targets carry bytes only: line settings are accepted and ignored,
+/// while modem line control reports `-ENOTSUP`. RFC 2217 settings and line
+/// changes wait for the server's answer and fail with `-EINVAL` when it
+/// keeps a different value.
+pub struct NetworkPort {
+    target: RemoteTarget,
+    stream: TcpStream,
+    writer: Arc<Mutex<TcpStream>>,
+    /// Telnet state until the reader thread takes it over.
+    link: Option<Link>,
+    /// Data that arrived while waiting for replies before the reader started.
+    early: Vec<u8>,
+    replies: Replies,
+    lines: Arc<Mutex<ModemLines>>,
+    stop: Arc<AtomicBool>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl NetworkPort {
+    pub fn connect(target: &RemoteTarget) -> Result<Self, i32> {
+        let addrs = target
+            .address
+            .to_socket_addrs()
+            .map_err(|err| errno(&err))?;
+        let mut last_error = -libc::EHOSTUNREACH;
+        let mut connected = None;
+        for addr in addrs {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(stream) => {
+                    connected = Some(stream);
+                    break;
+                }
+                Err(err) => last_error = errno(&err),
+            }
+        }
+        let stream = connected.ok_or(last_error)?;
+        let _ = stream.set_nodelay(true);
+        stream
+            .set_read_timeout(Some(READ_TICK))
+            .map_err(|err| errno(&err))?;
+        let writer = stream.try_clone().map_err(|err| errno(&err))?;
+
+        let mut port = Self {
+            target: target.clone(),
+            stream,
+            writer: Arc::new(Mutex::new(writer)),
+            link: None,
+            early: Vec::new(),
+            replies: Replies::default(),
+            lines: Arc::new(Mutex::new(ModemLines::default())),
+            stop: Arc::new(AtomicBool::new(false)),
+            reader: None,
+        };
+        if target.scheme == RemoteScheme::Rfc2217 {
+            let mut link = Link::default();
+            let mut offers = Vec::new();
+            for (command, option) in [
+                (rfc2217::WILL, rfc2217::OPT_BINARY),
+                (rfc2217::DO, rfc2217::OPT_BINARY),
+                (rfc2217::DO, rfc2217::OPT_SUPPRESS_GO_AHEAD),
+                (rfc2217::WILL, rfc2217::OPT_COM_PORT),
+            ] {
+                offers.extend(link.negotiator.offer(command, option));
+            }
+            port.link = Some(link);
+            port.send_raw(&offers)?;
+            // Ask for every input line change, and for the current DTR/RTS state.
+            port.request(&[
+                ComPortCommand::SetModemStateMask(0xFF),
+                ComPortCommand::SetControl(rfc2217::CONTROL_DTR_REQUEST),
+                ComPortCommand::SetControl(rfc2217::CONTROL_RTS_REQUEST),
+            ])?;
+        }
+        Ok(port)
+    }
+
+    pub fn target(&self) -> &RemoteTarget {
+        &self.target
+    }
+
+    /// Negotiates the line settings with an RFC 2217 server; a no-op for raw TCP.
+    pub fn configure(&mut self, config: &SerialConfig) -> Result<(), i32> {
+        if self.target.scheme == RemoteScheme::Tcp {
+            return Ok(());
+        }
+        let commands = rfc2217::config_commands(config);
+        if self.request(&commands)? != commands {
+            return Err(-libc::EINVAL);
+        }
+        Ok(())
+    }
+
+    pub fn start<F, E>(&mut self, mut on_data: F, mut on_event: E) -> Result<(), i32>
+    where
+        F: FnMut(&[u8]) + Send + 'static,
+        E: FnMut(i32, &str) + Send + 'static,
+    {
+        if self.reader.is_some() {
+            return Err(-libc::EALREADY);
+        }
+        let mut stream = self.stream.try_clone().map_err(|err| errno(&err))?;
+        let telnet = self.target.scheme == RemoteScheme::Rfc2217;
+        let mut link = self.link.take().unwrap_or_default();
+        let writer = Arc::clone(&self.writer);
+        let lines = Arc::clone(&self.lines);
+        let replies = Arc::clone(&self.replies);
+        let stop = Arc::clone(&self.stop);
+        stop.store(false, Ordering::SeqCst);
+        if !self.early.is_empty() {
+            on_data(&std::mem::take(&mut self.early));
+        }
+
+        self.reader = Some(thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            while !stop.load(Ordering::SeqCst) {
+                let n = match stream.read(&mut buf) {
+                    Ok(0) => {
+                        if !stop.load(Ordering::SeqCst) {
+                            on_event(-libc::ECONNRESET, "remote closed the connection");
+                        }
+                        break;
+                    }
+                    Ok(n) => n,
+                    Err(err)
+                        if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                    {
+                        continue;
+                    }
+                    Err(err) => {
+                        if !stop.load(Ordering::SeqCst) {
+                            on_event(errno(&err), "connection lost");
+                        }
+                        break;
+                    }
+                };
+                if !telnet {
+                    on_data(&buf[..n]);
+                    continue;
+                }
+                link.feed(&buf[..n], &writer, &lines, &replies, &mut on_data);
+            }
+        }));
+        Ok(())
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<usize, i32> {
+        match self.target.scheme {
+            RemoteScheme::Tcp => self.send_raw(data)?,
+            RemoteScheme::Rfc2217 => self.send_raw(&rfc2217::escape_iac(data))?,
+        }
+        Ok(data.len())
+    }
+
+    /// Drives DTR and/or RTS; `None` leaves the corresponding line untouched.
+    ///
+    /// The cached lines follow the server's answer, not the request.
+    pub fn set_modem_lines(&mut self, dtr: Option<bool>, rts: Option<bool>) -> Result<(), i32> {
+        self.require_control()?;
+        let mut commands = Vec::new();
+        if let Some(enabled) = dtr {
+            let value = if enabled {
+                rfc2217::CONTROL_DTR_ON
+            } else {
+                rfc2217::CONTROL_DTR_OFF
+            };
+            commands.push(ComPortCommand::SetControl(value));
+        }
+        if let Some(enabled) = rts {
+            let value = if enabled {
+                rfc2217::CONTROL_RTS_ON
+            } else {
+                rfc2217::CONTROL_RTS_OFF
+            };
+            commands.push(ComPortCommand::SetControl(value));
+        }
+        if self.request(&commands)? != commands {
+            return Err(-libc::EINVAL);
+        }
+        Ok(())
+    }
+
+    /// Returns the line state last reported by the server: inputs from
+    /// NOTIFY-MODEMSTATE, DTR and RTS from its SET-CONTROL replies.
+    pub fn modem_lines(&self) -> Result<ModemLines, i32> {
+        self.require_control()?;
+        self.lines
+            .lock()
+            .map(|lines| *lines)
+            .map_err(|_| -libc::EIO)
+    }
+
+    pub fn set_break(&mut self, enabled: bool) -> Result<(), i32> {
+        self.require_control()?;
+        let value = if enabled {
+            rfc2217::CONTROL_BREAK_ON
+        } else {
+            rfc2217::CONTROL_BREAK_OFF
+        };
+        let command = ComPortCommand::SetControl(value);
+        if self.request(&[command])? != [command] {
+            return Err(-libc::EINVAL);
+        }
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(reader) = self.reader.take() {
+            let _ = self.stream.shutdown(Shutdown::Both);
+            let _ = reader.join();
+        }
+    }
+
+    fn require_control(&self) -> Result<(), i32> {
+        match self.target.scheme {
+            RemoteScheme::Tcp => Err(-libc::ENOTSUP),
+            RemoteScheme::Rfc2217 => Ok(()),
+        }
+    }
+
+    /// Sends COM-PORT commands and returns the server's answer to each, in order.
+    fn request(&mut self, commands: &[ComPortCommand]) -> Result<Vec<ComPortCommand>, i32> {
+        let replies = Arc::clone(&self.replies);
+        let (queue, ready) = &*replies;
+        // Anything queued now answers an earlier request that already gave up.
+        queue.lock().map_err(|_| -libc::EIO)?.clear();
+        let mut bytes = Vec::new();
+        for command in commands {
+            bytes.extend(command.encode(false));
+        }
+        self.send_raw(&bytes)?;
+
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        let mut answers: Vec<Option<ComPortCommand>> = vec![None; commands.len()];
+        loop {
+            {
+                let mut queue = queue.lock().map_err(|_| -libc::EIO)?;
+                for reply in queue.drain(..) {
+                    if let Some((_, answer)) = commands
+                        .iter()
+                        .zip(answers.iter_mut())
+                        .find(|(command, answer)| answer.is_none() && answers_to(**command, reply))
+                    {
+                        *answer = Some(reply);
+                    }
+                }
+                if answers.iter().all(Option::is_some) {
+                    return Ok(answers.into_iter().flatten().collect());
+                }
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(-libc::ETIMEDOUT);
+                }
+                if self.reader.is_some() {
+                    let _ = ready.wait_timeout(queue, deadline - now);
+                    continue;
+                }
+            }
+            self.pump()?;
+        }
+    }
+
+    /// Reads the socket once while no reader thread runs, i.e. before `start`.
+    fn pump(&mut self) -> Result<(), i32> {
+        let mut buf = [0u8; 4096];
+        let n = match (&self.stream).read(&mut buf) {
+            Ok(0) => return Err(-libc::ECONNRESET),
+            Ok(n) => n,
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                return Ok(());
+            }
+            Err(err) => return Err(errno(&err)),
+        };
+        let link = self.link.get_or_insert_with(Link::default);
+        let early = &mut self.early;
+        link.feed(
+            &buf[..n],
+            &self.writer,
+            &self.lines,
+            &self.replies,
+            &mut |data| early.extend_from_slice(data),
+        );
+        Ok(())
+    }
+
+    fn send_raw(&self, bytes: &[u8]) -> Result<(), i32> {
+        let mut writer = self.writer.lock().map_err(|_| -libc::EIO)?;
+        writer.write_all(bytes).map_err(|err| errno(&err))
+    }
+}
+
+impl Drop for NetworkPort {
+    fn drop(&mut self) {
+        self.stop();
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Applies a server reply or notification to the cached line state.
+fn track_lines(lines: &mut ModemLines, command: ComPortCommand) {
+    match command {
+        ComPortCommand::NotifyModemState(state) => {
+            let inputs = rfc2217::modem_lines_from_state(state);
+            lines.cts = inputs.cts;
+            lines.dsr = inputs.dsr;
+            lines.ri = inputs.ri;
+            lines.cd = inputs.cd;
+        }
+        ComPortCommand::SetControl(rfc2217::CONTROL_DTR_ON) => lines.dtr = true,
+        ComPortCommand::SetControl(rfc2217::CONTROL_DTR_OFF) => lines.dtr = false,
+        ComPortCommand::SetControl(rfc2217::CONTROL_RTS_ON) => lines.rts = true,
+        ComPortCommand::SetControl(rfc2217::CONTROL_RTS_OFF) => lines.rts = false,
+        _ => {}
+    }
+}
+
+/// Whether `reply` is the server's answer to `request`: the same command, and for
+/// SET-CONTROL the same group of values (flow control, break, DTR or RTS).
+fn answers_to(request: ComPortCommand, reply: ComPortCommand) -> bool {
+    let group = |value: u8| match value {
+        0..=3 => 0,
+        4..=6 => 1,
+        7..=9 => 2,
+        10..=12 => 3,
+        other => other,
+    };
+    match (request, reply) {
+        (ComPortCommand::SetControl(request), ComPortCommand::SetControl(reply)) => {
+            group(request) == group(reply)
+        }
+        (request, reply) => std::mem::discriminant(&request) == std::mem::discriminant(&reply),
+    }
+}
+
+fn errno(err: &io::Error) -> i32 {
+    -err.raw_os_error().unwrap_or(libc::EIO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RemoteScheme, RemoteTarget};
+
+    #[test]
+    fn parses_remote_targets() {
+        let target = RemoteTarget::parse("rfc2217://lab-pi.local:4001").expect("rfc2217");
+        assert_eq!(target.scheme, RemoteScheme::Rfc2217);
+        assert_eq!(target.address, "lab-pi.local:4001");
+        assert_eq!(target.to_string(), "rfc2217://lab-pi.local:4001");
+
+        let target = RemoteTarget::parse("tcp://[::1]:23/").expect("tcp");
+        assert_eq!(target.scheme, RemoteScheme::Tcp);
+        assert_eq!(target.address, "[::1]:23");
+
+        assert_eq!(RemoteTarget::parse("/dev/ttyUSB0"), None);
+        assert_eq!(RemoteTarget::parse("tcp://host"), None);
+        assert_eq!(RemoteTarget::parse("tcp://:23"), None);
+    }
+}
@@ -0,0 +1,689 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+use thiserror::Error;
+
+use crate::core::{ModemLines, SerialConfig};
+use crate::rfc2217::{self, ComPortCommand, Negotiator, TelnetDecoder, TelnetEvent};
+
+const ACCEPT_TICK: Duration = Duration::from_millis(50);
+const READ_TICK: Duration = Duration::from_millis(200);
+const TOKEN_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_TOKEN_LINE: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum ServerProtocol {
+    #[strum(to_string = "Raw TCP")]
+    Raw,
+    #[strum(to_string = "Telnet (RFC 2217)")]
+    Rfc2217,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerSettings {
+    /// Address to listen on; binding to `127.0.0.1` keeps the port local.
+    pub bind: String,
+    pub protocol: ServerProtocol,
+    /// When set, clients must send this token followed by a newline first.
+    pub token: Option<String>,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1:7000".to_string(),
+            protocol: ServerProtocol::Rfc2217,
+            token: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display)]
+pub enum ClientRole {
+    #[strum(to_string = "writer")]
+    Writer,
+    #[strum(to_string = "observer")]
+    Observer,
+}
+
+/// Changes a network client asks the owner of the session to apply.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerRequest {
+    Write(Vec<u8>),
+    Configure(SerialConfig),
+    SetDtr(bool),
+    SetRts(bool),
+    SetBreak(bool),
+}
+
+#[derive(Debug, Clone)]
+pub enum ServerMessage {
+    Request {
+        peer: SocketAddr,
+        request: ServerRequest,
+    },
+    Connected {
+        peer: SocketAddr,
+        role: ClientRole,
+    },
+    Disconnected {
+        peer: SocketAddr,
+    },
+    /// An observer took over the writer slot after the writer left.
+    Promoted {
+        peer: SocketAddr,
+    },
+    Rejected {
+        peer: SocketAddr,
+        reason: String,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum ServerError {
+    #[error("invalid bind address '{0}'")]
+    Address(String),
+    #[error("bind failed: {0}")]
+    Bind(#[from] std::io::Error),
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct LineState {
+    lines: ModemLines,
+    brk: bool,
+}
+
+struct ClientHandle {
+    id: u64,
+    peer: SocketAddr,
+    role: ClientRole,
+    /// Modem lines the client wants NOTIFY-MODEMSTATE for; all of them by default.
+    modem_mask: u8,
+    /// Settings commands answered once the owner reports what it applied.
+    awaiting: Vec<ComPortCommand>,
+    outbound: Sender<Vec<u8>>,
+    stream: TcpStream,
+}
+
+struct Shared {
+    settings: ServerSettings,
+    stop: AtomicBool,
+    next_id: AtomicU64,
+    clients: Mutex<Vec<ClientHandle>>,
+    config: Mutex<SerialConfig>,
+    lines: Mutex<LineState>,
+    messages: Sender<ServerMessage>,
+}
+
+/// Exposes a session over TCP, ser2net style.
+///
+/// The owner keeps the session: received bytes are pushed in through
+/// [`SerialServer::publish`], and writes or line changes from the writer
+/// client come back as [`ServerRequest`]s from [`SerialServer::poll`]. The
+/// first client to connect becomes the writer; everyone else observes
+/// read-only, and the oldest observer is promoted when the writer leaves.
+pub struct SerialServer {
+    local_addr: SocketAddr,
+    shared: Arc<Shared>,
+    rx: Receiver<ServerMessage>,
+    listener: Option<JoinHandle<()>>,
+}
+
+impl SerialServer {
+    /// `lines` is the current state of the session's modem lines.
+    pub fn start(
+        settings: ServerSettings,
+        config: &SerialConfig,
+        lines: ModemLines,
+    ) -> Result<Self, ServerError> {
+        let addr: SocketAddr = settings
+            .bind
+            .parse()
+            .map_err(|_| ServerError::Address(settings.bind.clone()))?;
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+
+        let (tx, rx) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            settings,
+            stop: AtomicBool::new(false),
+            next_id: AtomicU64::new(1),
+            clients: Mutex::new(Vec::new()),
+            config: Mutex::new(config.clone()),
+            lines: Mutex::new(LineState { lines, brk: false }),
+            messages: tx,
+        });
+        let accept_shared = Arc::clone(&shared);
+        let handle = thread::spawn(move || accept_loop(listener, accept_shared));
+        Ok(Self {
+            local_addr,
+            shared,
+            rx,
+            listener: Some(handle),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn protocol(&self) -> ServerProtocol {
+        self.shared.settings.protocol
+    }
+
+    /// Forwards bytes received from the device to every connected client.
+    pub fn publish(&self, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        let payload = match self.shared.settings.protocol {
+            ServerProtocol::Raw => data.to_vec(),
+            ServerProtocol::Rfc2217 => rfc2217::escape_iac(data),
+        };
+        if let Ok(mut clients) = self.shared.clients.lock() {
+            clients.retain(|client| client.outbound.send(payload.clone()).is_ok());
+        }
+    }
+
+    /// Reports the configuration the session actually runs with.
+    ///
+    /// Call it after local changes, and after every [`ServerRequest::Configure`]
+    /// whether it succeeded or not: RFC 2217 clients get their answer only then.
+    pub fn update_config(&self, config: &SerialConfig) {
+        if let Ok(mut guard) = self.shared.config.lock() {
+            *guard = config.clone();
+        }
+        if let Ok(mut clients) = self.shared.clients.lock() {
+            for client in clients.iter_mut() {
+                for command in client.awaiting.drain(..) {
+                    let reply = applied_reply(command, config).encode(true);
+                    let _ = client.outbound.send(reply);
+                }
+            }
+        }
+    }
+
+    /// Records the session's modem lines; RFC 2217 clients are notified when an
+    /// input line they asked for changes.
+    pub fn update_lines(&self, lines: ModemLines) {
+        let Ok(mut state) = self.shared.lines.lock() else {
+            return;
+        };
+        let previous = std::mem::replace(&mut state.lines, lines);
+        drop(state);
+        if self.shared.settings.protocol != ServerProtocol::Rfc2217 {
+            return;
+        }
+        let modem_state = rfc2217::modem_state(lines, previous);
+        if modem_state & rfc2217::MODEM_STATE_DELTAS == 0 {
+            return;
+        }
+        if let Ok(clients) = self.shared.clients.lock() {
+            for client in clients.iter() {
+                let masked = modem_state & client.modem_mask;
+                if masked & rfc2217::MODEM_STATE_DELTAS != 0 {
+                    let notify = ComPortCommand::NotifyModemState(masked).encode(true);
+                    let _ = client.outbound.send(notify);
+                }
+            }
+        }
+    }
+
+    pub fn clients(&self) -> Vec<(SocketAddr, ClientRole)> {
+        match self.shared.clients.lock() {
+            Ok(clients) => clients
+                .iter()
+                .map(|client| (client.peer, client.role))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn poll(&self) -> Vec<ServerMessage> {
+        let mut messages = Vec::new();
+        while let Ok(msg) = self.rx.try_recv() {
+            messages.push(msg);
+        }
+        messages
+    }
+
+    pub fn stop(&mut self) {
+        self.shared.stop.store(true, Ordering::SeqCst);
+        if let Ok(mut clients) = self.shared.clients.lock() {
+            for client in clients.drain(..) {
+                let _ = client.stream.shutdown(Shutdown::Both);
+            }
+        }
+        if let Some(handle) = self.listener.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for SerialServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn accept_loop(listener: TcpListener, shared: Arc<Shared>) {
+    while !shared.stop.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                let client_shared = Arc::clone(&shared);
+                thread::spawn(move || serve_client(stream, peer, client_shared));
+            }
+            Err(_) => thread::sleep(ACCEPT_TICK),
+        }
+    }
+}
+
+fn serve_client(mut stream: TcpStream, peer: SocketAddr, shared: Arc<Shared>) {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_nodelay(true);
+    let _ = stream.set_read_timeout(Some(READ_TICK));
+
+    let mut pending = Vec::new();
+    if let Some(token) = &shared.settings.token {
+        match read_token_line(&mut stream, &shared.stop) {
+            Some((line, rest)) if line == *token => pending = rest,
+            _ => {
+                let _ = shared.messages.send(ServerMessage::Rejected {
+                    peer,
+                    reason: "invalid token".to_string(),
+                });
+                let _ = stream.shutdown(Shutdown::Both);
+                return;
+            }
+        }
+    }
+
+    let Ok(writer_stream) = stream.try_clone() else {
+        return;
+    };
+    let Ok(control_stream) = stream.try_clone() else {
+        return;
+    };
+    let (outbound_tx, outbound_rx) = mpsc::channel::<Vec<u8>>();
+    let id = shared.next_id.fetch_add(1, Ordering::SeqCst);
+    let role = {
+        let Ok(mut clients) = shared.clients.lock() else {
+            return;
+        };
+        let role = if clients
+            .iter()
+            .any(|client| client.role == ClientRole::Writer)
+        {
+            ClientRole::Observer
+        } else {
+            ClientRole::Writer
+        };
+        clients.push(ClientHandle {
+            id,
+            peer,
+            role,
+            modem_mask: 0xFF,
+            awaiting: Vec::new(),
+            outbound: outbound_tx.clone(),
+            stream: control_stream,
+        });
+        role
+    };
+    let _ = shared
+        .messages
+        .send(ServerMessage::Connected { peer, role });
+
+    let writer = thread::spawn(move || {
+        let mut stream = writer_stream;
+        while let Ok(chunk) = outbound_rx.recv() {
+            if stream.write_all(&chunk).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut session = ClientSession {
+        id,
+        peer,
+        shared: Arc::clone(&shared),
+        outbound: outbound_tx,
+        decoder: TelnetDecoder::default(),
+        negotiator: Negotiator::default(),
+    };
+    if shared.settings.protocol == ServerProtocol::Rfc2217 {
+        for (command, option) in [
+            (rfc2217::WILL, rfc2217::OPT_BINARY),
+            (rfc2217::DO, rfc2217::OPT_BINARY),
+            (rfc2217::WILL, rfc2217::OPT_SUPPRESS_GO_AHEAD),
+            (rfc2217::DO, rfc2217::OPT_COM_PORT),
+        ] {
+            let offer = session.negotiator.offer(command, option);
+            session.send(offer);
+        }
+    }
+    if !pending.is_empty() {
+        session.handle_input(&pending);
+    }
+
+    let mut buf = [0u8; 4096];
+    while !shared.stop.load(Ordering::SeqCst) {
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => session.handle_input(&buf[..n]),
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(_) => break,
+        }
+    }
+
+    let promoted = shared.clients.lock().ok().and_then(|mut clients| {
+        let was_writer = clients
+            .iter()
+            .any(|client| client.id == id && client.role == ClientRole::Writer);
+        clients.retain(|client| client.id != id);
+        // Clients are kept in connection order, so the first one is the oldest.
+        let next = clients.first_mut().filter(|_| was_writer)?;
+        next.role = ClientRole::Writer;
+        Some(next.peer)
+    });
+    drop(session);
+    let _ = writer.join();
+    let _ = shared.messages.send(ServerMessage::Disconnected { peer });
+    if let Some(peer) = promoted {
+        let _ = shared.messages.send(ServerMessage::Promoted { peer });
+    }
+}
+
+/// The answer to a settings command, reporting the value in effect.
+fn applied_reply(command: ComPortCommand, config: &SerialConfig) -> ComPortCommand {
+    match command {
+        ComPortCommand::SetBaudRate(_) => ComPortCommand::SetBaudRate(config.baud_rate),
+        ComPortCommand::SetDataSize(_) => ComPortCommand::SetDataSize(config.data_bits),
+        ComPortCommand::SetParity(_) => {
+            ComPortCommand::SetParity(rfc2217::parity_code(config.parity))
+        }
+        ComPortCommand::SetStopSize(_) => {
+            ComPortCommand::SetStopSize(rfc2217::stop_bits_code(config.stop_bits))
+        }
+        ComPortCommand::SetControl(_) => {
+            ComPortCommand::SetControl(rfc2217::flow_control_code(config.flow_control))
+        }
+        other => other,
+    }
+}
+
+/// Reads the authentication line, returning it with any bytes that followed.
+fn read_token_line(stream: &mut TcpStream, stop: &AtomicBool) -> Option<(String, Vec<u8>)> {
+    let started = Instant::now();
+    let mut line = Vec::new();
+    let mut buf = [0u8; 64];
+    while started.elapsed() < TOKEN_TIMEOUT && !stop.load(Ordering::SeqCst) {
+        match stream.read(&mut buf) {
+            Ok(0) => return None,
+            Ok(n) => {
+                line.extend_from_slice(&buf[..n]);
+                if let Some(end) = line.iter().position(|&byte| byte == b'\n') {
+                    let rest = line.split_off(end + 1);
+                    let text = String::from_utf8_lossy(&line).trim().to_string();
+                    return Some((text, rest));
+                }
+                if line.len() > MAX_TOKEN_LINE {
+                    return None;
+                }
+            }
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+struct ClientSession {
+    id: u64,
+    peer: SocketAddr,
+    shared: Arc<Shared>,
+    outbound: Sender<Vec<u8>>,
+    decoder: TelnetDecoder,
+    negotiator: Negotiator,
+}
+
+impl ClientSession {
+    fn send(&self, bytes: Vec<u8>) {
+        let _ = self.outbound.send(bytes);
+    }
+
+    /// The role can change while connected, when the client is promoted.
+    fn role(&self) -> ClientRole {
+        self.shared
+            .clients
+            .lock()
+            .ok()
+            .and_then(|clients| {
+                clients
+                    .iter()
+                    .find(|client| client.id == self.id)
+                    .map(|client| client.role)
+            })
+            .unwrap_or(ClientRole::Observer)
+    }
+
+    fn request(&self, request: ServerRequest) {
+        let _ = self.shared.messages.send(ServerMessage::Request {
+            peer: self.peer,
+            request,
+        });
+    }
+
+    fn handle_input(&mut self, input: &[u8]) {
+        if self.shared.settings.protocol == ServerProtocol::Raw {
+            self.handle_data(input.to_vec());
+            return;
+        }
+        for event in self.decoder.feed(input) {
+            match event {
+                TelnetEvent::Data(data) => self.handle_data(data),
+                TelnetEvent::Negotiate { command, option } => {
+                    self.handle_negotiation(command, option)
+                }
+                TelnetEvent::Subnegotiation { option, payload }
+                    if option == rfc2217::OPT_COM_PORT =>
+                {
+                    if let Some((command, false)) = ComPortCommand::parse(&payload) {
+                        self.handle_com_port(command);
+                    }
+                }
+                TelnetEvent::Subnegotiation { .. } => {}
+            }
+        }
+    }
+
+    fn handle_data(&self, data: Vec<u8>) {
+        // Observers are read-only: their keystrokes never reach the device.
+        if !data.is_empty() && self.role() == ClientRole::Writer {
+            self.request(ServerRequest::Write(data));
+        }
+    }
+
+    fn handle_negotiation(&mut self, command: u8, option: u8) {
+        if let Some(reply) = self.negotiator.answer(command, option) {
+            self.send(reply);
+        }
+    }
+
+    fn handle_com_port(&self, command: ComPortCommand) {
+        let writer = self.role() == ClientRole::Writer;
+        let Ok(mut config) = self.shared.config.lock().map(|guard| guard.clone()) else {
+            return;
+        };
+        let mut changed = false;
+        let reply = match command {
+            ComPortCommand::SetBaudRate(baud) => {
+                if baud != 0 && baud != config.baud_rate && writer {
+                    config.baud_rate = baud;
+                    changed = true;
+                }
+                ComPortCommand::SetBaudRate(config.baud_rate)
+            }
+            ComPortCommand::SetDataSize(bits) => {
+                if (5..=8).contains(&bits) && bits != config.data_bits && writer {
+                    config.data_bits = bits;
+                    changed = true;
+                }
+                ComPortCommand::SetDataSize(config.data_bits)
+            }
+            ComPortCommand::SetParity(code) => {
+                if let Some(parity) = rfc2217::parity_from_code(code)
+                    && parity != config.parity
+                    && writer
+                {
+                    config.parity = parity;
+                    changed = true;
+                }
+                ComPortCommand::SetParity(rfc2217::parity_code(config.parity))
+            }
+            ComPortCommand::SetStopSize(code) => {
+                if let Some(stop_bits) = rfc2217::stop_bits_from_code(code)
+                    && stop_bits != config.stop_bits
+                    && writer
+                {
+                    config.stop_bits = stop_bits;
+                    changed = true;
+                }
+                ComPortCommand::SetStopSize(rfc2217::stop_bits_code(config.stop_bits))
+            }
+            ComPortCommand::SetControl(value) => {
+                let reply = self.handle_control(value, writer, &mut config, &mut changed);
+                ComPortCommand::SetControl(reply)
+            }
+            ComPortCommand::SetLineStateMask(mask) => ComPortCommand::SetLineStateMask(mask),
+            ComPortCommand::SetModemStateMask(mask) => {
+                self.set_modem_mask(mask);
+                ComPortCommand::SetModemStateMask(mask)
+            }
+            ComPortCommand::PurgeData(value) => ComPortCommand::PurgeData(value),
+            ComPortCommand::FlowControlSuspend
+            | ComPortCommand::FlowControlResume
+            | ComPortCommand::NotifyLineState(_)
+            | ComPortCommand::NotifyModemState(_) => return,
+        };
+        // Settings already in effect are answered right away.
+        if changed {
+            // Answered from update_config with what the owner managed to apply.
+            if let Ok(mut clients) = self.shared.clients.lock()
+                && let Some(client) = clients.iter_mut().find(|client| client.id == self.id)
+            {
+                client.awaiting.push(command);
+            }
+            if let Ok(mut guard) = self.shared.config.lock() {
+                *guard = config.clone();
+            }
+            self.request(ServerRequest::Configure(config));
+            return;
+        }
+        self.send(reply.encode(true));
+        if let ComPortCommand::SetModemStateMask(mask) = reply {
+            self.notify_modem_state(mask);
+        }
+    }
+
+    fn set_modem_mask(&self, mask: u8) {
+        if let Ok(mut clients) = self.shared.clients.lock()
+            && let Some(client) = clients.iter_mut().find(|client| client.id == self.id)
+        {
+            client.modem_mask = mask;
+        }
+    }
+
+    /// Reports the current input lines covered by `mask`.
+    fn notify_modem_state(&self, mask: u8) {
+        let Ok(state) = self.shared.lines.lock() else {
+            return;
+        };
+        let current = rfc2217::modem_state(state.lines, state.lines) & mask;
+        self.send(ComPortCommand::NotifyModemState(current).encode(true));
+    }
+
+    fn handle_control(
+        &self,
+        value: u8,
+        writer: bool,
+        config: &mut SerialConfig,
+        changed: &mut bool,
+    ) -> u8 {
+        let Ok(mut state) = self.shared.lines.lock() else {
+            return value;
+        };
+        let on_off = |state: bool, on: u8, off: u8| if state { on } else { off };
+        match value {
+            rfc2217::CONTROL_FLOW_REQUEST => rfc2217::flow_control_code(config.flow_control),
+            rfc2217::CONTROL_FLOW_NONE
+            | rfc2217::CONTROL_FLOW_XON_XOFF
+            | rfc2217::CONTROL_FLOW_HARDWARE => {
+                if let Some(flow) = rfc2217::flow_control_from_code(value)
+                    && flow != config.flow_control
+                    && writer
+                {
+                    config.flow_control = flow;
+                    *changed = true;
+                }
+                rfc2217::flow_control_code(config.flow_control)
+            }
+            rfc2217::CONTROL_BREAK_REQUEST => on_off(
+                state.brk,
+                rfc2217::CONTROL_BREAK_ON,
+                rfc2217::CONTROL_BREAK_OFF,
+            ),
+            rfc2217::CONTROL_BREAK_ON | rfc2217::CONTROL_BREAK_OFF => {
+                if writer {
+                    state.brk = value == rfc2217::CONTROL_BREAK_ON;
+                    self.request(ServerRequest::SetBreak(state.brk));
+                }
+                on_off(
+                    state.brk,
+                    rfc2217::CONTROL_BREAK_ON,
+                    rfc2217::CONTROL_BREAK_OFF,
+                )
+            }
+            rfc2217::CONTROL_DTR_REQUEST => on_off(
+                state.lines.dtr,
+                rfc2217::CONTROL_DTR_ON,
+                rfc2217::CONTROL_DTR_OFF,
+            ),
+            rfc2217::CONTROL_DTR_ON | rfc2217::CONTROL_DTR_OFF => {
+                if writer {
+                    state.lines.dtr = value == rfc2217::CONTROL_DTR_ON;
+                    self.request(ServerRequest::SetDtr(state.lines.dtr));
+                }
+                on_off(
+                    state.lines.dtr,
+                    rfc2217::CONTROL_DTR_ON,
+                    rfc2217::CONTROL_DTR_OFF,
+                )
+            }
+            rfc2217::CONTROL_RTS_REQUEST => on_off(
+                state.lines.rts,
+                rfc2217::CONTROL_RTS_ON,
+                rfc2217::CONTROL_RTS_OFF,
+            ),
+            rfc2217::CONTROL_RTS_ON | rfc2217::CONTROL_RTS_OFF => {
+                if writer {
+                    state.lines.rts = value == rfc2217::CONTROL_RTS_ON;
+                    self.request(ServerRequest::SetRts(state.lines.rts));
+                }
+                on_off(
+                    state.lines.rts,
+                    rfc2217::CONTROL_RTS_ON,
+                    rfc2217::CONTROL_RTS_OFF,
+                )
+            }
+            other => other,
+        }
+    }
+}
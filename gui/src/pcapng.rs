@@ -0,0 +1,648 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+use thiserror::Error;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::core::SerialConfig;
+use crate::recorder::{self, RecordPayload, RecordingError, RecordingHeader};
+use crate::send_panel;
+
+const BLOCK_SHB: u32 = 0x0A0D_0D0A;
+const BLOCK_IDB: u32 = 0x0000_0001;
+const BLOCK_EPB: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const OPT_SHB_OS: u16 = 3;
+const OPT_SHB_USERAPPL: u16 = 4;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_DESCRIPTION: u16 = 3;
+const OPT_IF_TSRESOL: u16 = 9;
+const OPT_EPB_FLAGS: u16 = 2;
+
+/// `LINKTYPE_USER0`; map it to a dissector under Wireshark's DLT_USER preferences.
+pub const LINKTYPE_USER0: u16 = 147;
+/// Nanosecond timestamps (`if_tsresol` = 10^-9).
+const TSRESOL_NANOS: u8 = 9;
+const SNAPLEN: u32 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PacketDirection {
+    Inbound,
+    Outbound,
+}
+
+impl PacketDirection {
+    fn flags(self) -> u32 {
+        match self {
+            PacketDirection::Inbound => 0b01,
+            PacketDirection::Outbound => 0b10,
+        }
+    }
+
+    #[cfg(test)]
+    fn from_flags(flags: u32) -> Option<Self> {
+        match flags & 0b11 {
+            0b01 => Some(PacketDirection::Inbound),
+            0b10 => Some(PacketDirection::Outbound),
+            _ => None,
+        }
+    }
+}
+
+/// How read/write chunks are grouped into packets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PacketFraming {
+    /// Every chunk becomes its own packet.
+    Chunk,
+    /// A packet ends after each occurrence of the delimiter (kept in the packet).
+    Delimiter(Vec<u8>),
+    /// A packet ends when the line stays quiet for longer than the gap.
+    IdleGap(Duration),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum FramingKind {
+    #[strum(to_string = "Raw chunk")]
+    Chunk,
+    #[strum(to_string = "Delimiter")]
+    Delimiter,
+    #[strum(to_string = "Idle gap")]
+    IdleGap,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PcapSettings {
+    pub framing: FramingKind,
+    /// Delimiter as hex bytes, e.g. `0A` or `0D 0A`.
+    pub delimiter_hex: String,
+    pub idle_gap_ms: u64,
+}
+
+impl Default for PcapSettings {
+    fn default() -> Self {
+        Self {
+            framing: FramingKind::Chunk,
+            delimiter_hex: "0A".to_string(),
+            idle_gap_ms: 20,
+        }
+    }
+}
+
+impl PcapSettings {
+    pub fn framing(&self) -> Option<PacketFraming> {
+        Some(match self.framing {
+            FramingKind::Chunk => PacketFraming::Chunk,
+            FramingKind::Delimiter => {
+                let delimiter = send_panel::parse_hex(&self.delimiter_hex).ok()?;
+                if delimiter.is_empty() {
+                    return None;
+                }
+                PacketFraming::Delimiter(delimiter)
+            }
+            FramingKind::IdleGap => PacketFraming::IdleGap(Duration::from_millis(self.idle_gap_ms)),
+        })
+    }
+}
+
+/// Metadata stored in an interface description block.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InterfaceInfo {
+    pub name: String,
+    pub description: String,
+}
+
+impl InterfaceInfo {
+    pub fn for_port(port: &str, config: &SerialConfig, extra: Option<&str>) -> Self {
+        let mut description = describe_config(config);
+        if let Some(extra) = extra {
+            description.push_str(", ");
+            description.push_str(extra);
+        }
+        Self {
+            name: port.to_string(),
+            description,
+        }
+    }
+}
+
+pub fn describe_config(config: &SerialConfig) -> String {
+    format!(
+        "{} bps, {} data bits, parity {}, {} stop bits, flow {}",
+        config.baud_rate, config.data_bits, config.parity, config.stop_bits, config.flow_control
+    )
+}
+
+#[derive(Debug, Default)]
+struct PendingPacket {
+    timestamp: u64,
+    last_chunk: u64,
+    data: Vec<u8>,
+}
+
+/// Groups chunks of one interface and direction into packets.
+#[derive(Debug)]
+struct Framer {
+    framing: PacketFraming,
+    pending: Option<PendingPacket>,
+}
+
+impl Framer {
+    fn push(&mut self, timestamp: u64, data: &[u8]) -> Vec<(u64, Vec<u8>)> {
+        let mut packets = Vec::new();
+        match &self.framing {
+            PacketFraming::Chunk => packets.push((timestamp, data.to_vec())),
+            PacketFraming::IdleGap(gap) => {
+                let gap = gap.as_nanos() as u64;
+                if let Some(pending) = self
+                    .pending
+                    .take_if(|pending| timestamp.saturating_sub(pending.last_chunk) > gap)
+                {
+                    packets.push((pending.timestamp, pending.data));
+                }
+                let pending = self.pending.get_or_insert_with(|| PendingPacket {
+                    timestamp,
+                    ..PendingPacket::default()
+                });
+                pending.last_chunk = timestamp;
+                pending.data.extend_from_slice(data);
+            }
+            PacketFraming::Delimiter(delimiter) => {
+                let delimiter = delimiter.clone();
+                for &byte in data {
+                    let pending = self.pending.get_or_insert_with(|| PendingPacket {
+                        timestamp,
+                        ..PendingPacket::default()
+                    });
+                    pending.data.push(byte);
+                    if pending.data.ends_with(&delimiter) {
+                        let done = self.pending.take().expect("pending packet");
+                        packets.push((done.timestamp, done.data));
+                    }
+                }
+            }
+        }
+        packets
+    }
+
+    /// Emits an idle-gap packet once `now` is past the gap; used by live capture.
+    fn expire(&mut self, now: u64) -> Option<(u64, Vec<u8>)> {
+        let PacketFraming::IdleGap(gap) = self.framing else {
+            return None;
+        };
+        let gap = gap.as_nanos() as u64;
+        self.pending
+            .take_if(|pending| now.saturating_sub(pending.last_chunk) > gap)
+            .map(|pending| (pending.timestamp, pending.data))
+    }
+
+    fn flush(&mut self) -> Option<(u64, Vec<u8>)> {
+        self.pending
+            .take()
+            .map(|pending| (pending.timestamp, pending.data))
+    }
+}
+
+/// Streaming pcapng writer with one interface per serial port.
+///
+/// Timestamps are nanoseconds since the Unix epoch. Packets carry the
+/// `epb_flags` direction bits so Wireshark can filter on
+/// `frame.packet_flags_direction`.
+pub struct PcapngWriter<W: Write> {
+    out: W,
+    framers: Vec<[Framer; 2]>,
+    pending_comment: Vec<Option<String>>,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        let mut options = Vec::new();
+        push_option(&mut options, OPT_SHB_OS, std::env::consts::OS.as_bytes());
+        push_option(
+            &mut options,
+            OPT_SHB_USERAPPL,
+            format!("MicroSerial {}", env!("CARGO_PKG_VERSION")).as_bytes(),
+        );
+        finish_options(&mut options);
+        body.extend(options);
+        write_block(&mut out, BLOCK_SHB, &body)?;
+        Ok(Self {
+            out,
+            framers: Vec::new(),
+            pending_comment: Vec::new(),
+        })
+    }
+
+    /// Adds an interface and returns its id for [`PcapngWriter::write_chunk`].
+    pub fn add_interface(
+        &mut self,
+        info: &InterfaceInfo,
+        framing: PacketFraming,
+    ) -> io::Result<u32> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&LINKTYPE_USER0.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&SNAPLEN.to_le_bytes());
+        let mut options = Vec::new();
+        push_option(&mut options, OPT_IF_NAME, info.name.as_bytes());
+        push_option(
+            &mut options,
+            OPT_IF_DESCRIPTION,
+            info.description.as_bytes(),
+        );
+        push_option(&mut options, OPT_IF_TSRESOL, &[TSRESOL_NANOS]);
+        finish_options(&mut options);
+        body.extend(options);
+        write_block(&mut self.out, BLOCK_IDB, &body)?;
+
+        let framer = || Framer {
+            framing: framing.clone(),
+            pending: None,
+        };
+        self.framers.push([framer(), framer()]);
+        self.pending_comment.push(None);
+        Ok((self.framers.len() - 1) as u32)
+    }
+
+    /// Attaches a comment to the next packet written on `interface`.
+    pub fn annotate(&mut self, interface: u32, comment: impl Into<String>) {
+        if let Some(slot) = self.pending_comment.get_mut(interface as usize) {
+            *slot = Some(comment.into());
+        }
+    }
+
+    pub fn write_chunk(
+        &mut self,
+        interface: u32,
+        direction: PacketDirection,
+        timestamp: u64,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let Some(framers) = self.framers.get_mut(interface as usize) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unknown interface",
+            ));
+        };
+        let packets = framers[direction_index(direction)].push(timestamp, data);
+        for (timestamp, packet) in packets {
+            self.write_packet(interface, direction, timestamp, &packet)?;
+        }
+        Ok(())
+    }
+
+    /// Closes idle-gap packets whose gap has elapsed by `now`.
+    pub fn expire(&mut self, now: u64) -> io::Result<()> {
+        let mut ready = Vec::new();
+        for (interface, framers) in self.framers.iter_mut().enumerate() {
+            for direction in [PacketDirection::Inbound, PacketDirection::Outbound] {
+                if let Some(packet) = framers[direction_index(direction)].expire(now) {
+                    ready.push((interface as u32, direction, packet));
+                }
+            }
+        }
+        for (interface, direction, (timestamp, packet)) in ready {
+            self.write_packet(interface, direction, timestamp, &packet)?;
+        }
+        self.out.flush()
+    }
+
+    /// Emits partially framed packets and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut ready = Vec::new();
+        for (interface, framers) in self.framers.iter_mut().enumerate() {
+            for direction in [PacketDirection::Inbound, PacketDirection::Outbound] {
+                if let Some(packet) = framers[direction_index(direction)].flush() {
+                    ready.push((interface as u32, direction, packet));
+                }
+            }
+        }
+        ready.sort_by_key(|(_, _, (timestamp, _))| *timestamp);
+        for (interface, direction, (timestamp, packet)) in ready {
+            self.write_packet(interface, direction, timestamp, &packet)?;
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn write_packet(
+        &mut self,
+        interface: u32,
+        direction: PacketDirection,
+        timestamp: u64,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let mut body = Vec::with_capacity(20 + data.len() + 16);
+        body.extend_from_slice(&interface.to_le_bytes());
+        body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(data);
+        pad(&mut body);
+        let mut options = Vec::new();
+        push_option(
+            &mut options,
+            OPT_EPB_FLAGS,
+            &direction.flags().to_le_bytes(),
+        );
+        if let Some(comment) = self
+            .pending_comment
+            .get_mut(interface as usize)
+            .and_then(Option::take)
+        {
+            push_option(&mut options, OPT_COMMENT, comment.as_bytes());
+        }
+        finish_options(&mut options);
+        body.extend(options);
+        write_block(&mut self.out, BLOCK_EPB, &body)
+    }
+}
+
+fn direction_index(direction: PacketDirection) -> usize {
+    match direction {
+        PacketDirection::Inbound => 0,
+        PacketDirection::Outbound => 1,
+    }
+}
+
+fn pad(buf: &mut Vec<u8>) {
+    while !buf.len().is_multiple_of(4) {
+        buf.push(0);
+    }
+}
+
+fn push_option(options: &mut Vec<u8>, code: u16, value: &[u8]) {
+    options.extend_from_slice(&code.to_le_bytes());
+    options.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    options.extend_from_slice(value);
+    pad(options);
+}
+
+fn finish_options(options: &mut Vec<u8>) {
+    options.extend_from_slice(&OPT_END.to_le_bytes());
+    options.extend_from_slice(&0u16.to_le_bytes());
+}
+
+fn write_block(out: &mut impl Write, block_type: u32, body: &[u8]) -> io::Result<()> {
+    let total = (12 + body.len()) as u32;
+    let mut block = Vec::with_capacity(total as usize);
+    block.extend_from_slice(&block_type.to_le_bytes());
+    block.extend_from_slice(&total.to_le_bytes());
+    block.extend_from_slice(body);
+    block.extend_from_slice(&total.to_le_bytes());
+    out.write_all(&block)
+}
+
+pub fn unix_nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// A pcapng file written while a session or bridge is running.
+pub struct LiveCapture {
+    writer: PcapngWriter<BufWriter<File>>,
+    path: PathBuf,
+    chunks: u64,
+}
+
+impl LiveCapture {
+    /// Creates the file with one interface per entry in `interfaces`, in order.
+    pub fn create(
+        path: impl AsRef<Path>,
+        framing: PacketFraming,
+        interfaces: &[InterfaceInfo],
+    ) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut writer = PcapngWriter::new(BufWriter::new(File::create(&path)?))?;
+        for info in interfaces {
+            writer.add_interface(info, framing.clone())?;
+        }
+        Ok(Self {
+            writer,
+            path,
+            chunks: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn chunks(&self) -> u64 {
+        self.chunks
+    }
+
+    pub fn chunk(
+        &mut self,
+        interface: u32,
+        direction: PacketDirection,
+        data: &[u8],
+    ) -> io::Result<()> {
+        self.chunks += 1;
+        let now = unix_nanos(SystemTime::now());
+        self.writer.write_chunk(interface, direction, now, data)
+    }
+
+    /// Closes idle packets and flushes; call once per frame.
+    pub fn tick(&mut self) -> io::Result<()> {
+        self.writer.expire(unix_nanos(SystemTime::now()))
+    }
+
+    pub fn finish(self) -> io::Result<PathBuf> {
+        self.writer.finish()?;
+        Ok(self.path)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error(transparent)]
+    Recording(#[from] RecordingError),
+    #[error("pcapng write failed: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Converts a `.msrec` recording to pcapng, returning the number of chunks exported.
+///
+/// Configuration changes inside the recording are attached as comments to
+/// the next packet.
+pub fn export_recording(
+    recording: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    framing: PacketFraming,
+) -> Result<usize, ExportError> {
+    let (header, records) = recorder::load(recording)?;
+    let start = recording_start(&header);
+    let mut writer = PcapngWriter::new(BufWriter::new(File::create(output)?))?;
+    let interface = writer.add_interface(&interface_for_recording(&header), framing)?;
+    let mut chunks = 0;
+    for record in records {
+        let timestamp = start + record.timestamp.as_nanos() as u64;
+        match record.payload {
+            RecordPayload::Rx(data) => {
+                writer.write_chunk(interface, PacketDirection::Inbound, timestamp, &data)?;
+                chunks += 1;
+            }
+            RecordPayload::Tx(data) => {
+                writer.write_chunk(interface, PacketDirection::Outbound, timestamp, &data)?;
+                chunks += 1;
+            }
+            RecordPayload::Config(config) if config != header.config => {
+                writer.annotate(interface, format!("config: {}", describe_config(&config)));
+            }
+            _ => {}
+        }
+    }
+    writer.finish()?;
+    Ok(chunks)
+}
+
+fn recording_start(header: &RecordingHeader) -> u64 {
+    OffsetDateTime::parse(&header.started, &Rfc3339)
+        .map(|started| started.unix_timestamp_nanos().max(0) as u64)
+        .unwrap_or(0)
+}
+
+fn interface_for_recording(header: &RecordingHeader) -> InterfaceInfo {
+    let usb = header.usb.as_ref().map(|usb| {
+        let mut text = format!("USB {:04x}:{:04x}", usb.vendor_id, usb.product_id);
+        if let Some(serial) = &usb.serial {
+            text.push_str(&format!(" serial {serial}"));
+        }
+        text
+    });
+    InterfaceInfo::for_port(&header.port, &header.config, usb.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use pcap_file::DataLink;
+    use pcap_file::pcapng::PcapNgReader;
+    use pcap_file::pcapng::blocks::Block;
+    use pcap_file::pcapng::blocks::enhanced_packet::{EnhancedPacketBlock, EnhancedPacketOption};
+    use pcap_file::pcapng::blocks::interface_description::{
+        InterfaceDescriptionBlock, InterfaceDescriptionOption,
+    };
+
+    use super::*;
+
+    /// The writer's output as parsed by an independent pcapng implementation.
+    #[derive(Default)]
+    struct Capture {
+        interfaces: Vec<InterfaceDescriptionBlock<'static>>,
+        packets: Vec<EnhancedPacketBlock<'static>>,
+    }
+
+    fn roundtrip(framing: PacketFraming, chunks: &[(u64, PacketDirection, &[u8])]) -> Capture {
+        let mut writer = PcapngWriter::new(Vec::new()).expect("writer");
+        let info = InterfaceInfo::for_port("/dev/ttyUSB0", &SerialConfig::default(), None);
+        let interface = writer.add_interface(&info, framing).expect("interface");
+        for (timestamp, direction, data) in chunks {
+            writer
+                .write_chunk(interface, *direction, *timestamp, data)
+                .expect("chunk");
+        }
+        let bytes = writer.finish().expect("finish");
+        let mut reader = PcapNgReader::new(bytes.as_slice()).expect("reader");
+        let mut capture = Capture::default();
+        while let Some(block) = reader.next_block() {
+            match block.expect("block").into_owned() {
+                Block::InterfaceDescription(block) => capture.interfaces.push(block),
+                Block::EnhancedPacket(block) => capture.packets.push(block),
+                _ => {}
+            }
+        }
+        capture
+    }
+
+    fn payloads(capture: &Capture) -> Vec<&[u8]> {
+        capture.packets.iter().map(|p| p.data.as_ref()).collect()
+    }
+
+    fn direction(packet: &EnhancedPacketBlock) -> Option<PacketDirection> {
+        packet.options.iter().find_map(|option| match option {
+            EnhancedPacketOption::Flags(flags) => PacketDirection::from_flags(*flags),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn chunks_keep_direction_and_interface_metadata() {
+        let capture = roundtrip(
+            PacketFraming::Chunk,
+            &[
+                (1_000, PacketDirection::Outbound, b"AT\r"),
+                (2_000, PacketDirection::Inbound, b"OK\r\n"),
+            ],
+        );
+        assert_eq!(capture.interfaces.len(), 1);
+        let interface = &capture.interfaces[0];
+        assert_eq!(interface.linktype, DataLink::USER0);
+        let options = &interface.options;
+        assert!(options.contains(&InterfaceDescriptionOption::IfName("/dev/ttyUSB0".into())));
+        assert!(options.iter().any(|option| matches!(
+            option,
+            InterfaceDescriptionOption::IfDescription(text) if text.contains("115200 bps")
+        )));
+        assert!(options.contains(&InterfaceDescriptionOption::IfTsResol(TSRESOL_NANOS)));
+        assert_eq!(payloads(&capture), vec![&b"AT\r"[..], &b"OK\r\n"[..]]);
+        assert_eq!(
+            direction(&capture.packets[0]),
+            Some(PacketDirection::Outbound)
+        );
+        assert_eq!(
+            direction(&capture.packets[1]),
+            Some(PacketDirection::Inbound)
+        );
+        assert_eq!(capture.packets[1].timestamp, Duration::from_nanos(2_000));
+    }
+
+    #[test]
+    fn delimiter_framing_splits_across_chunks() {
+        let capture = roundtrip(
+            PacketFraming::Delimiter(b"\r\n".to_vec()),
+            &[
+                (10, PacketDirection::Inbound, b"li"),
+                (20, PacketDirection::Inbound, b"ne1\r"),
+                (30, PacketDirection::Inbound, b"\nline2\r\nta"),
+            ],
+        );
+        assert_eq!(
+            payloads(&capture),
+            vec![&b"line1\r\n"[..], &b"line2\r\n"[..], &b"ta"[..]]
+        );
+        assert_eq!(capture.packets[0].timestamp, Duration::from_nanos(10));
+        assert_eq!(capture.packets[1].timestamp, Duration::from_nanos(30));
+    }
+
+    #[test]
+    fn idle_gap_groups_bursts() {
+        let ms = 1_000_000;
+        let capture = roundtrip(
+            PacketFraming::IdleGap(Duration::from_millis(5)),
+            &[
+                (0, PacketDirection::Inbound, b"ab"),
+                (2 * ms, PacketDirection::Inbound, b"cd"),
+                (20 * ms, PacketDirection::Inbound, b"ef"),
+            ],
+        );
+        assert_eq!(payloads(&capture), vec![&b"abcd"[..], &b"ef"[..]]);
+    }
+}
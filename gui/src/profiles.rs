@@ -1,10 +1,29 @@
 use crate::core::SerialConfig;
+use crate::macros::Macro;
+use crate::scheduler::ScheduledItem;
+use crate::send_panel::{HistoryEntry, TextOptions};
+use crate::triggers::TriggerRule;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SerialProfile {
     pub name: String,
     pub config: SerialConfig,
+    /// Buttons of the macro bar while this profile is active.
+    #[serde(default)]
+    pub macros: Vec<Macro>,
+    /// Items of the Scheduled list while this profile is active.
+    #[serde(default)]
+    pub schedules: Vec<ScheduledItem>,
+    /// Trigger rules evaluated on received data while this profile is active.
+    #[serde(default)]
+    pub triggers: Vec<TriggerRule>,
+    /// Line ending, escapes and encoding of text sends.
+    #[serde(default)]
+    pub send: TextOptions,
+    /// Send history and favorites, newest first.
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
 }
 
 impl SerialProfile {
@@ -12,6 +31,11 @@ impl SerialProfile {
         Self {
             name: name.into(),
             config,
+            macros: Vec::new(),
+            schedules: Vec::new(),
+            triggers: Vec::new(),
+            send: TextOptions::default(),
+            history: Vec::new(),
         }
     }
 }
@@ -38,6 +62,13 @@ impl ProfileStore {
         }
     }
 
+    pub fn get_active_mut(&mut self) -> Option<&mut SerialProfile> {
+        let name = self.active.as_deref()?;
+        self.profiles
+            .iter_mut()
+            .find(|profile| profile.name == name)
+    }
+
     pub fn set_active(&mut self, name: &str) {
         if self.profiles.iter().any(|profile| profile.name == name) {
             self.active = Some(name.to_string());
@@ -56,6 +87,18 @@ impl ProfileStore {
         }
     }
 
+    /// Stores `config` under `name`, keeping the rest of the profile.
+    pub fn save_config(&mut self, name: &str, config: SerialConfig) {
+        match self
+            .profiles
+            .iter_mut()
+            .find(|profile| profile.name == name)
+        {
+            Some(profile) => profile.config = config,
+            None => self.profiles.push(SerialProfile::new(name, config)),
+        }
+    }
+
     pub fn delete(&mut self, name: &str) {
         self.profiles.retain(|profile| profile.name != name);
         if self.active.as_deref() == Some(name) {
@@ -0,0 +1,552 @@
+use std::fs::File;
+use std::io::{self, BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::core::{ModemLines, SerialConfig};
+use crate::usb::UsbIdentity;
+
+/// First bytes of every recording; see `docs/recording_format.md`.
+pub const MAGIC: &[u8; 8] = b"MSREC\r\n\x1a";
+pub const FORMAT_VERSION: u16 = 1;
+pub const FILE_EXTENSION: &str = "msrec";
+
+const KIND_RX: u8 = 1;
+const KIND_TX: u8 = 2;
+const KIND_EVENT: u8 = 3;
+const KIND_CONFIG: u8 = 4;
+const KIND_MODEM_LINES: u8 = 5;
+
+const RECORD_HEADER_LEN: usize = 1 + 8 + 4;
+const MAX_HEADER_LEN: u32 = 1 << 20;
+/// Longer data is split across records; a longer length field means corruption.
+const MAX_RECORD_LEN: usize = 1 << 24;
+
+const LINE_DTR: u8 = 1 << 0;
+const LINE_RTS: u8 = 1 << 1;
+const LINE_CTS: u8 = 1 << 2;
+const LINE_DSR: u8 = 1 << 3;
+const LINE_RI: u8 = 1 << 4;
+const LINE_CD: u8 = 1 << 5;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordingSettings {
+    /// Directory new recordings are created in.
+    pub directory: String,
+}
+
+impl Default for RecordingSettings {
+    fn default() -> Self {
+        let directory = ProjectDirs::from("dev", "MicroSerial", "MicroSerial")
+            .map(|dirs| dirs.data_dir().join("recordings"))
+            .unwrap_or_else(|| PathBuf::from("recordings"));
+        Self {
+            directory: directory.display().to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostInfo {
+    pub hostname: String,
+    pub os: String,
+    pub arch: String,
+    pub app_version: String,
+}
+
+impl HostInfo {
+    pub fn current() -> Self {
+        Self {
+            hostname: hostname(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn hostname() -> String {
+    let mut name = [0u8; 256];
+    let rc = unsafe { libc::gethostname(name.as_mut_ptr().cast(), name.len()) };
+    if rc != 0 {
+        return String::new();
+    }
+    let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    String::from_utf8_lossy(&name[..end]).into_owned()
+}
+
+/// Windows publishes the machine name in the environment of every process.
+#[cfg(not(unix))]
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_default()
+}
+
+/// JSON metadata stored once at the start of a recording.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    /// Wall-clock start time (RFC 3339); record timestamps are offsets from it.
+    pub started: String,
+    pub port: String,
+    pub usb: Option<UsbIdentity>,
+    pub profile: Option<String>,
+    pub host: HostInfo,
+    pub config: SerialConfig,
+}
+
+impl RecordingHeader {
+    /// Describes a recording of `port` starting now on this machine.
+    pub fn new(port: &str, config: &SerialConfig, profile: Option<String>) -> Self {
+        Self {
+            started: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+            port: port.to_string(),
+            usb: UsbIdentity::lookup(port),
+            profile,
+            host: HostInfo::current(),
+            config: config.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordPayload {
+    Rx(Vec<u8>),
+    Tx(Vec<u8>),
+    Event { code: i32, message: String },
+    Config(SerialConfig),
+    ModemLines(ModemLines),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    /// Monotonic offset from the start of the recording.
+    pub timestamp: Duration,
+    pub payload: RecordPayload,
+}
+
+#[derive(Debug, Error)]
+pub enum RecordingError {
+    #[error("recording I/O failed: {0}")]
+    Io(#[from] io::Error),
+    #[error("not a MicroSerial recording")]
+    NotARecording,
+    #[error("unsupported recording format version {0}")]
+    UnsupportedVersion(u16),
+    #[error("invalid recording header: {0}")]
+    Header(#[from] serde_json::Error),
+    #[error("corrupt record: {0}")]
+    Corrupt(&'static str),
+}
+
+/// Streams session traffic to a recording file.
+///
+/// Each record is written with a single `write` call and no user-space
+/// buffering, so a crash loses at most the record being written.
+pub struct Recorder {
+    file: File,
+    path: PathBuf,
+    started: Instant,
+    records: u64,
+    bytes: u64,
+}
+
+impl Recorder {
+    pub fn create(
+        path: impl AsRef<Path>,
+        header: &RecordingHeader,
+    ) -> Result<Self, RecordingError> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = File::create(&path)?;
+        let json = serde_json::to_vec(header)?;
+        let mut preamble = Vec::with_capacity(MAGIC.len() + 6 + json.len());
+        preamble.extend_from_slice(MAGIC);
+        preamble.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        preamble.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        preamble.extend_from_slice(&json);
+        file.write_all(&preamble)?;
+        Ok(Self {
+            file,
+            path,
+            started: Instant::now(),
+            records: 0,
+            bytes: preamble.len() as u64,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Size of the file written so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn write(&mut self, payload: &RecordPayload) -> Result<(), RecordingError> {
+        match payload {
+            RecordPayload::Rx(data) if data.len() > MAX_RECORD_LEN => {
+                for chunk in data.chunks(MAX_RECORD_LEN) {
+                    self.write(&RecordPayload::Rx(chunk.to_vec()))?;
+                }
+                return Ok(());
+            }
+            RecordPayload::Tx(data) if data.len() > MAX_RECORD_LEN => {
+                for chunk in data.chunks(MAX_RECORD_LEN) {
+                    self.write(&RecordPayload::Tx(chunk.to_vec()))?;
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        let timestamp = self.started.elapsed();
+        let (kind, body) = encode_payload(payload)?;
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + body.len());
+        record.push(kind);
+        record.extend_from_slice(&(timestamp.as_nanos() as u64).to_le_bytes());
+        record.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        record.extend_from_slice(&body);
+        self.file.write_all(&record)?;
+        self.records += 1;
+        self.bytes += record.len() as u64;
+        Ok(())
+    }
+}
+
+/// Reads a recording record by record.
+///
+/// Iteration stops cleanly at a truncated final record, which is what a
+/// recording cut short by a crash or power loss ends with; check
+/// [`RecordingReader::truncated`] afterwards to tell the two apart. Records of
+/// kinds added by later format revisions are skipped.
+pub struct RecordingReader<R = BufReader<File>> {
+    input: R,
+    header: RecordingHeader,
+    truncated: bool,
+    finished: bool,
+}
+
+impl RecordingReader {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RecordingError> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> RecordingReader<R> {
+    pub fn new(mut input: R) -> Result<Self, RecordingError> {
+        let mut magic = [0u8; 8];
+        read_exact_or(&mut input, &mut magic, RecordingError::NotARecording)?;
+        if &magic != MAGIC {
+            return Err(RecordingError::NotARecording);
+        }
+        let mut fixed = [0u8; 6];
+        read_exact_or(&mut input, &mut fixed, RecordingError::NotARecording)?;
+        let version = u16::from_le_bytes([fixed[0], fixed[1]]);
+        if version != FORMAT_VERSION {
+            return Err(RecordingError::UnsupportedVersion(version));
+        }
+        let header_len = u32::from_le_bytes([fixed[2], fixed[3], fixed[4], fixed[5]]);
+        if header_len > MAX_HEADER_LEN {
+            return Err(RecordingError::Corrupt("header too large"));
+        }
+        let mut json = vec![0u8; header_len as usize];
+        read_exact_or(
+            &mut input,
+            &mut json,
+            RecordingError::Corrupt("truncated header"),
+        )?;
+        let header = serde_json::from_slice(&json)?;
+        Ok(Self {
+            input,
+            header,
+            truncated: false,
+            finished: false,
+        })
+    }
+
+    pub fn header(&self) -> &RecordingHeader {
+        &self.header
+    }
+
+    /// Whether the file ended in the middle of a record.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn read_record(&mut self) -> Result<Option<Record>, RecordingError> {
+        loop {
+            let mut fixed = [0u8; RECORD_HEADER_LEN];
+            match read_full(&mut self.input, &mut fixed)? {
+                0 => return Ok(None),
+                n if n < fixed.len() => {
+                    self.truncated = true;
+                    return Ok(None);
+                }
+                _ => {}
+            }
+            let kind = fixed[0];
+            let nanos = u64::from_le_bytes(fixed[1..9].try_into().expect("8 bytes"));
+            let len = u32::from_le_bytes(fixed[9..13].try_into().expect("4 bytes")) as usize;
+            if len > MAX_RECORD_LEN {
+                return Err(RecordingError::Corrupt("record too large"));
+            }
+            let mut body = Vec::new();
+            (&mut self.input).take(len as u64).read_to_end(&mut body)?;
+            if body.len() < len {
+                self.truncated = true;
+                return Ok(None);
+            }
+            if let Some(payload) = decode_payload(kind, body)? {
+                return Ok(Some(Record {
+                    timestamp: Duration::from_nanos(nanos),
+                    payload,
+                }));
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for RecordingReader<R> {
+    type Item = Result<Record, RecordingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.read_record() {
+            Ok(Some(record)) => Some(Ok(record)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(err) => {
+                self.finished = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Loads a whole recording into memory.
+pub fn load(path: impl AsRef<Path>) -> Result<(RecordingHeader, Vec<Record>), RecordingError> {
+    let mut reader = RecordingReader::open(path)?;
+    let records = reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+    Ok((reader.header, records))
+}
+
+fn encode_payload(payload: &RecordPayload) -> Result<(u8, Vec<u8>), RecordingError> {
+    Ok(match payload {
+        RecordPayload::Rx(data) => (KIND_RX, data.clone()),
+        RecordPayload::Tx(data) => (KIND_TX, data.clone()),
+        RecordPayload::Event { code, message } => {
+            let mut body = code.to_le_bytes().to_vec();
+            body.extend_from_slice(message.as_bytes());
+            (KIND_EVENT, body)
+        }
+        RecordPayload::Config(config) => (KIND_CONFIG, serde_json::to_vec(config)?),
+        RecordPayload::ModemLines(lines) => (KIND_MODEM_LINES, vec![lines_to_bits(lines)]),
+    })
+}
+
+fn decode_payload(kind: u8, body: Vec<u8>) -> Result<Option<RecordPayload>, RecordingError> {
+    Ok(Some(match kind {
+        KIND_RX => RecordPayload::Rx(body),
+        KIND_TX => RecordPayload::Tx(body),
+        KIND_EVENT => {
+            let code = body
+                .get(..4)
+                .ok_or(RecordingError::Corrupt("short event record"))?;
+            RecordPayload::Event {
+                code: i32::from_le_bytes(code.try_into().expect("4 bytes")),
+                message: String::from_utf8_lossy(&body[4..]).into_owned(),
+            }
+        }
+        KIND_CONFIG => RecordPayload::Config(serde_json::from_slice(&body)?),
+        KIND_MODEM_LINES => {
+            let bits = *body
+                .first()
+                .ok_or(RecordingError::Corrupt("empty modem line record"))?;
+            RecordPayload::ModemLines(lines_from_bits(bits))
+        }
+        _ => return Ok(None),
+    }))
+}
+
+fn lines_to_bits(lines: &ModemLines) -> u8 {
+    [
+        (lines.dtr, LINE_DTR),
+        (lines.rts, LINE_RTS),
+        (lines.cts, LINE_CTS),
+        (lines.dsr, LINE_DSR),
+        (lines.ri, LINE_RI),
+        (lines.cd, LINE_CD),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .fold(0, |bits, (_, bit)| bits | bit)
+}
+
+fn lines_from_bits(bits: u8) -> ModemLines {
+    ModemLines {
+        dtr: bits & LINE_DTR != 0,
+        rts: bits & LINE_RTS != 0,
+        cts: bits & LINE_CTS != 0,
+        dsr: bits & LINE_DSR != 0,
+        ri: bits & LINE_RI != 0,
+        cd: bits & LINE_CD != 0,
+    }
+}
+
+/// Reads until `buf` is full or the input ends, returning the bytes read.
+fn read_full(input: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match input.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+fn read_exact_or(
+    input: &mut impl Read,
+    buf: &mut [u8],
+    short: RecordingError,
+) -> Result<(), RecordingError> {
+    if read_full(input, buf)? < buf.len() {
+        return Err(short);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> RecordingHeader {
+        RecordingHeader {
+            started: "2024-01-01T00:00:00Z".to_string(),
+            port: "/dev/ttyUSB0".to_string(),
+            usb: Some(UsbIdentity {
+                vendor_id: 0x0403,
+                product_id: 0x6001,
+                serial: Some("A10K".to_string()),
+                manufacturer: None,
+                product: Some("FT232R".to_string()),
+            }),
+            profile: Some("Default".to_string()),
+            host: HostInfo::current(),
+            config: SerialConfig::default(),
+        }
+    }
+
+    fn write_sample(path: &Path) -> Vec<RecordPayload> {
+        let payloads = vec![
+            RecordPayload::Tx(b"AT\r".to_vec()),
+            RecordPayload::Rx(vec![0x00, 0xFF, b'O', b'K']),
+            RecordPayload::Event {
+                code: -5,
+                message: "read failed".to_string(),
+            },
+            RecordPayload::Config(SerialConfig {
+                baud_rate: 9_600,
+                ..SerialConfig::default()
+            }),
+            RecordPayload::ModemLines(ModemLines {
+                dtr: true,
+                cts: true,
+                ..ModemLines::default()
+            }),
+        ];
+        let mut recorder = Recorder::create(path, &header()).expect("create");
+        for payload in &payloads {
+            recorder.write(payload).expect("write");
+        }
+        assert_eq!(recorder.records(), payloads.len() as u64);
+        payloads
+    }
+
+    #[test]
+    fn roundtrip_preserves_every_record() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("session.msrec");
+        let payloads = write_sample(&path);
+
+        let (loaded_header, records) = load(&path).expect("load");
+        assert_eq!(loaded_header, header());
+        let loaded: Vec<_> = records.iter().map(|r| r.payload.clone()).collect();
+        assert_eq!(loaded, payloads);
+        assert!(
+            records
+                .windows(2)
+                .all(|pair| pair[0].timestamp <= pair[1].timestamp)
+        );
+    }
+
+    #[test]
+    fn truncated_tail_and_unknown_kinds_are_tolerated() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("session.msrec");
+        write_sample(&path);
+        let mut bytes = std::fs::read(&path).expect("read");
+        // A record kind from a future revision, followed by a partial record.
+        bytes.push(0x7F);
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(&[1, 2]);
+        bytes.extend_from_slice(&[KIND_RX, 0, 0]);
+
+        let mut reader = RecordingReader::new(bytes.as_slice()).expect("open");
+        let records: Vec<_> = reader.by_ref().collect::<Result<_, _>>().expect("records");
+        assert_eq!(records.len(), 5);
+        assert!(reader.truncated());
+    }
+
+    #[test]
+    fn oversized_record_length_is_corrupt() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("session.msrec");
+        write_sample(&path);
+        let mut bytes = std::fs::read(&path).expect("read");
+        bytes.push(KIND_RX);
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+
+        let reader = RecordingReader::new(bytes.as_slice()).expect("open");
+        let err = reader
+            .collect::<Result<Vec<_>, _>>()
+            .expect_err("corrupt length");
+        assert!(matches!(err, RecordingError::Corrupt(_)));
+    }
+
+    #[test]
+    fn rejects_foreign_files() {
+        let err = RecordingReader::new(&b"not a recording at all"[..])
+            .err()
+            .expect("error");
+        assert!(matches!(err, RecordingError::NotARecording));
+    }
+}
@@ -108,6 +108,9 @@ pub struct LaunchConfig {
     original_power_pref: Option<String>,
 }
 
+/// Command-line flags [`LaunchConfig::from_args`] understands.
+pub const LAUNCH_FLAGS: &[&str] = &["--force-software", "--headless-detect"];
+
 impl LaunchConfig {
     pub fn from_args() -> Self {
         let original_backend = env::var("WGPU_BACKEND").ok();
@@ -0,0 +1,422 @@
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+use crate::recorder::{Record, RecordPayload};
+
+/// Longest sleep between position updates, so progress bars keep moving
+/// through long silent stretches.
+const PROGRESS_TICK: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplayTiming {
+    /// Gaps between chunks as recorded.
+    Original,
+    /// Recorded gaps divided by the factor; `2.0` plays twice as fast.
+    Scaled(f64),
+    /// No gaps at all.
+    AsFastAsPossible,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum ReplayTimingKind {
+    #[strum(to_string = "Original")]
+    Original,
+    #[strum(to_string = "Scaled")]
+    Scaled,
+    #[strum(to_string = "As fast as possible")]
+    AsFastAsPossible,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum ReplayTargetKind {
+    #[strum(to_string = "Console (as live)")]
+    Console,
+    #[strum(to_string = "Serial port")]
+    Port,
+    #[strum(to_string = "Virtual port")]
+    VirtualPort,
+}
+
+impl ReplayTargetKind {
+    /// Virtual ports are ptys, which only unix hosts provide.
+    pub fn is_supported(self) -> bool {
+        cfg!(unix) || self != Self::VirtualPort
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplaySettings {
+    pub target: ReplayTargetKind,
+    pub timing: ReplayTimingKind,
+    pub speed: f64,
+    /// Symlink published when replaying into a virtual port.
+    pub link: String,
+}
+
+impl Default for ReplaySettings {
+    fn default() -> Self {
+        Self {
+            target: ReplayTargetKind::Console,
+            timing: ReplayTimingKind::Original,
+            speed: 2.0,
+            link: "/tmp/ttyMicroSerialReplay".to_string(),
+        }
+    }
+}
+
+impl ReplaySettings {
+    pub fn timing(&self) -> ReplayTiming {
+        match self.timing {
+            ReplayTimingKind::Original => ReplayTiming::Original,
+            ReplayTimingKind::Scaled => ReplayTiming::Scaled(self.speed.max(0.01)),
+            ReplayTimingKind::AsFastAsPossible => ReplayTiming::AsFastAsPossible,
+        }
+    }
+}
+
+/// Receives replayed bytes on the replay thread.
+pub type ReplaySink = Box<dyn FnMut(&[u8]) -> io::Result<()> + Send>;
+
+/// Where replayed RX chunks go.
+pub enum ReplayOutput {
+    /// Delivered as [`ReplayMessage::Data`] through [`Replayer::poll`].
+    Channel,
+    /// Written on the replay thread, so timing does not depend on the UI frame rate.
+    Sink(ReplaySink),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReplayMessage {
+    Data(Vec<u8>),
+    /// The sink failed; playback stopped.
+    Error(String),
+    /// The last chunk was delivered. Seeking back resumes playback.
+    Finished,
+}
+
+#[derive(Debug)]
+struct Control {
+    timing: ReplayTiming,
+    paused: bool,
+    stopped: bool,
+    seek: Option<Duration>,
+    /// Set when the wall-clock anchor must be taken again (resume, speed change).
+    reanchor: bool,
+    position: Duration,
+    finished: bool,
+}
+
+struct Shared {
+    control: Mutex<Control>,
+    wake: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Control> {
+        self.control.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn update(&self, apply: impl FnOnce(&mut Control)) {
+        apply(&mut self.lock());
+        self.wake.notify_all();
+    }
+}
+
+/// Plays the RX stream of a recording back with its original pacing.
+pub struct Replayer {
+    shared: Arc<Shared>,
+    duration: Duration,
+    chunks: usize,
+    rx: Receiver<ReplayMessage>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Replayer {
+    pub fn start(records: &[Record], timing: ReplayTiming, output: ReplayOutput) -> Self {
+        Self::spawn(records, timing, output, false)
+    }
+
+    /// Like [`Replayer::start`], but nothing is sent until playback is resumed,
+    /// so the position can be set first.
+    pub fn start_paused(records: &[Record], timing: ReplayTiming, output: ReplayOutput) -> Self {
+        Self::spawn(records, timing, output, true)
+    }
+
+    fn spawn(records: &[Record], timing: ReplayTiming, output: ReplayOutput, paused: bool) -> Self {
+        let chunks: Vec<(Duration, Vec<u8>)> = records
+            .iter()
+            .filter_map(|record| match &record.payload {
+                RecordPayload::Rx(data) if !data.is_empty() => {
+                    Some((record.timestamp, data.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        let duration = chunks.last().map(|(at, _)| *at).unwrap_or_default();
+        let shared = Arc::new(Shared {
+            control: Mutex::new(Control {
+                timing,
+                paused,
+                stopped: false,
+                seek: None,
+                reanchor: false,
+                position: Duration::ZERO,
+                finished: false,
+            }),
+            wake: Condvar::new(),
+        });
+        let (tx, rx) = mpsc::channel();
+        let count = chunks.len();
+        let worker_shared = Arc::clone(&shared);
+        let worker = thread::spawn(move || play(&worker_shared, &chunks, output, &tx));
+        Self {
+            shared,
+            duration,
+            chunks: count,
+            rx,
+            worker: Some(worker),
+        }
+    }
+
+    /// Timestamp of the last RX chunk in the recording.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn chunks(&self) -> usize {
+        self.chunks
+    }
+
+    /// Current playback position on the recording's timeline.
+    pub fn position(&self) -> Duration {
+        self.shared.lock().position
+    }
+
+    /// Playback progress between 0 and 1.
+    pub fn progress(&self) -> f32 {
+        if self.duration.is_zero() {
+            return if self.is_finished() { 1.0 } else { 0.0 };
+        }
+        (self.position().as_secs_f64() / self.duration.as_secs_f64()).clamp(0.0, 1.0) as f32
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.shared.lock().paused
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.shared.lock().finished
+    }
+
+    pub fn timing(&self) -> ReplayTiming {
+        self.shared.lock().timing
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.shared.update(|control| {
+            control.reanchor |= control.paused && !paused;
+            control.paused = paused;
+        });
+    }
+
+    pub fn set_timing(&self, timing: ReplayTiming) {
+        self.shared.update(|control| {
+            control.timing = timing;
+            control.reanchor = true;
+        });
+    }
+
+    /// Jumps to `position`; chunks before it are skipped, not sent.
+    pub fn seek(&self, position: Duration) {
+        let position = position.min(self.duration);
+        self.shared.update(|control| {
+            control.seek = Some(position);
+            control.position = position;
+        });
+    }
+
+    pub fn poll(&self) -> Vec<ReplayMessage> {
+        let mut messages = Vec::new();
+        while let Ok(message) = self.rx.try_recv() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    pub fn stop(&mut self) {
+        self.shared.update(|control| control.stopped = true);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for Replayer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn play(
+    shared: &Shared,
+    chunks: &[(Duration, Vec<u8>)],
+    mut output: ReplayOutput,
+    tx: &Sender<ReplayMessage>,
+) {
+    let mut index = 0;
+    let mut anchor = (Instant::now(), Duration::ZERO);
+    let mut control = shared.lock();
+    loop {
+        if control.stopped {
+            return;
+        }
+        if let Some(target) = control.seek.take() {
+            index = chunks.partition_point(|(at, _)| *at < target);
+            anchor = (Instant::now(), target);
+            control.finished = false;
+        }
+        if control.paused {
+            control = shared
+                .wake
+                .wait(control)
+                .unwrap_or_else(|err| err.into_inner());
+            continue;
+        }
+        if control.reanchor {
+            control.reanchor = false;
+            anchor = (Instant::now(), control.position);
+        }
+        let Some((at, data)) = chunks.get(index) else {
+            if !control.finished {
+                control.finished = true;
+                control.position = chunks.last().map(|(at, _)| *at).unwrap_or_default();
+                let _ = tx.send(ReplayMessage::Finished);
+            }
+            control = shared
+                .wake
+                .wait(control)
+                .unwrap_or_else(|err| err.into_inner());
+            continue;
+        };
+
+        let elapsed = anchor.0.elapsed();
+        let now = match control.timing {
+            ReplayTiming::Original => anchor.1 + elapsed,
+            ReplayTiming::Scaled(factor) => anchor.1 + elapsed.mul_f64(factor),
+            ReplayTiming::AsFastAsPossible => *at,
+        };
+        if now < *at {
+            control.position = now;
+            let remaining = *at - now;
+            let wait = match control.timing {
+                ReplayTiming::Scaled(factor) => remaining.div_f64(factor),
+                _ => remaining,
+            };
+            control = shared
+                .wake
+                .wait_timeout(control, wait.min(PROGRESS_TICK))
+                .unwrap_or_else(|err| err.into_inner())
+                .0;
+            continue;
+        }
+
+        control.position = control.position.max(*at);
+        index += 1;
+        // Deliver without holding the lock so controls stay responsive.
+        drop(control);
+        let delivered = match &mut output {
+            ReplayOutput::Channel => tx
+                .send(ReplayMessage::Data(data.clone()))
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe)),
+            ReplayOutput::Sink(sink) => sink(data),
+        };
+        control = shared.lock();
+        if let Err(err) = delivered {
+            control.stopped = true;
+            let _ = tx.send(ReplayMessage::Error(err.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rx(millis: u64, data: &[u8]) -> Record {
+        Record {
+            timestamp: Duration::from_millis(millis),
+            payload: RecordPayload::Rx(data.to_vec()),
+        }
+    }
+
+    fn collect(replayer: &Replayer, timeout: Duration) -> Vec<ReplayMessage> {
+        let start = Instant::now();
+        let mut messages = Vec::new();
+        while start.elapsed() < timeout {
+            messages.extend(replayer.poll());
+            if messages.last() == Some(&ReplayMessage::Finished) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        messages
+    }
+
+    #[test]
+    fn plays_only_rx_in_order() {
+        let records = vec![
+            rx(0, b"a"),
+            Record {
+                timestamp: Duration::from_millis(1),
+                payload: RecordPayload::Tx(b"ignored".to_vec()),
+            },
+            rx(2, b"b"),
+        ];
+        let replayer = Replayer::start(
+            &records,
+            ReplayTiming::AsFastAsPossible,
+            ReplayOutput::Channel,
+        );
+        assert_eq!(
+            collect(&replayer, Duration::from_secs(1)),
+            vec![
+                ReplayMessage::Data(b"a".to_vec()),
+                ReplayMessage::Data(b"b".to_vec()),
+                ReplayMessage::Finished,
+            ]
+        );
+        assert_eq!(replayer.progress(), 1.0);
+    }
+
+    #[test]
+    fn seek_skips_earlier_chunks() {
+        let records = vec![rx(0, b"a"), rx(100, b"b"), rx(200, b"c")];
+        let replayer =
+            Replayer::start_paused(&records, ReplayTiming::Original, ReplayOutput::Channel);
+        replayer.seek(Duration::from_millis(150));
+        replayer.set_timing(ReplayTiming::AsFastAsPossible);
+        replayer.set_paused(false);
+        assert_eq!(
+            collect(&replayer, Duration::from_secs(1)),
+            vec![ReplayMessage::Data(b"c".to_vec()), ReplayMessage::Finished]
+        );
+    }
+
+    #[test]
+    fn scaled_timing_shortens_gaps() {
+        let records = vec![rx(0, b"a"), rx(400, b"b")];
+        let start = Instant::now();
+        let replayer = Replayer::start(&records, ReplayTiming::Scaled(4.0), ReplayOutput::Channel);
+        let messages = collect(&replayer, Duration::from_secs(2));
+        let elapsed = start.elapsed();
+        assert_eq!(messages.len(), 3);
+        assert!(elapsed >= Duration::from_millis(90), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(350), "{elapsed:?}");
+    }
+}
@@ -0,0 +1,448 @@
+use std::collections::HashSet;
+
+use crate::core::{FlowControl, ModemLines, Parity, SerialConfig, StopBits};
+
+pub const IAC: u8 = 255;
+pub const DONT: u8 = 254;
+pub const DO: u8 = 253;
+pub const WONT: u8 = 252;
+pub const WILL: u8 = 251;
+pub const SB: u8 = 250;
+pub const SE: u8 = 240;
+
+pub const OPT_BINARY: u8 = 0;
+pub const OPT_ECHO: u8 = 1;
+pub const OPT_SUPPRESS_GO_AHEAD: u8 = 3;
+pub const OPT_COM_PORT: u8 = 44;
+
+/// Server replies use the client command code plus this offset.
+pub const SERVER_OFFSET: u8 = 100;
+
+const SET_BAUDRATE: u8 = 1;
+const SET_DATASIZE: u8 = 2;
+const SET_PARITY: u8 = 3;
+const SET_STOPSIZE: u8 = 4;
+const SET_CONTROL: u8 = 5;
+const NOTIFY_LINESTATE: u8 = 6;
+const NOTIFY_MODEMSTATE: u8 = 7;
+const FLOWCONTROL_SUSPEND: u8 = 8;
+const FLOWCONTROL_RESUME: u8 = 9;
+const SET_LINESTATE_MASK: u8 = 10;
+const SET_MODEMSTATE_MASK: u8 = 11;
+const PURGE_DATA: u8 = 12;
+
+pub const CONTROL_FLOW_REQUEST: u8 = 0;
+pub const CONTROL_FLOW_NONE: u8 = 1;
+pub const CONTROL_FLOW_XON_XOFF: u8 = 2;
+pub const CONTROL_FLOW_HARDWARE: u8 = 3;
+pub const CONTROL_BREAK_REQUEST: u8 = 4;
+pub const CONTROL_BREAK_ON: u8 = 5;
+pub const CONTROL_BREAK_OFF: u8 = 6;
+pub const CONTROL_DTR_REQUEST: u8 = 7;
+pub const CONTROL_DTR_ON: u8 = 8;
+pub const CONTROL_DTR_OFF: u8 = 9;
+pub const CONTROL_RTS_REQUEST: u8 = 10;
+pub const CONTROL_RTS_ON: u8 = 11;
+pub const CONTROL_RTS_OFF: u8 = 12;
+
+/// The low nibble of a NOTIFY-MODEMSTATE value marks which lines changed.
+pub const MODEM_STATE_DELTAS: u8 = 0x0F;
+const MODEM_STATE_DELTA_CTS: u8 = 0x01;
+const MODEM_STATE_DELTA_DSR: u8 = 0x02;
+const MODEM_STATE_RI_TRAILING: u8 = 0x04;
+const MODEM_STATE_DELTA_CD: u8 = 0x08;
+const MODEM_STATE_CTS: u8 = 0x10;
+const MODEM_STATE_DSR: u8 = 0x20;
+const MODEM_STATE_RI: u8 = 0x40;
+const MODEM_STATE_CD: u8 = 0x80;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TelnetEvent {
+    Data(Vec<u8>),
+    Negotiate { command: u8, option: u8 },
+    Subnegotiation { option: u8, payload: Vec<u8> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecodeState {
+    Data,
+    Iac,
+    Negotiate(u8),
+    SubOption,
+    Sub,
+    SubIac,
+}
+
+/// Incremental telnet parser that separates in-band data from negotiation.
+///
+/// It is transport-agnostic so the network server and client share it: feed
+/// socket bytes in, get data and option events out.
+#[derive(Debug, Clone)]
+pub struct TelnetDecoder {
+    state: DecodeState,
+    sub_option: u8,
+    sub_payload: Vec<u8>,
+}
+
+impl Default for TelnetDecoder {
+    fn default() -> Self {
+        Self {
+            state: DecodeState::Data,
+            sub_option: 0,
+            sub_payload: Vec::new(),
+        }
+    }
+}
+
+impl TelnetDecoder {
+    pub fn feed(&mut self, input: &[u8]) -> Vec<TelnetEvent> {
+        let mut events = Vec::new();
+        let mut data = Vec::new();
+        for &byte in input {
+            match self.state {
+                DecodeState::Data => {
+                    if byte == IAC {
+                        self.state = DecodeState::Iac;
+                    } else {
+                        data.push(byte);
+                    }
+                }
+                DecodeState::Iac => match byte {
+                    IAC => {
+                        data.push(IAC);
+                        self.state = DecodeState::Data;
+                    }
+                    WILL | WONT | DO | DONT => self.state = DecodeState::Negotiate(byte),
+                    SB => self.state = DecodeState::SubOption,
+                    // NOP, GA and the other single-byte commands carry no payload.
+                    _ => self.state = DecodeState::Data,
+                },
+                DecodeState::Negotiate(command) => {
+                    flush_data(&mut data, &mut events);
+                    events.push(TelnetEvent::Negotiate {
+                        command,
+                        option: byte,
+                    });
+                    self.state = DecodeState::Data;
+                }
+                DecodeState::SubOption => {
+                    self.sub_option = byte;
+                    self.sub_payload.clear();
+                    self.state = DecodeState::Sub;
+                }
+                DecodeState::Sub => {
+                    if byte == IAC {
+                        self.state = DecodeState::SubIac;
+                    } else {
+                        self.sub_payload.push(byte);
+                    }
+                }
+                DecodeState::SubIac => match byte {
+                    SE => {
+                        flush_data(&mut data, &mut events);
+                        events.push(TelnetEvent::Subnegotiation {
+                            option: self.sub_option,
+                            payload: std::mem::take(&mut self.sub_payload),
+                        });
+                        self.state = DecodeState::Data;
+                    }
+                    IAC => {
+                        self.sub_payload.push(IAC);
+                        self.state = DecodeState::Sub;
+                    }
+                    _ => {
+                        // Malformed subnegotiation; drop it and resynchronise.
+                        self.sub_payload.clear();
+                        self.state = DecodeState::Data;
+                    }
+                },
+            }
+        }
+        flush_data(&mut data, &mut events);
+        events
+    }
+}
+
+fn flush_data(data: &mut Vec<u8>, events: &mut Vec<TelnetEvent>) {
+    if !data.is_empty() {
+        events.push(TelnetEvent::Data(std::mem::take(data)));
+    }
+}
+
+/// Doubles every IAC byte so payload data survives telnet framing.
+pub fn escape_iac(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(data.len());
+    for &byte in data {
+        output.push(byte);
+        if byte == IAC {
+            output.push(IAC);
+        }
+    }
+    output
+}
+
+pub fn negotiate(command: u8, option: u8) -> Vec<u8> {
+    vec![IAC, command, option]
+}
+
+/// Tracks option negotiation for one side of a connection.
+///
+/// Only BINARY, SUPPRESS-GO-AHEAD and COM-PORT-OPTION are accepted. Each
+/// reply is sent once per state so the two peers never loop acknowledging
+/// each other.
+#[derive(Debug, Default)]
+pub struct Negotiator {
+    sent: HashSet<(u8, u8)>,
+}
+
+impl Negotiator {
+    /// Encodes an unsolicited request and remembers it as already sent.
+    pub fn offer(&mut self, command: u8, option: u8) -> Vec<u8> {
+        self.sent.insert((command, option));
+        negotiate(command, option)
+    }
+
+    /// Returns the reply to a DO/WILL from the peer, if one is still owed.
+    pub fn answer(&mut self, command: u8, option: u8) -> Option<Vec<u8>> {
+        let supported = matches!(option, OPT_BINARY | OPT_SUPPRESS_GO_AHEAD | OPT_COM_PORT);
+        let reply = match (command, supported) {
+            (DO, true) => WILL,
+            (DO, false) => WONT,
+            (WILL, true) => DO,
+            (WILL, false) => DONT,
+            _ => return None,
+        };
+        self.sent
+            .insert((reply, option))
+            .then(|| negotiate(reply, option))
+    }
+}
+
+/// COM-PORT-OPTION commands shared by client requests and server replies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComPortCommand {
+    SetBaudRate(u32),
+    SetDataSize(u8),
+    SetParity(u8),
+    SetStopSize(u8),
+    SetControl(u8),
+    NotifyLineState(u8),
+    NotifyModemState(u8),
+    FlowControlSuspend,
+    FlowControlResume,
+    SetLineStateMask(u8),
+    SetModemStateMask(u8),
+    PurgeData(u8),
+}
+
+impl ComPortCommand {
+    /// Parses a COM-PORT-OPTION subnegotiation payload.
+    ///
+    /// Returns the command and whether it was sent in the server (reply) range.
+    pub fn parse(payload: &[u8]) -> Option<(Self, bool)> {
+        let (&code, rest) = payload.split_first()?;
+        let (code, from_server) = if code >= SERVER_OFFSET {
+            (code - SERVER_OFFSET, true)
+        } else {
+            (code, false)
+        };
+        let byte = || rest.first().copied();
+        let command = match code {
+            SET_BAUDRATE => {
+                let bytes: [u8; 4] = rest.get(..4)?.try_into().ok()?;
+                ComPortCommand::SetBaudRate(u32::from_be_bytes(bytes))
+            }
+            SET_DATASIZE => ComPortCommand::SetDataSize(byte()?),
+            SET_PARITY => ComPortCommand::SetParity(byte()?),
+            SET_STOPSIZE => ComPortCommand::SetStopSize(byte()?),
+            SET_CONTROL => ComPortCommand::SetControl(byte()?),
+            NOTIFY_LINESTATE => ComPortCommand::NotifyLineState(byte()?),
+            NOTIFY_MODEMSTATE => ComPortCommand::NotifyModemState(byte()?),
+            FLOWCONTROL_SUSPEND => ComPortCommand::FlowControlSuspend,
+            FLOWCONTROL_RESUME => ComPortCommand::FlowControlResume,
+            SET_LINESTATE_MASK => ComPortCommand::SetLineStateMask(byte()?),
+            SET_MODEMSTATE_MASK => ComPortCommand::SetModemStateMask(byte()?),
+            PURGE_DATA => ComPortCommand::PurgeData(byte()?),
+            _ => return None,
+        };
+        Some((command, from_server))
+    }
+
+    /// Encodes the command as a full `IAC SB COM-PORT-OPTION ... IAC SE` sequence.
+    pub fn encode(self, from_server: bool) -> Vec<u8> {
+        let (code, value): (u8, Vec<u8>) = match self {
+            ComPortCommand::SetBaudRate(baud) => (SET_BAUDRATE, baud.to_be_bytes().to_vec()),
+            ComPortCommand::SetDataSize(value) => (SET_DATASIZE, vec![value]),
+            ComPortCommand::SetParity(value) => (SET_PARITY, vec![value]),
+            ComPortCommand::SetStopSize(value) => (SET_STOPSIZE, vec![value]),
+            ComPortCommand::SetControl(value) => (SET_CONTROL, vec![value]),
+            ComPortCommand::NotifyLineState(value) => (NOTIFY_LINESTATE, vec![value]),
+            ComPortCommand::NotifyModemState(value) => (NOTIFY_MODEMSTATE, vec![value]),
+            ComPortCommand::FlowControlSuspend => (FLOWCONTROL_SUSPEND, Vec::new()),
+            ComPortCommand::FlowControlResume => (FLOWCONTROL_RESUME, Vec::new()),
+            ComPortCommand::SetLineStateMask(value) => (SET_LINESTATE_MASK, vec![value]),
+            ComPortCommand::SetModemStateMask(value) => (SET_MODEMSTATE_MASK, vec![value]),
+            ComPortCommand::PurgeData(value) => (PURGE_DATA, vec![value]),
+        };
+        let code = if from_server {
+            code + SERVER_OFFSET
+        } else {
+            code
+        };
+        let mut output = vec![IAC, SB, OPT_COM_PORT, code];
+        output.extend(escape_iac(&value));
+        output.extend_from_slice(&[IAC, SE]);
+        output
+    }
+}
+
+pub fn parity_code(parity: Parity) -> u8 {
+    match parity {
+        Parity::None => 1,
+        Parity::Odd => 2,
+        Parity::Even => 3,
+    }
+}
+
+pub fn parity_from_code(code: u8) -> Option<Parity> {
+    match code {
+        1 => Some(Parity::None),
+        2 => Some(Parity::Odd),
+        3 => Some(Parity::Even),
+        _ => None,
+    }
+}
+
+pub fn stop_bits_code(stop_bits: StopBits) -> u8 {
+    match stop_bits {
+        StopBits::One => 1,
+        StopBits::Two => 2,
+    }
+}
+
+pub fn stop_bits_from_code(code: u8) -> Option<StopBits> {
+    match code {
+        1 => Some(StopBits::One),
+        2 => Some(StopBits::Two),
+        _ => None,
+    }
+}
+
+pub fn flow_control_code(flow: FlowControl) -> u8 {
+    match flow {
+        FlowControl::None => CONTROL_FLOW_NONE,
+        FlowControl::XonXoff => CONTROL_FLOW_XON_XOFF,
+        FlowControl::RtsCts => CONTROL_FLOW_HARDWARE,
+    }
+}
+
+pub fn flow_control_from_code(code: u8) -> Option<FlowControl> {
+    match code {
+        CONTROL_FLOW_NONE => Some(FlowControl::None),
+        CONTROL_FLOW_XON_XOFF => Some(FlowControl::XonXoff),
+        CONTROL_FLOW_HARDWARE => Some(FlowControl::RtsCts),
+        _ => None,
+    }
+}
+
+/// Decodes the input lines of a NOTIFY-MODEMSTATE value.
+///
+/// DTR and RTS are outputs and never reported by the server.
+pub fn modem_lines_from_state(state: u8) -> ModemLines {
+    ModemLines {
+        cts: state & MODEM_STATE_CTS != 0,
+        dsr: state & MODEM_STATE_DSR != 0,
+        ri: state & MODEM_STATE_RI != 0,
+        cd: state & MODEM_STATE_CD != 0,
+        ..ModemLines::default()
+    }
+}
+
+/// Encodes the input lines of `lines` as a NOTIFY-MODEMSTATE value, with the
+/// delta bits set for lines that differ from `previous`.
+pub fn modem_state(lines: ModemLines, previous: ModemLines) -> u8 {
+    let bit = |on: bool, mask: u8| if on { mask } else { 0 };
+    bit(lines.cts, MODEM_STATE_CTS)
+        | bit(lines.dsr, MODEM_STATE_DSR)
+        | bit(lines.ri, MODEM_STATE_RI)
+        | bit(lines.cd, MODEM_STATE_CD)
+        | bit(lines.cts != previous.cts, MODEM_STATE_DELTA_CTS)
+        | bit(lines.dsr != previous.dsr, MODEM_STATE_DELTA_DSR)
+        | bit(previous.ri && !lines.ri, MODEM_STATE_RI_TRAILING)
+        | bit(lines.cd != previous.cd, MODEM_STATE_DELTA_CD)
+}
+
+/// The SET commands describing the line settings of `config`.
+pub fn config_commands(config: &SerialConfig) -> [ComPortCommand; 5] {
+    [
+        ComPortCommand::SetBaudRate(config.baud_rate),
+        ComPortCommand::SetDataSize(config.data_bits),
+        ComPortCommand::SetParity(parity_code(config.parity)),
+        ComPortCommand::SetStopSize(stop_bits_code(config.stop_bits)),
+        ComPortCommand::SetControl(flow_control_code(config.flow_control)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoder_splits_data_and_negotiation() {
+        let mut decoder = TelnetDecoder::default();
+        let mut input = b"ab".to_vec();
+        input.extend_from_slice(&[IAC, IAC, IAC, WILL, OPT_COM_PORT]);
+        input.extend_from_slice(b"c");
+        let events = decoder.feed(&input);
+        assert_eq!(
+            events,
+            vec![
+                TelnetEvent::Data(vec![b'a', b'b', IAC]),
+                TelnetEvent::Negotiate {
+                    command: WILL,
+                    option: OPT_COM_PORT
+                },
+                TelnetEvent::Data(b"c".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn com_port_command_roundtrip_across_chunks() {
+        let encoded = ComPortCommand::SetBaudRate(0x00FF_FF00).encode(false);
+        let mut decoder = TelnetDecoder::default();
+        let (head, tail) = encoded.split_at(5);
+        assert!(decoder.feed(head).is_empty());
+        let events = decoder.feed(tail);
+        let TelnetEvent::Subnegotiation { option, payload } = &events[0] else {
+            panic!("expected subnegotiation, got {events:?}");
+        };
+        assert_eq!(*option, OPT_COM_PORT);
+        assert_eq!(
+            ComPortCommand::parse(payload),
+            Some((ComPortCommand::SetBaudRate(0x00FF_FF00), false))
+        );
+    }
+
+    #[test]
+    fn server_replies_use_offset_codes() {
+        let encoded = ComPortCommand::SetParity(3).encode(true);
+        assert_eq!(encoded, vec![IAC, SB, OPT_COM_PORT, 103, 3, IAC, SE]);
+    }
+
+    #[test]
+    fn modem_state_round_trips_with_deltas() {
+        let previous = ModemLines {
+            ri: true,
+            ..ModemLines::default()
+        };
+        let lines = ModemLines {
+            cts: true,
+            cd: true,
+            ..ModemLines::default()
+        };
+        let state = modem_state(lines, previous);
+        assert_eq!(state, 0x10 | 0x80 | 0x01 | 0x04 | 0x08);
+        assert_eq!(modem_lines_from_state(state), lines);
+    }
+}
@@ -0,0 +1,406 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::checksum::Checksum;
+use crate::send_panel::{PayloadError, SendMode, TextOptions};
+use crate::template::{TemplateContext, TemplateCounters};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledStep {
+    pub value: String,
+    pub mode: SendMode,
+    /// Wait before this step, counted from the previous step (or the cycle start).
+    #[serde(default)]
+    pub delay_ms: u64,
+    #[serde(default)]
+    pub checksum: Option<Checksum>,
+}
+
+impl ScheduledStep {
+    fn payload(
+        &self,
+        options: &TextOptions,
+        context: &TemplateContext,
+    ) -> Result<Vec<u8>, PayloadError> {
+        self.mode
+            .build(&self.value, options, self.checksum.as_ref(), context)
+            .map(|payload| payload.bytes)
+    }
+}
+
+/// A payload or sequence that is sent repeatedly.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledItem {
+    pub name: String,
+    pub steps: Vec<ScheduledStep>,
+    /// Time from the start of one cycle to the start of the next.
+    pub interval_ms: u64,
+    /// Number of cycles; `None` repeats until stopped.
+    pub repeat: Option<u32>,
+}
+
+impl ScheduledItem {
+    pub const DEFAULT_INTERVAL_MS: u64 = 1000;
+
+    /// A single payload repeated every second until stopped.
+    pub fn single(value: &str, mode: SendMode) -> Self {
+        Self {
+            name: value.to_string(),
+            steps: vec![ScheduledStep {
+                value: value.to_string(),
+                mode,
+                delay_ms: 0,
+                checksum: None,
+            }],
+            interval_ms: Self::DEFAULT_INTERVAL_MS,
+            repeat: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum TimerStep {
+    Fixed(Vec<u8>),
+    /// Expanded again for every send.
+    Template(ScheduledStep),
+}
+
+/// A running [`ScheduledItem`].
+#[derive(Debug)]
+pub struct Timer {
+    /// Caller-chosen identifier, e.g. the item's index in the saved list.
+    pub key: usize,
+    pub name: String,
+    steps: Vec<TimerStep>,
+    options: TextOptions,
+    delays: Vec<Duration>,
+    interval: Duration,
+    repeat: Option<u32>,
+    step: usize,
+    cycle_started: Instant,
+    next_at: Instant,
+    cycles: u32,
+    sent: u64,
+}
+
+impl Timer {
+    /// Completed cycles.
+    pub fn cycles(&self) -> u32 {
+        self.cycles
+    }
+
+    /// Payloads sent so far.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    pub fn repeat(&self) -> Option<u32> {
+        self.repeat
+    }
+
+    fn finished(&self) -> bool {
+        self.repeat.is_some_and(|repeat| self.cycles >= repeat)
+    }
+
+    /// Returns the step that is due at `now`, advancing to the next step.
+    fn pop_due(&mut self, now: Instant) -> Option<usize> {
+        if self.finished() || now < self.next_at {
+            return None;
+        }
+        let due = self.step;
+        self.sent += 1;
+        self.step += 1;
+        if self.step == self.steps.len() {
+            self.step = 0;
+            self.cycles += 1;
+            // Cycles keep their cadence. Cycles missed by an overrunning sequence or a
+            // stalled UI are skipped rather than sent in a burst.
+            let mut next = self.cycle_started + self.interval;
+            if next < now {
+                let behind = (now - next).as_nanos() / self.interval.as_nanos();
+                next += self.interval * (behind.min(u32::MAX as u128 - 1) as u32 + 1);
+            }
+            self.cycle_started = next;
+            self.next_at = next + self.delays[0];
+        } else {
+            self.next_at += self.delays[self.step];
+        }
+        Some(due)
+    }
+
+    fn payload(
+        &self,
+        step: usize,
+        templates: &mut TemplateCounters,
+    ) -> Result<Vec<u8>, PayloadError> {
+        match &self.steps[step] {
+            TimerStep::Fixed(payload) => Ok(payload.clone()),
+            TimerStep::Template(step) => {
+                let payload = step.payload(&self.options, &templates.context(&step.value))?;
+                templates.advance(&step.value);
+                Ok(payload)
+            }
+        }
+    }
+}
+
+/// Runs any number of [`ScheduledItem`]s side by side for one session.
+#[derive(Debug, Default)]
+pub struct SendScheduler {
+    timers: Vec<Timer>,
+    /// Timers stopped because a template could not be expanded, by name.
+    failures: Vec<(String, PayloadError)>,
+}
+
+impl SendScheduler {
+    /// Starts `item`, replacing a timer with the same key.
+    ///
+    /// Text steps are encoded once, with the `options` of the active profile. Template
+    /// steps are checked now and expanded again before every send.
+    pub fn start(
+        &mut self,
+        key: usize,
+        item: &ScheduledItem,
+        options: &TextOptions,
+        templates: &TemplateCounters,
+        now: Instant,
+    ) -> Result<(), PayloadError> {
+        let steps = item
+            .steps
+            .iter()
+            .map(|step| {
+                let payload = step.payload(options, &templates.context(&step.value))?;
+                Ok(match step.mode {
+                    SendMode::Template => TimerStep::Template(step.clone()),
+                    SendMode::Text | SendMode::Hex => TimerStep::Fixed(payload),
+                })
+            })
+            .collect::<Result<Vec<_>, PayloadError>>()?;
+        self.stop(key);
+        if steps.is_empty() {
+            return Ok(());
+        }
+        let delays: Vec<Duration> = item
+            .steps
+            .iter()
+            .map(|step| Duration::from_millis(step.delay_ms))
+            .collect();
+        self.timers.push(Timer {
+            key,
+            name: item.name.clone(),
+            steps,
+            options: options.clone(),
+            interval: Duration::from_millis(item.interval_ms.max(1)),
+            repeat: item.repeat,
+            step: 0,
+            cycle_started: now,
+            next_at: now + delays[0],
+            delays,
+            cycles: 0,
+            sent: 0,
+        });
+        Ok(())
+    }
+
+    pub fn stop(&mut self, key: usize) {
+        self.timers.retain(|timer| timer.key != key);
+    }
+
+    /// Moves a running timer to another key, e.g. after the saved list shifted.
+    pub fn rekey(&mut self, from: usize, to: usize) {
+        if let Some(timer) = self.timers.iter_mut().find(|timer| timer.key == from) {
+            timer.key = to;
+        }
+    }
+
+    pub fn stop_all(&mut self) {
+        self.timers.clear();
+    }
+
+    pub fn timer(&self, key: usize) -> Option<&Timer> {
+        self.timers.iter().find(|timer| timer.key == key)
+    }
+
+    pub fn timers(&self) -> &[Timer] {
+        &self.timers
+    }
+
+    /// Payloads due at `now`, in order, with the key of the timer that sent them.
+    ///
+    /// Timers that completed their cycles are removed, and so are timers whose template
+    /// failed to expand; see [`Self::take_failures`].
+    pub fn due(&mut self, now: Instant, templates: &mut TemplateCounters) -> Vec<(usize, Vec<u8>)> {
+        let mut due = Vec::new();
+        let mut failed = Vec::new();
+        for timer in &mut self.timers {
+            while let Some(step) = timer.pop_due(now) {
+                match timer.payload(step, templates) {
+                    Ok(payload) => due.push((timer.key, payload)),
+                    Err(err) => {
+                        failed.push(timer.key);
+                        self.failures.push((timer.name.clone(), err));
+                        break;
+                    }
+                }
+            }
+        }
+        self.timers
+            .retain(|timer| !timer.finished() && !failed.contains(&timer.key));
+        due
+    }
+
+    pub fn take_failures(&mut self) -> Vec<(String, PayloadError)> {
+        std::mem::take(&mut self.failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn repeats_at_interval_until_count() {
+        let mut scheduler = SendScheduler::default();
+        let item = ScheduledItem {
+            repeat: Some(4),
+            interval_ms: 250,
+            ..ScheduledItem::single("READ?\r", SendMode::Text)
+        };
+        let options = TextOptions::default();
+        let mut templates = TemplateCounters::default();
+        let start = Instant::now();
+        scheduler
+            .start(0, &item, &options, &templates, start)
+            .expect("start");
+        assert_eq!(
+            scheduler.due(start, &mut templates),
+            vec![(0, b"READ?\r".to_vec())]
+        );
+        assert!(scheduler.due(start + ms(249), &mut templates).is_empty());
+        assert_eq!(scheduler.due(start + ms(250), &mut templates).len(), 1);
+        assert_eq!(scheduler.timer(0).map(Timer::sent), Some(2));
+        // A stall does not cause a burst of missed cycles.
+        assert_eq!(scheduler.due(start + ms(2000), &mut templates).len(), 1);
+        assert!(scheduler.due(start + ms(2249), &mut templates).is_empty());
+        assert_eq!(scheduler.due(start + ms(2250), &mut templates).len(), 1);
+        assert!(scheduler.timer(0).is_none());
+    }
+
+    #[test]
+    fn sequences_honour_step_delays_alongside_other_timers() {
+        let mut scheduler = SendScheduler::default();
+        let sequence = ScheduledItem {
+            name: "wake and read".to_string(),
+            steps: vec![
+                ScheduledStep {
+                    value: "00".to_string(),
+                    mode: SendMode::Hex,
+                    delay_ms: 0,
+                    checksum: None,
+                },
+                ScheduledStep {
+                    value: "R".to_string(),
+                    mode: SendMode::Text,
+                    delay_ms: 50,
+                    checksum: None,
+                },
+            ],
+            interval_ms: 100,
+            repeat: None,
+        };
+        let options = TextOptions::default();
+        let mut templates = TemplateCounters::default();
+        let start = Instant::now();
+        scheduler
+            .start(1, &sequence, &options, &templates, start)
+            .expect("start");
+        scheduler
+            .start(
+                2,
+                &ScheduledItem::single("P", SendMode::Text),
+                &options,
+                &templates,
+                start,
+            )
+            .expect("start");
+        assert_eq!(
+            scheduler.due(start, &mut templates),
+            vec![(1, vec![0x00]), (2, b"P".to_vec())]
+        );
+        assert!(scheduler.due(start + ms(49), &mut templates).is_empty());
+        assert_eq!(
+            scheduler.due(start + ms(50), &mut templates),
+            vec![(1, b"R".to_vec())]
+        );
+        assert_eq!(
+            scheduler.due(start + ms(100), &mut templates),
+            vec![(1, vec![0x00])]
+        );
+        scheduler.stop(1);
+        assert_eq!(scheduler.timers().len(), 1);
+
+        let broken = ScheduledItem::single("zz", SendMode::Hex);
+        assert!(
+            scheduler
+                .start(3, &broken, &options, &templates, start)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn templates_expand_on_every_send() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("values.txt");
+        std::fs::write(&path, "A\nB\n").expect("write");
+        let mut scheduler = SendScheduler::default();
+        let mut templates = TemplateCounters::default();
+        let options = TextOptions::default();
+        let counted = ScheduledItem {
+            interval_ms: 10,
+            ..ScheduledItem::single("#{seq:u8}", SendMode::Template)
+        };
+        let from_file = ScheduledItem {
+            interval_ms: 10,
+            ..ScheduledItem::single(&format!("{{file:{}}}", path.display()), SendMode::Template)
+        };
+        let start = Instant::now();
+        scheduler
+            .start(0, &counted, &options, &templates, start)
+            .expect("start");
+        scheduler
+            .start(1, &from_file, &options, &templates, start)
+            .expect("start");
+        assert_eq!(
+            scheduler.due(start, &mut templates),
+            vec![(0, vec![b'#', 0]), (1, b"A".to_vec())]
+        );
+        // The send panel shares the counters.
+        templates.advance("#{seq:u8}");
+        assert_eq!(
+            scheduler.due(start + ms(10), &mut templates),
+            vec![(0, vec![b'#', 2]), (1, b"B".to_vec())]
+        );
+
+        std::fs::remove_file(&path).expect("remove");
+        assert_eq!(
+            scheduler.due(start + ms(20), &mut templates),
+            vec![(0, vec![b'#', 3])]
+        );
+        assert!(scheduler.timer(1).is_none());
+        let failures = scheduler.take_failures();
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].1.to_string().contains("values.txt"));
+
+        let unknown = ScheduledItem::single("{what}", SendMode::Template);
+        assert!(
+            scheduler
+                .start(2, &unknown, &options, &templates, start)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,400 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use regex::bytes::Regex;
+use rhai::{Blob, Dynamic, Engine, EvalAltResult};
+use serde::{Deserialize, Serialize};
+
+use crate::decoder::{DecoderKind, FrameDecoder};
+use crate::send_panel;
+
+/// Longest stretch a blocking script call waits before checking for cancellation.
+const CANCEL_TICK: Duration = Duration::from_millis(20);
+/// Received bytes kept for `wait_for`; older output is dropped.
+const MAX_PENDING: usize = 64 * 1024;
+const MAX_FRAMES: usize = 1024;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedScript {
+    pub name: String,
+    pub source: String,
+    /// Shown as a button next to the send bar.
+    pub pinned: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ScriptSettings {
+    pub scripts: Vec<SavedScript>,
+}
+
+/// A change a script asks the owner of the session to apply.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScriptRequest {
+    Send(Vec<u8>),
+    SetDtr(bool),
+    SetRts(bool),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScriptOutput {
+    Request(ScriptRequest),
+    /// Text from `log` or `print`.
+    Log(String),
+    /// The script ended; `Err` holds the error or `"cancelled"`.
+    Finished(Result<(), String>),
+}
+
+/// Received data waiting to be consumed by `wait_for` and `wait_frame`.
+struct Inbox {
+    input: Receiver<Vec<u8>>,
+    pending: Vec<u8>,
+    frames: VecDeque<Vec<u8>>,
+    decoder: FrameDecoder,
+}
+
+impl Inbox {
+    /// Waits up to `timeout` for more data; returns false once the owner is gone.
+    fn pump(&mut self, timeout: Duration) -> bool {
+        let chunk = match self.input.recv_timeout(timeout) {
+            Ok(chunk) => chunk,
+            Err(RecvTimeoutError::Timeout) => return true,
+            Err(RecvTimeoutError::Disconnected) => return false,
+        };
+        self.push(&chunk);
+        while let Ok(chunk) = self.input.try_recv() {
+            self.push(&chunk);
+        }
+        true
+    }
+
+    fn push(&mut self, chunk: &[u8]) {
+        self.pending.extend_from_slice(chunk);
+        if self.pending.len() > MAX_PENDING {
+            let excess = self.pending.len() - MAX_PENDING;
+            self.pending.drain(..excess);
+        }
+        self.frames.extend(self.decoder.feed(chunk));
+        while self.frames.len() > MAX_FRAMES {
+            self.frames.pop_front();
+        }
+    }
+}
+
+/// Everything the registered functions share inside the script thread.
+#[derive(Clone)]
+struct ScriptContext {
+    inbox: Rc<RefCell<Inbox>>,
+    output: Sender<ScriptOutput>,
+    cancel: Arc<AtomicBool>,
+}
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+impl ScriptContext {
+    fn check_cancel(&self) -> ScriptResult<()> {
+        if self.cancel.load(Ordering::SeqCst) {
+            return Err("cancelled".into());
+        }
+        Ok(())
+    }
+
+    fn request(&self, request: ScriptRequest) -> ScriptResult<()> {
+        self.output
+            .send(ScriptOutput::Request(request))
+            .map_err(|_| "session closed".into())
+    }
+
+    fn log(&self, text: &str) {
+        let _ = self.output.send(ScriptOutput::Log(text.to_string()));
+    }
+
+    fn sleep(&self, millis: i64) -> ScriptResult<()> {
+        let deadline = Instant::now() + duration(millis);
+        loop {
+            self.check_cancel()?;
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+            // Keep draining received data so nothing is lost while asleep.
+            self.inbox
+                .borrow_mut()
+                .pump((deadline - now).min(CANCEL_TICK));
+        }
+    }
+
+    /// Blocks until `check` yields a value, the timeout passes or the script is cancelled.
+    fn wait<T>(
+        &self,
+        millis: i64,
+        mut check: impl FnMut(&mut Inbox) -> Option<T>,
+    ) -> ScriptResult<Option<T>> {
+        let deadline = Instant::now() + duration(millis);
+        loop {
+            self.check_cancel()?;
+            let mut inbox = self.inbox.borrow_mut();
+            if let Some(value) = check(&mut inbox) {
+                return Ok(Some(value));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            if !inbox.pump((deadline - now).min(CANCEL_TICK)) {
+                return Err("session closed".into());
+            }
+        }
+    }
+
+    fn wait_for(&self, pattern: &str, millis: i64) -> ScriptResult<Dynamic> {
+        let regex = Regex::new(pattern).map_err(|err| err.to_string())?;
+        let found = self.wait(millis, |inbox| {
+            let (end, text) = regex
+                .find(&inbox.pending)
+                .map(|found| (found.end(), found.as_bytes().to_vec()))?;
+            inbox.pending.drain(..end);
+            Some(String::from_utf8_lossy(&text).into_owned())
+        })?;
+        Ok(found.map_or(Dynamic::UNIT, Dynamic::from))
+    }
+
+    fn wait_frame(&self, millis: i64) -> ScriptResult<Dynamic> {
+        let frame = self.wait(millis, |inbox| inbox.frames.pop_front())?;
+        Ok(frame.map_or(Dynamic::UNIT, Dynamic::from_blob))
+    }
+
+    fn set_decoder(&self, name: &str) -> ScriptResult<()> {
+        let kind = match name.to_ascii_lowercase().as_str() {
+            "none" => DecoderKind::None,
+            "lines" => DecoderKind::Lines,
+            "slip" => DecoderKind::Slip,
+            "cobs" => DecoderKind::Cobs,
+            other => return Err(format!("unknown decoder '{other}'").into()),
+        };
+        let mut inbox = self.inbox.borrow_mut();
+        inbox.decoder.set_kind(kind);
+        inbox.frames.clear();
+        Ok(())
+    }
+}
+
+fn duration(millis: i64) -> Duration {
+    Duration::from_millis(millis.max(0) as u64)
+}
+
+/// Builds a sandboxed engine exposing the session API to scripts.
+///
+/// Rhai scripts cannot touch files, processes or the network; they only see
+/// the functions registered here.
+fn engine(context: &ScriptContext) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_expr_depths(64, 64);
+    engine.set_max_call_levels(64);
+    engine.set_max_string_size(1024 * 1024);
+    engine.set_max_array_size(64 * 1024);
+
+    let cancel = Arc::clone(&context.cancel);
+    engine.on_progress(move |_| {
+        cancel
+            .load(Ordering::SeqCst)
+            .then(|| Dynamic::from("cancelled"))
+    });
+    let ctx = context.clone();
+    engine.on_print(move |text| ctx.log(text));
+    let ctx = context.clone();
+    engine.on_debug(move |text, _, _| ctx.log(text));
+
+    let ctx = context.clone();
+    engine.register_fn("log", move |text: &str| ctx.log(text));
+    let ctx = context.clone();
+    engine.register_fn("send", move |text: &str| {
+        ctx.request(ScriptRequest::Send(text.as_bytes().to_vec()))
+    });
+    let ctx = context.clone();
+    engine.register_fn("send", move |data: Blob| {
+        ctx.request(ScriptRequest::Send(data))
+    });
+    let ctx = context.clone();
+    engine.register_fn("send_hex", move |hex: &str| -> ScriptResult<()> {
+        let data = send_panel::parse_hex(hex).map_err(|err| err.to_string())?;
+        ctx.request(ScriptRequest::Send(data))
+    });
+    let ctx = context.clone();
+    engine.register_fn("set_dtr", move |enabled: bool| {
+        ctx.request(ScriptRequest::SetDtr(enabled))
+    });
+    let ctx = context.clone();
+    engine.register_fn("set_rts", move |enabled: bool| {
+        ctx.request(ScriptRequest::SetRts(enabled))
+    });
+    let ctx = context.clone();
+    engine.register_fn("sleep", move |millis: i64| ctx.sleep(millis));
+    let ctx = context.clone();
+    engine.register_fn("wait_for", move |pattern: &str, millis: i64| {
+        ctx.wait_for(pattern, millis)
+    });
+    let ctx = context.clone();
+    engine.register_fn("wait_frame", move |millis: i64| ctx.wait_frame(millis));
+    let ctx = context.clone();
+    engine.register_fn("set_decoder", move |name: &str| ctx.set_decoder(name));
+    engine
+}
+
+/// A script running on its own thread against the owner's session.
+///
+/// Like [`SerialServer`](crate::net_server::SerialServer), the script never
+/// touches the session: received data is pushed in with
+/// [`ScriptRunner::feed`] and its writes and line changes come back as
+/// [`ScriptRequest`]s from [`ScriptRunner::poll`].
+pub struct ScriptRunner {
+    name: String,
+    input: Sender<Vec<u8>>,
+    output: Receiver<ScriptOutput>,
+    cancel: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+    finished: bool,
+}
+
+impl ScriptRunner {
+    /// Starts `source`; `decoder` picks the initial framing for `wait_frame`.
+    pub fn start(name: &str, source: &str, decoder: DecoderKind) -> Self {
+        let (input, inbox) = mpsc::channel();
+        let (output_tx, output) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let worker_cancel = Arc::clone(&cancel);
+        let source = source.to_string();
+        let worker = thread::spawn(move || {
+            let context = ScriptContext {
+                inbox: Rc::new(RefCell::new(Inbox {
+                    input: inbox,
+                    pending: Vec::new(),
+                    frames: VecDeque::new(),
+                    decoder: FrameDecoder::new(decoder),
+                })),
+                output: output_tx.clone(),
+                cancel: worker_cancel,
+            };
+            let result = engine(&context).run(&source).map_err(|err| {
+                let terminated = matches!(*err, EvalAltResult::ErrorTerminated(..));
+                if terminated || context.cancel.load(Ordering::SeqCst) {
+                    "cancelled".to_string()
+                } else {
+                    err.to_string()
+                }
+            });
+            let _ = output_tx.send(ScriptOutput::Finished(result));
+        });
+        Self {
+            name: name.to_string(),
+            input,
+            output,
+            cancel,
+            worker: Some(worker),
+            finished: false,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Hands bytes received from the device to the script.
+    pub fn feed(&self, data: &[u8]) {
+        let _ = self.input.send(data.to_vec());
+    }
+
+    pub fn poll(&mut self) -> Vec<ScriptOutput> {
+        let mut outputs = Vec::new();
+        while let Ok(output) = self.output.try_recv() {
+            if matches!(output, ScriptOutput::Finished(_)) {
+                self.finished = true;
+            }
+            outputs.push(output);
+        }
+        outputs
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Asks the script to stop; it ends at its next statement or blocking call.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Drop for ScriptRunner {
+    fn drop(&mut self) {
+        self.cancel();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_to_end(runner: &mut ScriptRunner) -> Vec<ScriptOutput> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut outputs = Vec::new();
+        while !runner.is_finished() && Instant::now() < deadline {
+            outputs.extend(runner.poll());
+            thread::sleep(Duration::from_millis(5));
+        }
+        outputs
+    }
+
+    #[test]
+    fn script_sends_and_waits_for_responses() {
+        let source = r#"
+            send("AT\r");
+            let reply = wait_for("OK|ERROR", 2000);
+            log(`reply: ${reply}`);
+            send_hex("01 02");
+            set_dtr(false);
+            let frame = wait_frame(2000);
+            print(frame.len());
+            if wait_for("never", 10) == () { log("timed out"); }
+        "#;
+        let mut runner = ScriptRunner::start("test", source, DecoderKind::Lines);
+        runner.feed(b"AT\r\r\nO");
+        runner.feed(b"K\r\nline two\r\n");
+        let outputs = run_to_end(&mut runner);
+        assert_eq!(
+            outputs,
+            vec![
+                ScriptOutput::Request(ScriptRequest::Send(b"AT\r".to_vec())),
+                ScriptOutput::Log("reply: OK".to_string()),
+                ScriptOutput::Request(ScriptRequest::Send(vec![1, 2])),
+                ScriptOutput::Request(ScriptRequest::SetDtr(false)),
+                // The first line is the echo "AT\r\r\n" with one CR stripped.
+                ScriptOutput::Log("3".to_string()),
+                ScriptOutput::Log("timed out".to_string()),
+                ScriptOutput::Finished(Ok(())),
+            ]
+        );
+    }
+
+    #[test]
+    fn cancel_stops_blocked_and_busy_scripts() {
+        for source in ["sleep(60000);", "loop { }", r#"wait_for("x", 60000);"#] {
+            let mut runner = ScriptRunner::start("stuck", source, DecoderKind::None);
+            thread::sleep(Duration::from_millis(50));
+            runner.cancel();
+            let outputs = run_to_end(&mut runner);
+            assert_eq!(
+                outputs.last(),
+                Some(&ScriptOutput::Finished(Err("cancelled".to_string())))
+            );
+        }
+    }
+}
@@ -1,11 +1,19 @@
 use std::collections::VecDeque;
+use std::str::FromStr;
 
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
 use thiserror::Error;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use crate::checksum::Checksum;
+use crate::template::{self, TemplateContext, TemplateError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
 pub enum SendMode {
     Text,
     Hex,
+    /// Text with placeholders such as `{seq:u16le}`, expanded at send time.
+    Template,
 }
 
 impl SendMode {
@@ -13,22 +21,157 @@ impl SendMode {
         match self {
             SendMode::Text => "Text",
             SendMode::Hex => "Hex",
+            SendMode::Template => "Template",
+        }
+    }
+
+    /// Builds the payload for `value`; `options` apply to text and templates, `context` to templates.
+    ///
+    /// The checksum goes after the data but before the line ending.
+    pub fn build(
+        self,
+        value: &str,
+        options: &TextOptions,
+        checksum: Option<&Checksum>,
+        context: &TemplateContext,
+    ) -> Result<Payload, PayloadError> {
+        let mut bytes = match self {
+            SendMode::Text => encode_text(value, options)?,
+            SendMode::Hex => parse_hex(value)?,
+            SendMode::Template => template::expand(value, options, context)?,
+        };
+        let checksum = match checksum {
+            Some(checksum) => {
+                let value = checksum
+                    .compute(&bytes)
+                    .ok_or(PayloadError::ChecksumRange)?;
+                bytes.extend(checksum.to_bytes(value));
+                Some(value)
+            }
+            None => None,
+        };
+        if let (SendMode::Text | SendMode::Template, Some(ending)) = (self, options.ending) {
+            bytes.extend_from_slice(ending.bytes());
         }
+        Ok(Payload { bytes, checksum })
     }
 }
 
-#[derive(Debug, Clone)]
+/// Bytes ready to send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payload {
+    pub bytes: Vec<u8>,
+    /// The checksum value that was appended, if any.
+    pub checksum: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumIter, Display, Serialize, Deserialize)]
+pub enum LineEnding {
+    #[default]
+    #[strum(to_string = "cr")]
+    Cr,
+    #[strum(to_string = "lf")]
+    Lf,
+    #[strum(to_string = "crlf")]
+    CrLf,
+}
+
+impl FromStr for LineEnding {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "cr" => Ok(Self::Cr),
+            "lf" => Ok(Self::Lf),
+            "crlf" => Ok(Self::CrLf),
+            _ => Err(()),
+        }
+    }
+}
+
+impl LineEnding {
+    pub fn bytes(self) -> &'static [u8] {
+        match self {
+            Self::Cr => b"\r",
+            Self::Lf => b"\n",
+            Self::CrLf => b"\r\n",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum TextEncoding {
+    #[default]
+    #[strum(to_string = "UTF-8")]
+    Utf8,
+    #[strum(to_string = "Latin-1")]
+    Latin1,
+    /// Rejects anything outside 7-bit ASCII.
+    #[strum(to_string = "ASCII")]
+    Ascii,
+}
+
+/// How text payloads are turned into bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextOptions {
+    /// Appended to every text payload.
+    pub ending: Option<LineEnding>,
+    /// Interpret C-style escapes such as `\r`, `\x1b` and `\0`.
+    pub escapes: bool,
+    pub encoding: TextEncoding,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            ending: None,
+            escapes: false,
+            encoding: TextEncoding::Utf8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct HistoryEntry {
     pub value: String,
     pub mode: SendMode,
+    #[serde(default)]
     pub favorited: bool,
+    #[serde(default)]
+    pub checksum: Option<Checksum>,
+}
+
+impl HistoryEntry {
+    fn same_payload(&self, other: &HistoryEntry) -> bool {
+        self.value == other.value && self.mode == other.mode && self.checksum == other.checksum
+    }
+}
+
+/// How the send history is kept; the entries live on each profile.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistorySettings {
+    /// Entries kept per profile. Favorites are never dropped to make room.
+    pub limit: usize,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self { limit: 50 }
+    }
 }
 
 pub struct SendPanelState {
     pub input: String,
     pub mode: SendMode,
+    /// Appended to every payload when set.
+    pub checksum: Option<Checksum>,
     pub history: VecDeque<HistoryEntry>,
-    pub max_history: usize,
+    /// Filters the history list.
+    pub search: String,
+    /// History index shown by Up/Down recall.
+    recall: Option<usize>,
+    /// What was typed before recall started.
+    draft: String,
 }
 
 impl SendPanelState {
@@ -36,7 +179,9 @@ impl SendPanelState {
         Self::default()
     }
 
-    pub fn push_history(&mut self, value: String) {
+    /// Adds a sent payload, unless it repeats the newest entry, keeping at most `limit`.
+    pub fn push_history(&mut self, value: String, limit: usize) {
+        self.recall = None;
         if value.trim().is_empty() {
             return;
         }
@@ -44,14 +189,88 @@ impl SendPanelState {
             value: value.clone(),
             mode: self.mode,
             favorited: false,
+            checksum: self.checksum.clone(),
         };
-        self.history.push_front(entry);
-        while self.history.len() > self.max_history {
-            self.history.pop_back();
+        if !self
+            .history
+            .front()
+            .is_some_and(|newest| newest.same_payload(&entry))
+        {
+            self.history.push_front(entry);
+            self.trim_history(limit);
         }
         self.input.clear();
     }
 
+    /// Drops the oldest entries that are not favorites until the history fits `limit`.
+    pub fn trim_history(&mut self, limit: usize) {
+        while self.history.len() > limit {
+            let Some(oldest) = self.history.iter().rposition(|entry| !entry.favorited) else {
+                break;
+            };
+            self.history.remove(oldest);
+        }
+    }
+
+    /// Replaces the history, e.g. with the one saved for another profile.
+    pub fn load_history(&mut self, entries: &[HistoryEntry], limit: usize) {
+        self.history = entries.iter().cloned().collect();
+        self.recall = None;
+        self.trim_history(limit);
+    }
+
+    /// Removes every entry that is not a favorite.
+    pub fn clear_history(&mut self) {
+        self.history.retain(|entry| entry.favorited);
+        self.recall = None;
+    }
+
+    /// Puts an entry into the input, with its mode and checksum.
+    pub fn select(&mut self, index: usize) {
+        if let Some(entry) = self.history.get(index) {
+            self.input = entry.value.clone();
+            self.mode = entry.mode;
+            self.checksum = entry.checksum.clone();
+        }
+    }
+
+    /// Shows the next older entry, like Up in a shell.
+    pub fn recall_older(&mut self) {
+        let next = self.recall.map_or(0, |index| index + 1);
+        if next >= self.history.len() {
+            return;
+        }
+        if self.recall.is_none() {
+            self.draft = self.input.clone();
+        }
+        self.recall = Some(next);
+        self.select(next);
+    }
+
+    /// Shows the next newer entry, and finally what was typed before, like Down in a shell.
+    pub fn recall_newer(&mut self) {
+        match self.recall {
+            None => {}
+            Some(0) => {
+                self.recall = None;
+                self.input = std::mem::take(&mut self.draft);
+            }
+            Some(index) => {
+                self.recall = Some(index - 1);
+                self.select(index - 1);
+            }
+        }
+    }
+
+    /// Entries whose payload contains `search`, ignoring case, with their indices.
+    pub fn matching_history(&self) -> impl Iterator<Item = (usize, &HistoryEntry)> {
+        let query = self.search.trim().to_lowercase();
+        self.history
+            .iter()
+            .enumerate()
+            .filter(move |(_, entry)| entry.value.to_lowercase().contains(&query))
+    }
+
     pub fn toggle_favorite(&mut self, index: usize) {
         if let Some(entry) = self.history.get_mut(index) {
             entry.favorited = !entry.favorited;
@@ -62,11 +281,14 @@ impl SendPanelState {
         self.history.iter().filter(|entry| entry.favorited)
     }
 
-    pub fn parse_payload(&self, value: &str) -> Result<Vec<u8>, PayloadError> {
-        match self.mode {
-            SendMode::Text => Ok(value.as_bytes().to_vec()),
-            SendMode::Hex => parse_hex(value),
-        }
+    pub fn parse_payload(
+        &self,
+        value: &str,
+        options: &TextOptions,
+        context: &TemplateContext,
+    ) -> Result<Payload, PayloadError> {
+        self.mode
+            .build(value, options, self.checksum.as_ref(), context)
     }
 }
 
@@ -75,8 +297,11 @@ impl Default for SendPanelState {
         Self {
             input: String::new(),
             mode: SendMode::Text,
+            checksum: None,
             history: VecDeque::new(),
-            max_history: 50,
+            search: String::new(),
+            recall: None,
+            draft: String::new(),
         }
     }
 }
@@ -85,9 +310,90 @@ impl Default for SendPanelState {
 pub enum PayloadError {
     #[error("invalid hex sequence")]
     InvalidHex,
+    #[error("invalid escape sequence `{0}`")]
+    InvalidEscape(String),
+    #[error("`{character}` cannot be sent as {encoding}")]
+    Unencodable {
+        character: char,
+        encoding: TextEncoding,
+    },
+    #[error("payload is shorter than the bytes skipped by the checksum")]
+    ChecksumRange,
+    #[error(transparent)]
+    Template(#[from] TemplateError),
 }
 
-fn parse_hex(input: &str) -> Result<Vec<u8>, PayloadError> {
+/// Encodes text with the chosen encoding and escapes, without the line ending.
+///
+/// Escapes produce raw bytes, so `\xff` is one byte even in UTF-8.
+pub fn encode_text(value: &str, options: &TextOptions) -> Result<Vec<u8>, PayloadError> {
+    let mut bytes = Vec::with_capacity(value.len() + 2);
+    let mut chars = value.chars().peekable();
+    while let Some(character) = chars.next() {
+        if character == '\\' && options.escapes {
+            bytes.push(parse_escape(&mut chars)?);
+            continue;
+        }
+        match options.encoding {
+            TextEncoding::Utf8 => {
+                bytes.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes())
+            }
+            TextEncoding::Latin1 if u32::from(character) <= 0xFF => bytes.push(character as u8),
+            TextEncoding::Ascii if character.is_ascii() => bytes.push(character as u8),
+            encoding => {
+                return Err(PayloadError::Unencodable {
+                    character,
+                    encoding,
+                });
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+/// Parses the escape after a backslash: `\xHH`, octal `\0`..`\377`, or a C letter escape.
+fn parse_escape(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<u8, PayloadError> {
+    let Some(escape) = chars.next() else {
+        return Err(PayloadError::InvalidEscape("\\".to_string()));
+    };
+    let byte = match escape {
+        'r' => b'\r',
+        'n' => b'\n',
+        't' => b'\t',
+        'a' => 0x07,
+        'b' => 0x08,
+        'e' => 0x1B,
+        'f' => 0x0C,
+        'v' => 0x0B,
+        '\\' | '\'' | '"' | '?' => escape as u8,
+        'x' => {
+            let mut digits = String::new();
+            while digits.len() < 2 {
+                match chars.next_if(char::is_ascii_hexdigit) {
+                    Some(digit) => digits.push(digit),
+                    None => break,
+                }
+            }
+            u8::from_str_radix(&digits, 16)
+                .map_err(|_| PayloadError::InvalidEscape(format!("\\x{digits}")))?
+        }
+        '0'..='7' => {
+            let mut digits = escape.to_string();
+            while digits.len() < 3 {
+                match chars.next_if(|digit| ('0'..='7').contains(digit)) {
+                    Some(digit) => digits.push(digit),
+                    None => break,
+                }
+            }
+            u8::from_str_radix(&digits, 8)
+                .map_err(|_| PayloadError::InvalidEscape(format!("\\{digits}")))?
+        }
+        other => return Err(PayloadError::InvalidEscape(format!("\\{other}"))),
+    };
+    Ok(byte)
+}
+
+pub fn parse_hex(input: &str) -> Result<Vec<u8>, PayloadError> {
     let mut bytes = Vec::new();
     let mut buffer = String::new();
     for ch in input.chars() {
@@ -112,7 +418,8 @@ fn parse_hex(input: &str) -> Result<Vec<u8>, PayloadError> {
 
 #[cfg(test)]
 mod tests {
-    use super::{SendMode, SendPanelState, parse_hex};
+    use super::*;
+    use crate::checksum::ChecksumAlgorithm;
 
     #[test]
     fn parse_hex_accepts_whitespace() {
@@ -130,8 +437,157 @@ mod tests {
         let mut panel = SendPanelState::new();
         panel.mode = SendMode::Text;
         panel.input = "hello".to_string();
-        panel.push_history(panel.input.clone());
+        panel.push_history(panel.input.clone(), 50);
         assert_eq!(panel.history.len(), 1);
         assert_eq!(panel.history.front().unwrap().value, "hello");
     }
+
+    #[test]
+    fn history_dedups_keeps_favorites_and_recalls() {
+        let mut panel = SendPanelState::new();
+        for value in ["AT", "AT", "ATI", "AT+CSQ", "ATZ"] {
+            panel.push_history(value.to_string(), 3);
+        }
+        let values = |panel: &SendPanelState| -> Vec<String> {
+            panel
+                .history
+                .iter()
+                .map(|entry| entry.value.clone())
+                .collect()
+        };
+        assert_eq!(values(&panel), ["ATZ", "AT+CSQ", "ATI"]);
+
+        panel.toggle_favorite(2);
+        panel.push_history("ATE0".to_string(), 3);
+        assert_eq!(values(&panel), ["ATE0", "ATZ", "ATI"]);
+        // Same text in another mode is a different entry.
+        panel.mode = SendMode::Hex;
+        panel.push_history("ATZ".to_string(), 3);
+        panel.push_history("ATZ".to_string(), 3);
+        assert_eq!(panel.history.len(), 3);
+        assert_eq!(panel.history[0].mode, SendMode::Hex);
+
+        panel.mode = SendMode::Text;
+        panel.input = "draft".to_string();
+        panel.recall_older();
+        assert_eq!((panel.input.as_str(), panel.mode), ("ATZ", SendMode::Hex));
+        panel.recall_older();
+        panel.recall_older();
+        panel.recall_older();
+        assert_eq!(panel.input, "ATI");
+        panel.recall_newer();
+        assert_eq!(panel.input, "ATE0");
+        panel.recall_newer();
+        panel.recall_newer();
+        assert_eq!(panel.input, "draft");
+
+        panel.search = "at".to_string();
+        assert_eq!(panel.matching_history().count(), 3);
+        panel.search = "E0".to_string();
+        assert_eq!(
+            panel
+                .matching_history()
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>(),
+            [1]
+        );
+
+        panel.clear_history();
+        assert_eq!(values(&panel), ["ATI"]);
+    }
+
+    #[test]
+    fn text_escapes_endings_and_encodings() {
+        let context = TemplateContext::default();
+        let options = TextOptions {
+            ending: Some(LineEnding::CrLf),
+            escapes: true,
+            ..TextOptions::default()
+        };
+        assert_eq!(
+            SendMode::Text
+                .build(r"AT\x1b[0m\0\101\\é", &options, None, &context)
+                .expect("encode")
+                .bytes,
+            b"AT\x1b[0m\0A\\\xc3\xa9\r\n"
+        );
+        // Escapes are opt-in, so saved profiles keep sending text verbatim.
+        let verbatim = TextOptions::default();
+        assert_eq!(encode_text(r"C:\n", &verbatim).expect("encode"), br"C:\n");
+
+        let latin1 = TextOptions {
+            encoding: TextEncoding::Latin1,
+            escapes: true,
+            ..TextOptions::default()
+        };
+        assert_eq!(
+            encode_text("é\\xff", &latin1).expect("encode"),
+            [0xE9, 0xFF]
+        );
+        let ascii = TextOptions {
+            encoding: TextEncoding::Ascii,
+            ..TextOptions::default()
+        };
+        assert_eq!(
+            encode_text("é", &ascii).unwrap_err().to_string(),
+            "`é` cannot be sent as ASCII"
+        );
+        assert!(matches!(
+            encode_text(r"\q", &options),
+            Err(PayloadError::InvalidEscape(_))
+        ));
+        assert!(encode_text(r"\xg", &options).is_err());
+        assert!(encode_text("trailing\\", &options).is_err());
+    }
+
+    #[test]
+    fn checksum_goes_before_the_line_ending() {
+        let modbus = Checksum::default();
+        let payload = SendMode::Hex
+            .build(
+                "01 03 00 00 00 0A",
+                &TextOptions::default(),
+                Some(&modbus),
+                &TemplateContext::default(),
+            )
+            .expect("build");
+        assert_eq!(
+            payload.bytes,
+            [0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD]
+        );
+        assert_eq!(payload.checksum, Some(0xCDC5));
+
+        let xor = Checksum {
+            algorithm: ChecksumAlgorithm::Xor8,
+            skip_start: 1,
+            ..Checksum::default()
+        };
+        let options = TextOptions {
+            ending: Some(LineEnding::CrLf),
+            ..TextOptions::default()
+        };
+        let payload = SendMode::Text
+            .build("$AB", &options, Some(&xor), &TemplateContext::default())
+            .expect("build");
+        assert_eq!(payload.bytes, [b'$', b'A', b'B', b'A' ^ b'B', b'\r', b'\n']);
+        assert!(matches!(
+            SendMode::Text.build("", &options, Some(&xor), &TemplateContext::default()),
+            Err(PayloadError::ChecksumRange)
+        ));
+
+        let mut panel = SendPanelState::new();
+        panel.checksum = Some(xor.clone());
+        panel.input = "$AB".to_string();
+        panel.push_history(panel.input.clone(), 50);
+        assert_eq!(panel.history[0].checksum, panel.checksum);
+
+        let context = TemplateContext {
+            seq: 2,
+            ..TemplateContext::default()
+        };
+        let payload = SendMode::Template
+            .build("${seq:u8}", &options, Some(&xor), &context)
+            .expect("build");
+        assert_eq!(payload.bytes, [b'$', 2, 2, b'\r', b'\n']);
+    }
 }
@@ -1,6 +1,17 @@
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::core::{SerialConfig, SerialPort};
+use crate::core::{ModemLines, SerialConfig, SerialPort};
+#[cfg(unix)]
+use crate::daemon::DaemonSettings;
+#[cfg(unix)]
+use crate::daemon_client::DaemonPort;
+use crate::net_client::{NetworkPort, RemoteTarget};
+use crate::recorder::{RecordPayload, Recorder, RecordingError, RecordingHeader};
 use thiserror::Error;
 
 #[derive(Debug, Clone)]
@@ -15,13 +26,41 @@ pub struct SessionEvent {
     pub message: String,
 }
 
+enum Transport {
+    Serial(SerialPort),
+    Network(NetworkPort),
+    #[cfg(unix)]
+    Daemon(DaemonPort),
+}
+
+type SharedRecorder = Arc<Mutex<Option<Recorder>>>;
+
+/// How often a recording session samples the input lines for changes.
+const LINE_SAMPLE_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Progress of the recording attached to a session.
+#[derive(Debug, Clone)]
+pub struct RecordingStatus {
+    pub path: PathBuf,
+    pub records: u64,
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+/// A running connection to a local port, a `tcp://`/`rfc2217://` target or
+/// a port owned by the daemon.
 pub struct SerialSession {
-    port: SerialPort,
+    transport: Transport,
+    config: SerialConfig,
     rx: Receiver<SessionMessage>,
-    _tx: Sender<SessionMessage>,
+    tx: Sender<SessionMessage>,
+    recorder: SharedRecorder,
+    /// Line state last written to the recording, and when it was sampled.
+    recorded_lines: Cell<Option<ModemLines>>,
+    lines_sampled: Cell<Instant>,
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
 pub enum SessionError {
     #[error("open failed with code {0}")]
     Open(i32),
@@ -33,28 +72,202 @@ pub enum SessionError {
     Write(i32),
     #[error("write truncated")]
     Truncated,
+    #[error("modem control failed with code {0}")]
+    Control(i32),
 }
 
 impl SerialSession {
     pub fn open(path: &str, config: &SerialConfig) -> Result<Self, SessionError> {
-        let mut port = SerialPort::open(path).map_err(SessionError::Open)?;
-        port.configure(config).map_err(SessionError::Configure)?;
         let (tx, rx) = mpsc::channel();
-        let data_tx = tx.clone();
-        let event_tx = tx.clone();
+        let recorder = SharedRecorder::default();
+        let transport = match RemoteTarget::parse(path) {
+            Some(target) => {
+                let mut port = NetworkPort::connect(&target).map_err(SessionError::Open)?;
+                port.configure(config).map_err(SessionError::Configure)?;
+                port.start(
+                    data_sink(tx.clone(), Arc::clone(&recorder)),
+                    event_sink(tx.clone(), Arc::clone(&recorder)),
+                )
+                .map_err(SessionError::Start)?;
+                Transport::Network(port)
+            }
+            None => {
+                let mut port = SerialPort::open(path).map_err(SessionError::Open)?;
+                port.configure(config).map_err(SessionError::Configure)?;
+                start_port(&mut port, &tx, &recorder)?;
+                Transport::Serial(port)
+            }
+        };
+        Ok(Self {
+            transport,
+            config: config.clone(),
+            rx,
+            tx,
+            recorder,
+            recorded_lines: Cell::new(None),
+            lines_sampled: Cell::new(Instant::now()),
+        })
+    }
+
+    /// Opens `path` through the daemon reachable with `daemon`.
+    ///
+    /// A port the daemon already has open is joined as-is, so the session
+    /// adopts its current configuration instead of applying `config`.
+    #[cfg(unix)]
+    pub fn attach(
+        daemon: &DaemonSettings,
+        path: &str,
+        config: &SerialConfig,
+    ) -> Result<Self, SessionError> {
+        let (tx, rx) = mpsc::channel();
+        let recorder = SharedRecorder::default();
+        let mut port = DaemonPort::open(daemon, path, config).map_err(SessionError::Open)?;
         port.start(
-            move |bytes| {
-                let _ = data_tx.send(SessionMessage::Data(bytes.to_vec()));
-            },
-            move |code, message| {
-                let _ = event_tx.send(SessionMessage::Event(SessionEvent {
-                    code,
-                    message: message.to_string(),
-                }));
-            },
+            data_sink(tx.clone(), Arc::clone(&recorder)),
+            event_sink(tx.clone(), Arc::clone(&recorder)),
         )
         .map_err(SessionError::Start)?;
-        Ok(Self { port, rx, _tx: tx })
+        Ok(Self {
+            config: port.config().clone(),
+            transport: Transport::Daemon(port),
+            rx,
+            tx,
+            recorder,
+            recorded_lines: Cell::new(None),
+            lines_sampled: Cell::new(Instant::now()),
+        })
+    }
+
+    /// Streams all further traffic, events and line changes to `path`.
+    ///
+    /// Received chunks are recorded from the I/O thread as they arrive, so
+    /// timestamps do not depend on how often the session is polled. Changes
+    /// of the input lines (CTS, DSR, DCD, RI) are sampled from [`Self::poll`].
+    pub fn start_recording(
+        &mut self,
+        path: impl AsRef<Path>,
+        header: &RecordingHeader,
+    ) -> Result<(), RecordingError> {
+        let mut recorder = Recorder::create(path, header)?;
+        recorder.write(&RecordPayload::Config(self.config.clone()))?;
+        let lines = self.modem_lines().ok();
+        if let Some(lines) = lines {
+            recorder.write(&RecordPayload::ModemLines(lines))?;
+        }
+        self.recorded_lines.set(lines);
+        if let Ok(mut guard) = self.recorder.lock() {
+            *guard = Some(recorder);
+        }
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> Option<RecordingStatus> {
+        let recorder = self.recorder.lock().ok()?.take()?;
+        Some(recording_status(&recorder))
+    }
+
+    pub fn recording(&self) -> Option<RecordingStatus> {
+        let guard = self.recorder.lock().ok()?;
+        guard.as_ref().map(recording_status)
+    }
+
+    pub fn is_remote(&self) -> bool {
+        !matches!(self.transport, Transport::Serial(_))
+    }
+
+    /// The configuration last applied to the port.
+    pub fn config(&self) -> &SerialConfig {
+        &self.config
+    }
+
+    /// Applies a new configuration to the live port.
+    ///
+    /// Local ports restart the I/O thread around the change because the core
+    /// reallocates its ring buffers while configuring; bytes still queued for
+    /// transmit at that moment are discarded. If the port refuses the new
+    /// settings it keeps running with the previous ones. Remote ports
+    /// negotiate the change over RFC 2217 instead.
+    pub fn reconfigure(&mut self, config: &SerialConfig) -> Result<(), SessionError> {
+        match &mut self.transport {
+            Transport::Serial(port) => {
+                port.stop();
+                // The I/O thread always comes back, on the previous settings if
+                // the new ones are refused.
+                let applied = port.configure(config);
+                if applied.is_err() {
+                    let _ = port.configure(&self.config);
+                }
+                start_port(port, &self.tx, &self.recorder)?;
+                applied.map_err(SessionError::Configure)?;
+            }
+            Transport::Network(port) => {
+                port.configure(config).map_err(SessionError::Configure)?;
+            }
+            #[cfg(unix)]
+            Transport::Daemon(port) => {
+                port.configure(config).map_err(SessionError::Configure)?;
+            }
+        }
+        self.config = config.clone();
+        self.record(RecordPayload::Config(self.config.clone()));
+        Ok(())
+    }
+
+    pub fn set_dtr(&mut self, enabled: bool) -> Result<(), SessionError> {
+        self.set_modem_lines(Some(enabled), None)
+    }
+
+    pub fn set_rts(&mut self, enabled: bool) -> Result<(), SessionError> {
+        self.set_modem_lines(None, Some(enabled))
+    }
+
+    fn set_modem_lines(
+        &mut self,
+        dtr: Option<bool>,
+        rts: Option<bool>,
+    ) -> Result<(), SessionError> {
+        match &mut self.transport {
+            Transport::Serial(port) => port.set_modem_lines(dtr, rts),
+            Transport::Network(port) => port.set_modem_lines(dtr, rts),
+            #[cfg(unix)]
+            Transport::Daemon(port) => port.set_modem_lines(dtr, rts),
+        }
+        .map_err(SessionError::Control)?;
+        self.record_lines(true);
+        Ok(())
+    }
+
+    pub fn set_break(&mut self, enabled: bool) -> Result<(), SessionError> {
+        match &mut self.transport {
+            Transport::Serial(port) => port.set_break(enabled),
+            Transport::Network(port) => port.set_break(enabled),
+            #[cfg(unix)]
+            Transport::Daemon(port) => port.set_break(enabled),
+        }
+        .map_err(SessionError::Control)?;
+        let state = if enabled { "on" } else { "off" };
+        self.record(RecordPayload::Event {
+            code: 0,
+            message: format!("break {state}"),
+        });
+        Ok(())
+    }
+
+    /// Holds a break condition for `duration`, blocking the caller meanwhile.
+    pub fn send_break(&mut self, duration: Duration) -> Result<(), SessionError> {
+        self.set_break(true)?;
+        thread::sleep(duration);
+        self.set_break(false)
+    }
+
+    pub fn modem_lines(&self) -> Result<ModemLines, SessionError> {
+        match &self.transport {
+            Transport::Serial(port) => port.modem_lines(),
+            Transport::Network(port) => port.modem_lines(),
+            #[cfg(unix)]
+            Transport::Daemon(port) => port.modem_lines(),
+        }
+        .map_err(SessionError::Control)
     }
 
     pub fn poll(&self) -> Vec<SessionMessage> {
@@ -62,19 +275,128 @@ impl SerialSession {
         while let Ok(msg) = self.rx.try_recv() {
             messages.push(msg);
         }
+        if self.lines_sampled.get().elapsed() >= LINE_SAMPLE_INTERVAL {
+            self.record_lines(false);
+        }
         messages
     }
 
+    /// Records the line state while a recording runs, if it changed since the
+    /// last record or `force` is set.
+    fn record_lines(&self, force: bool) {
+        self.lines_sampled.set(Instant::now());
+        if !self.recorder.lock().is_ok_and(|guard| guard.is_some()) {
+            return;
+        }
+        let Ok(lines) = self.modem_lines() else {
+            return;
+        };
+        if self.recorded_lines.replace(Some(lines)) != Some(lines) || force {
+            self.record(RecordPayload::ModemLines(lines));
+        }
+    }
+
+    /// Written bytes still queued for the device.
+    ///
+    /// Remote transports hand data to the network right away, so they report zero.
+    pub fn tx_pending(&self) -> usize {
+        match &self.transport {
+            Transport::Serial(port) => port.tx_pending(),
+            _ => 0,
+        }
+    }
+
     pub fn write(&mut self, data: &[u8]) -> Result<(), SessionError> {
-        let written = self.port.write(data).map_err(SessionError::Write)?;
+        let written = match &mut self.transport {
+            Transport::Serial(port) => port.write(data),
+            Transport::Network(port) => port.write(data),
+            #[cfg(unix)]
+            Transport::Daemon(port) => port.write(data),
+        }
+        .map_err(SessionError::Write)?;
+        self.record(RecordPayload::Tx(data[..written].to_vec()));
         if written != data.len() {
             return Err(SessionError::Truncated);
         }
         Ok(())
     }
 
+    fn record(&self, payload: RecordPayload) {
+        record(&self.recorder, &self.tx, &payload);
+    }
+
     pub fn stop(&mut self) {
-        self.port.stop();
+        match &mut self.transport {
+            Transport::Serial(port) => port.stop(),
+            Transport::Network(port) => port.stop(),
+            #[cfg(unix)]
+            Transport::Daemon(port) => port.stop(),
+        }
+    }
+}
+
+fn start_port(
+    port: &mut SerialPort,
+    tx: &Sender<SessionMessage>,
+    recorder: &SharedRecorder,
+) -> Result<(), SessionError> {
+    port.start(
+        data_sink(tx.clone(), Arc::clone(recorder)),
+        event_sink(tx.clone(), Arc::clone(recorder)),
+    )
+    .map_err(SessionError::Start)
+}
+
+fn data_sink(
+    tx: Sender<SessionMessage>,
+    recorder: SharedRecorder,
+) -> impl FnMut(&[u8]) + Send + 'static {
+    move |bytes| {
+        record(&recorder, &tx, &RecordPayload::Rx(bytes.to_vec()));
+        let _ = tx.send(SessionMessage::Data(bytes.to_vec()));
+    }
+}
+
+fn event_sink(
+    tx: Sender<SessionMessage>,
+    recorder: SharedRecorder,
+) -> impl FnMut(i32, &str) + Send + 'static {
+    move |code, message| {
+        let event = RecordPayload::Event {
+            code,
+            message: message.to_string(),
+        };
+        record(&recorder, &tx, &event);
+        let _ = tx.send(SessionMessage::Event(SessionEvent {
+            code,
+            message: message.to_string(),
+        }));
+    }
+}
+
+/// Appends to the active recording, ending it with an event if the write fails.
+fn record(recorder: &SharedRecorder, tx: &Sender<SessionMessage>, payload: &RecordPayload) {
+    let Ok(mut guard) = recorder.lock() else {
+        return;
+    };
+    let Some(active) = guard.as_mut() else {
+        return;
+    };
+    if let Err(err) = active.write(payload) {
+        *guard = None;
+        let _ = tx.send(SessionMessage::Event(SessionEvent {
+            code: 0,
+            message: format!("recording stopped: {err}"),
+        }));
+    }
+}
+
+fn recording_status(recorder: &Recorder) -> RecordingStatus {
+    RecordingStatus {
+        path: recorder.path().to_path_buf(),
+        records: recorder.records(),
+        bytes: recorder.bytes(),
+        elapsed: recorder.elapsed(),
     }
 }
 
@@ -5,9 +5,23 @@ use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::console::ConsoleViewMode;
+use crate::console::{ConsoleSettings, ConsoleViewMode};
+#[cfg(unix)]
+use crate::daemon::DaemonSettings;
+use crate::emulator::EmulatorSettings;
+use crate::file_send::FileSendSettings;
+use crate::net_server::ServerSettings;
+use crate::pcapng::PcapSettings;
 use crate::profiles::ProfileStore;
+use crate::recorder::RecordingSettings;
+use crate::replay::ReplaySettings;
+use crate::scripting::ScriptSettings;
+use crate::send_panel::HistorySettings;
 use crate::theme::{ThemePreference, ThemeState};
+use crate::transfer::TransferSettings;
+#[cfg(unix)]
+use crate::virtual_port::VirtualPortSettings;
+use crate::websocket::WebSocketSettings;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Settings {
@@ -16,6 +30,39 @@ pub struct Settings {
     pub profiles: ProfileStore,
     pub console_view: ConsoleViewMode,
     pub show_timestamps: bool,
+    /// Memory limit and spill file of the console history.
+    #[serde(default)]
+    pub console: ConsoleSettings,
+    #[serde(default)]
+    pub server: ServerSettings,
+    /// `tcp://` and `rfc2217://` targets listed alongside local devices.
+    #[serde(default)]
+    pub remote_targets: Vec<String>,
+    #[serde(default)]
+    #[cfg(unix)]
+    pub virtual_port: VirtualPortSettings,
+    #[serde(default)]
+    pub recordings: RecordingSettings,
+    #[serde(default)]
+    pub pcap: PcapSettings,
+    #[serde(default)]
+    pub replay: ReplaySettings,
+    #[serde(default)]
+    pub emulator: EmulatorSettings,
+    #[serde(default)]
+    #[cfg(unix)]
+    pub daemon: DaemonSettings,
+    #[serde(default)]
+    pub websocket: WebSocketSettings,
+    #[serde(default)]
+    pub scripts: ScriptSettings,
+    #[serde(default)]
+    pub transfer: TransferSettings,
+    #[serde(default)]
+    pub file_send: FileSendSettings,
+    /// How many history entries each profile keeps.
+    #[serde(default)]
+    pub history: HistorySettings,
 }
 
 impl Default for Settings {
@@ -28,6 +75,22 @@ impl Default for Settings {
             profiles,
             console_view: ConsoleViewMode::Mixed,
             show_timestamps: true,
+            console: ConsoleSettings::default(),
+            server: ServerSettings::default(),
+            remote_targets: Vec::new(),
+            #[cfg(unix)]
+            virtual_port: VirtualPortSettings::default(),
+            recordings: RecordingSettings::default(),
+            pcap: PcapSettings::default(),
+            replay: ReplaySettings::default(),
+            emulator: EmulatorSettings::default(),
+            #[cfg(unix)]
+            daemon: DaemonSettings::default(),
+            websocket: WebSocketSettings::default(),
+            scripts: ScriptSettings::default(),
+            transfer: TransferSettings::default(),
+            file_send: FileSendSettings::default(),
+            history: HistorySettings::default(),
         }
     }
 }
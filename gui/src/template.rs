@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use thiserror::Error;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::send_panel::{self, PayloadError, TextOptions};
+
+/// Longest `{rand:N}` accepted.
+const MAX_RANDOM: usize = 4096;
+
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("unclosed `{{` in template")]
+    Unclosed,
+    #[error("unmatched `}}` in template; write `}}}}` for a literal brace")]
+    Unmatched,
+    #[error("unknown placeholder `{{{0}}}`")]
+    Unknown(String),
+    #[error("cannot read {path}: {source}")]
+    File {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("{0} has no lines")]
+    EmptyFile(String),
+}
+
+/// How `{seq:...}` is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IntFormat {
+    U8,
+    U16Le,
+    U16Be,
+    U32Le,
+    U32Be,
+    /// ASCII decimal.
+    Dec,
+    /// ASCII hex, upper case.
+    Hex,
+}
+
+impl IntFormat {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "u8" => IntFormat::U8,
+            "u16le" => IntFormat::U16Le,
+            "u16be" => IntFormat::U16Be,
+            "u32le" => IntFormat::U32Le,
+            "u32be" => IntFormat::U32Be,
+            "dec" => IntFormat::Dec,
+            "hex" => IntFormat::Hex,
+            _ => return None,
+        })
+    }
+
+    /// Wider values wrap to the format's width.
+    fn write(self, value: u64, out: &mut Vec<u8>) {
+        match self {
+            IntFormat::U8 => out.push(value as u8),
+            IntFormat::U16Le => out.extend((value as u16).to_le_bytes()),
+            IntFormat::U16Be => out.extend((value as u16).to_be_bytes()),
+            IntFormat::U32Le => out.extend((value as u32).to_le_bytes()),
+            IntFormat::U32Be => out.extend((value as u32).to_be_bytes()),
+            IntFormat::Dec => out.extend(value.to_string().bytes()),
+            IntFormat::Hex => out.extend(format!("{value:X}").bytes()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TimeFormat {
+    /// Seconds since 1970 in ASCII decimal.
+    Unix,
+    UnixMs,
+    /// RFC 3339 in UTC.
+    Iso,
+    /// Seconds since 1970 as a little-endian `u32`.
+    U32Le,
+    U32Be,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Seq(IntFormat),
+    Now(TimeFormat),
+    Random(usize),
+    File(String),
+}
+
+fn parse_placeholder(body: &str) -> Result<Part, TemplateError> {
+    let unknown = || TemplateError::Unknown(body.to_string());
+    let (name, arg) = body.split_once(':').unwrap_or((body, ""));
+    Ok(match name {
+        "seq" if arg.is_empty() => Part::Seq(IntFormat::Dec),
+        "seq" => Part::Seq(IntFormat::parse(arg).ok_or_else(unknown)?),
+        "now" => Part::Now(match arg {
+            "" | "unix" => TimeFormat::Unix,
+            "unix_ms" => TimeFormat::UnixMs,
+            "iso" => TimeFormat::Iso,
+            "u32le" => TimeFormat::U32Le,
+            "u32be" => TimeFormat::U32Be,
+            _ => return Err(unknown()),
+        }),
+        "rand" => match arg.parse() {
+            Ok(len @ 1..=MAX_RANDOM) => Part::Random(len),
+            _ => return Err(unknown()),
+        },
+        "file" if !arg.is_empty() => Part::File(arg.to_string()),
+        _ => return Err(unknown()),
+    })
+}
+
+/// Splits a template into literal text and placeholders. `{{` and `}}` are literal braces.
+fn parse(template: &str) -> Result<Vec<Part>, TemplateError> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(character) = chars.next() {
+        match character {
+            '{' if chars.next_if_eq(&'{').is_some() => text.push('{'),
+            '}' if chars.next_if_eq(&'}').is_some() => text.push('}'),
+            '}' => return Err(TemplateError::Unmatched),
+            '{' => {
+                let mut body = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(character) => body.push(character),
+                        None => return Err(TemplateError::Unclosed),
+                    }
+                }
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(parse_placeholder(&body)?);
+            }
+            _ => text.push(character),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Ok(parts)
+}
+
+/// The values one expansion of a template uses.
+#[derive(Clone, Debug)]
+pub struct TemplateContext {
+    /// Sends of this template so far, starting at 0.
+    pub seq: u64,
+    pub now: OffsetDateTime,
+    /// Seeds `{rand:N}`.
+    pub seed: u64,
+}
+
+impl Default for TemplateContext {
+    fn default() -> Self {
+        Self {
+            seq: 0,
+            now: OffsetDateTime::UNIX_EPOCH,
+            seed: 1,
+        }
+    }
+}
+
+/// xorshift64*; plenty for test data.
+fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+}
+
+/// Expands `template`. Literal text is encoded with `options`, without the line ending.
+pub fn expand(
+    template: &str,
+    options: &TextOptions,
+    context: &TemplateContext,
+) -> Result<Vec<u8>, PayloadError> {
+    let mut out = Vec::new();
+    let mut random = context.seed.max(1);
+    for part in parse(template)? {
+        match part {
+            Part::Text(text) => out.extend(send_panel::encode_text(&text, options)?),
+            Part::Seq(format) => format.write(context.seq, &mut out),
+            Part::Now(format) => {
+                let seconds = context.now.unix_timestamp();
+                match format {
+                    TimeFormat::Unix => out.extend(seconds.to_string().bytes()),
+                    TimeFormat::UnixMs => out.extend(
+                        (context.now.unix_timestamp_nanos() / 1_000_000)
+                            .to_string()
+                            .bytes(),
+                    ),
+                    TimeFormat::Iso => out.extend(
+                        context
+                            .now
+                            .replace_nanosecond(0)
+                            .unwrap_or(context.now)
+                            .format(&Rfc3339)
+                            .unwrap_or_default()
+                            .bytes(),
+                    ),
+                    TimeFormat::U32Le => IntFormat::U32Le.write(seconds as u64, &mut out),
+                    TimeFormat::U32Be => IntFormat::U32Be.write(seconds as u64, &mut out),
+                }
+            }
+            Part::Random(len) => {
+                out.extend((0..len).map(|_| (next_random(&mut random) >> 56) as u8));
+            }
+            Part::File(path) => {
+                let contents =
+                    std::fs::read_to_string(&path).map_err(|source| TemplateError::File {
+                        path: path.clone(),
+                        source,
+                    })?;
+                let lines: Vec<&str> = contents
+                    .lines()
+                    .map(str::trim_end)
+                    .filter(|line| !line.is_empty())
+                    .collect();
+                if lines.is_empty() {
+                    return Err(TemplateError::EmptyFile(path).into());
+                }
+                let line = lines[(context.seq % lines.len() as u64) as usize];
+                // Values from the file are data, not escapes.
+                let verbatim = TextOptions {
+                    escapes: false,
+                    ..options.clone()
+                };
+                out.extend(send_panel::encode_text(line, &verbatim)?);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Counters of every template sent this session, keyed by the template text.
+#[derive(Debug)]
+pub struct TemplateCounters {
+    counters: HashMap<String, u64>,
+    seed: u64,
+}
+
+impl Default for TemplateCounters {
+    fn default() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Self {
+            counters: HashMap::new(),
+            seed: nanos | 1,
+        }
+    }
+}
+
+impl TemplateCounters {
+    pub fn count(&self, template: &str) -> u64 {
+        self.counters.get(template).copied().unwrap_or(0)
+    }
+
+    /// The context the next send of `template` will use; it does not change until [`Self::advance`].
+    pub fn context(&self, template: &str) -> TemplateContext {
+        TemplateContext {
+            seq: self.count(template),
+            now: OffsetDateTime::now_utc(),
+            seed: self.seed,
+        }
+    }
+
+    /// Records a send of `template`.
+    pub fn advance(&mut self, template: &str) {
+        *self.counters.entry(template.to_string()).or_default() += 1;
+        next_random(&mut self.seed);
+    }
+
+    pub fn reset(&mut self, template: &str) {
+        self.counters.remove(template);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_expand_with_literal_text() {
+        let context = TemplateContext {
+            seq: 0x1234,
+            now: OffsetDateTime::from_unix_timestamp(1_700_000_000).expect("time"),
+            seed: 7,
+        };
+        let options = TextOptions {
+            escapes: true,
+            ..TextOptions::default()
+        };
+        assert_eq!(
+            expand(
+                r"\x02{seq:u16le}{seq:u16be}|{seq}|{seq:hex}",
+                &options,
+                &context
+            )
+            .expect("expand"),
+            b"\x02\x34\x12\x12\x34|4660|1234"
+        );
+        assert_eq!(
+            expand("{now:unix} {now:iso} {{x}}", &options, &context).expect("expand"),
+            b"1700000000 2023-11-14T22:13:20Z {x}"
+        );
+        assert_eq!(
+            expand("{now:u32be}", &options, &context).expect("expand"),
+            1_700_000_000u32.to_be_bytes()
+        );
+
+        let random = expand("{rand:4}", &options, &context).expect("expand");
+        assert_eq!(random.len(), 4);
+        assert_eq!(
+            expand("{rand:4}", &options, &context).expect("expand"),
+            random,
+            "the same seed gives the same bytes"
+        );
+
+        for broken in ["{seq:u12}", "{rand:0}", "{open", "close}", "{file:}"] {
+            assert!(expand(broken, &options, &context).is_err(), "{broken}");
+        }
+    }
+
+    #[test]
+    fn counters_and_file_lines_advance_per_template() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("serials.csv");
+        std::fs::write(&path, "SN001\r\nSN002\n\n").expect("write");
+        let template = format!("SET {{file:{}}} #{{seq}}", path.display());
+
+        let mut counters = TemplateCounters::default();
+        let options = TextOptions::default();
+        let mut sent = Vec::new();
+        for _ in 0..3 {
+            let context = counters.context(&template);
+            sent.push(expand(&template, &options, &context).expect("expand"));
+            counters.advance(&template);
+        }
+        assert_eq!(
+            sent,
+            [
+                b"SET SN001 #0".to_vec(),
+                b"SET SN002 #1".to_vec(),
+                b"SET SN001 #2".to_vec()
+            ]
+        );
+        assert_eq!(counters.count(&template), 3);
+        assert_eq!(counters.count("other {seq}"), 0);
+        counters.reset(&template);
+        assert_eq!(counters.context(&template).seq, 0);
+
+        let missing = "{file:/nonexistent/values.csv}";
+        assert!(matches!(
+            expand(missing, &options, &counters.context(missing)),
+            Err(PayloadError::Template(TemplateError::File { .. }))
+        ));
+    }
+}
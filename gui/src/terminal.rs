@@ -0,0 +1,475 @@
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::cli::{CliError, PortOptions};
+use crate::core::SerialConfig;
+use crate::pcapng;
+use crate::send_panel::LineEnding;
+use crate::session::{SerialSession, SessionMessage};
+
+/// Ctrl-T, as in miniterm.
+pub const MENU_KEY: u8 = 0x14;
+/// Ctrl-].
+pub const QUIT_KEY: u8 = 0x1d;
+const BREAK_DURATION: Duration = Duration::from_millis(250);
+const INPUT_TICK: Duration = Duration::from_millis(10);
+
+const HELP: &str = "\
+Ctrl-]        quit
+Ctrl-T b      change baud rate
+Ctrl-T Ctrl-B send break
+Ctrl-T d      toggle DTR
+Ctrl-T r      toggle RTS
+Ctrl-T e      toggle local echo
+Ctrl-T x      toggle hex dump
+Ctrl-T Ctrl-T send Ctrl-T
+Ctrl-T q      quit";
+
+impl LineEnding {
+    /// Expands the device's line ending to CR LF, which a raw-mode tty needs.
+    pub fn translate_rx(self, data: &[u8]) -> Vec<u8> {
+        let lone = match self {
+            Self::Cr => b'\r',
+            Self::Lf => b'\n',
+            Self::CrLf => return data.to_vec(),
+        };
+        let mut out = Vec::with_capacity(data.len());
+        for &byte in data {
+            if byte == lone {
+                out.extend_from_slice(b"\r\n");
+            } else {
+                out.push(byte);
+            }
+        }
+        out
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TerminalOptions {
+    pub port: PortOptions,
+    pub hex: bool,
+    pub echo: bool,
+    pub eol: LineEnding,
+}
+
+/// Formats received bytes as `offset  hex  |ascii|` rows.
+#[derive(Debug, Default)]
+pub struct HexDump {
+    offset: u64,
+}
+
+impl HexDump {
+    pub fn format(&mut self, data: &[u8]) -> String {
+        let mut out = String::new();
+        for row in data.chunks(16) {
+            let hex: Vec<String> = row.iter().map(|byte| format!("{byte:02x}")).collect();
+            let ascii: String = row
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            out.push_str(&format!(
+                "{:08x}  {:<47}  |{ascii}|\n",
+                self.offset,
+                hex.join(" ")
+            ));
+            self.offset += row.len() as u64;
+        }
+        out
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyAction {
+    Send(Vec<u8>),
+    /// Text to show locally, e.g. prompt input.
+    Show(String),
+    SetBaud(u32),
+    SendBreak,
+    ToggleDtr,
+    ToggleRts,
+    ToggleEcho,
+    ToggleHex,
+    Help,
+    Quit,
+}
+
+#[derive(Debug)]
+enum KeyState {
+    Normal,
+    Menu,
+    Baud(String),
+}
+
+/// Turns raw keyboard bytes into terminal actions.
+#[derive(Debug)]
+pub struct KeyHandler {
+    eol: LineEnding,
+    state: KeyState,
+}
+
+impl KeyHandler {
+    pub fn new(eol: LineEnding) -> Self {
+        Self {
+            eol,
+            state: KeyState::Normal,
+        }
+    }
+
+    pub fn feed(&mut self, keys: &[u8]) -> Vec<KeyAction> {
+        let mut actions = Vec::new();
+        let mut send = Vec::new();
+        for &key in keys {
+            let action = match &mut self.state {
+                KeyState::Normal => match key {
+                    QUIT_KEY => Some(KeyAction::Quit),
+                    MENU_KEY => {
+                        self.state = KeyState::Menu;
+                        None
+                    }
+                    b'\r' => {
+                        send.extend_from_slice(self.eol.bytes());
+                        None
+                    }
+                    _ => {
+                        send.push(key);
+                        None
+                    }
+                },
+                KeyState::Menu => {
+                    self.state = KeyState::Normal;
+                    match key {
+                        MENU_KEY => {
+                            send.push(MENU_KEY);
+                            None
+                        }
+                        b'b' => {
+                            self.state = KeyState::Baud(String::new());
+                            Some(KeyAction::Show("--- baud rate: ".to_string()))
+                        }
+                        0x02 => Some(KeyAction::SendBreak),
+                        b'd' | 0x04 => Some(KeyAction::ToggleDtr),
+                        b'r' | 0x12 => Some(KeyAction::ToggleRts),
+                        b'e' | 0x05 => Some(KeyAction::ToggleEcho),
+                        b'x' | 0x18 => Some(KeyAction::ToggleHex),
+                        b'q' | QUIT_KEY => Some(KeyAction::Quit),
+                        _ => Some(KeyAction::Help),
+                    }
+                }
+                KeyState::Baud(input) => match key {
+                    b'0'..=b'9' => {
+                        input.push(key as char);
+                        Some(KeyAction::Show((key as char).to_string()))
+                    }
+                    0x7f | 0x08 if input.pop().is_some() => {
+                        Some(KeyAction::Show("\x08 \x08".into()))
+                    }
+                    b'\r' | b'\n' => {
+                        let baud = input.parse().ok().filter(|&baud| baud > 0);
+                        self.state = KeyState::Normal;
+                        Some(match baud {
+                            Some(baud) => KeyAction::SetBaud(baud),
+                            None => KeyAction::Show(" cancelled\r\n".to_string()),
+                        })
+                    }
+                    0x1b | 0x03 => {
+                        self.state = KeyState::Normal;
+                        Some(KeyAction::Show(" cancelled\r\n".to_string()))
+                    }
+                    _ => None,
+                },
+            };
+            if let Some(action) = action {
+                if !send.is_empty() {
+                    actions.push(KeyAction::Send(std::mem::take(&mut send)));
+                }
+                actions.push(action);
+            }
+        }
+        if !send.is_empty() {
+            actions.push(KeyAction::Send(send));
+        }
+        actions
+    }
+}
+
+/// Puts a tty in raw mode and restores the previous settings on drop.
+#[cfg(unix)]
+struct RawMode {
+    saved: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawMode {
+    /// Returns `None` when stdin is not a terminal, e.g. with piped input.
+    fn stdin() -> io::Result<Option<Self>> {
+        let fd = libc::STDIN_FILENO;
+        if unsafe { libc::isatty(fd) } != 1 {
+            return Ok(None);
+        }
+        let mut saved = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = saved;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Some(Self { saved }))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.saved) };
+    }
+}
+
+#[cfg(windows)]
+mod console {
+    use std::ffi::c_void;
+
+    pub type Handle = *mut c_void;
+
+    pub const STD_INPUT_HANDLE: u32 = -10i32 as u32;
+    pub const ENABLE_PROCESSED_INPUT: u32 = 0x0001;
+    pub const ENABLE_LINE_INPUT: u32 = 0x0002;
+    pub const ENABLE_ECHO_INPUT: u32 = 0x0004;
+    pub const ENABLE_VIRTUAL_TERMINAL_INPUT: u32 = 0x0200;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        pub fn GetStdHandle(which: u32) -> Handle;
+        pub fn GetConsoleMode(handle: Handle, mode: *mut u32) -> i32;
+        pub fn SetConsoleMode(handle: Handle, mode: u32) -> i32;
+    }
+}
+
+/// Switches the console to unbuffered, unechoed input with control keys
+/// delivered as bytes, and restores the previous mode on drop.
+#[cfg(windows)]
+struct RawMode {
+    handle: console::Handle,
+    saved: u32,
+}
+
+#[cfg(windows)]
+impl RawMode {
+    /// Returns `None` when stdin is not a console, e.g. with piped input.
+    fn stdin() -> io::Result<Option<Self>> {
+        let handle = unsafe { console::GetStdHandle(console::STD_INPUT_HANDLE) };
+        let mut saved = 0;
+        if unsafe { console::GetConsoleMode(handle, &mut saved) } == 0 {
+            return Ok(None);
+        }
+        let raw = (saved
+            & !(console::ENABLE_LINE_INPUT
+                | console::ENABLE_ECHO_INPUT
+                | console::ENABLE_PROCESSED_INPUT))
+            | console::ENABLE_VIRTUAL_TERMINAL_INPUT;
+        if unsafe { console::SetConsoleMode(handle, raw) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Some(Self { handle, saved }))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { console::SetConsoleMode(self.handle, self.saved) };
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+struct RawMode;
+
+#[cfg(not(any(unix, windows)))]
+impl RawMode {
+    fn stdin() -> io::Result<Option<Self>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "raw terminal input is not supported on this platform",
+        ))
+    }
+}
+
+fn notice(message: &str) {
+    eprint!("\r\n--- {message} ---\r\n");
+}
+
+/// Runs an interactive terminal on `options.port` until the user quits or
+/// stdin closes.
+pub fn run(options: &TerminalOptions, config: &SerialConfig) -> Result<(), CliError> {
+    let port = options.port.port.clone();
+    let session_error = |source| CliError::Session {
+        port: port.clone(),
+        source,
+    };
+    let mut config = config.clone();
+    let mut session = SerialSession::open(&port, &config).map_err(session_error)?;
+    let mut lines = session.modem_lines().unwrap_or_default();
+    let mut echo = options.echo;
+    let mut hex = options.hex.then(HexDump::default);
+    let mut keys = KeyHandler::new(options.eol);
+
+    notice(&format!(
+        "{port}: {} --- Ctrl-] quit, Ctrl-T h help",
+        pcapng::describe_config(&config)
+    ));
+    let _raw = RawMode::stdin()?;
+    let (input_tx, input) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = io::stdin();
+        let mut buf = [0u8; 256];
+        loop {
+            match stdin.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if input_tx.send(buf[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    let mut stdout = io::stdout();
+    loop {
+        for message in session.poll() {
+            match message {
+                SessionMessage::Data(bytes) => {
+                    let shown = match &mut hex {
+                        Some(dump) => dump.format(&bytes).replace('\n', "\r\n").into_bytes(),
+                        None => options.eol.translate_rx(&bytes),
+                    };
+                    stdout.write_all(&shown)?;
+                }
+                SessionMessage::Event(event) => notice(&event.message),
+            }
+        }
+        stdout.flush()?;
+
+        let typed = match input.recv_timeout(INPUT_TICK) {
+            Ok(typed) => typed,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        };
+        for action in keys.feed(&typed) {
+            match action {
+                KeyAction::Send(data) => {
+                    session.write(&data).map_err(session_error)?;
+                    if echo {
+                        stdout.write_all(&options.eol.translate_rx(&data))?;
+                    }
+                }
+                KeyAction::Show(text) => eprint!("{text}"),
+                KeyAction::SetBaud(baud) => {
+                    config.baud_rate = baud;
+                    match session.reconfigure(&config) {
+                        Ok(()) => notice(&format!("{baud} bps")),
+                        Err(err) => {
+                            config = session.config().clone();
+                            notice(&format!("baud change failed: {err}"));
+                        }
+                    }
+                }
+                KeyAction::SendBreak => match session.send_break(BREAK_DURATION) {
+                    Ok(()) => notice("break sent"),
+                    Err(err) => notice(&format!("break failed: {err}")),
+                },
+                KeyAction::ToggleDtr => match session.set_dtr(!lines.dtr) {
+                    Ok(()) => {
+                        lines.dtr = !lines.dtr;
+                        notice(&format!("DTR {}", if lines.dtr { "on" } else { "off" }));
+                    }
+                    Err(err) => notice(&format!("DTR change failed: {err}")),
+                },
+                KeyAction::ToggleRts => match session.set_rts(!lines.rts) {
+                    Ok(()) => {
+                        lines.rts = !lines.rts;
+                        notice(&format!("RTS {}", if lines.rts { "on" } else { "off" }));
+                    }
+                    Err(err) => notice(&format!("RTS change failed: {err}")),
+                },
+                KeyAction::ToggleEcho => {
+                    echo = !echo;
+                    notice(&format!("local echo {}", if echo { "on" } else { "off" }));
+                }
+                KeyAction::ToggleHex => {
+                    hex = match hex {
+                        Some(_) => None,
+                        None => Some(HexDump::default()),
+                    };
+                    notice(&format!(
+                        "hex dump {}",
+                        if hex.is_some() { "on" } else { "off" }
+                    ));
+                }
+                KeyAction::Help => notice(&HELP.replace('\n', "\r\n")),
+                KeyAction::Quit => {
+                    notice("exit");
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enter_sends_configured_line_ending() {
+        let mut keys = KeyHandler::new(LineEnding::CrLf);
+        assert_eq!(
+            keys.feed(b"AT\r"),
+            vec![KeyAction::Send(b"AT\r\n".to_vec())]
+        );
+    }
+
+    #[test]
+    fn menu_keys_split_outgoing_text() {
+        let mut keys = KeyHandler::new(LineEnding::Cr);
+        assert_eq!(
+            keys.feed(&[b'a', MENU_KEY, b'd', b'b', MENU_KEY, MENU_KEY, QUIT_KEY]),
+            vec![
+                KeyAction::Send(b"a".to_vec()),
+                KeyAction::ToggleDtr,
+                KeyAction::Send(vec![b'b', MENU_KEY]),
+                KeyAction::Quit,
+            ]
+        );
+    }
+
+    #[test]
+    fn baud_prompt_collects_digits() {
+        let mut keys = KeyHandler::new(LineEnding::Cr);
+        let actions = keys.feed(&[MENU_KEY, b'b', b'9', b'6', b'x', b'0', b'0', b'\r']);
+        assert_eq!(actions.last(), Some(&KeyAction::SetBaud(9600)));
+        assert_eq!(keys.feed(b"z"), vec![KeyAction::Send(b"z".to_vec())]);
+    }
+
+    #[test]
+    fn rx_translation_and_hex_dump() {
+        assert_eq!(LineEnding::Lf.translate_rx(b"a\nb"), b"a\r\nb");
+        assert_eq!(LineEnding::CrLf.translate_rx(b"a\r\n"), b"a\r\n");
+        let mut dump = HexDump::default();
+        assert_eq!(
+            dump.format(b"OK\r\n"),
+            format!("00000000  {:<47}  |OK..|\n", "4f 4b 0d 0a")
+        );
+        assert!(dump.format(b"x").starts_with("00000004"));
+    }
+}
@@ -0,0 +1,317 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+use crate::checksum::CrcParams;
+use crate::xmodem::{XmodemReceiver, XmodemSender, XmodemVariant};
+use crate::zmodem::{ZmodemReceiver, ZmodemSender};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum TransferProtocol {
+    #[strum(to_string = "XMODEM (checksum)")]
+    Xmodem,
+    #[strum(to_string = "XMODEM-CRC")]
+    XmodemCrc,
+    #[strum(to_string = "XMODEM-1K")]
+    Xmodem1k,
+    #[strum(to_string = "YMODEM batch")]
+    Ymodem,
+    #[strum(to_string = "ZMODEM")]
+    Zmodem,
+}
+
+impl TransferProtocol {
+    /// Whether the protocol carries file names, so several files can go in one transfer.
+    pub fn is_batch(self) -> bool {
+        matches!(self, TransferProtocol::Ymodem | TransferProtocol::Zmodem)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum TransferDirection {
+    #[strum(to_string = "Send")]
+    Send,
+    #[strum(to_string = "Receive")]
+    Receive,
+}
+
+/// Protocol and paths last used in the transfer dialog.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransferSettings {
+    pub protocol: TransferProtocol,
+    pub direction: TransferDirection,
+    /// Files to send, one path per line.
+    pub files: String,
+    /// Directory for received files (or the file itself for XMODEM).
+    pub destination: String,
+}
+
+impl Default for TransferSettings {
+    fn default() -> Self {
+        Self {
+            protocol: TransferProtocol::Ymodem,
+            direction: TransferDirection::Send,
+            files: String::new(),
+            destination: std::env::temp_dir().display().to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransferFile {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+impl TransferFile {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "file".to_string()),
+            data: std::fs::read(path)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransferState {
+    Running,
+    Completed,
+    Failed(String),
+    Cancelled,
+}
+
+#[derive(Clone, Debug)]
+pub struct TransferProgress {
+    /// Name of the file being transferred, once known.
+    pub file: String,
+    /// Files finished so far.
+    pub files_done: usize,
+    pub bytes: u64,
+    /// Size of the current file, when the protocol tells.
+    pub total: Option<u64>,
+    pub retries: u32,
+    /// Recoverable errors, oldest first.
+    pub errors: Vec<String>,
+    pub state: TransferState,
+}
+
+impl TransferProgress {
+    pub(crate) fn new() -> Self {
+        Self {
+            file: String::new(),
+            files_done: 0,
+            bytes: 0,
+            total: None,
+            retries: 0,
+            errors: Vec::new(),
+            state: TransferState::Running,
+        }
+    }
+
+    pub(crate) fn retry(&mut self, error: String) {
+        self.retries += 1;
+        self.errors.push(error);
+    }
+
+    pub(crate) fn fail(&mut self, error: impl Into<String>) {
+        if self.state == TransferState::Running {
+            self.state = TransferState::Failed(error.into());
+        }
+    }
+
+    pub(crate) fn complete(&mut self) {
+        if self.state == TransferState::Running {
+            self.state = TransferState::Completed;
+        }
+    }
+
+    pub(crate) fn cancelled(&mut self) {
+        if self.state == TransferState::Running {
+            self.state = TransferState::Cancelled;
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state == TransferState::Running
+    }
+}
+
+/// One side of a transfer protocol.
+///
+/// Bytes for the peer are appended to `out`; received files are returned by
+/// [`Machine::take_files`].
+pub(crate) trait Machine: Send {
+    fn on_data(&mut self, data: &[u8], now: Instant, out: &mut Vec<u8>);
+    /// Handles timeouts and paced output.
+    fn on_tick(&mut self, now: Instant, out: &mut Vec<u8>);
+    fn cancel(&mut self, out: &mut Vec<u8>);
+    fn progress(&self) -> &TransferProgress;
+    fn take_files(&mut self) -> Vec<TransferFile>;
+}
+
+/// A file transfer running over the owner's session.
+///
+/// The owner feeds received bytes with [`FileTransfer::feed`] and writes what
+/// [`FileTransfer::poll`] returns, like [`TestRun`](crate::expect::TestRun).
+pub struct FileTransfer {
+    protocol: TransferProtocol,
+    machine: Box<dyn Machine>,
+    outgoing: Vec<u8>,
+    destination: Option<PathBuf>,
+    saved: Vec<PathBuf>,
+    /// Set when the transfer failed outside the protocol, e.g. saving a file.
+    error: Option<String>,
+}
+
+impl FileTransfer {
+    pub fn send(protocol: TransferProtocol, files: Vec<TransferFile>, now: Instant) -> Self {
+        let mut outgoing = Vec::new();
+        let machine: Box<dyn Machine> = match protocol {
+            TransferProtocol::Zmodem => Box::new(ZmodemSender::new(files, now, &mut outgoing)),
+            _ => Box::new(XmodemSender::new(XmodemVariant::from(protocol), files, now)),
+        };
+        let mut transfer = Self::new(protocol, machine, None);
+        transfer.outgoing = outgoing;
+        transfer
+    }
+
+    /// Receives into `destination`: a directory, or for XMODEM also a file path.
+    pub fn receive(protocol: TransferProtocol, destination: PathBuf, now: Instant) -> Self {
+        let mut outgoing = Vec::new();
+        let machine: Box<dyn Machine> = match protocol {
+            TransferProtocol::Zmodem => Box::new(ZmodemReceiver::new(now, &mut outgoing)),
+            _ => Box::new(XmodemReceiver::new(
+                XmodemVariant::from(protocol),
+                now,
+                &mut outgoing,
+            )),
+        };
+        let mut transfer = Self::new(protocol, machine, Some(destination));
+        transfer.outgoing = outgoing;
+        transfer
+    }
+
+    fn new(
+        protocol: TransferProtocol,
+        machine: Box<dyn Machine>,
+        destination: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            protocol,
+            machine,
+            outgoing: Vec::new(),
+            destination,
+            saved: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn protocol(&self) -> TransferProtocol {
+        self.protocol
+    }
+
+    pub fn feed(&mut self, data: &[u8], now: Instant) {
+        if self.is_running() {
+            self.machine.on_data(data, now, &mut self.outgoing);
+        }
+    }
+
+    /// Returns the bytes to write to the port and saves finished files.
+    pub fn poll(&mut self, now: Instant) -> Vec<u8> {
+        if self.is_running() {
+            self.machine.on_tick(now, &mut self.outgoing);
+        }
+        for file in self.machine.take_files() {
+            if let Err(err) = self.save(&file) {
+                self.abort(format!("saving {} failed: {err}", file.name));
+            }
+        }
+        std::mem::take(&mut self.outgoing)
+    }
+
+    pub fn cancel(&mut self) {
+        if self.is_running() {
+            self.machine.cancel(&mut self.outgoing);
+        }
+    }
+
+    /// Stops the transfer because of a local problem, such as a failed port write.
+    pub fn abort(&mut self, reason: String) {
+        if self.is_running() {
+            self.machine.cancel(&mut self.outgoing);
+            self.error = Some(reason);
+        }
+    }
+
+    pub fn progress(&self) -> &TransferProgress {
+        self.machine.progress()
+    }
+
+    /// The final state, with local errors taking precedence over the protocol's view.
+    pub fn state(&self) -> TransferState {
+        match &self.error {
+            Some(err) => TransferState::Failed(err.clone()),
+            None => self.progress().state.clone(),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.error.is_none() && self.progress().is_running()
+    }
+
+    /// Paths of received files written so far.
+    pub fn saved(&self) -> &[PathBuf] {
+        &self.saved
+    }
+
+    fn save(&mut self, file: &TransferFile) -> io::Result<()> {
+        let Some(destination) = &self.destination else {
+            return Ok(());
+        };
+        let path = if destination.is_dir() {
+            destination.join(safe_file_name(&file.name))
+        } else {
+            destination.clone()
+        };
+        std::fs::write(&path, &file.data)?;
+        self.saved.push(path);
+        Ok(())
+    }
+}
+
+/// Keeps only the last path component of a name chosen by the peer.
+fn safe_file_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    match base {
+        "" | "." | ".." => "received.bin".to_string(),
+        base => base.to_string(),
+    }
+}
+
+/// CRC-16/XMODEM, the block check of XMODEM, YMODEM and ZMODEM headers.
+pub(crate) fn crc16(data: &[u8]) -> u16 {
+    CrcParams::CRC16_XMODEM.compute(data) as u16
+}
+
+/// CRC-32 as used by ZMODEM (and zip, Ethernet).
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    CrcParams::CRC32.compute(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peer_file_names_stay_in_the_destination() {
+        assert_eq!(safe_file_name("../../etc/passwd"), "passwd");
+        assert_eq!(safe_file_name("C:\\fw\\app.bin"), "app.bin");
+        assert_eq!(safe_file_name(".."), "received.bin");
+    }
+}
@@ -0,0 +1,414 @@
+use std::time::{Duration, Instant};
+
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+use crate::send_panel::{self, TextOptions};
+
+/// Longest line kept while waiting for its newline; longer lines are matched in pieces.
+const MAX_LINE: usize = 4096;
+/// Shortest debounce of a rule that sends, so a device echoing the payload
+/// back cannot drive a send loop.
+pub const MIN_SEND_DEBOUNCE: Duration = Duration::from_millis(100);
+/// Desktop notifications closer together than this are dropped.
+const NOTIFY_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum PatternKind {
+    /// Matched against each received line, without its line ending.
+    #[strum(to_string = "Regex")]
+    Regex,
+    /// Hex bytes matched anywhere in the stream.
+    #[strum(to_string = "Bytes")]
+    Bytes,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum TriggerAction {
+    /// Marks the console entry that completed the match.
+    Highlight,
+    Notify,
+    Send {
+        payload: String,
+        #[serde(default)]
+        hex: bool,
+    },
+    StartRecording,
+    StopRecording,
+    PauseConsole,
+}
+
+impl TriggerAction {
+    pub const ALL: [TriggerAction; 6] = [
+        TriggerAction::Highlight,
+        TriggerAction::Notify,
+        TriggerAction::Send {
+            payload: String::new(),
+            hex: false,
+        },
+        TriggerAction::StartRecording,
+        TriggerAction::StopRecording,
+        TriggerAction::PauseConsole,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TriggerAction::Highlight => "Highlight",
+            TriggerAction::Notify => "Notify",
+            TriggerAction::Send { .. } => "Send",
+            TriggerAction::StartRecording => "Start recording",
+            TriggerAction::StopRecording => "Stop recording",
+            TriggerAction::PauseConsole => "Pause console",
+        }
+    }
+
+    /// Bytes for a `Send` action; text payloads are UTF-8 with C-style escapes.
+    pub fn payload(&self) -> Option<Result<Vec<u8>, String>> {
+        let TriggerAction::Send { payload, hex } = self else {
+            return None;
+        };
+        Some(if *hex {
+            send_panel::parse_hex(payload).map_err(|err| err.to_string())
+        } else {
+            let options = TextOptions {
+                escapes: true,
+                ..TextOptions::default()
+            };
+            send_panel::encode_text(payload, &options).map_err(|err| err.to_string())
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TriggerRule {
+    pub name: String,
+    pub enabled: bool,
+    pub kind: PatternKind,
+    pub pattern: String,
+    /// Minimum time between two firings; matches in between are ignored.
+    /// Rules with a `Send` action use at least [`MIN_SEND_DEBOUNCE`].
+    #[serde(default)]
+    pub debounce_ms: u64,
+    pub actions: Vec<TriggerAction>,
+}
+
+impl TriggerRule {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            enabled: true,
+            kind: PatternKind::Regex,
+            pattern: String::new(),
+            debounce_ms: 0,
+            actions: vec![TriggerAction::Highlight],
+        }
+    }
+
+    /// The debounce actually applied, raised to [`MIN_SEND_DEBOUNCE`] for sends.
+    pub fn debounce(&self) -> Duration {
+        let configured = Duration::from_millis(self.debounce_ms);
+        if self
+            .actions
+            .iter()
+            .any(|action| matches!(action, TriggerAction::Send { .. }))
+        {
+            configured.max(MIN_SEND_DEBOUNCE)
+        } else {
+            configured
+        }
+    }
+
+    /// Why the rule cannot run, if it cannot.
+    pub fn problem(&self) -> Option<String> {
+        if self.pattern.trim().is_empty() {
+            return Some("empty pattern".to_string());
+        }
+        let pattern = match self.kind {
+            PatternKind::Regex => Regex::new(&self.pattern).err().map(|err| err.to_string()),
+            PatternKind::Bytes => match send_panel::parse_hex(&self.pattern) {
+                Ok(bytes) if bytes.is_empty() => Some("empty pattern".to_string()),
+                Ok(_) => None,
+                Err(err) => Some(err.to_string()),
+            },
+        };
+        pattern.or_else(|| {
+            self.actions
+                .iter()
+                .find_map(|action| action.payload()?.err())
+        })
+    }
+}
+
+/// A rule that matched, with the actions the owner should run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TriggerFire {
+    pub rule: String,
+    /// The matched text, lossily decoded.
+    pub matched: String,
+    pub actions: Vec<TriggerAction>,
+}
+
+enum Matcher {
+    Regex(Regex),
+    Bytes(Vec<u8>),
+}
+
+struct CompiledRule {
+    rule: TriggerRule,
+    matcher: Matcher,
+    last_fired: Option<Instant>,
+}
+
+impl CompiledRule {
+    fn fire(&mut self, matched: &[u8], now: Instant, fires: &mut Vec<TriggerFire>) {
+        let debounce = self.rule.debounce();
+        if self
+            .last_fired
+            .is_some_and(|last| now.duration_since(last) < debounce)
+        {
+            return;
+        }
+        self.last_fired = Some(now);
+        fires.push(TriggerFire {
+            rule: self.rule.name.clone(),
+            matched: String::from_utf8_lossy(matched).into_owned(),
+            actions: self.rule.actions.clone(),
+        });
+    }
+}
+
+/// Evaluates a set of rules against the received byte stream.
+#[derive(Default)]
+pub struct TriggerEngine {
+    rules: Vec<CompiledRule>,
+    /// The unterminated line seen so far.
+    line: Vec<u8>,
+    /// Last bytes of the stream, so byte patterns match across chunks.
+    tail: Vec<u8>,
+}
+
+impl TriggerEngine {
+    /// Compiles the enabled rules; rules with a [`TriggerRule::problem`] are skipped.
+    pub fn new(rules: &[TriggerRule]) -> Self {
+        let compiled = rules
+            .iter()
+            .filter(|rule| rule.enabled && rule.problem().is_none())
+            .filter_map(|rule| {
+                let matcher = match rule.kind {
+                    PatternKind::Regex => Matcher::Regex(Regex::new(&rule.pattern).ok()?),
+                    PatternKind::Bytes => {
+                        Matcher::Bytes(send_panel::parse_hex(&rule.pattern).ok()?)
+                    }
+                };
+                Some(CompiledRule {
+                    rule: rule.clone(),
+                    matcher,
+                    last_fired: None,
+                })
+            })
+            .collect();
+        Self {
+            rules: compiled,
+            line: Vec::new(),
+            tail: Vec::new(),
+        }
+    }
+
+    pub fn feed(&mut self, data: &[u8], now: Instant) -> Vec<TriggerFire> {
+        let mut fires = Vec::new();
+        if self.rules.is_empty() {
+            return fires;
+        }
+        self.match_bytes(data, now, &mut fires);
+        for &byte in data {
+            self.line.push(byte);
+            if byte == b'\n' || self.line.len() >= MAX_LINE {
+                let line = std::mem::take(&mut self.line);
+                self.match_line(&line, now, &mut fires);
+            }
+        }
+        fires
+    }
+
+    fn match_line(&mut self, line: &[u8], now: Instant, fires: &mut Vec<TriggerFire>) {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        for rule in &mut self.rules {
+            let Matcher::Regex(regex) = &rule.matcher else {
+                continue;
+            };
+            if let Some(found) = regex.find(line) {
+                let matched = found.as_bytes().to_vec();
+                rule.fire(&matched, now, fires);
+            }
+        }
+    }
+
+    fn match_bytes(&mut self, data: &[u8], now: Instant, fires: &mut Vec<TriggerFire>) {
+        let carried = self.tail.len();
+        let mut window = std::mem::take(&mut self.tail);
+        window.extend_from_slice(data);
+        let mut keep = 0;
+        for rule in &mut self.rules {
+            let Matcher::Bytes(pattern) = &rule.matcher else {
+                continue;
+            };
+            keep = keep.max(pattern.len() - 1);
+            let pattern = pattern.clone();
+            // Only count occurrences that end in the new data.
+            for (start, candidate) in window.windows(pattern.len()).enumerate() {
+                if candidate == pattern && start + pattern.len() > carried {
+                    rule.fire(&pattern, now, fires);
+                }
+            }
+        }
+        self.tail = window[window.len().saturating_sub(keep)..].to_vec();
+    }
+}
+
+/// Rate-limits desktop notifications so a chatty rule cannot flood the desktop.
+#[derive(Default)]
+pub struct Notifier {
+    last: Option<Instant>,
+}
+
+impl Notifier {
+    /// Shows the notification unless one was shown within [`NOTIFY_INTERVAL`].
+    pub fn notify(&mut self, summary: &str, body: &str, now: Instant) -> bool {
+        if !self.ready(now) {
+            return false;
+        }
+        notify(summary, body);
+        true
+    }
+
+    fn ready(&mut self, now: Instant) -> bool {
+        if self
+            .last
+            .is_some_and(|last| now.duration_since(last) < NOTIFY_INTERVAL)
+        {
+            return false;
+        }
+        self.last = Some(now);
+        true
+    }
+}
+
+/// Shows a desktop notification where the platform has a notifier; errors are ignored.
+pub fn notify(summary: &str, body: &str) {
+    #[cfg(target_os = "linux")]
+    let _ = std::process::Command::new("notify-send")
+        .args(["--app-name=MicroSerial", summary, body])
+        .spawn();
+    #[cfg(target_os = "macos")]
+    let _ = std::process::Command::new("osascript")
+        .arg("-e")
+        .arg(format!(
+            "display notification {body:?} with title {summary:?}"
+        ))
+        .spawn();
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    let _ = (summary, body);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(kind: PatternKind, pattern: &str, debounce_ms: u64) -> TriggerRule {
+        TriggerRule {
+            kind,
+            pattern: pattern.to_string(),
+            debounce_ms,
+            ..TriggerRule::new(pattern)
+        }
+    }
+
+    #[test]
+    fn regex_rules_match_whole_lines_with_debounce() {
+        let mut engine = TriggerEngine::new(&[rule(PatternKind::Regex, "panic|assert", 100)]);
+        let start = Instant::now();
+        assert!(engine.feed(b"kernel pan", start).is_empty());
+        let fires = engine.feed(b"ic: oops\r\nassert failed\n", start);
+        assert_eq!(fires.len(), 1);
+        assert_eq!(fires[0].matched, "panic");
+        assert!(
+            engine
+                .feed(b"assert again\n", start + Duration::from_millis(50))
+                .is_empty()
+        );
+        assert_eq!(
+            engine
+                .feed(b"assert later\n", start + Duration::from_millis(150))
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn byte_rules_match_across_chunks_once() {
+        let mut engine = TriggerEngine::new(&[
+            rule(PatternKind::Bytes, "7E", 0),
+            rule(PatternKind::Bytes, "C0 01 C0", 0),
+        ]);
+        let now = Instant::now();
+        assert!(engine.feed(&[0x01, 0xc0], now).is_empty());
+        let fires = engine.feed(&[0x01, 0xc0, 0x7e], now);
+        let rules: Vec<&str> = fires.iter().map(|fire| fire.rule.as_str()).collect();
+        assert_eq!(rules, ["7E", "C0 01 C0"]);
+        // The 0x7E carried over for the longer pattern is not reported again.
+        assert!(engine.feed(&[0x00], now).is_empty());
+    }
+
+    #[test]
+    fn send_rules_have_a_minimum_debounce() {
+        let mut send = rule(PatternKind::Regex, "login:", 0);
+        send.actions = vec![TriggerAction::Send {
+            payload: "root\\r".to_string(),
+            hex: false,
+        }];
+        assert_eq!(send.debounce(), MIN_SEND_DEBOUNCE);
+        let mut engine = TriggerEngine::new(&[send]);
+        let start = Instant::now();
+        assert_eq!(engine.feed(b"login:\n", start).len(), 1);
+        assert!(
+            engine
+                .feed(b"login:\n", start + MIN_SEND_DEBOUNCE / 2)
+                .is_empty()
+        );
+        assert_eq!(engine.feed(b"login:\n", start + MIN_SEND_DEBOUNCE).len(), 1);
+    }
+
+    #[test]
+    fn notifications_are_rate_limited() {
+        let mut notifier = Notifier::default();
+        let start = Instant::now();
+        assert!(notifier.ready(start));
+        assert!(!notifier.ready(start + NOTIFY_INTERVAL / 2));
+        assert!(notifier.ready(start + NOTIFY_INTERVAL));
+    }
+
+    #[test]
+    fn invalid_and_disabled_rules_are_skipped() {
+        let broken = rule(PatternKind::Regex, "(", 0);
+        assert!(broken.problem().is_some());
+        let disabled = TriggerRule {
+            enabled: false,
+            ..rule(PatternKind::Regex, "OK", 0)
+        };
+        let mut engine = TriggerEngine::new(&[broken, disabled]);
+        assert!(engine.feed(b"OK\n", Instant::now()).is_empty());
+        let send = TriggerAction::Send {
+            payload: "y\\r\\n".to_string(),
+            hex: false,
+        };
+        assert_eq!(send.payload(), Some(Ok(b"y\r\n".to_vec())));
+        let bad_escape = TriggerAction::Send {
+            payload: "\\q".to_string(),
+            hex: false,
+        };
+        assert!(matches!(bad_escape.payload(), Some(Err(_))));
+    }
+}
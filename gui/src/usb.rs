@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+/// USB descriptor strings of the adapter behind a serial port.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsbIdentity {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub serial: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+}
+
+impl UsbIdentity {
+    /// Looks up the USB device owning `port_path`, following symlinks such as
+    /// `/dev/serial/by-id/...`. Returns `None` for non-USB ports and on
+    /// platforms without sysfs.
+    #[cfg(target_os = "linux")]
+    pub fn lookup(port_path: &str) -> Option<Self> {
+        use std::fs;
+        use std::path::Path;
+
+        let device = fs::canonicalize(port_path).ok()?;
+        let name = device.file_name()?.to_str()?;
+        let mut dir =
+            fs::canonicalize(Path::new("/sys/class/tty").join(name).join("device")).ok()?;
+        loop {
+            if dir.join("idVendor").exists() {
+                break;
+            }
+            dir = dir.parent()?.to_path_buf();
+        }
+        let read = |attr: &str| {
+            fs::read_to_string(dir.join(attr))
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let hex = |attr: &str| read(attr).and_then(|value| u16::from_str_radix(&value, 16).ok());
+        Some(Self {
+            vendor_id: hex("idVendor")?,
+            product_id: hex("idProduct")?,
+            serial: read("serial"),
+            manufacturer: read("manufacturer"),
+            product: read("product"),
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn lookup(_port_path: &str) -> Option<Self> {
+        None
+    }
+}
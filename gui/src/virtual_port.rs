@@ -0,0 +1,167 @@
+use std::fs;
+use std::io;
+use std::os::fd::OwnedFd;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::pty::{self, Pty};
+
+const READ_TICK: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VirtualPortSettings {
+    /// Stable path other programs open; points at the pty allocated on start.
+    pub link: String,
+}
+
+impl Default for VirtualPortSettings {
+    fn default() -> Self {
+        Self {
+            link: "/tmp/ttyMicroSerial0".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum VirtualPortError {
+    #[error("pty allocation failed: {0}")]
+    Pty(io::Error),
+    #[error("cannot publish {path}: {source}")]
+    Link { path: String, source: io::Error },
+}
+
+/// Shares a session with other programs through a pty behind a symlink.
+///
+/// Like [`SerialServer`](crate::net_server::SerialServer), the owner keeps
+/// the session: device bytes go in through [`VirtualPort::publish`] and bytes
+/// written by the attached program come back from [`VirtualPort::poll`].
+pub struct VirtualPort {
+    link: PathBuf,
+    slave_path: PathBuf,
+    master: Arc<OwnedFd>,
+    _slave: OwnedFd,
+    rx: Receiver<Vec<u8>>,
+    dropped: AtomicU64,
+    stop: Arc<AtomicBool>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl VirtualPort {
+    pub fn create(link: impl AsRef<Path>) -> Result<Self, VirtualPortError> {
+        let link = link.as_ref().to_path_buf();
+        let pty = Pty::open_raw().map_err(VirtualPortError::Pty)?;
+        publish_link(&pty.path, &link).map_err(|source| VirtualPortError::Link {
+            path: link.display().to_string(),
+            source,
+        })?;
+
+        let master = Arc::new(pty.master);
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let reader_master = Arc::clone(&master);
+        let reader_stop = Arc::clone(&stop);
+        let reader = thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            while !reader_stop.load(Ordering::SeqCst) {
+                match pty::read_timeout(&reader_master, &mut buf, READ_TICK) {
+                    Ok(0) => {}
+                    Ok(n) => {
+                        if tx.send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => thread::sleep(READ_TICK),
+                }
+            }
+        });
+
+        Ok(Self {
+            link,
+            slave_path: pty.path,
+            master,
+            _slave: pty.slave,
+            rx,
+            dropped: AtomicU64::new(0),
+            stop,
+            reader: Some(reader),
+        })
+    }
+
+    pub fn link(&self) -> &Path {
+        &self.link
+    }
+
+    pub fn slave_path(&self) -> &Path {
+        &self.slave_path
+    }
+
+    /// Bytes discarded because the attached program was not reading.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Hands device bytes to the attached program without blocking the caller.
+    pub fn publish(&self, data: &[u8]) {
+        let written = pty::write_nonblocking(&self.master, data).unwrap_or(0);
+        if written < data.len() {
+            self.dropped
+                .fetch_add((data.len() - written) as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Bytes the attached program wrote, to be forwarded to the device.
+    pub fn poll(&self) -> Vec<Vec<u8>> {
+        let mut chunks = Vec::new();
+        while let Ok(chunk) = self.rx.try_recv() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+            remove_link(&self.link, &self.slave_path);
+        }
+    }
+}
+
+impl Drop for VirtualPort {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Points `link` at `target`, replacing a stale symlink but never a real file.
+fn publish_link(target: &Path, link: &Path) -> io::Result<()> {
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::symlink_metadata(link) {
+        Ok(meta) if meta.file_type().is_symlink() => fs::remove_file(link)?,
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "path exists and is not a symlink",
+            ));
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    std::os::unix::fs::symlink(target, link)
+}
+
+/// Removes the link only while it still points at our pty.
+fn remove_link(link: &Path, target: &Path) {
+    if fs::read_link(link).is_ok_and(|current| current == target) {
+        let _ = fs::remove_file(link);
+    }
+}
@@ -0,0 +1,445 @@
+use std::io::ErrorKind;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use time::OffsetDateTime;
+use tungstenite::handshake::server::{Callback, ErrorResponse, Request, Response};
+use tungstenite::{Message, WebSocket};
+
+use crate::session::SessionMessage;
+
+const ACCEPT_TICK: Duration = Duration::from_millis(50);
+const READ_TICK: Duration = Duration::from_millis(20);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WebSocketSettings {
+    /// Address to listen on; binding to `127.0.0.1` keeps the stream local.
+    pub bind: String,
+    /// Clients presenting this token may write; everyone else only watches.
+    pub token: Option<String>,
+}
+
+impl Default for WebSocketSettings {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1:7080".to_string(),
+            token: None,
+        }
+    }
+}
+
+/// Serializes bytes as standard base64 in JSON.
+pub mod base64_bytes {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use serde::{Deserialize, Deserializer, Serializer, de};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        STANDARD.decode(text).map_err(de::Error::custom)
+    }
+}
+
+/// A JSON text frame sent to clients.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamFrame {
+    /// First frame on every connection.
+    Hello { port: String, writable: bool },
+    Rx {
+        #[serde(with = "time::serde::rfc3339")]
+        timestamp: OffsetDateTime,
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+    },
+    Tx {
+        #[serde(with = "time::serde::rfc3339")]
+        timestamp: OffsetDateTime,
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+    },
+    Event {
+        #[serde(with = "time::serde::rfc3339")]
+        timestamp: OffsetDateTime,
+        code: i32,
+        message: String,
+    },
+    /// Reply to a command the server could not accept.
+    Error { message: String },
+}
+
+impl StreamFrame {
+    pub fn from_session(message: &SessionMessage, timestamp: OffsetDateTime) -> Self {
+        match message {
+            SessionMessage::Data(data) => StreamFrame::Rx {
+                timestamp,
+                data: data.clone(),
+            },
+            SessionMessage::Event(event) => StreamFrame::Event {
+                timestamp,
+                code: event.code,
+                message: event.message.clone(),
+            },
+        }
+    }
+}
+
+/// A JSON text frame sent by clients.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientCommand {
+    Write {
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebSocketMessage {
+    /// An authorized client asks to write to the session.
+    Write {
+        peer: SocketAddr,
+        data: Vec<u8>,
+    },
+    Connected {
+        peer: SocketAddr,
+        writable: bool,
+    },
+    Disconnected {
+        peer: SocketAddr,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum WebSocketError {
+    #[error("invalid bind address '{0}'")]
+    Address(String),
+    #[error("bind failed: {0}")]
+    Bind(#[from] std::io::Error),
+}
+
+struct ClientHandle {
+    id: u64,
+    peer: SocketAddr,
+    writable: bool,
+    outbound: Sender<String>,
+    stream: TcpStream,
+}
+
+struct Shared {
+    port: String,
+    token: Option<String>,
+    stop: AtomicBool,
+    next_id: AtomicU64,
+    clients: Mutex<Vec<ClientHandle>>,
+    messages: Sender<WebSocketMessage>,
+}
+
+/// Streams a session to WebSocket clients as JSON frames.
+///
+/// Like [`SerialServer`](crate::net_server::SerialServer), the owner keeps
+/// the session: traffic is pushed in with [`WebSocketServer::publish`] and
+/// [`WebSocketServer::publish_tx`], and writes from authorized clients come
+/// back through [`WebSocketServer::poll`]. A client is authorized when it
+/// passes the configured token as `?token=` or as an
+/// `Authorization: Bearer` header; without a token every client is
+/// read-only.
+pub struct WebSocketServer {
+    local_addr: SocketAddr,
+    shared: Arc<Shared>,
+    rx: Receiver<WebSocketMessage>,
+    listener: Option<JoinHandle<()>>,
+}
+
+impl WebSocketServer {
+    pub fn start(settings: &WebSocketSettings, port: &str) -> Result<Self, WebSocketError> {
+        let addr: SocketAddr = settings
+            .bind
+            .parse()
+            .map_err(|_| WebSocketError::Address(settings.bind.clone()))?;
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+
+        let (tx, rx) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            port: port.to_string(),
+            token: settings.token.clone().filter(|token| !token.is_empty()),
+            stop: AtomicBool::new(false),
+            next_id: AtomicU64::new(1),
+            clients: Mutex::new(Vec::new()),
+            messages: tx,
+        });
+        let accept_shared = Arc::clone(&shared);
+        let handle = thread::spawn(move || accept_loop(listener, accept_shared));
+        Ok(Self {
+            local_addr,
+            shared,
+            rx,
+            listener: Some(handle),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Forwards received data or a session event to every client.
+    pub fn publish(&self, message: &SessionMessage) {
+        self.broadcast(&StreamFrame::from_session(
+            message,
+            OffsetDateTime::now_utc(),
+        ));
+    }
+
+    /// Forwards bytes written to the device, whoever wrote them.
+    pub fn publish_tx(&self, data: &[u8]) {
+        self.broadcast(&StreamFrame::Tx {
+            timestamp: OffsetDateTime::now_utc(),
+            data: data.to_vec(),
+        });
+    }
+
+    fn broadcast(&self, frame: &StreamFrame) {
+        let Ok(json) = serde_json::to_string(frame) else {
+            return;
+        };
+        if let Ok(mut clients) = self.shared.clients.lock() {
+            clients.retain(|client| client.outbound.send(json.clone()).is_ok());
+        }
+    }
+
+    pub fn clients(&self) -> Vec<(SocketAddr, bool)> {
+        match self.shared.clients.lock() {
+            Ok(clients) => clients
+                .iter()
+                .map(|client| (client.peer, client.writable))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn poll(&self) -> Vec<WebSocketMessage> {
+        let mut messages = Vec::new();
+        while let Ok(msg) = self.rx.try_recv() {
+            messages.push(msg);
+        }
+        messages
+    }
+
+    pub fn stop(&mut self) {
+        self.shared.stop.store(true, Ordering::SeqCst);
+        if let Ok(mut clients) = self.shared.clients.lock() {
+            for client in clients.drain(..) {
+                let _ = client.stream.shutdown(Shutdown::Both);
+            }
+        }
+        if let Some(handle) = self.listener.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for WebSocketServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn accept_loop(listener: TcpListener, shared: Arc<Shared>) {
+    while !shared.stop.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                let client_shared = Arc::clone(&shared);
+                thread::spawn(move || serve_client(stream, peer, client_shared));
+            }
+            Err(_) => thread::sleep(ACCEPT_TICK),
+        }
+    }
+}
+
+/// Whether the upgrade request carries `token` in the query or a bearer header.
+fn presents_token(request: &Request, token: &str) -> bool {
+    let in_query = request.uri().query().is_some_and(|query| {
+        query
+            .split('&')
+            .any(|pair| pair.strip_prefix("token=") == Some(token))
+    });
+    let in_header = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|value| value.trim() == token);
+    in_query || in_header
+}
+
+/// Records during the upgrade whether the client may write.
+struct Authorize<'a> {
+    token: Option<&'a str>,
+    writable: &'a mut bool,
+}
+
+impl Callback for Authorize<'_> {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        *self.writable = self
+            .token
+            .is_some_and(|token| presents_token(request, token));
+        Ok(response)
+    }
+}
+
+fn serve_client(stream: TcpStream, peer: SocketAddr, shared: Arc<Shared>) {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_nodelay(true);
+    let _ = stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT));
+    let Ok(control_stream) = stream.try_clone() else {
+        return;
+    };
+
+    let mut writable = false;
+    let authorize = Authorize {
+        token: shared.token.as_deref(),
+        writable: &mut writable,
+    };
+    let Ok(mut socket) = tungstenite::accept_hdr(stream, authorize) else {
+        return;
+    };
+    let _ = socket.get_ref().set_read_timeout(Some(READ_TICK));
+
+    let (outbound_tx, outbound_rx) = mpsc::channel::<String>();
+    let id = shared.next_id.fetch_add(1, Ordering::SeqCst);
+    if let Ok(mut clients) = shared.clients.lock() {
+        clients.push(ClientHandle {
+            id,
+            peer,
+            writable,
+            outbound: outbound_tx.clone(),
+            stream: control_stream,
+        });
+    }
+    let _ = shared
+        .messages
+        .send(WebSocketMessage::Connected { peer, writable });
+
+    let hello = StreamFrame::Hello {
+        port: shared.port.clone(),
+        writable,
+    };
+    if let Ok(json) = serde_json::to_string(&hello) {
+        let _ = outbound_tx.send(json);
+    }
+    run_client(
+        &mut socket,
+        peer,
+        writable,
+        &shared,
+        &outbound_tx,
+        &outbound_rx,
+    );
+
+    if let Ok(mut clients) = shared.clients.lock() {
+        clients.retain(|client| client.id != id);
+    }
+    let _ = socket.close(None);
+    let _ = socket.flush();
+    let _ = shared
+        .messages
+        .send(WebSocketMessage::Disconnected { peer });
+}
+
+/// Alternates between flushing queued frames and reading client commands.
+fn run_client(
+    socket: &mut WebSocket<TcpStream>,
+    peer: SocketAddr,
+    writable: bool,
+    shared: &Shared,
+    outbound_tx: &Sender<String>,
+    outbound_rx: &Receiver<String>,
+) {
+    let reject = |message: &str| {
+        let frame = StreamFrame::Error {
+            message: message.to_string(),
+        };
+        if let Ok(json) = serde_json::to_string(&frame) {
+            let _ = outbound_tx.send(json);
+        }
+    };
+    while !shared.stop.load(Ordering::SeqCst) {
+        while let Ok(json) = outbound_rx.try_recv() {
+            if socket.write(Message::Text(json)).is_err() {
+                return;
+            }
+        }
+        if socket.flush().is_err() {
+            return;
+        }
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => return,
+            Ok(_) => continue,
+            Err(tungstenite::Error::Io(err))
+                if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                continue;
+            }
+            Err(_) => return,
+        };
+        match serde_json::from_str::<ClientCommand>(&text) {
+            Ok(ClientCommand::Write { .. }) if !writable => reject("not authorized to write"),
+            Ok(ClientCommand::Write { data }) => {
+                let _ = shared.messages.send(WebSocketMessage::Write { peer, data });
+            }
+            Err(err) => reject(&format!("invalid command: {err}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionEvent;
+
+    #[test]
+    fn frames_carry_base64_payloads_and_timestamps() {
+        let timestamp = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let frame = StreamFrame::from_session(&SessionMessage::Data(b"OK\r\n".to_vec()), timestamp);
+        let json = serde_json::to_value(&frame).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "rx",
+                "timestamp": "2023-11-14T22:13:20Z",
+                "data": "T0sNCg==",
+            })
+        );
+        let event = StreamFrame::from_session(
+            &SessionMessage::Event(SessionEvent {
+                code: -5,
+                message: "read failed".to_string(),
+            }),
+            timestamp,
+        );
+        assert!(matches!(event, StreamFrame::Event { code: -5, .. }));
+
+        let command: ClientCommand =
+            serde_json::from_str(r#"{"type":"write","data":"QVQNCg=="}"#).unwrap();
+        assert_eq!(
+            command,
+            ClientCommand::Write {
+                data: b"AT\r\n".to_vec()
+            }
+        );
+    }
+}
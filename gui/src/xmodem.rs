@@ -0,0 +1,624 @@
+//! XMODEM (checksum, CRC and 1K blocks) and YMODEM batch transfers.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::transfer::{Machine, TransferFile, TransferProgress, TransferProtocol, crc16};
+
+const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+const SUB: u8 = 0x1A;
+const CRC_START: u8 = b'C';
+
+/// How long a sender waits for the receiver to ask for the first block.
+const START_TIMEOUT: Duration = Duration::from_secs(60);
+/// How often a receiver repeats its start byte.
+const START_INTERVAL: Duration = Duration::from_secs(3);
+const BLOCK_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_RETRIES: u32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct XmodemVariant {
+    pub crc: bool,
+    pub block_1k: bool,
+    /// YMODEM: every file is preceded by a block 0 with its name and size.
+    pub batch: bool,
+}
+
+impl From<TransferProtocol> for XmodemVariant {
+    fn from(protocol: TransferProtocol) -> Self {
+        let (crc, block_1k, batch) = match protocol {
+            TransferProtocol::Xmodem => (false, false, false),
+            TransferProtocol::XmodemCrc => (true, false, false),
+            TransferProtocol::Xmodem1k => (true, true, false),
+            TransferProtocol::Ymodem | TransferProtocol::Zmodem => (true, true, true),
+        };
+        Self {
+            crc,
+            block_1k,
+            batch,
+        }
+    }
+}
+
+fn encode_block(number: u8, data: &[u8], size: usize, pad: u8, crc: bool) -> Vec<u8> {
+    let mut block = Vec::with_capacity(size + 5);
+    block.push(if size == 1024 { STX } else { SOH });
+    block.push(number);
+    block.push(!number);
+    let start = block.len();
+    block.extend_from_slice(data);
+    block.resize(start + size, pad);
+    if crc {
+        let crc = crc16(&block[start..]);
+        block.extend_from_slice(&crc.to_be_bytes());
+    } else {
+        let sum = block[start..]
+            .iter()
+            .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        block.push(sum);
+    }
+    block
+}
+
+/// The YMODEM block 0 payload: `name NUL size NUL`, or empty to end the batch.
+fn header_payload(file: Option<&TransferFile>) -> Vec<u8> {
+    let Some(file) = file else {
+        return Vec::new();
+    };
+    let mut payload = file.name.as_bytes().to_vec();
+    payload.push(0);
+    payload.extend_from_slice(file.data.len().to_string().as_bytes());
+    payload.push(0);
+    payload
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SendState {
+    /// Waiting for `C` or NAK before the first block of a file (or the batch trailer).
+    Start,
+    /// YMODEM: block 0 was acknowledged; waiting for `C` before the data.
+    Data,
+    /// A block is out and waits for its ACK.
+    Block,
+    Eot,
+    Done,
+}
+
+pub(crate) struct XmodemSender {
+    variant: XmodemVariant,
+    files: VecDeque<TransferFile>,
+    current: Option<TransferFile>,
+    crc: bool,
+    state: SendState,
+    /// The block (or EOT) last sent, for retransmission.
+    last: Vec<u8>,
+    number: u8,
+    /// Offset of the block in flight within the current file.
+    offset: usize,
+    len: usize,
+    /// The block in flight is a YMODEM block 0.
+    header: bool,
+    deadline: Instant,
+    attempts: u32,
+    cancels: u8,
+    progress: TransferProgress,
+}
+
+impl XmodemSender {
+    pub(crate) fn new(variant: XmodemVariant, files: Vec<TransferFile>, now: Instant) -> Self {
+        let mut files: VecDeque<TransferFile> = files.into();
+        if !variant.batch {
+            files.truncate(1);
+        }
+        let current = files.pop_front();
+        let mut progress = TransferProgress::new();
+        if let Some(file) = &current {
+            progress.file = file.name.clone();
+            progress.total = Some(file.data.len() as u64);
+        }
+        Self {
+            variant,
+            files,
+            current,
+            crc: variant.crc,
+            state: SendState::Start,
+            last: Vec::new(),
+            number: 0,
+            offset: 0,
+            len: 0,
+            header: false,
+            deadline: now + START_TIMEOUT,
+            attempts: 0,
+            cancels: 0,
+            progress,
+        }
+    }
+
+    fn transmit(&mut self, bytes: Vec<u8>, now: Instant, out: &mut Vec<u8>) {
+        out.extend_from_slice(&bytes);
+        self.last = bytes;
+        self.deadline = now + BLOCK_TIMEOUT;
+    }
+
+    fn send_header(&mut self, now: Instant, out: &mut Vec<u8>) {
+        let payload = header_payload(self.current.as_ref());
+        let size = if payload.len() > 128 { 1024 } else { 128 };
+        self.header = true;
+        self.number = 0;
+        self.state = SendState::Block;
+        self.transmit(encode_block(0, &payload, size, 0, true), now, out);
+    }
+
+    /// Sends the block at `offset`, or EOT once the file is exhausted.
+    fn send_data(&mut self, now: Instant, out: &mut Vec<u8>) {
+        self.header = false;
+        let data = self.current.as_ref().map_or(&[][..], |file| {
+            &file.data[self.offset.min(file.data.len())..]
+        });
+        if data.is_empty() {
+            self.state = SendState::Eot;
+            self.transmit(vec![EOT], now, out);
+            return;
+        }
+        let size = if self.variant.block_1k && self.crc && data.len() > 128 {
+            1024
+        } else {
+            128
+        };
+        self.len = data.len().min(size);
+        let block = encode_block(self.number, &data[..self.len], size, SUB, self.crc);
+        self.state = SendState::Block;
+        self.transmit(block, now, out);
+    }
+
+    fn start_file(&mut self, now: Instant, out: &mut Vec<u8>) {
+        self.offset = 0;
+        self.number = 1;
+        if self.variant.batch {
+            self.send_header(now, out);
+        } else {
+            self.send_data(now, out);
+        }
+    }
+
+    fn resend(&mut self, error: &str, now: Instant, out: &mut Vec<u8>) {
+        self.attempts += 1;
+        if self.attempts > MAX_RETRIES {
+            self.progress.fail(format!("{error}, giving up"));
+            self.state = SendState::Done;
+            out.extend_from_slice(&[CAN; 3]);
+            return;
+        }
+        self.progress.retry(format!("{error}, retrying"));
+        let last = std::mem::take(&mut self.last);
+        self.transmit(last, now, out);
+    }
+
+    fn on_byte(&mut self, byte: u8, now: Instant, out: &mut Vec<u8>) {
+        match (self.state, byte) {
+            (SendState::Start, CRC_START) | (SendState::Start, NAK) => {
+                if byte == NAK && self.variant.batch {
+                    return;
+                }
+                self.crc = byte == CRC_START;
+                self.start_file(now, out);
+            }
+            (SendState::Data, CRC_START) => self.send_data(now, out),
+            (SendState::Block, ACK) => {
+                self.attempts = 0;
+                if self.header {
+                    if self.current.is_none() {
+                        self.progress.complete();
+                        self.state = SendState::Done;
+                    } else {
+                        self.number = 1;
+                        self.state = SendState::Data;
+                        self.deadline = now + START_TIMEOUT;
+                    }
+                    return;
+                }
+                self.offset += self.len;
+                self.progress.bytes = self.offset as u64;
+                self.number = self.number.wrapping_add(1);
+                self.send_data(now, out);
+            }
+            (SendState::Block, NAK) => {
+                let block = if self.header { 0 } else { self.number };
+                self.resend(&format!("block {block} rejected"), now, out);
+            }
+            (SendState::Eot, ACK) => {
+                self.attempts = 0;
+                self.progress.files_done += 1;
+                if !self.variant.batch {
+                    self.progress.complete();
+                    self.state = SendState::Done;
+                    return;
+                }
+                self.current = self.files.pop_front();
+                if let Some(file) = &self.current {
+                    self.progress.file = file.name.clone();
+                    self.progress.total = Some(file.data.len() as u64);
+                    self.progress.bytes = 0;
+                }
+                self.state = SendState::Start;
+                self.deadline = now + START_TIMEOUT;
+            }
+            // YMODEM receivers NAK the first EOT to make sure it was not line noise.
+            (SendState::Eot, NAK) if self.variant.batch && self.attempts == 0 => {
+                self.attempts += 1;
+                let last = std::mem::take(&mut self.last);
+                self.transmit(last, now, out);
+            }
+            (SendState::Eot, NAK) => self.resend("end of file rejected", now, out),
+            _ => {}
+        }
+    }
+}
+
+impl Machine for XmodemSender {
+    fn on_data(&mut self, data: &[u8], now: Instant, out: &mut Vec<u8>) {
+        for &byte in data {
+            if self.state == SendState::Done {
+                return;
+            }
+            if byte == CAN {
+                self.cancels += 1;
+                if self.cancels >= 2 {
+                    self.progress.fail("cancelled by the receiver");
+                    self.state = SendState::Done;
+                }
+                continue;
+            }
+            self.cancels = 0;
+            self.on_byte(byte, now, out);
+        }
+    }
+
+    fn on_tick(&mut self, now: Instant, out: &mut Vec<u8>) {
+        if self.state == SendState::Done || now < self.deadline {
+            return;
+        }
+        match self.state {
+            SendState::Start | SendState::Data => {
+                self.progress.fail("the receiver did not ask for data");
+                self.state = SendState::Done;
+            }
+            SendState::Block | SendState::Eot => self.resend("no answer", now, out),
+            SendState::Done => {}
+        }
+    }
+
+    fn cancel(&mut self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&[CAN; 3]);
+        self.progress.cancelled();
+        self.state = SendState::Done;
+    }
+
+    fn progress(&self) -> &TransferProgress {
+        &self.progress
+    }
+
+    fn take_files(&mut self) -> Vec<TransferFile> {
+        Vec::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ReceiveState {
+    /// YMODEM: waiting for block 0.
+    Header,
+    Data,
+    Done,
+}
+
+pub(crate) struct XmodemReceiver {
+    variant: XmodemVariant,
+    state: ReceiveState,
+    buffer: Vec<u8>,
+    expected: u8,
+    /// Any block arrived, so the sender is running.
+    started: bool,
+    name: String,
+    size: Option<usize>,
+    data: Vec<u8>,
+    /// YMODEM: the first EOT was NAKed.
+    eot_seen: bool,
+    deadline: Instant,
+    attempts: u32,
+    files: Vec<TransferFile>,
+    progress: TransferProgress,
+}
+
+impl XmodemReceiver {
+    pub(crate) fn new(variant: XmodemVariant, now: Instant, out: &mut Vec<u8>) -> Self {
+        let receiver = Self {
+            variant,
+            state: if variant.batch {
+                ReceiveState::Header
+            } else {
+                ReceiveState::Data
+            },
+            buffer: Vec::new(),
+            expected: if variant.batch { 0 } else { 1 },
+            started: false,
+            name: "xmodem.bin".to_string(),
+            size: None,
+            data: Vec::new(),
+            eot_seen: false,
+            deadline: now + START_INTERVAL,
+            attempts: 0,
+            files: Vec::new(),
+            progress: TransferProgress::new(),
+        };
+        out.push(receiver.start_byte());
+        receiver
+    }
+
+    fn start_byte(&self) -> u8 {
+        if self.variant.crc { CRC_START } else { NAK }
+    }
+
+    fn block_len(&self, kind: u8) -> usize {
+        let size = if kind == STX { 1024 } else { 128 };
+        size + 3 + if self.variant.crc { 2 } else { 1 }
+    }
+
+    fn check(&self, payload: &[u8], trailer: &[u8]) -> bool {
+        if self.variant.crc {
+            crc16(payload).to_be_bytes() == trailer
+        } else {
+            let sum = payload
+                .iter()
+                .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+            trailer == [sum]
+        }
+    }
+
+    fn reject(&mut self, error: String, now: Instant, out: &mut Vec<u8>) {
+        self.attempts += 1;
+        if self.attempts > MAX_RETRIES {
+            self.abort(format!("{error}, giving up"), out);
+            return;
+        }
+        self.progress.retry(error);
+        out.push(NAK);
+        self.deadline = now + BLOCK_TIMEOUT;
+    }
+
+    fn abort(&mut self, error: String, out: &mut Vec<u8>) {
+        self.progress.fail(error);
+        self.state = ReceiveState::Done;
+        out.extend_from_slice(&[CAN; 3]);
+    }
+
+    fn accept(&mut self, now: Instant, out: &mut Vec<u8>) {
+        self.attempts = 0;
+        self.deadline = now + BLOCK_TIMEOUT;
+        out.push(ACK);
+    }
+
+    fn on_block(&mut self, block: &[u8], now: Instant, out: &mut Vec<u8>) {
+        let number = block[1];
+        let payload = &block[3..block.len() - if self.variant.crc { 2 } else { 1 }];
+        let trailer = &block[3 + payload.len()..];
+        if block[2] != !number || !self.check(payload, trailer) {
+            self.reject(format!("block {number} is corrupt"), now, out);
+            return;
+        }
+        self.started = true;
+        match self.state {
+            ReceiveState::Header if number == 0 => self.on_header(payload, now, out),
+            // The sender missed our ACK for block 0 and sent it again.
+            ReceiveState::Data if number == 0 && self.variant.batch && self.expected == 1 => {
+                self.accept(now, out);
+                out.push(CRC_START);
+            }
+            ReceiveState::Data if number == self.expected => {
+                self.data.extend_from_slice(payload);
+                self.expected = self.expected.wrapping_add(1);
+                let received = self
+                    .size
+                    .map_or(self.data.len(), |size| size.min(self.data.len()));
+                self.progress.bytes = received as u64;
+                self.accept(now, out);
+            }
+            ReceiveState::Data if number == self.expected.wrapping_sub(1) => {
+                self.accept(now, out);
+            }
+            _ => self.abort(
+                format!("expected block {}, got {number}", self.expected),
+                out,
+            ),
+        }
+    }
+
+    fn on_header(&mut self, payload: &[u8], now: Instant, out: &mut Vec<u8>) {
+        let mut fields = payload.split(|byte| *byte == 0);
+        let name = String::from_utf8_lossy(fields.next().unwrap_or_default()).into_owned();
+        self.accept(now, out);
+        if name.is_empty() {
+            self.progress.complete();
+            self.state = ReceiveState::Done;
+            return;
+        }
+        self.size = fields
+            .next()
+            .and_then(|info| info.split(|byte| *byte == b' ').next())
+            .and_then(|size| std::str::from_utf8(size).ok()?.parse().ok());
+        self.progress.file = name.clone();
+        self.progress.total = self.size.map(|size| size as u64);
+        self.progress.bytes = 0;
+        self.name = name;
+        self.data.clear();
+        self.expected = 1;
+        self.state = ReceiveState::Data;
+        out.push(CRC_START);
+    }
+
+    fn on_eot(&mut self, now: Instant, out: &mut Vec<u8>) {
+        if self.state != ReceiveState::Data {
+            return;
+        }
+        if self.variant.batch && !self.eot_seen {
+            self.eot_seen = true;
+            out.push(NAK);
+            self.deadline = now + BLOCK_TIMEOUT;
+            return;
+        }
+        self.eot_seen = false;
+        self.accept(now, out);
+        let mut data = std::mem::take(&mut self.data);
+        if let Some(size) = self.size {
+            data.truncate(size);
+        }
+        self.files.push(TransferFile {
+            name: self.name.clone(),
+            data,
+        });
+        self.progress.files_done += 1;
+        if self.variant.batch {
+            self.state = ReceiveState::Header;
+            self.expected = 0;
+            out.push(CRC_START);
+        } else {
+            self.progress.complete();
+            self.state = ReceiveState::Done;
+        }
+    }
+}
+
+impl Machine for XmodemReceiver {
+    fn on_data(&mut self, data: &[u8], now: Instant, out: &mut Vec<u8>) {
+        self.buffer.extend_from_slice(data);
+        while self.state != ReceiveState::Done {
+            let Some(&first) = self.buffer.first() else {
+                break;
+            };
+            match first {
+                SOH | STX => {
+                    let len = self.block_len(first);
+                    if self.buffer.len() < len {
+                        break;
+                    }
+                    let block: Vec<u8> = self.buffer.drain(..len).collect();
+                    self.on_block(&block, now, out);
+                }
+                EOT => {
+                    self.buffer.remove(0);
+                    self.on_eot(now, out);
+                }
+                CAN => {
+                    if self.buffer.len() < 2 {
+                        break;
+                    }
+                    if self.buffer[1] == CAN {
+                        self.progress.fail("cancelled by the sender");
+                        self.state = ReceiveState::Done;
+                    }
+                    self.buffer.remove(0);
+                }
+                _ => {
+                    self.buffer.remove(0);
+                }
+            }
+        }
+    }
+
+    fn on_tick(&mut self, now: Instant, out: &mut Vec<u8>) {
+        if self.state == ReceiveState::Done || now < self.deadline {
+            return;
+        }
+        self.buffer.clear();
+        if !self.started || self.state == ReceiveState::Header {
+            self.attempts += 1;
+            if self.attempts > MAX_RETRIES {
+                self.abort("the sender did not start".to_string(), out);
+                return;
+            }
+            out.push(self.start_byte());
+            self.deadline = now + START_INTERVAL;
+            return;
+        }
+        self.reject("timed out waiting for a block".to_string(), now, out);
+    }
+
+    fn cancel(&mut self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&[CAN; 3]);
+        self.progress.cancelled();
+        self.state = ReceiveState::Done;
+    }
+
+    fn progress(&self) -> &TransferProgress {
+        &self.progress
+    }
+
+    fn take_files(&mut self) -> Vec<TransferFile> {
+        std::mem::take(&mut self.files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrupt_block_is_retried() {
+        let data: Vec<u8> = (0..600u32).map(|i| i as u8).collect();
+        let file = TransferFile {
+            name: "fw.bin".to_string(),
+            data: data.clone(),
+        };
+        let variant = XmodemVariant::from(TransferProtocol::Ymodem);
+        let now = Instant::now();
+        let mut sender = XmodemSender::new(variant, vec![file], now);
+        let mut to_sender = Vec::new();
+        let mut receiver = XmodemReceiver::new(variant, now, &mut to_sender);
+        let mut corrupted = false;
+        for _ in 0..100 {
+            if !sender.progress().is_running() && !receiver.progress().is_running() {
+                break;
+            }
+            let mut to_receiver = Vec::new();
+            sender.on_data(&std::mem::take(&mut to_sender), now, &mut to_receiver);
+            // Flip a bit in the first data block.
+            if !corrupted && to_receiver.first() == Some(&STX) && to_receiver[1] == 1 {
+                to_receiver[200] ^= 0x01;
+                corrupted = true;
+            }
+            receiver.on_data(&to_receiver, now, &mut to_sender);
+        }
+        assert!(corrupted);
+        assert_eq!(receiver.progress().retries, 1);
+        assert_eq!(sender.progress().retries, 1);
+        let files = receiver.take_files();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].name, "fw.bin");
+        assert_eq!(files[0].data, data);
+    }
+
+    #[test]
+    fn endless_eot_nak_gives_up() {
+        let file = TransferFile {
+            name: "fw.bin".to_string(),
+            data: b"firmware".to_vec(),
+        };
+        let variant = XmodemVariant::from(TransferProtocol::Xmodem);
+        let now = Instant::now();
+        let mut sender = XmodemSender::new(variant, vec![file], now);
+        let mut out = Vec::new();
+        sender.on_data(&[CRC_START, ACK], now, &mut out);
+        assert_eq!(out.last(), Some(&EOT));
+        for _ in 0..=MAX_RETRIES {
+            out.clear();
+            sender.on_data(&[NAK], now, &mut out);
+        }
+        assert!(!sender.progress().is_running());
+        assert_eq!(sender.progress().retries, MAX_RETRIES);
+        assert_eq!(out, [CAN; 3]);
+    }
+}
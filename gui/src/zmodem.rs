@@ -0,0 +1,881 @@
+//! ZMODEM sender and receiver.
+//!
+//! Only the parts needed for plain binary file transfers are implemented:
+//! hex and binary (CRC-16 and CRC-32) headers, ZDLE escaping, streaming with
+//! ZCRCQ acknowledgements as a send window, and ZRPOS error recovery.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::transfer::{Machine, TransferFile, TransferProgress, crc16, crc32};
+
+const ZPAD: u8 = b'*';
+const ZDLE: u8 = 0x18;
+const CAN: u8 = 0x18;
+const BS: u8 = 0x08;
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+
+const ZBIN: u8 = b'A';
+const ZHEX: u8 = b'B';
+const ZBIN32: u8 = b'C';
+
+// Data subpacket terminators.
+const ZCRCE: u8 = b'h';
+const ZCRCG: u8 = b'i';
+const ZCRCQ: u8 = b'j';
+const ZCRCW: u8 = b'k';
+const ZRUB0: u8 = b'l';
+const ZRUB1: u8 = b'm';
+
+// Frame types.
+const ZRQINIT: u8 = 0;
+const ZRINIT: u8 = 1;
+const ZSINIT: u8 = 2;
+const ZACK: u8 = 3;
+const ZFILE: u8 = 4;
+const ZSKIP: u8 = 5;
+const ZNAK: u8 = 6;
+const ZABORT: u8 = 7;
+const ZFIN: u8 = 8;
+const ZRPOS: u8 = 9;
+const ZDATA: u8 = 10;
+const ZEOF: u8 = 11;
+const ZFERR: u8 = 12;
+
+/// ZRINIT capabilities: full duplex, can receive while writing to disk.
+const CANFDX: u8 = 0x01;
+const CANOVIO: u8 = 0x02;
+/// ZFILE conversion option: binary transfer.
+const ZCBIN: u8 = 1;
+
+const SUBPACKET: usize = 1024;
+const MAX_SUBPACKET: usize = 8192;
+/// Bytes the sender may get ahead of the last ZACK.
+const WINDOW: usize = 8 * SUBPACKET;
+/// Every n-th subpacket asks for a ZACK.
+const ACK_EVERY: usize = 4;
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_RETRIES: u32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Header {
+    kind: u8,
+    /// ZP0..ZP3, or ZF3..ZF0 for flags.
+    data: [u8; 4],
+    /// Arrived as a CRC-32 binary header, so its data subpackets use CRC-32.
+    crc32: bool,
+}
+
+impl Header {
+    fn at(kind: u8, position: usize) -> Self {
+        Self {
+            kind,
+            data: (position as u32).to_le_bytes(),
+            crc32: false,
+        }
+    }
+
+    fn flags(kind: u8, zf0: u8) -> Self {
+        Self {
+            kind,
+            data: [0, 0, 0, zf0],
+            crc32: false,
+        }
+    }
+
+    fn position(&self) -> usize {
+        u32::from_le_bytes(self.data) as usize
+    }
+
+    fn raw(&self) -> Vec<u8> {
+        let mut raw = vec![self.kind];
+        raw.extend_from_slice(&self.data);
+        raw
+    }
+
+    fn write_hex(&self, out: &mut Vec<u8>) {
+        let mut raw = self.raw();
+        raw.extend_from_slice(&crc16(&raw).to_be_bytes());
+        out.extend_from_slice(&[ZPAD, ZPAD, ZDLE, ZHEX]);
+        for byte in raw {
+            out.extend_from_slice(format!("{byte:02x}").as_bytes());
+        }
+        out.extend_from_slice(&[b'\r', b'\n' | 0x80]);
+        if self.kind != ZACK && self.kind != ZFIN {
+            out.push(XON);
+        }
+    }
+
+    fn write_binary(&self, out: &mut Vec<u8>) {
+        let mut raw = self.raw();
+        raw.extend_from_slice(&crc16(&raw).to_be_bytes());
+        out.extend_from_slice(&[ZPAD, ZDLE, ZBIN]);
+        for byte in raw {
+            escape(byte, out);
+        }
+    }
+}
+
+fn escape(byte: u8, out: &mut Vec<u8>) {
+    match byte {
+        ZDLE | 0x10 | 0x90 | XON | 0x91 | XOFF | 0x93 => {
+            out.extend_from_slice(&[ZDLE, byte ^ 0x40])
+        }
+        _ => out.push(byte),
+    }
+}
+
+fn write_subpacket(data: &[u8], end: u8, out: &mut Vec<u8>) {
+    for &byte in data {
+        escape(byte, out);
+    }
+    out.extend_from_slice(&[ZDLE, end]);
+    let mut covered = data.to_vec();
+    covered.push(end);
+    for byte in crc16(&covered).to_be_bytes() {
+        escape(byte, out);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Frame {
+    Header(Header),
+    BadHeader,
+    Data {
+        data: Vec<u8>,
+        end: u8,
+    },
+    BadData,
+    /// Five or more CANs: the peer aborted.
+    Cancel,
+}
+
+enum Parse {
+    Frame(Frame, usize),
+    Skip(usize),
+    Incomplete,
+}
+
+enum Escaped {
+    Byte(u8),
+    End(u8),
+    Invalid,
+    Cancel,
+    Incomplete,
+}
+
+/// Decodes one ZDLE-escaped byte at `*pos`, skipping raw flow control characters.
+fn read_escaped(buffer: &[u8], pos: &mut usize) -> Escaped {
+    loop {
+        let Some(&byte) = buffer.get(*pos) else {
+            return Escaped::Incomplete;
+        };
+        match byte {
+            XON | XOFF | 0x91 | 0x93 => *pos += 1,
+            ZDLE => {
+                let Some(&next) = buffer.get(*pos + 1) else {
+                    return Escaped::Incomplete;
+                };
+                if next == CAN {
+                    let run = buffer[*pos..].iter().take_while(|b| **b == CAN).count();
+                    return if run >= 5 {
+                        Escaped::Cancel
+                    } else if *pos + run == buffer.len() {
+                        Escaped::Incomplete
+                    } else {
+                        Escaped::Invalid
+                    };
+                }
+                *pos += 2;
+                return match next {
+                    ZCRCE..=ZCRCW => Escaped::End(next),
+                    ZRUB0 => Escaped::Byte(0x7f),
+                    ZRUB1 => Escaped::Byte(0xff),
+                    _ if next & 0x60 == 0x40 => Escaped::Byte(next ^ 0x40),
+                    _ => Escaped::Invalid,
+                };
+            }
+            _ => {
+                *pos += 1;
+                return Escaped::Byte(byte);
+            }
+        }
+    }
+}
+
+fn parse_header(buffer: &[u8]) -> Parse {
+    let pads = buffer.iter().take_while(|b| **b == ZPAD).count();
+    match (buffer.get(pads), buffer.get(pads + 1)) {
+        (None, _) | (Some(&ZDLE), None) => Parse::Incomplete,
+        (Some(&ZDLE), Some(&ZHEX)) => parse_hex_header(buffer, pads + 2),
+        (Some(&ZDLE), Some(&ZBIN)) => parse_binary_header(buffer, pads + 2, false),
+        (Some(&ZDLE), Some(&ZBIN32)) => parse_binary_header(buffer, pads + 2, true),
+        _ => Parse::Skip(pads),
+    }
+}
+
+fn parse_hex_header(buffer: &[u8], start: usize) -> Parse {
+    let Some(digits) = buffer.get(start..start + 14) else {
+        return Parse::Incomplete;
+    };
+    let raw: Option<Vec<u8>> = digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect();
+    let frame = match raw {
+        Some(raw) if crc16(&raw[..5]).to_be_bytes() == raw[5..] => {
+            Frame::Header(header_from(&raw, false))
+        }
+        _ => Frame::BadHeader,
+    };
+    Parse::Frame(frame, start + 14)
+}
+
+fn parse_binary_header(buffer: &[u8], start: usize, crc32_format: bool) -> Parse {
+    let len = if crc32_format { 9 } else { 7 };
+    let mut pos = start;
+    let mut raw = Vec::with_capacity(len);
+    while raw.len() < len {
+        match read_escaped(buffer, &mut pos) {
+            Escaped::Byte(byte) => raw.push(byte),
+            Escaped::Incomplete => return Parse::Incomplete,
+            Escaped::Cancel => return Parse::Frame(Frame::Cancel, buffer.len()),
+            Escaped::End(_) | Escaped::Invalid => return Parse::Frame(Frame::BadHeader, pos),
+        }
+    }
+    let valid = if crc32_format {
+        crc32(&raw[..5]).to_le_bytes() == raw[5..]
+    } else {
+        crc16(&raw[..5]).to_be_bytes() == raw[5..]
+    };
+    let frame = if valid {
+        Frame::Header(header_from(&raw, crc32_format))
+    } else {
+        Frame::BadHeader
+    };
+    Parse::Frame(frame, pos)
+}
+
+fn header_from(raw: &[u8], crc32: bool) -> Header {
+    Header {
+        kind: raw[0],
+        data: [raw[1], raw[2], raw[3], raw[4]],
+        crc32,
+    }
+}
+
+fn parse_subpacket(buffer: &[u8], crc32_format: bool) -> Parse {
+    let mut pos = 0;
+    let mut data = Vec::new();
+    let end = loop {
+        match read_escaped(buffer, &mut pos) {
+            Escaped::Byte(byte) => {
+                data.push(byte);
+                if data.len() > MAX_SUBPACKET {
+                    return Parse::Frame(Frame::BadData, pos);
+                }
+            }
+            Escaped::End(end) => break end,
+            Escaped::Incomplete => return Parse::Incomplete,
+            Escaped::Cancel => return Parse::Frame(Frame::Cancel, buffer.len()),
+            Escaped::Invalid => return Parse::Frame(Frame::BadData, pos),
+        }
+    };
+    let mut crc = Vec::with_capacity(4);
+    while crc.len() < if crc32_format { 4 } else { 2 } {
+        match read_escaped(buffer, &mut pos) {
+            Escaped::Byte(byte) => crc.push(byte),
+            Escaped::Incomplete => return Parse::Incomplete,
+            Escaped::Cancel => return Parse::Frame(Frame::Cancel, buffer.len()),
+            Escaped::End(_) | Escaped::Invalid => return Parse::Frame(Frame::BadData, pos),
+        }
+    }
+    data.push(end);
+    let valid = if crc32_format {
+        crc32(&data).to_le_bytes()[..] == crc[..]
+    } else {
+        crc16(&data).to_be_bytes()[..] == crc[..]
+    };
+    data.pop();
+    let frame = if valid {
+        Frame::Data { data, end }
+    } else {
+        Frame::BadData
+    };
+    Parse::Frame(frame, pos)
+}
+
+/// Splits the received byte stream into headers and data subpackets.
+#[derive(Default)]
+struct Decoder {
+    buffer: Vec<u8>,
+    /// Data subpackets follow, with CRC-32 if `true`.
+    subpacket: Option<bool>,
+}
+
+impl Decoder {
+    fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    fn next_frame(&mut self) -> Option<Frame> {
+        loop {
+            let parsed = match self.subpacket {
+                Some(crc32_format) => parse_subpacket(&self.buffer, crc32_format),
+                None => match self.buffer.iter().position(|b| *b == ZPAD || *b == CAN) {
+                    None => {
+                        self.buffer.clear();
+                        return None;
+                    }
+                    Some(0) if self.buffer[0] == CAN => {
+                        let run = self.buffer.iter().take_while(|b| **b == CAN).count();
+                        if run >= 5 {
+                            Parse::Frame(Frame::Cancel, run)
+                        } else if run == self.buffer.len() {
+                            Parse::Incomplete
+                        } else {
+                            Parse::Skip(run)
+                        }
+                    }
+                    Some(0) => parse_header(&self.buffer),
+                    Some(start) => Parse::Skip(start),
+                },
+            };
+            match parsed {
+                Parse::Incomplete => return None,
+                Parse::Skip(len) => {
+                    self.buffer.drain(..len);
+                }
+                Parse::Frame(frame, len) => {
+                    self.buffer.drain(..len);
+                    self.subpacket = match &frame {
+                        Frame::Header(header) if matches!(header.kind, ZFILE | ZSINIT | ZDATA) => {
+                            Some(header.crc32)
+                        }
+                        Frame::Data { end, .. } if matches!(*end, ZCRCG | ZCRCQ) => self.subpacket,
+                        _ => None,
+                    };
+                    return Some(frame);
+                }
+            }
+        }
+    }
+}
+
+fn cancel_sequence(out: &mut Vec<u8>) {
+    out.extend_from_slice(&[CAN; 8]);
+    out.extend_from_slice(&[BS; 8]);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SendState {
+    /// ZRQINIT sent; waiting for ZRINIT.
+    Init,
+    /// ZFILE sent; waiting for ZRPOS.
+    File,
+    Streaming,
+    /// ZEOF sent; waiting for ZRINIT.
+    Eof,
+    /// ZFIN sent; waiting for ZFIN.
+    Fin,
+    Done,
+}
+
+pub(crate) struct ZmodemSender {
+    files: VecDeque<TransferFile>,
+    current: Option<TransferFile>,
+    decoder: Decoder,
+    state: SendState,
+    /// Next byte to send.
+    offset: usize,
+    /// Last position the receiver confirmed.
+    acked: usize,
+    subpackets: usize,
+    deadline: Instant,
+    attempts: u32,
+    progress: TransferProgress,
+}
+
+impl ZmodemSender {
+    pub(crate) fn new(files: Vec<TransferFile>, now: Instant, out: &mut Vec<u8>) -> Self {
+        let mut files: VecDeque<TransferFile> = files.into();
+        let sender = Self {
+            current: files.pop_front(),
+            files,
+            decoder: Decoder::default(),
+            state: SendState::Init,
+            offset: 0,
+            acked: 0,
+            subpackets: 0,
+            deadline: now + TIMEOUT,
+            attempts: 0,
+            progress: TransferProgress::new(),
+        };
+        // Starts `rz` on a remote shell; harmless for anything else.
+        out.extend_from_slice(b"rz\r");
+        Header::at(ZRQINIT, 0).write_hex(out);
+        sender
+    }
+
+    fn len(&self) -> usize {
+        self.current.as_ref().map_or(0, |file| file.data.len())
+    }
+
+    fn send_file(&mut self, now: Instant, out: &mut Vec<u8>) {
+        self.deadline = now + TIMEOUT;
+        let Some(file) = &self.current else {
+            self.state = SendState::Fin;
+            Header::at(ZFIN, 0).write_hex(out);
+            return;
+        };
+        self.progress.file = file.name.clone();
+        self.progress.total = Some(file.data.len() as u64);
+        self.progress.bytes = 0;
+        let mut info = file.name.as_bytes().to_vec();
+        info.push(0);
+        info.extend_from_slice(file.data.len().to_string().as_bytes());
+        info.push(0);
+        Header::flags(ZFILE, ZCBIN).write_binary(out);
+        write_subpacket(&info, ZCRCW, out);
+        self.state = SendState::File;
+    }
+
+    fn next_file(&mut self, now: Instant, out: &mut Vec<u8>) {
+        self.current = self.files.pop_front();
+        self.send_file(now, out);
+    }
+
+    /// Restarts the data at `position`, closing a frame that is still open.
+    fn start_data(&mut self, position: usize, now: Instant, out: &mut Vec<u8>) {
+        if self.state == SendState::Streaming {
+            write_subpacket(&[], ZCRCE, out);
+        }
+        self.offset = position.min(self.len());
+        self.acked = self.offset;
+        self.progress.bytes = self.offset as u64;
+        self.deadline = now + TIMEOUT;
+        if self.offset == self.len() {
+            self.state = SendState::Eof;
+            Header::at(ZEOF, self.offset).write_binary(out);
+            return;
+        }
+        self.state = SendState::Streaming;
+        self.subpackets = 0;
+        Header::at(ZDATA, self.offset).write_binary(out);
+    }
+
+    /// Sends subpackets until the window is full or the file ends.
+    fn stream(&mut self, now: Instant, out: &mut Vec<u8>) {
+        let len = self.len();
+        while self.state == SendState::Streaming && self.offset < self.acked + WINDOW {
+            let Some(file) = &self.current else {
+                return;
+            };
+            let end = (self.offset + SUBPACKET).min(len);
+            self.subpackets += 1;
+            let terminator = if end == len {
+                ZCRCE
+            } else if self.subpackets.is_multiple_of(ACK_EVERY) {
+                ZCRCQ
+            } else {
+                ZCRCG
+            };
+            write_subpacket(&file.data[self.offset..end], terminator, out);
+            self.offset = end;
+            self.progress.bytes = end as u64;
+            self.deadline = now + TIMEOUT;
+            if end == len {
+                self.state = SendState::Eof;
+                Header::at(ZEOF, len).write_binary(out);
+            }
+        }
+    }
+
+    /// Repeats the last step after a timeout or ZNAK.
+    fn retransmit(&mut self, error: &str, now: Instant, out: &mut Vec<u8>) {
+        self.attempts += 1;
+        if self.attempts > MAX_RETRIES {
+            self.progress.fail(format!("{error}, giving up"));
+            self.state = SendState::Done;
+            cancel_sequence(out);
+            return;
+        }
+        self.progress.retry(format!("{error}, retrying"));
+        self.deadline = now + TIMEOUT;
+        match self.state {
+            SendState::Init => Header::at(ZRQINIT, 0).write_hex(out),
+            SendState::File | SendState::Fin => self.send_file(now, out),
+            SendState::Streaming => self.start_data(self.acked, now, out),
+            SendState::Eof => Header::at(ZEOF, self.len()).write_binary(out),
+            SendState::Done => {}
+        }
+    }
+
+    fn on_header(&mut self, header: Header, now: Instant, out: &mut Vec<u8>) {
+        match (self.state, header.kind) {
+            (SendState::Init, ZRINIT) => {
+                self.attempts = 0;
+                self.send_file(now, out);
+            }
+            (SendState::File | SendState::Streaming | SendState::Eof, ZRPOS) => {
+                if self.state != SendState::File {
+                    self.progress.retry(format!(
+                        "receiver asked to resume at byte {}",
+                        header.position()
+                    ));
+                }
+                self.start_data(header.position(), now, out);
+            }
+            (SendState::Streaming, ZACK) => {
+                self.attempts = 0;
+                self.acked = self.acked.max(header.position().min(self.offset));
+                self.deadline = now + TIMEOUT;
+            }
+            (SendState::File, ZSKIP) => self.next_file(now, out),
+            (SendState::Eof, ZRINIT) => {
+                self.attempts = 0;
+                self.progress.files_done += 1;
+                self.next_file(now, out);
+            }
+            (SendState::Fin, ZFIN) => {
+                out.extend_from_slice(b"OO");
+                self.progress.complete();
+                self.state = SendState::Done;
+            }
+            (_, ZNAK) => self.retransmit("receiver rejected a header", now, out),
+            (_, ZABORT | ZFERR) => {
+                self.progress.fail("the receiver aborted");
+                self.state = SendState::Done;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Machine for ZmodemSender {
+    fn on_data(&mut self, data: &[u8], now: Instant, out: &mut Vec<u8>) {
+        self.decoder.push(data);
+        while self.state != SendState::Done {
+            match self.decoder.next_frame() {
+                None => break,
+                Some(Frame::Cancel) => {
+                    self.progress.fail("cancelled by the receiver");
+                    self.state = SendState::Done;
+                }
+                Some(Frame::Header(header)) => self.on_header(header, now, out),
+                Some(_) => {}
+            }
+        }
+    }
+
+    fn on_tick(&mut self, now: Instant, out: &mut Vec<u8>) {
+        if self.state == SendState::Done {
+            return;
+        }
+        self.stream(now, out);
+        if now >= self.deadline {
+            self.retransmit("no answer from the receiver", now, out);
+        }
+    }
+
+    fn cancel(&mut self, out: &mut Vec<u8>) {
+        cancel_sequence(out);
+        self.progress.cancelled();
+        self.state = SendState::Done;
+    }
+
+    fn progress(&self) -> &TransferProgress {
+        &self.progress
+    }
+
+    fn take_files(&mut self) -> Vec<TransferFile> {
+        Vec::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ReceiveState {
+    /// ZRINIT sent; waiting for ZFILE or ZFIN.
+    Init,
+    /// Waiting for the ZFILE subpacket with name and size.
+    FileInfo,
+    /// ZRPOS sent; waiting for ZDATA or ZEOF.
+    Position,
+    Data,
+    Done,
+}
+
+pub(crate) struct ZmodemReceiver {
+    decoder: Decoder,
+    state: ReceiveState,
+    /// A ZSINIT subpacket is expected and must be acknowledged.
+    sinit: bool,
+    name: String,
+    data: Vec<u8>,
+    deadline: Instant,
+    attempts: u32,
+    files: Vec<TransferFile>,
+    progress: TransferProgress,
+}
+
+impl ZmodemReceiver {
+    pub(crate) fn new(now: Instant, out: &mut Vec<u8>) -> Self {
+        let receiver = Self {
+            decoder: Decoder::default(),
+            state: ReceiveState::Init,
+            sinit: false,
+            name: String::new(),
+            data: Vec::new(),
+            deadline: now + TIMEOUT,
+            attempts: 0,
+            files: Vec::new(),
+            progress: TransferProgress::new(),
+        };
+        receiver.send_init(out);
+        receiver
+    }
+
+    fn send_init(&self, out: &mut Vec<u8>) {
+        Header::flags(ZRINIT, CANFDX | CANOVIO).write_hex(out);
+    }
+
+    fn resume(&mut self, error: String, now: Instant, out: &mut Vec<u8>) {
+        self.attempts += 1;
+        if self.attempts > MAX_RETRIES {
+            self.progress.fail(format!("{error}, giving up"));
+            self.state = ReceiveState::Done;
+            cancel_sequence(out);
+            return;
+        }
+        self.progress.retry(error);
+        self.state = ReceiveState::Position;
+        self.deadline = now + TIMEOUT;
+        Header::at(ZRPOS, self.data.len()).write_hex(out);
+    }
+
+    fn on_header(&mut self, header: Header, now: Instant, out: &mut Vec<u8>) {
+        self.deadline = now + TIMEOUT;
+        match (self.state, header.kind) {
+            (ReceiveState::Init, ZRQINIT) => self.send_init(out),
+            (ReceiveState::Init, ZSINIT) => self.sinit = true,
+            (ReceiveState::Init | ReceiveState::Position, ZFILE) => {
+                self.state = ReceiveState::FileInfo;
+            }
+            (ReceiveState::Position | ReceiveState::Data, ZDATA) => {
+                if header.position() == self.data.len() {
+                    self.state = ReceiveState::Data;
+                } else {
+                    let error = format!(
+                        "data at byte {}, expected {}",
+                        header.position(),
+                        self.data.len()
+                    );
+                    self.resume(error, now, out);
+                }
+            }
+            // A ZEOF at another position was sent before our last ZRPOS arrived.
+            (ReceiveState::Position | ReceiveState::Data, ZEOF)
+                if header.position() == self.data.len() =>
+            {
+                self.attempts = 0;
+                self.files.push(TransferFile {
+                    name: std::mem::take(&mut self.name),
+                    data: std::mem::take(&mut self.data),
+                });
+                self.progress.files_done += 1;
+                self.state = ReceiveState::Init;
+                self.send_init(out);
+            }
+            (ReceiveState::Init, ZFIN) => {
+                Header::at(ZFIN, 0).write_hex(out);
+                self.progress.complete();
+                self.state = ReceiveState::Done;
+            }
+            _ => {}
+        }
+    }
+
+    fn on_subpacket(&mut self, data: Vec<u8>, end: u8, now: Instant, out: &mut Vec<u8>) {
+        if self.sinit {
+            self.sinit = false;
+            Header::at(ZACK, 0).write_hex(out);
+            return;
+        }
+        match self.state {
+            ReceiveState::FileInfo => {
+                let mut fields = data.split(|byte| *byte == 0);
+                let name = fields.next().unwrap_or_default();
+                self.name = String::from_utf8_lossy(name).into_owned();
+                let size = fields
+                    .next()
+                    .and_then(|info| info.split(|byte| *byte == b' ').next())
+                    .and_then(|size| std::str::from_utf8(size).ok()?.parse().ok());
+                self.progress.file = self.name.clone();
+                self.progress.total = size;
+                self.progress.bytes = 0;
+                self.data.clear();
+                self.state = ReceiveState::Position;
+                self.deadline = now + TIMEOUT;
+                Header::at(ZRPOS, 0).write_hex(out);
+            }
+            ReceiveState::Data => {
+                self.attempts = 0;
+                self.deadline = now + TIMEOUT;
+                self.data.extend_from_slice(&data);
+                self.progress.bytes = self.data.len() as u64;
+                if matches!(end, ZCRCQ | ZCRCW) {
+                    Header::at(ZACK, self.data.len()).write_hex(out);
+                }
+                if matches!(end, ZCRCE | ZCRCW) {
+                    self.state = ReceiveState::Position;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Machine for ZmodemReceiver {
+    fn on_data(&mut self, data: &[u8], now: Instant, out: &mut Vec<u8>) {
+        self.decoder.push(data);
+        while self.state != ReceiveState::Done {
+            match self.decoder.next_frame() {
+                None => break,
+                Some(Frame::Cancel) => {
+                    self.progress.fail("cancelled by the sender");
+                    self.state = ReceiveState::Done;
+                }
+                Some(Frame::Header(header)) => self.on_header(header, now, out),
+                Some(Frame::Data { data, end }) => self.on_subpacket(data, end, now, out),
+                Some(Frame::BadData) => match self.state {
+                    ReceiveState::Data => {
+                        let error = format!("bad data at byte {}", self.data.len());
+                        self.resume(error, now, out);
+                    }
+                    ReceiveState::FileInfo => {
+                        self.progress.retry("bad file header".to_string());
+                        self.state = ReceiveState::Init;
+                        Header::at(ZNAK, 0).write_hex(out);
+                    }
+                    _ => {}
+                },
+                Some(Frame::BadHeader) => {}
+            }
+        }
+    }
+
+    fn on_tick(&mut self, now: Instant, out: &mut Vec<u8>) {
+        if self.state == ReceiveState::Done || now < self.deadline {
+            return;
+        }
+        match self.state {
+            ReceiveState::Init | ReceiveState::FileInfo => {
+                // Waiting for the sender to start is not an error yet.
+                self.attempts += 1;
+                if self.attempts > MAX_RETRIES {
+                    self.progress.fail("no answer from the sender");
+                    self.state = ReceiveState::Done;
+                    cancel_sequence(out);
+                    return;
+                }
+                self.state = ReceiveState::Init;
+                self.deadline = now + TIMEOUT;
+                self.send_init(out);
+            }
+            ReceiveState::Position | ReceiveState::Data => {
+                let error = format!("timed out at byte {}", self.data.len());
+                self.resume(error, now, out);
+            }
+            ReceiveState::Done => {}
+        }
+    }
+
+    fn cancel(&mut self, out: &mut Vec<u8>) {
+        cancel_sequence(out);
+        self.progress.cancelled();
+        self.state = ReceiveState::Done;
+    }
+
+    fn progress(&self) -> &TransferProgress {
+        &self.progress
+    }
+
+    fn take_files(&mut self) -> Vec<TransferFile> {
+        std::mem::take(&mut self.files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_survive_escaping_and_split_reads() {
+        let mut encoded = Vec::new();
+        Header::at(ZDATA, 0x1234).write_binary(&mut encoded);
+        let payload: Vec<u8> = (0..=255).collect();
+        write_subpacket(&payload, ZCRCW, &mut encoded);
+        Header::at(ZEOF, 256).write_hex(&mut encoded);
+        assert!(!encoded.contains(&XON) || encoded.ends_with(&[XON]));
+
+        let mut decoder = Decoder::default();
+        let mut frames = Vec::new();
+        for chunk in encoded.chunks(7) {
+            decoder.push(chunk);
+            while let Some(frame) = decoder.next_frame() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(
+            frames,
+            vec![
+                Frame::Header(Header::at(ZDATA, 0x1234)),
+                Frame::Data {
+                    data: payload,
+                    end: ZCRCW
+                },
+                Frame::Header(Header::at(ZEOF, 256)),
+            ]
+        );
+
+        decoder.push(&[CAN; 5]);
+        assert_eq!(decoder.next_frame(), Some(Frame::Cancel));
+    }
+
+    #[test]
+    fn corrupt_subpacket_resumes_from_last_good_position() {
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i * 13) as u8).collect();
+        let file = TransferFile {
+            name: "log.bin".to_string(),
+            data: data.clone(),
+        };
+        let now = Instant::now();
+        let mut to_receiver = Vec::new();
+        let mut sender = ZmodemSender::new(vec![file], now, &mut to_receiver);
+        let mut to_sender = Vec::new();
+        let mut receiver = ZmodemReceiver::new(now, &mut to_sender);
+        let mut corrupted = false;
+        for _ in 0..1000 {
+            if !sender.progress().is_running() && !receiver.progress().is_running() {
+                break;
+            }
+            sender.on_data(&std::mem::take(&mut to_sender), now, &mut to_receiver);
+            sender.on_tick(now, &mut to_receiver);
+            if !corrupted && to_receiver.len() > 5000 {
+                to_receiver[4000] ^= 0x20;
+                corrupted = true;
+            }
+            receiver.on_data(&std::mem::take(&mut to_receiver), now, &mut to_sender);
+        }
+        assert!(corrupted);
+        assert!(receiver.progress().retries >= 1);
+        assert_eq!(
+            receiver.progress().state,
+            crate::transfer::TransferState::Completed
+        );
+        let files = receiver.take_files();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].data, data);
+    }
+}
@@ -0,0 +1,75 @@
+#![cfg(unix)]
+
+use std::os::fd::AsRawFd;
+use std::time::{Duration, Instant};
+
+use microserial_gui::bridge::{
+    BridgeDirection, BridgeMessage, BridgeRule, RuleAction, RuleScope, SerialBridge,
+};
+use microserial_gui::core::SerialConfig;
+use nix::pty::{PtyMaster, openpty, ptsname};
+use nix::unistd::{read, write};
+
+fn pty_pair() -> (PtyMaster, String) {
+    let pty = openpty(None, None).expect("openpty");
+    let master = unsafe { PtyMaster::from_owned_fd(pty.master) };
+    unsafe {
+        libc::fcntl(master.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK);
+    }
+    let path = unsafe { ptsname(&master).expect("ptsname") };
+    (master, path)
+}
+
+fn read_until(master: &PtyMaster, expected: usize) -> Vec<u8> {
+    let start = Instant::now();
+    let mut received = Vec::new();
+    let mut buf = [0u8; 64];
+    while received.len() < expected && start.elapsed() < Duration::from_secs(2) {
+        match read(master, &mut buf) {
+            Ok(n) if n > 0 => received.extend_from_slice(&buf[..n]),
+            _ => std::thread::sleep(Duration::from_millis(5)),
+        }
+    }
+    received
+}
+
+#[test]
+fn bridge_forwards_both_directions() {
+    let (master_a, path_a) = pty_pair();
+    let (master_b, path_b) = pty_pair();
+    let config = SerialConfig::default();
+    let bridge = SerialBridge::open(&path_a, &config, &path_b, &config).expect("bridge");
+    bridge.set_rules(vec![BridgeRule {
+        enabled: true,
+        scope: RuleScope::BToA,
+        pattern: b"OK".to_vec(),
+        action: RuleAction::Replace(b"ERR".to_vec()),
+    }]);
+
+    write(&master_a, b"AT\r").expect("write a");
+    assert_eq!(read_until(&master_b, 3), b"AT\r");
+
+    write(&master_b, b"OK\r").expect("write b");
+    assert_eq!(read_until(&master_a, 4), b"ERR\r");
+
+    let start = Instant::now();
+    let mut seen = Vec::new();
+    while seen.len() < 2 && start.elapsed() < Duration::from_secs(1) {
+        for message in bridge.poll() {
+            if let BridgeMessage::Data {
+                direction,
+                original,
+                forwarded,
+            } = message
+            {
+                seen.push((direction, original, forwarded));
+            }
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(seen.len(), 2);
+    assert_eq!(seen[0].0, BridgeDirection::AToB);
+    assert_eq!(seen[1].0, BridgeDirection::BToA);
+    assert_eq!(seen[1].1, b"OK\r");
+    assert_eq!(seen[1].2, b"ERR\r");
+}
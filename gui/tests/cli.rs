@@ -0,0 +1,188 @@
+#![cfg(unix)]
+
+use std::os::fd::OwnedFd;
+use std::time::Duration;
+
+use microserial_gui::cli::{self, CliError, PortOptions};
+use microserial_gui::commands::{
+    self, CaptureOptions, Payload, ProbeOptions, SendOptions, TestOptions,
+};
+use microserial_gui::core::SerialConfig;
+use microserial_gui::recorder;
+use nix::pty::{PtyMaster, openpty, ptsname};
+use nix::unistd::{read, write};
+use tempfile::tempdir;
+
+/// The slave is returned so the master does not see a hang-up between sessions.
+fn device() -> (PtyMaster, OwnedFd, String) {
+    let pty = openpty(None, None).expect("openpty");
+    let master = unsafe { PtyMaster::from_owned_fd(pty.master) };
+    let path = unsafe { ptsname(&master).expect("ptsname") };
+    (master, pty.slave, path)
+}
+
+fn port(path: &str) -> PortOptions {
+    PortOptions {
+        port: path.to_string(),
+        ..PortOptions::default()
+    }
+}
+
+#[test]
+fn send_waits_for_response_pattern() {
+    let (master, _slave, path) = device();
+    let responder = std::thread::spawn(move || {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 16];
+        while !request.ends_with(b"\r") {
+            let n = read(&master, &mut buf).expect("read master");
+            request.extend_from_slice(&buf[..n]);
+        }
+        write(&master, b"PO").expect("write master");
+        std::thread::sleep(Duration::from_millis(20));
+        write(&master, b"NG\r\n").expect("write master");
+        (request, master)
+    });
+
+    let options = SendOptions {
+        port: port(&path),
+        payload: Payload::Text("PING".to_string()),
+        eol: Some(microserial_gui::send_panel::LineEnding::Cr),
+        wait: Some(b"PONG".to_vec()),
+        timeout: Duration::from_secs(2),
+        hex: false,
+    };
+    commands::send(&options, &SerialConfig::default()).expect("send");
+    let (request, master) = responder.join().expect("responder");
+    assert_eq!(request, b"PING\r");
+
+    let silent = SendOptions {
+        wait: Some(b"never".to_vec()),
+        timeout: Duration::from_millis(100),
+        ..options
+    };
+    let err = commands::send(&silent, &SerialConfig::default()).expect_err("timeout");
+    assert_eq!(err.exit_code(), cli::EXIT_TIMEOUT);
+    drop(master);
+}
+
+#[test]
+fn send_streams_payloads_larger_than_the_tx_ring() {
+    let (master, _slave, path) = device();
+    let payload: Vec<u8> = (0..40_000u32).map(|index| (index % 251) as u8).collect();
+    let expected = payload.len();
+    let reader = std::thread::spawn(move || {
+        let mut received = Vec::new();
+        let mut buf = [0u8; 4096];
+        while received.len() < expected {
+            let n = read(&master, &mut buf).expect("read master");
+            received.extend_from_slice(&buf[..n]);
+        }
+        received
+    });
+
+    let options = SendOptions {
+        port: port(&path),
+        payload: Payload::Bytes(payload.clone()),
+        eol: None,
+        wait: None,
+        timeout: Duration::from_secs(5),
+        hex: false,
+    };
+    commands::send(&options, &SerialConfig::default()).expect("send");
+    assert_eq!(reader.join().expect("reader"), payload);
+}
+
+#[test]
+fn capture_records_until_pattern() {
+    let (master, _slave, path) = device();
+    let dir = tempdir().expect("tempdir");
+    let output = dir.path().join("boot.msrec");
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        write(&master, b"booting...\r\nlogin: ").expect("write master");
+        master
+    });
+    let options = CaptureOptions {
+        port: port(&path),
+        duration: Some(Duration::from_secs(2)),
+        until: Some(b"login:".to_vec()),
+        output: Some(output.clone()),
+        hex: true,
+    };
+    commands::capture(&options, &SerialConfig::default()).expect("capture");
+    drop(writer.join().expect("writer"));
+
+    let (header, records) = recorder::load(&output).expect("load");
+    assert_eq!(header.port, path);
+    let rx: Vec<u8> = records
+        .into_iter()
+        .filter_map(|record| match record.payload {
+            recorder::RecordPayload::Rx(data) => Some(data),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    assert!(rx.ends_with(b"login: "));
+}
+
+#[test]
+fn probe_reports_open_failure() {
+    let dir = tempdir().expect("tempdir");
+    let missing = dir.path().join("ttyMissing");
+    let options = ProbeOptions {
+        port: port(&missing.display().to_string()),
+        json: true,
+    };
+    let err = commands::probe(&options, &SerialConfig::default()).expect_err("missing port");
+    assert!(matches!(err, CliError::Session { .. }));
+    assert_eq!(err.exit_code(), cli::EXIT_OPEN);
+}
+
+#[test]
+fn test_runs_suite_and_writes_junit() {
+    let (master, _slave, path) = device();
+    let dir = tempdir().expect("tempdir");
+    let file = dir.path().join("smoke.toml");
+    let junit = dir.path().join("smoke.xml");
+    std::fs::write(
+        &file,
+        r#"
+name = "smoke"
+
+[[case]]
+name = "version"
+step = [{ send = "VER?\r" }, { expect = 'v(\d+\.\d+)', capture = "version", timeout = 2000 }]
+
+[[case]]
+name = "never answers"
+step = [{ expect = "READY", timeout = 100 }]
+"#,
+    )
+    .expect("write suite");
+    let responder = std::thread::spawn(move || {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 16];
+        while !request.ends_with(b"\r") {
+            let n = read(&master, &mut buf).expect("read master");
+            request.extend_from_slice(&buf[..n]);
+        }
+        write(&master, b"v1.2\r\n").expect("write master");
+        (request, master)
+    });
+
+    let options = TestOptions {
+        port: port(&path),
+        file,
+        junit: Some(junit.clone()),
+    };
+    let err = commands::test(&options, &SerialConfig::default()).expect_err("one case fails");
+    assert_eq!(err.exit_code(), cli::EXIT_TESTS_FAILED);
+    let (request, _master) = responder.join().expect("responder");
+    assert_eq!(request, b"VER?\r");
+
+    let report = std::fs::read_to_string(&junit).expect("junit report");
+    assert!(report.contains(r#"tests="2" failures="1" errors="0""#));
+    assert!(report.contains("capture version = &quot;1.2&quot;"));
+    assert!(report.contains(r#"<testcase name="never answers""#));
+}
@@ -0,0 +1,171 @@
+#![cfg(unix)]
+
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use microserial_gui::core::SerialConfig;
+use microserial_gui::daemon::{ApiErrorKind, Daemon, DaemonError, DaemonEvent, DaemonSettings};
+use microserial_gui::daemon_client::{DaemonClient, DaemonPort};
+use microserial_gui::session::{SerialSession, SessionMessage};
+use nix::pty::{PtyMaster, openpty, ptsname};
+use nix::unistd::{read, write};
+use tempfile::tempdir;
+
+fn read_exact(master: &PtyMaster, len: usize) -> Vec<u8> {
+    let mut received = Vec::new();
+    let mut buf = [0u8; 64];
+    while received.len() < len {
+        let n = read(master, &mut buf).expect("read master");
+        received.extend_from_slice(&buf[..n]);
+    }
+    received
+}
+
+#[test]
+fn clients_share_a_daemon_port() {
+    let pty = openpty(None, None).expect("openpty");
+    let master = unsafe { PtyMaster::from_owned_fd(pty.master) };
+    let path = unsafe { ptsname(&master).expect("ptsname") };
+    let dir = tempdir().expect("tempdir");
+    let settings = DaemonSettings {
+        socket: dir.path().join("daemon.sock").display().to_string(),
+        ..DaemonSettings::default()
+    };
+    let mut daemon = Daemon::start(&settings, dir.path()).expect("start daemon");
+    assert!(Daemon::start(&settings, dir.path()).is_err());
+
+    let observer = DaemonClient::connect(&settings.socket).expect("connect observer");
+    let events = observer.events().expect("event queue");
+    let missing = observer.subscribe(&path).expect_err("port not open yet");
+    assert_eq!(missing.kind, ApiErrorKind::NotOpen);
+    observer
+        .open(&path, &SerialConfig::default())
+        .expect("open port");
+    observer.subscribe(&path).expect("subscribe");
+
+    // The GUI path: a session attached through the daemon joins the open port.
+    let wanted = SerialConfig {
+        baud_rate: 9600,
+        ..SerialConfig::default()
+    };
+    let mut session = SerialSession::attach(&settings, &path, &wanted).expect("attach");
+    assert_eq!(*session.config(), SerialConfig::default());
+    session.write(b"PING\r").expect("write");
+    assert_eq!(read_exact(&master, 5), b"PING\r");
+
+    write(&master, b"PONG\r\n").expect("write master");
+    let deadline = Instant::now() + Duration::from_secs(2);
+    let mut attached_rx = Vec::new();
+    while attached_rx.len() < 6 && Instant::now() < deadline {
+        for message in session.poll() {
+            if let SessionMessage::Data(data) = message {
+                attached_rx.extend_from_slice(&data);
+            }
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(attached_rx, b"PONG\r\n");
+
+    let mut observed_tx = Vec::new();
+    let mut observed_rx = Vec::new();
+    while observed_rx.len() < 6 {
+        match events.recv_timeout(Duration::from_secs(2)).expect("event") {
+            DaemonEvent::Tx { data, .. } => observed_tx.extend_from_slice(&data),
+            DaemonEvent::Rx { data, .. } => observed_rx.extend_from_slice(&data),
+            _ => {}
+        }
+    }
+    assert_eq!(observed_tx, b"PING\r");
+    assert_eq!(observed_rx, b"PONG\r\n");
+
+    // Detaching leaves the port open for the remaining client.
+    drop(session);
+    let sessions = observer.sessions().expect("sessions");
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].port, path);
+    assert_eq!(sessions[0].subscribers, 1);
+
+    let recording = observer
+        .start_recording(&path, None)
+        .expect("start recording");
+    assert!(recording.starts_with(dir.path()));
+    let info = observer
+        .stop_recording(&path)
+        .expect("stop recording")
+        .expect("recording info");
+    assert_eq!(info.path, recording);
+
+    observer.close(&path).expect("close");
+    assert!(observer.sessions().expect("sessions").is_empty());
+    daemon.stop();
+    assert!(!dir.path().join("daemon.sock").exists());
+}
+
+#[test]
+fn tcp_clients_need_the_token() {
+    let dir = tempdir().expect("tempdir");
+    let socket = |name: &str| dir.path().join(name).display().to_string();
+    let open = DaemonSettings {
+        socket: socket("open.sock"),
+        tcp: Some("127.0.0.1:0".into()),
+        ..DaemonSettings::default()
+    };
+    assert!(matches!(
+        Daemon::start(&open, dir.path()),
+        Err(DaemonError::TokenRequired)
+    ));
+    let exposed = DaemonSettings {
+        socket: socket("exposed.sock"),
+        tcp: Some("0.0.0.0:0".into()),
+        token: Some("s3cret".into()),
+        ..DaemonSettings::default()
+    };
+    assert!(matches!(
+        Daemon::start(&exposed, dir.path()),
+        Err(DaemonError::NotLoopback(_))
+    ));
+
+    let settings = DaemonSettings {
+        socket: socket("daemon.sock"),
+        tcp: Some("127.0.0.1:0".into()),
+        token: Some("s3cret".into()),
+        ..DaemonSettings::default()
+    };
+    let mut daemon = Daemon::start(&settings, dir.path()).expect("start daemon");
+    let address = daemon.tcp_addr().expect("tcp address").to_string();
+
+    let client = DaemonClient::connect_with_token(&address, Some("s3cret")).expect("connect");
+    assert!(client.sessions().expect("sessions").is_empty());
+    let intruder = DaemonClient::connect_with_token(&address, Some("guess")).expect("connect");
+    assert!(intruder.sessions().is_err());
+    daemon.stop();
+}
+
+#[test]
+fn daemon_port_restarts_after_stop() {
+    let pty = openpty(None, None).expect("openpty");
+    let master = unsafe { PtyMaster::from_owned_fd(pty.master) };
+    let path = unsafe { ptsname(&master).expect("ptsname") };
+    let dir = tempdir().expect("tempdir");
+    let settings = DaemonSettings {
+        socket: dir.path().join("daemon.sock").display().to_string(),
+        ..DaemonSettings::default()
+    };
+    let mut daemon = Daemon::start(&settings, dir.path()).expect("start daemon");
+
+    let mut port = DaemonPort::open(&settings, &path, &SerialConfig::default()).expect("open");
+    port.start(|_| {}, |_, _| {}).expect("first start");
+    port.stop();
+    let (tx, rx) = mpsc::channel();
+    port.start(move |data| tx.send(data.to_vec()).unwrap(), |_, _| {})
+        .expect("second start");
+
+    write(&master, b"again").expect("write master");
+    let mut received = Vec::new();
+    while received.len() < 5 {
+        received.extend(rx.recv_timeout(Duration::from_secs(2)).expect("data"));
+    }
+    assert_eq!(received, b"again");
+    port.stop();
+    daemon.stop();
+}
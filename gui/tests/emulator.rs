@@ -0,0 +1,81 @@
+#![cfg(unix)]
+
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::os::fd::AsRawFd;
+use std::time::{Duration, Instant};
+
+use microserial_gui::core::SerialConfig;
+use microserial_gui::emulator::{DeviceEmulator, EmulatorEvent, EmulatorScript};
+use microserial_gui::recorder::{self, RecordPayload, Recorder, RecordingHeader};
+use tempfile::tempdir;
+
+fn read_until(port: &mut impl Read, expected: usize, timeout: Duration) -> Vec<u8> {
+    let start = Instant::now();
+    let mut received = Vec::new();
+    let mut buf = [0u8; 64];
+    while received.len() < expected && start.elapsed() < timeout {
+        if let Ok(n) = port.read(&mut buf) {
+            received.extend_from_slice(&buf[..n]);
+        }
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    received
+}
+
+#[test]
+fn host_requests_get_recorded_responses() {
+    let dir = tempdir().expect("tempdir");
+    let recording = dir.path().join("device.msrec");
+    let header = RecordingHeader::new("/dev/ttyACM0", &SerialConfig::default(), None);
+    let mut recorder = Recorder::create(&recording, &header).expect("create recorder");
+    recorder
+        .write(&RecordPayload::Tx(b"VER?\r".to_vec()))
+        .expect("write tx");
+    std::thread::sleep(Duration::from_millis(120));
+    recorder
+        .write(&RecordPayload::Rx(b"v1.2\r\n".to_vec()))
+        .expect("write rx");
+    recorder
+        .write(&RecordPayload::Tx(b"PING\r".to_vec()))
+        .expect("write tx");
+    recorder
+        .write(&RecordPayload::Rx(b"PONG\r\n".to_vec()))
+        .expect("write rx");
+    drop(recorder);
+
+    let (_, records) = recorder::load(&recording).expect("load");
+    let link = dir.path().join("ttyEmu");
+    let emulator =
+        DeviceEmulator::start(EmulatorScript::from_records(&records), &link).expect("start");
+    let mut host = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&link)
+        .expect("open link");
+    unsafe {
+        libc::fcntl(host.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK);
+    }
+
+    let sent = Instant::now();
+    host.write_all(b"VER?\r").expect("write request");
+    assert_eq!(
+        read_until(&mut host, 6, Duration::from_secs(2)),
+        b"v1.2\r\n"
+    );
+    assert!(sent.elapsed() >= Duration::from_millis(110));
+
+    host.write_all(b"PI").expect("write request");
+    host.write_all(b"NG\r").expect("write request");
+    assert_eq!(
+        read_until(&mut host, 6, Duration::from_secs(2)),
+        b"PONG\r\n"
+    );
+    assert_eq!(
+        emulator.poll(),
+        vec![
+            EmulatorEvent::Answered { exchange: 0 },
+            EmulatorEvent::Answered { exchange: 1 },
+        ]
+    );
+}
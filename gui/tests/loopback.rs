@@ -35,4 +35,39 @@ fn loopback_via_pty() {
     let mut buf = [0u8; 8];
     let read_bytes = read(&master, &mut buf).expect("read");
     assert_eq!(&buf[..read_bytes], b"pong");
+    // The I/O thread drops bytes from the ring just after the write returns.
+    let start = Instant::now();
+    while session.tx_pending() != 0 && start.elapsed() < Duration::from_secs(1) {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(session.tx_pending(), 0);
+}
+
+#[test]
+fn refused_reconfigure_keeps_the_port_running() {
+    let pty = openpty(None, None).expect("openpty");
+    let master = unsafe { PtyMaster::from_owned_fd(pty.master) };
+    let slave_path = unsafe { ptsname(&master).expect("ptsname") };
+
+    let config = SerialConfig::default();
+    let mut session = SerialSession::open(&slave_path, &config).expect("session");
+    let broken = SerialConfig {
+        rx_buffer_size: 0,
+        ..config.clone()
+    };
+    assert!(session.reconfigure(&broken).is_err());
+    assert_eq!(session.config().rx_buffer_size, config.rx_buffer_size);
+
+    write(&master, b"still here").expect("write master");
+    let start = Instant::now();
+    let mut rx = Vec::new();
+    while rx.len() < 10 && start.elapsed() < Duration::from_secs(1) {
+        for msg in session.poll() {
+            if let SessionMessage::Data(bytes) = msg {
+                rx.extend(bytes);
+            }
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(rx, b"still here");
 }
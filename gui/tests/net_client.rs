@@ -0,0 +1,158 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+
+use microserial_gui::core::{ModemLines, SerialConfig};
+use microserial_gui::net_server::{
+    SerialServer, ServerMessage, ServerProtocol, ServerRequest, ServerSettings,
+};
+use microserial_gui::session::{SerialSession, SessionMessage};
+
+fn wait_for_request<F>(server: &SerialServer, mut matches: F) -> ServerRequest
+where
+    F: FnMut(&ServerRequest) -> bool,
+{
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(2) {
+        for message in server.poll() {
+            match message {
+                ServerMessage::Request { request, .. } if matches(&request) => return request,
+                _ => {}
+            }
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    panic!("expected request not received");
+}
+
+fn read_session(session: &SerialSession, expected: usize) -> Vec<u8> {
+    let start = Instant::now();
+    let mut received = Vec::new();
+    while received.len() < expected && start.elapsed() < Duration::from_secs(2) {
+        for message in session.poll() {
+            if let SessionMessage::Data(bytes) = message {
+                received.extend_from_slice(&bytes);
+            }
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    received
+}
+
+#[test]
+fn rfc2217_session_negotiates_configuration() {
+    let settings = ServerSettings {
+        bind: "127.0.0.1:0".to_string(),
+        protocol: ServerProtocol::Rfc2217,
+        token: None,
+    };
+    let server = SerialServer::start(settings, &SerialConfig::default(), ModemLines::default())
+        .expect("start server");
+    let url = format!("rfc2217://{}", server.local_addr());
+
+    // Opening waits for the server, which answers once the owner applied the change.
+    let mut config = SerialConfig {
+        baud_rate: 57_600,
+        ..SerialConfig::default()
+    };
+    let opening = {
+        let config = config.clone();
+        std::thread::spawn(move || SerialSession::open(&url, &config))
+    };
+    let ServerRequest::Configure(applied) = wait_for_request(
+        &server,
+        |request| matches!(request, ServerRequest::Configure(config) if config.baud_rate == 57_600),
+    ) else {
+        unreachable!()
+    };
+    server.update_config(&applied);
+    let mut session = opening
+        .join()
+        .expect("open thread")
+        .expect("open remote session");
+    assert!(session.is_remote());
+
+    let payload = [b'A', 0xFF, b'B'];
+    session.write(&payload).expect("write");
+    let request = wait_for_request(&server, |request| {
+        matches!(request, ServerRequest::Write(_))
+    });
+    assert_eq!(request, ServerRequest::Write(payload.to_vec()));
+
+    server.publish(&[0x01, 0xFF, 0x02]);
+    assert_eq!(read_session(&session, 3), vec![0x01, 0xFF, 0x02]);
+
+    // A server that keeps the old rate makes the change fail.
+    config.baud_rate = 9_600;
+    std::thread::scope(|scope| {
+        let reconfigure = scope.spawn(|| session.reconfigure(&config));
+        wait_for_request(
+            &server,
+            |request| matches!(request, ServerRequest::Configure(config) if config.baud_rate == 9_600),
+        );
+        server.update_config(&applied);
+        assert!(reconfigure.join().expect("reconfigure thread").is_err());
+    });
+    assert_eq!(session.config().baud_rate, 57_600);
+
+    session.set_dtr(false).expect("dtr");
+    wait_for_request(&server, |request| *request == ServerRequest::SetDtr(false));
+    assert!(!session.modem_lines().expect("lines").dtr);
+}
+
+#[test]
+fn raw_tcp_session_passes_bytes_through() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let url = format!("tcp://{}", listener.local_addr().expect("addr"));
+    let echo = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().expect("accept");
+        let mut buf = [0u8; 16];
+        let n = stream.read(&mut buf).expect("read");
+        stream.write_all(&buf[..n]).expect("echo");
+    });
+
+    let mut session =
+        SerialSession::open(&url, &SerialConfig::default()).expect("open remote session");
+    session.write(b"ping\xff").expect("write");
+    assert_eq!(read_session(&session, 5), b"ping\xff");
+    assert!(session.set_dtr(true).is_err());
+    echo.join().expect("echo thread");
+}
+
+#[test]
+fn rfc2217_session_reports_server_modem_lines() {
+    let settings = ServerSettings {
+        bind: "127.0.0.1:0".to_string(),
+        protocol: ServerProtocol::Rfc2217,
+        token: None,
+    };
+    let lines = ModemLines {
+        rts: true,
+        cts: true,
+        ..ModemLines::default()
+    };
+    let server =
+        SerialServer::start(settings, &SerialConfig::default(), lines).expect("start server");
+    let url = format!("rfc2217://{}", server.local_addr());
+    let session = SerialSession::open(&url, &SerialConfig::default()).expect("open remote session");
+
+    let wait_for_lines = |expected: ModemLines| {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(2) {
+            if session.modem_lines().ok() == Some(expected) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        panic!("modem lines stayed {:?}", session.modem_lines());
+    };
+    wait_for_lines(lines);
+
+    let changed = ModemLines {
+        dsr: true,
+        cd: true,
+        ..lines
+    };
+    server.update_lines(changed);
+    wait_for_lines(changed);
+}
@@ -0,0 +1,277 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use microserial_gui::core::{ModemLines, SerialConfig};
+use microserial_gui::net_server::{
+    ClientRole, SerialServer, ServerMessage, ServerProtocol, ServerRequest, ServerSettings,
+};
+use microserial_gui::rfc2217::{ComPortCommand, OPT_COM_PORT, TelnetDecoder, TelnetEvent};
+
+fn start(protocol: ServerProtocol, token: Option<&str>) -> SerialServer {
+    let settings = ServerSettings {
+        bind: "127.0.0.1:0".to_string(),
+        protocol,
+        token: token.map(str::to_string),
+    };
+    SerialServer::start(settings, &SerialConfig::default(), ModemLines::default())
+        .expect("start server")
+}
+
+fn connect(server: &SerialServer) -> TcpStream {
+    let stream = TcpStream::connect(server.local_addr()).expect("connect");
+    stream
+        .set_read_timeout(Some(Duration::from_millis(50)))
+        .expect("read timeout");
+    stream
+}
+
+fn wait_for<F>(server: &SerialServer, mut matches: F) -> Vec<ServerMessage>
+where
+    F: FnMut(&ServerMessage) -> bool,
+{
+    let start = Instant::now();
+    let mut seen = Vec::new();
+    while start.elapsed() < Duration::from_secs(2) {
+        for message in server.poll() {
+            let done = matches(&message);
+            seen.push(message);
+            if done {
+                return seen;
+            }
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    panic!("expected message not received, saw {seen:?}");
+}
+
+fn read_until<F>(stream: &mut TcpStream, mut done: F) -> Vec<u8>
+where
+    F: FnMut(&[u8]) -> bool,
+{
+    let start = Instant::now();
+    let mut received = Vec::new();
+    let mut buf = [0u8; 256];
+    while !done(&received) && start.elapsed() < Duration::from_secs(2) {
+        if let Ok(n) = stream.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            received.extend_from_slice(&buf[..n]);
+        }
+    }
+    received
+}
+
+#[test]
+fn raw_server_fans_out_and_only_accepts_writer_input() {
+    let server = start(ServerProtocol::Raw, None);
+    let mut writer = connect(&server);
+    wait_for(&server, |message| {
+        matches!(
+            message,
+            ServerMessage::Connected {
+                role: ClientRole::Writer,
+                ..
+            }
+        )
+    });
+    let mut observer = connect(&server);
+    wait_for(&server, |message| {
+        matches!(
+            message,
+            ServerMessage::Connected {
+                role: ClientRole::Observer,
+                ..
+            }
+        )
+    });
+
+    server.publish(b"boot ok\r\n");
+    for stream in [&mut writer, &mut observer] {
+        let received = read_until(stream, |data| data.len() >= 9);
+        assert_eq!(received, b"boot ok\r\n");
+    }
+
+    observer.write_all(b"ignored").expect("observer write");
+    writer.write_all(b"AT\r").expect("writer write");
+    let messages = wait_for(&server, |message| {
+        matches!(message, ServerMessage::Request { .. })
+    });
+    let requests: Vec<_> = messages
+        .into_iter()
+        .filter_map(|message| match message {
+            ServerMessage::Request { request, .. } => Some(request),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(requests, vec![ServerRequest::Write(b"AT\r".to_vec())]);
+}
+
+#[test]
+fn token_mismatch_is_rejected() {
+    let server = start(ServerProtocol::Raw, Some("secret"));
+    let mut stream = connect(&server);
+    stream.write_all(b"wrong\n").expect("send token");
+    wait_for(&server, |message| {
+        matches!(message, ServerMessage::Rejected { .. })
+    });
+
+    let mut stream = connect(&server);
+    stream.write_all(b"secret\nhi").expect("send token");
+    wait_for(&server, |message| {
+        matches!(
+            message,
+            ServerMessage::Request {
+                request: ServerRequest::Write(data),
+                ..
+            } if data == b"hi"
+        )
+    });
+}
+
+#[test]
+fn rfc2217_baud_change_is_requested_and_acknowledged() {
+    let server = start(ServerProtocol::Rfc2217, None);
+    let mut stream = connect(&server);
+    stream
+        .write_all(&ComPortCommand::SetBaudRate(9_600).encode(false))
+        .expect("send baud");
+
+    let messages = wait_for(&server, |message| {
+        matches!(message, ServerMessage::Request { .. })
+    });
+    let configured = messages.iter().find_map(|message| match message {
+        ServerMessage::Request {
+            request: ServerRequest::Configure(config),
+            ..
+        } => Some(config.baud_rate),
+        _ => None,
+    });
+    assert_eq!(configured, Some(9_600));
+
+    server.update_config(&SerialConfig {
+        baud_rate: 9_600,
+        ..SerialConfig::default()
+    });
+    assert_eq!(
+        com_port_reply(&mut stream),
+        Some((ComPortCommand::SetBaudRate(9_600), true))
+    );
+}
+
+#[test]
+fn rfc2217_refused_settings_are_answered_with_the_current_value() {
+    let server = start(ServerProtocol::Rfc2217, None);
+    let mut stream = connect(&server);
+    stream
+        .write_all(&ComPortCommand::SetBaudRate(12_345).encode(false))
+        .expect("send baud");
+    wait_for(&server, |message| {
+        matches!(message, ServerMessage::Request { .. })
+    });
+
+    // The owner could not apply it and reports the settings still in effect.
+    let current = SerialConfig::default();
+    server.update_config(&current);
+    assert_eq!(
+        com_port_reply(&mut stream),
+        Some((ComPortCommand::SetBaudRate(current.baud_rate), true))
+    );
+}
+
+fn com_port_reply(stream: &mut TcpStream) -> Option<(ComPortCommand, bool)> {
+    let mut reply = None;
+    read_until(stream, |data| {
+        reply = TelnetDecoder::default()
+            .feed(data)
+            .into_iter()
+            .find_map(|event| match event {
+                TelnetEvent::Subnegotiation { option, payload } if option == OPT_COM_PORT => {
+                    ComPortCommand::parse(&payload)
+                }
+                _ => None,
+            });
+        reply.is_some()
+    });
+    reply
+}
+
+#[test]
+fn oldest_observer_is_promoted_when_writer_leaves() {
+    let server = start(ServerProtocol::Raw, None);
+    let writer = connect(&server);
+    wait_for(&server, |message| {
+        matches!(message, ServerMessage::Connected { .. })
+    });
+    let mut observer = connect(&server);
+    wait_for(&server, |message| {
+        matches!(message, ServerMessage::Connected { .. })
+    });
+    let _late = connect(&server);
+    wait_for(&server, |message| {
+        matches!(message, ServerMessage::Connected { .. })
+    });
+
+    let observer_addr = observer.local_addr().expect("observer address");
+    drop(writer);
+    wait_for(
+        &server,
+        |message| matches!(message, ServerMessage::Promoted { peer } if *peer == observer_addr),
+    );
+
+    observer.write_all(b"AT\r").expect("promoted write");
+    wait_for(&server, |message| {
+        matches!(
+            message,
+            ServerMessage::Request {
+                request: ServerRequest::Write(data),
+                ..
+            } if data == b"AT\r"
+        )
+    });
+}
+
+#[test]
+fn rfc2217_modem_state_changes_are_notified_through_the_mask() {
+    let server = start(ServerProtocol::Rfc2217, None);
+    let mut stream = connect(&server);
+    // Only CTS changes are wanted; the reply reports the current lines.
+    let cts_mask = 0x01 | 0x10;
+    stream
+        .write_all(&ComPortCommand::SetModemStateMask(cts_mask).encode(false))
+        .expect("send mask");
+    let notifies = |stream: &mut TcpStream, count: usize| {
+        let mut found = Vec::new();
+        read_until(stream, |data| {
+            found = TelnetDecoder::default()
+                .feed(data)
+                .into_iter()
+                .filter_map(|event| match event {
+                    TelnetEvent::Subnegotiation { option, payload } if option == OPT_COM_PORT => {
+                        match ComPortCommand::parse(&payload) {
+                            Some((ComPortCommand::NotifyModemState(state), true)) => Some(state),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                })
+                .collect();
+            found.len() >= count
+        });
+        found
+    };
+    assert_eq!(notifies(&mut stream, 1), [0]);
+
+    // DSR is masked out, so only the CTS change reaches the client.
+    server.update_lines(ModemLines {
+        dsr: true,
+        ..ModemLines::default()
+    });
+    server.update_lines(ModemLines {
+        dsr: true,
+        cts: true,
+        ..ModemLines::default()
+    });
+    assert_eq!(notifies(&mut stream, 1), [0x11]);
+}
@@ -0,0 +1,98 @@
+use microserial_gui::core::SerialConfig;
+use microserial_gui::pcapng::{self, PacketFraming};
+use microserial_gui::recorder::{RecordPayload, Recorder, RecordingHeader};
+use pcap_file::pcapng::PcapNgReader;
+use pcap_file::pcapng::blocks::Block;
+use pcap_file::pcapng::blocks::enhanced_packet::{EnhancedPacketBlock, EnhancedPacketOption};
+use pcap_file::pcapng::blocks::interface_description::{
+    InterfaceDescriptionBlock, InterfaceDescriptionOption,
+};
+use tempfile::tempdir;
+
+/// EPB flags direction bits: inbound `0b01`, outbound `0b10`.
+fn direction(packet: &EnhancedPacketBlock) -> Option<u32> {
+    packet.options.iter().find_map(|option| match option {
+        EnhancedPacketOption::Flags(flags) => Some(flags & 0b11),
+        _ => None,
+    })
+}
+
+fn comment<'a>(packet: &'a EnhancedPacketBlock) -> Option<&'a str> {
+    packet.options.iter().find_map(|option| match option {
+        EnhancedPacketOption::Comment(text) => Some(text.as_ref()),
+        _ => None,
+    })
+}
+
+#[test]
+fn recording_exports_to_readable_pcapng() {
+    let dir = tempdir().expect("tempdir");
+    let recording = dir.path().join("session.msrec");
+    let output = dir.path().join("session.pcapng");
+    let config = SerialConfig::default();
+    let header = RecordingHeader::new("/dev/ttyUSB0", &config, None);
+
+    let mut recorder = Recorder::create(&recording, &header).expect("create recorder");
+    recorder
+        .write(&RecordPayload::Tx(b"AT\r\n".to_vec()))
+        .expect("write tx");
+    recorder
+        .write(&RecordPayload::Rx(b"O".to_vec()))
+        .expect("write rx");
+    recorder
+        .write(&RecordPayload::Rx(b"K\r\n+CSQ".to_vec()))
+        .expect("write rx");
+    let faster = SerialConfig {
+        baud_rate: 921_600,
+        ..config
+    };
+    recorder
+        .write(&RecordPayload::Config(faster))
+        .expect("write config");
+    recorder
+        .write(&RecordPayload::Rx(b": 9\n".to_vec()))
+        .expect("write rx");
+    drop(recorder);
+
+    let chunks = pcapng::export_recording(
+        &recording,
+        &output,
+        PacketFraming::Delimiter(b"\n".to_vec()),
+    )
+    .expect("export");
+    assert_eq!(chunks, 4);
+
+    let bytes = std::fs::read(&output).expect("read output");
+    let mut reader = PcapNgReader::new(bytes.as_slice()).expect("parse");
+    let mut interfaces: Vec<InterfaceDescriptionBlock> = Vec::new();
+    let mut packets: Vec<EnhancedPacketBlock> = Vec::new();
+    while let Some(block) = reader.next_block() {
+        match block.expect("block").into_owned() {
+            Block::InterfaceDescription(block) => interfaces.push(block),
+            Block::EnhancedPacket(block) => packets.push(block),
+            _ => {}
+        }
+    }
+    assert_eq!(interfaces.len(), 1);
+    let options = &interfaces[0].options;
+    assert!(options.contains(&InterfaceDescriptionOption::IfName("/dev/ttyUSB0".into())));
+    assert!(options.iter().any(|option| matches!(
+        option,
+        InterfaceDescriptionOption::IfDescription(text) if text.contains("bps")
+    )));
+
+    let payloads: Vec<(Option<u32>, &[u8])> = packets
+        .iter()
+        .map(|packet| (direction(packet), packet.data.as_ref()))
+        .collect();
+    assert_eq!(
+        payloads,
+        vec![
+            (Some(0b10), &b"AT\r\n"[..]),
+            (Some(0b01), &b"OK\r\n"[..]),
+            (Some(0b01), &b"+CSQ: 9\n"[..]),
+        ]
+    );
+    assert!(comment(&packets[2]).is_some_and(|comment| comment.contains("921600")));
+    assert!(packets.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+}
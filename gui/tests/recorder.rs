@@ -0,0 +1,165 @@
+#![cfg(unix)]
+
+use std::time::{Duration, Instant};
+
+use microserial_gui::core::{ModemLines, SerialConfig};
+use microserial_gui::net_server::{SerialServer, ServerProtocol, ServerSettings};
+use microserial_gui::recorder::{self, RecordPayload, RecordingHeader};
+use microserial_gui::session::{SerialSession, SessionMessage};
+use nix::pty::{PtyMaster, openpty, ptsname};
+use nix::unistd::{read, write};
+use tempfile::tempdir;
+
+#[test]
+fn session_recording_captures_both_directions() {
+    let pty = openpty(None, None).expect("openpty");
+    let master = unsafe { PtyMaster::from_owned_fd(pty.master) };
+    let slave_path = unsafe { ptsname(&master).expect("ptsname") };
+    let config = SerialConfig::default();
+    let mut session = SerialSession::open(&slave_path, &config).expect("session");
+
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("capture.msrec");
+    let header = RecordingHeader::new(&slave_path, &config, Some("Default".to_string()));
+    session
+        .start_recording(&path, &header)
+        .expect("start recording");
+
+    session.write(b"AT\r").expect("write session");
+    let mut buf = [0u8; 8];
+    let n = read(&master, &mut buf).expect("read master");
+    assert_eq!(&buf[..n], b"AT\r");
+
+    write(&master, b"OK\r\n").expect("write master");
+    let start = Instant::now();
+    let mut rx = Vec::new();
+    while rx.len() < 4 && start.elapsed() < Duration::from_secs(1) {
+        for msg in session.poll() {
+            if let SessionMessage::Data(bytes) = msg {
+                rx.extend(bytes);
+            }
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let status = session.stop_recording().expect("recording status");
+    assert_eq!(status.path, path);
+
+    let (loaded, records) = recorder::load(&path).expect("load");
+    assert_eq!(loaded.port, slave_path);
+    assert_eq!(loaded.profile.as_deref(), Some("Default"));
+    assert!(matches!(records[0].payload, RecordPayload::Config(_)));
+    let tx: Vec<u8> = records
+        .iter()
+        .filter_map(|record| match &record.payload {
+            RecordPayload::Tx(data) => Some(data.clone()),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    let rx_recorded: Vec<u8> = records
+        .iter()
+        .filter_map(|record| match &record.payload {
+            RecordPayload::Rx(data) => Some(data.clone()),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    assert_eq!(tx, b"AT\r");
+    assert_eq!(rx_recorded, b"OK\r\n");
+}
+
+#[test]
+fn session_recording_captures_line_changes_and_break() {
+    // A pty has no modem lines, so drive them through an RFC 2217 server.
+    let settings = ServerSettings {
+        bind: "127.0.0.1:0".to_string(),
+        protocol: ServerProtocol::Rfc2217,
+        token: None,
+    };
+    let config = SerialConfig::default();
+    let lines = ModemLines {
+        dtr: true,
+        rts: true,
+        ..ModemLines::default()
+    };
+    let server = SerialServer::start(settings, &config, lines).expect("start server");
+    let url = format!("rfc2217://{}", server.local_addr());
+    let mut session = SerialSession::open(&url, &config).expect("session");
+
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("lines.msrec");
+    let header = RecordingHeader::new(&url, &config, None);
+    session
+        .start_recording(&path, &header)
+        .expect("start recording");
+    session.set_dtr(false).expect("dtr");
+    session.set_break(true).expect("break on");
+    session.set_break(false).expect("break off");
+    session.stop_recording().expect("recording status");
+
+    let (_, records) = recorder::load(&path).expect("load");
+    let lines: Vec<_> = records
+        .iter()
+        .filter_map(|record| match &record.payload {
+            RecordPayload::ModemLines(lines) => Some(*lines),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(lines.len(), 2, "initial sample and the DTR change");
+    assert!(!lines[1].dtr);
+    let events: Vec<_> = records
+        .iter()
+        .filter_map(|record| match &record.payload {
+            RecordPayload::Event { code: 0, message } => Some(message.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(events, ["break on", "break off"]);
+}
+
+#[test]
+fn session_recording_captures_input_line_changes() {
+    let settings = ServerSettings {
+        bind: "127.0.0.1:0".to_string(),
+        protocol: ServerProtocol::Rfc2217,
+        token: None,
+    };
+    let config = SerialConfig::default();
+    let server =
+        SerialServer::start(settings, &config, ModemLines::default()).expect("start server");
+    let url = format!("rfc2217://{}", server.local_addr());
+    let mut session = SerialSession::open(&url, &config).expect("session");
+
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("inputs.msrec");
+    let header = RecordingHeader::new(&url, &config, None);
+    session
+        .start_recording(&path, &header)
+        .expect("start recording");
+    // Let the client subscribe to line changes before the device raises CTS.
+    std::thread::sleep(Duration::from_millis(100));
+    server.update_lines(ModemLines {
+        cts: true,
+        ..ModemLines::default()
+    });
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(1) && !session.modem_lines().expect("lines").cts {
+        session.poll();
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    std::thread::sleep(Duration::from_millis(30));
+    session.poll();
+    session.stop_recording().expect("recording status");
+
+    let (_, records) = recorder::load(&path).expect("load");
+    let lines: Vec<_> = records
+        .iter()
+        .filter_map(|record| match &record.payload {
+            RecordPayload::ModemLines(lines) => Some(*lines),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(lines.len(), 2, "initial sample and the CTS change");
+    assert!(!lines[0].cts);
+    assert!(lines[1].cts);
+}
@@ -0,0 +1,76 @@
+#![cfg(unix)]
+
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::os::fd::AsRawFd;
+use std::time::{Duration, Instant};
+
+use microserial_gui::core::SerialConfig;
+use microserial_gui::recorder::{self, RecordPayload, Recorder, RecordingHeader};
+use microserial_gui::replay::{ReplayMessage, ReplayOutput, ReplayTiming, Replayer};
+use microserial_gui::virtual_port::VirtualPort;
+use tempfile::tempdir;
+
+#[test]
+fn recording_replays_into_virtual_port_with_original_gaps() {
+    let dir = tempdir().expect("tempdir");
+    let recording = dir.path().join("field.msrec");
+    let header = RecordingHeader::new("/dev/ttyUSB0", &SerialConfig::default(), None);
+    let mut recorder = Recorder::create(&recording, &header).expect("create recorder");
+    recorder
+        .write(&RecordPayload::Rx(b"boot\r\n".to_vec()))
+        .expect("write rx");
+    recorder
+        .write(&RecordPayload::Tx(b"AT\r".to_vec()))
+        .expect("write tx");
+    std::thread::sleep(Duration::from_millis(150));
+    recorder
+        .write(&RecordPayload::Rx(b"OK\r\n".to_vec()))
+        .expect("write rx");
+    drop(recorder);
+
+    let link = dir.path().join("ttyReplay");
+    let virtual_port = VirtualPort::create(&link).expect("create virtual port");
+    let mut host = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&link)
+        .expect("open link");
+    unsafe {
+        libc::fcntl(host.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK);
+    }
+
+    let (_, records) = recorder::load(&recording).expect("load");
+    let start = Instant::now();
+    let replayer = Replayer::start(
+        &records,
+        ReplayTiming::Original,
+        ReplayOutput::Sink(Box::new(move |data| {
+            virtual_port.publish(data);
+            Ok(())
+        })),
+    );
+    assert_eq!(replayer.chunks(), 2);
+
+    let mut received = Vec::new();
+    let mut first_at = None;
+    let mut buf = [0u8; 64];
+    while received.len() < 10 && start.elapsed() < Duration::from_secs(2) {
+        if let Ok(n) = host.read(&mut buf) {
+            received.extend_from_slice(&buf[..n]);
+            first_at.get_or_insert_with(|| start.elapsed());
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    let elapsed = start.elapsed();
+    assert_eq!(received, b"boot\r\nOK\r\n");
+    assert!(first_at.expect("first chunk") < Duration::from_millis(100));
+    assert!(elapsed >= Duration::from_millis(140), "{elapsed:?}");
+
+    let finish = Instant::now();
+    while !replayer.is_finished() && finish.elapsed() < Duration::from_secs(1) {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(replayer.poll(), vec![ReplayMessage::Finished]);
+    assert_eq!(replayer.progress(), 1.0);
+}
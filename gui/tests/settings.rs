@@ -2,6 +2,8 @@ use std::fs;
 
 use microserial_gui::core::SerialConfig;
 use microserial_gui::profiles::SerialProfile;
+use microserial_gui::scheduler::ScheduledItem;
+use microserial_gui::send_panel::{HistoryEntry, LineEnding, SendMode};
 use microserial_gui::settings::Settings;
 use microserial_gui::theme::ThemePreference;
 use once_cell::sync::Lazy;
@@ -21,15 +23,34 @@ fn settings_round_trip() {
     let mut settings = Settings::default();
     settings.force_software = true;
     settings.theme.preference = ThemePreference::Dark;
-    settings
-        .profiles
-        .upsert(SerialProfile::new("Lab", SerialConfig::default()));
+    let mut lab = SerialProfile::new("Lab", SerialConfig::default());
+    let poll = ScheduledItem::single("AT+CSQ", SendMode::Text);
+    lab.schedules.push(poll.clone());
+    let favorite = HistoryEntry {
+        value: "AT+CSQ".to_string(),
+        mode: SendMode::Text,
+        favorited: true,
+        checksum: None,
+    };
+    lab.history.push(favorite.clone());
+    lab.send.ending = Some(LineEnding::CrLf);
+    settings.profiles.upsert(lab);
     settings.save().expect("save");
 
     let loaded = Settings::load().expect("load");
     assert!(loaded.force_software);
     assert_eq!(loaded.theme.preference, ThemePreference::Dark);
-    assert!(!loaded.profiles.profiles.is_empty());
+    let lab = loaded
+        .profiles
+        .profiles
+        .iter()
+        .find(|profile| profile.name == "Lab")
+        .expect("Lab profile");
+    assert_eq!(lab.schedules, [poll]);
+    assert_eq!(lab.history, [favorite]);
+    assert_eq!(lab.send.ending, Some(LineEnding::CrLf));
+    let default = loaded.profiles.get_active().expect("active profile");
+    assert!(default.history.is_empty());
 
     unsafe {
         std::env::remove_var("MICROSERIAL_CONFIG_DIR");
@@ -0,0 +1,162 @@
+#![cfg(unix)]
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use microserial_gui::core::SerialConfig;
+use microserial_gui::session::{SerialSession, SessionMessage};
+use microserial_gui::transfer::{FileTransfer, TransferFile, TransferProtocol, TransferState};
+use nix::pty::{PtyMaster, openpty, ptsname};
+use nix::unistd::{read, write};
+use tempfile::tempdir;
+
+/// Bytes that cover every value, including the ones ZMODEM has to escape.
+fn sample(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 + i / 256) as u8).collect()
+}
+
+fn file(name: &str, len: usize) -> TransferFile {
+    TransferFile {
+        name: name.to_string(),
+        data: sample(len),
+    }
+}
+
+/// Runs `local` over a [`SerialSession`] on the pty slave and `remote` on the master.
+fn run(local: &mut FileTransfer, remote: &mut FileTransfer) {
+    let pty = openpty(None, None).expect("openpty");
+    let master = Arc::new(unsafe { PtyMaster::from_owned_fd(pty.master) });
+    let path = unsafe { ptsname(&master).expect("ptsname") };
+    let mut session = SerialSession::open(&path, &SerialConfig::default()).expect("session");
+
+    let (tx, rx) = mpsc::channel();
+    let reader = Arc::clone(&master);
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        while let Ok(n @ 1..) = read(&*reader, &mut buf) {
+            if tx.send(buf[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let start = Instant::now();
+    while (local.is_running() || remote.is_running()) && start.elapsed() < Duration::from_secs(30) {
+        let now = Instant::now();
+        for msg in session.poll() {
+            if let SessionMessage::Data(bytes) = msg {
+                local.feed(&bytes, now);
+            }
+        }
+        while let Ok(bytes) = rx.try_recv() {
+            remote.feed(&bytes, now);
+        }
+        let outgoing = local.poll(now);
+        if !outgoing.is_empty() {
+            session.write(&outgoing).expect("write session");
+        }
+        let mut outgoing = &remote.poll(now)[..];
+        while !outgoing.is_empty() {
+            let n = write(&*master, outgoing).expect("write master");
+            outgoing = &outgoing[n..];
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(local.state(), TransferState::Completed, "local side");
+    assert_eq!(remote.state(), TransferState::Completed, "remote side");
+}
+
+fn send(protocol: TransferProtocol, files: Vec<TransferFile>) -> FileTransfer {
+    FileTransfer::send(protocol, files, Instant::now())
+}
+
+fn receive(protocol: TransferProtocol, destination: &Path) -> FileTransfer {
+    FileTransfer::receive(protocol, destination.to_path_buf(), Instant::now())
+}
+
+#[test]
+fn xmodem_variants_transfer_both_ways() {
+    let dir = tempdir().expect("tempdir");
+    let data = sample(3000);
+    for protocol in [
+        TransferProtocol::Xmodem,
+        TransferProtocol::XmodemCrc,
+        TransferProtocol::Xmodem1k,
+    ] {
+        let target = dir.path().join(format!("{protocol:?}.bin"));
+        let mut sender = send(protocol, vec![file("fw.bin", 3000)]);
+        let mut receiver = receive(protocol, &target);
+        run(&mut sender, &mut receiver);
+        assert_eq!(sender.progress().bytes, 3000);
+
+        // XMODEM has no length field, so the last block keeps its padding.
+        let received = std::fs::read(&target).expect("received file");
+        assert_eq!(&received[..3000], &data[..], "{protocol}");
+        assert_eq!(received.len() % 128, 0);
+        assert!(received[3000..].iter().all(|byte| *byte == 0x1A));
+
+        std::fs::remove_file(&target).expect("remove");
+        let mut receiver = receive(protocol, &target);
+        let mut sender = send(protocol, vec![file("fw.bin", 3000)]);
+        run(&mut receiver, &mut sender);
+        assert_eq!(receiver.saved(), std::slice::from_ref(&target));
+        assert_eq!(
+            &std::fs::read(&target).expect("received")[..3000],
+            &data[..]
+        );
+    }
+}
+
+#[test]
+fn ymodem_and_zmodem_batches_keep_names_and_sizes() {
+    for protocol in [TransferProtocol::Ymodem, TransferProtocol::Zmodem] {
+        let files = vec![
+            file("boot.cfg", 0),
+            file("image.bin", 40_000),
+            file("notes.txt", 129),
+        ];
+        let outbound = tempdir().expect("tempdir");
+        let mut sender = send(protocol, files.clone());
+        let mut receiver = receive(protocol, outbound.path());
+        run(&mut sender, &mut receiver);
+        assert_eq!(sender.progress().files_done, 3);
+
+        let inbound = tempdir().expect("tempdir");
+        let mut receiver = receive(protocol, inbound.path());
+        let mut sender = send(protocol, files.clone());
+        run(&mut receiver, &mut sender);
+        assert_eq!(receiver.saved().len(), 3);
+
+        for dir in [&outbound, &inbound] {
+            for expected in &files {
+                let received = std::fs::read(dir.path().join(&expected.name)).expect("received");
+                assert_eq!(received, expected.data, "{protocol} {}", expected.name);
+            }
+        }
+    }
+}
+
+#[test]
+fn cancelling_stops_the_peer() {
+    let dir = tempdir().expect("tempdir");
+    for protocol in [TransferProtocol::Xmodem1k, TransferProtocol::Zmodem] {
+        let mut sender = send(protocol, vec![file("big.bin", 200_000)]);
+        let mut receiver = receive(protocol, dir.path());
+        let now = Instant::now();
+        // Let the receiver's start request go out, then give up right away.
+        let start = receiver.poll(now);
+        sender.feed(&start, now);
+        let _ = sender.poll(now);
+        receiver.cancel();
+        let cancel = receiver.poll(now);
+        sender.feed(&cancel, now);
+        assert_eq!(receiver.state(), TransferState::Cancelled);
+        assert!(
+            matches!(sender.state(), TransferState::Failed(ref reason) if reason.contains("cancelled")),
+            "{protocol}: {:?}",
+            sender.state()
+        );
+    }
+}
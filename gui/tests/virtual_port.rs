@@ -0,0 +1,55 @@
+#![cfg(unix)]
+
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::os::fd::AsRawFd;
+use std::time::{Duration, Instant};
+
+use microserial_gui::virtual_port::VirtualPort;
+use tempfile::tempdir;
+
+#[test]
+fn virtual_port_forwards_both_ways_through_link() {
+    let dir = tempdir().expect("tempdir");
+    let link = dir.path().join("ttyShared");
+    let virtual_port = VirtualPort::create(&link).expect("create virtual port");
+    assert_eq!(
+        std::fs::read_link(&link).expect("link"),
+        virtual_port.slave_path()
+    );
+
+    let mut tool = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&link)
+        .expect("open link");
+    unsafe {
+        libc::fcntl(tool.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK);
+    }
+
+    tool.write_all(b"flash\x00\xff").expect("tool write");
+    let start = Instant::now();
+    let mut forwarded = Vec::new();
+    while forwarded.len() < 7 && start.elapsed() < Duration::from_secs(2) {
+        for chunk in virtual_port.poll() {
+            forwarded.extend(chunk);
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(forwarded, b"flash\x00\xff");
+
+    virtual_port.publish(b"ack\r\n");
+    let start = Instant::now();
+    let mut received = Vec::new();
+    let mut buf = [0u8; 64];
+    while received.len() < 5 && start.elapsed() < Duration::from_secs(2) {
+        match tool.read(&mut buf) {
+            Ok(n) if n > 0 => received.extend_from_slice(&buf[..n]),
+            _ => std::thread::sleep(Duration::from_millis(5)),
+        }
+    }
+    assert_eq!(received, b"ack\r\n");
+
+    drop(virtual_port);
+    assert!(std::fs::symlink_metadata(&link).is_err());
+}
@@ -0,0 +1,107 @@
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use microserial_gui::session::{SessionEvent, SessionMessage};
+use microserial_gui::websocket::{
+    StreamFrame, WebSocketMessage, WebSocketServer, WebSocketSettings,
+};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+type Client = WebSocket<MaybeTlsStream<TcpStream>>;
+
+fn connect(url: &str) -> Client {
+    let (client, _) = tungstenite::connect(url).expect("connect");
+    if let MaybeTlsStream::Plain(stream) = client.get_ref() {
+        stream
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .expect("read timeout");
+    }
+    client
+}
+
+fn next_frame(client: &mut Client) -> StreamFrame {
+    loop {
+        match client.read().expect("read frame") {
+            Message::Text(text) => return serde_json::from_str(&text).expect("frame json"),
+            _ => continue,
+        }
+    }
+}
+
+fn wait_for(
+    server: &WebSocketServer,
+    what: impl Fn(&WebSocketMessage) -> bool,
+) -> WebSocketMessage {
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while Instant::now() < deadline {
+        if let Some(message) = server.poll().into_iter().find(|message| what(message)) {
+            return message;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    panic!("no matching server message");
+}
+
+#[test]
+fn streams_traffic_and_accepts_authorized_writes() {
+    let settings = WebSocketSettings {
+        bind: "127.0.0.1:0".to_string(),
+        token: Some("s3cret".to_string()),
+    };
+    let server = WebSocketServer::start(&settings, "/dev/ttyTEST").expect("start");
+    let addr = server.local_addr();
+
+    let mut viewer = connect(&format!("ws://{addr}/"));
+    assert_eq!(
+        next_frame(&mut viewer),
+        StreamFrame::Hello {
+            port: "/dev/ttyTEST".to_string(),
+            writable: false,
+        }
+    );
+    let mut writer = connect(&format!("ws://{addr}/?token=s3cret"));
+    assert!(matches!(
+        next_frame(&mut writer),
+        StreamFrame::Hello { writable: true, .. }
+    ));
+    wait_for(&server, |message| {
+        matches!(message, WebSocketMessage::Connected { writable: true, .. })
+    });
+
+    server.publish(&SessionMessage::Data(b"OK\r\n".to_vec()));
+    server.publish_tx(b"AT\r");
+    server.publish(&SessionMessage::Event(SessionEvent {
+        code: -5,
+        message: "read failed".to_string(),
+    }));
+    for client in [&mut viewer, &mut writer] {
+        assert!(matches!(next_frame(client), StreamFrame::Rx { data, .. } if data == b"OK\r\n"));
+        assert!(matches!(next_frame(client), StreamFrame::Tx { data, .. } if data == b"AT\r"));
+        assert!(matches!(
+            next_frame(client),
+            StreamFrame::Event { code: -5, .. }
+        ));
+    }
+
+    // "QVQNCg==" is base64 for "AT\r\n".
+    let command = r#"{"type":"write","data":"QVQNCg=="}"#;
+    writer
+        .send(Message::Text(command.to_string()))
+        .expect("send write");
+    let message = wait_for(&server, |message| {
+        matches!(message, WebSocketMessage::Write { .. })
+    });
+    assert!(matches!(message, WebSocketMessage::Write { data, .. } if data == b"AT\r\n"));
+
+    viewer
+        .send(Message::Text(command.to_string()))
+        .expect("send write");
+    assert!(matches!(next_frame(&mut viewer), StreamFrame::Error { .. }));
+    assert!(
+        !server
+            .poll()
+            .iter()
+            .any(|message| matches!(message, WebSocketMessage::Write { .. }))
+    );
+}